@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS library_root (
+    name TEXT PRIMARY KEY,
+    path TEXT NOT NULL
+);
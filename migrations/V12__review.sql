@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS review (
+    hash TEXT PRIMARY KEY,
+    added_at TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE library ADD COLUMN kind TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS version (
+    master TEXT NOT NULL,
+    derived TEXT NOT NULL,
+    tool TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (master, derived)
+);
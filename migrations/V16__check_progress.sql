@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS check_progress (
+    name TEXT PRIMARY KEY,
+    position TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
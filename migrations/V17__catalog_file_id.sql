@@ -0,0 +1,25 @@
+ALTER TABLE catalog ADD COLUMN device INTEGER;
+ALTER TABLE catalog ADD COLUMN inode INTEGER;
+
+CREATE INDEX IF NOT EXISTS catalog_file_id ON catalog (device, inode);
+
+DROP VIEW IF EXISTS v_duplicates;
+
+CREATE VIEW v_duplicates AS
+SELECT
+    catalog.hash,
+    catalog.path,
+    copies.count AS copies
+FROM catalog
+JOIN (
+    SELECT hash, COUNT(DISTINCT coalesce(device || ':' || inode, path)) AS count
+    FROM catalog
+    GROUP BY hash
+    HAVING COUNT(DISTINCT coalesce(device || ':' || inode, path)) > 1
+) AS copies
+    ON catalog.hash = copies.hash
+WHERE catalog.inode IS NULL
+    OR catalog.path = (
+        SELECT MIN(same.path) FROM catalog AS same
+        WHERE same.device = catalog.device AND same.inode = catalog.inode
+    );
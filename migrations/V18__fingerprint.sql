@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS fingerprint (
+    device INTEGER NOT NULL,
+    inode INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    PRIMARY KEY (device, inode)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS layout_deviation (
+    hash TEXT PRIMARY KEY,
+    layout TEXT NOT NULL,
+    detected_at TEXT NOT NULL
+);
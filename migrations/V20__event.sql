@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS event (
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (start_date, end_date)
+);
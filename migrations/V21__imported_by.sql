@@ -0,0 +1,2 @@
+ALTER TABLE library ADD COLUMN imported_by TEXT;
+ALTER TABLE operation ADD COLUMN imported_by TEXT;
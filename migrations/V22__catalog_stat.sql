@@ -0,0 +1,2 @@
+ALTER TABLE catalog ADD COLUMN size INTEGER;
+ALTER TABLE catalog ADD COLUMN modified INTEGER;
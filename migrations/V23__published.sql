@@ -0,0 +1,8 @@
+CREATE TABLE published (
+    target TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    remote_id TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    published_at TEXT NOT NULL,
+    PRIMARY KEY (target, hash)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE sync_state (
+    target TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    synced_hash TEXT NOT NULL,
+    synced_at TEXT NOT NULL,
+    PRIMARY KEY (target, hash)
+);
+
+INSERT INTO sync_state (target, hash, synced_hash, synced_at)
+SELECT 'publish:' || target, hash, hash, published_at FROM published;
@@ -0,0 +1,8 @@
+CREATE TABLE trashed (
+    path TEXT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    original_path TEXT NOT NULL,
+    trashed_at TEXT NOT NULL
+);
+
+CREATE INDEX trashed_hash ON trashed (hash);
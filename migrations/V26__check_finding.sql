@@ -0,0 +1,8 @@
+CREATE TABLE check_finding (
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    status TEXT NOT NULL,
+    found_at TEXT NOT NULL,
+    PRIMARY KEY (name, path)
+);
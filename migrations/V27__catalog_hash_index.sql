@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS catalog_hash ON catalog (hash);
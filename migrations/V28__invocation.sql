@@ -0,0 +1,5 @@
+CREATE TABLE invocation (
+    command TEXT PRIMARY KEY,
+    arguments TEXT NOT NULL,
+    invoked_at TEXT NOT NULL
+);
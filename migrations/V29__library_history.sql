@@ -0,0 +1,29 @@
+CREATE TABLE library_history (
+    hash TEXT NOT NULL,
+    path TEXT NOT NULL,
+    change TEXT NOT NULL,
+    changed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS library_history_changed_at ON library_history (changed_at);
+
+INSERT INTO library_history (hash, path, change, changed_at)
+SELECT hash, path, 'import', imported_at FROM library ORDER BY rowid;
+
+CREATE TRIGGER library_history_import AFTER INSERT ON library
+BEGIN
+    INSERT INTO library_history (hash, path, change, changed_at)
+    VALUES (NEW.hash, NEW.path, 'import', COALESCE(NEW.imported_at, strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime')));
+END;
+
+CREATE TRIGGER library_history_move AFTER UPDATE OF path ON library WHEN OLD.path IS NOT NEW.path
+BEGIN
+    INSERT INTO library_history (hash, path, change, changed_at)
+    VALUES (NEW.hash, NEW.path, 'move', strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime'));
+END;
+
+CREATE TRIGGER library_history_remove AFTER DELETE ON library
+BEGIN
+    INSERT INTO library_history (hash, path, change, changed_at)
+    VALUES (OLD.hash, OLD.path, 'remove', strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime'));
+END;
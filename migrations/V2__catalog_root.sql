@@ -0,0 +1,3 @@
+CREATE TABLE IF NOT EXISTS catalog_root (
+    path TEXT PRIMARY KEY
+);
@@ -0,0 +1,4 @@
+CREATE TABLE sidecar (
+    hash TEXT PRIMARY KEY,
+    path TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE pair (
+    raw TEXT NOT NULL,
+    companion TEXT NOT NULL,
+    PRIMARY KEY (raw, companion)
+);
+
+CREATE INDEX pair_companion ON pair (companion);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS saved_query (
+    name TEXT PRIMARY KEY,
+    terms TEXT NOT NULL,
+    saved_at TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE library ADD COLUMN date_source TEXT;
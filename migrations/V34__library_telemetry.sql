@@ -0,0 +1,3 @@
+ALTER TABLE library ADD COLUMN altitude REAL;
+ALTER TABLE library ADD COLUMN direction REAL;
+ALTER TABLE library ADD COLUMN speed REAL;
@@ -0,0 +1,7 @@
+CREATE TABLE stack (
+    hash TEXT PRIMARY KEY,
+    stack TEXT NOT NULL,
+    kind TEXT NOT NULL
+);
+
+CREATE INDEX stack_stack ON stack (stack);
@@ -0,0 +1 @@
+ALTER TABLE trashed ADD COLUMN redundant INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE library ADD COLUMN mime_type TEXT;
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS problem (
+    path TEXT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    description TEXT NOT NULL
+);
@@ -0,0 +1,26 @@
+CREATE VIEW IF NOT EXISTS v_photos AS
+SELECT
+    library.path,
+    library.hash,
+    library.mime_type,
+    library.original_date,
+    strftime('%Y', library.original_date) AS year
+FROM library;
+
+CREATE VIEW IF NOT EXISTS v_duplicates AS
+SELECT
+    catalog.hash,
+    catalog.path,
+    copies.count AS copies
+FROM catalog
+JOIN (SELECT hash, COUNT(path) AS count FROM catalog GROUP BY hash HAVING COUNT(path) > 1) AS copies
+    ON catalog.hash = copies.hash;
+
+CREATE VIEW IF NOT EXISTS v_pending_import AS
+SELECT
+    catalog.path,
+    catalog.hash
+FROM catalog
+LEFT JOIN library ON catalog.hash = library.hash
+WHERE library.hash IS NULL
+    AND catalog.path NOT IN (SELECT path FROM problem);
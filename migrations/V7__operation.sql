@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS operation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command TEXT NOT NULL,
+    argument TEXT,
+    started_at TEXT NOT NULL
+);
+
+ALTER TABLE catalog ADD COLUMN operation INTEGER REFERENCES operation (id);
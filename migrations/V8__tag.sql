@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS tag (
+    hash TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (hash, name)
+);
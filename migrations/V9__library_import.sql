@@ -0,0 +1,2 @@
+ALTER TABLE library ADD COLUMN imported_at TEXT;
+ALTER TABLE library ADD COLUMN size INTEGER;
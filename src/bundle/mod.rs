@@ -0,0 +1,151 @@
+use std::{
+    fs::{copy, create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{catalog_entry::CatalogEntry, common::sha256_digest};
+
+const BUNDLE_FILE: &str = "bundle.json";
+const FILES_DIRECTORY: &str = "files";
+
+/// Catalog entries carried from one repository to another, written as
+/// `bundle.json` in a directory along with an optional copy of the files.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct CatalogBundle {
+    /// The last operation of the exporting repository, to pass to the next
+    /// `--since`.
+    pub(crate) last_operation: Option<i64>,
+    pub(crate) entries: Vec<BundleEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct BundleEntry {
+    sha256: String,
+    path: String,
+    /// The copy of the file, relative to the bundle directory.
+    file: Option<String>,
+}
+
+impl CatalogBundle {
+    /// Writes the entries in the bundle directory, copying the local files when
+    /// `with_files` is set.
+    pub(crate) fn write(
+        directory: &Path,
+        entries: &[CatalogEntry],
+        last_operation: Option<i64>,
+        with_files: bool,
+    ) -> Result<Self> {
+        create_dir_all(directory.join(FILES_DIRECTORY))?;
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let file = if with_files && !entry.is_remote() {
+                    let file = bundled_file(entry);
+                    copy(entry.path(), directory.join(&file))
+                        .wrap_err_with(|| format!("Failed to bundle {}", entry.path().display()))?;
+                    Some(file)
+                } else {
+                    None
+                };
+                Ok(BundleEntry {
+                    sha256: entry.sha256().to_owned(),
+                    path: entry.path().to_string_lossy().to_string(),
+                    file,
+                })
+            })
+            .collect::<Result<Vec<BundleEntry>>>()?;
+        let bundle = Self {
+            last_operation,
+            entries,
+        };
+        write(
+            directory.join(BUNDLE_FILE),
+            serde_json::to_string_pretty(&bundle)?,
+        )?;
+        Ok(bundle)
+    }
+
+    pub(crate) fn read(directory: &Path) -> Result<Self> {
+        let path = directory.join(BUNDLE_FILE);
+        let content =
+            read_to_string(&path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The entries to catalog: the bundled copy of a file, verified against its
+    /// digest, when there is one and the original path otherwise.
+    pub(crate) fn catalog_entries(&self, directory: &Path) -> Result<Vec<CatalogEntry>> {
+        self.entries
+            .iter()
+            .map(|entry| match &entry.file {
+                Some(file) => {
+                    let path = directory.join(file);
+                    if sha256_digest(&path)? != entry.sha256 {
+                        return Err(eyre!("{} does not match its digest", path.display()));
+                    }
+                    Ok(CatalogEntry::new(
+                        entry.sha256.clone(),
+                        path.to_string_lossy().to_string(),
+                    ))
+                }
+                None => Ok(CatalogEntry::new(entry.sha256.clone(), entry.path.clone())),
+            })
+            .collect()
+    }
+}
+
+/// The copy of a file in a bundle is named after its digest, keeping its extension.
+fn bundled_file(entry: &CatalogEntry) -> String {
+    let mut file = PathBuf::from(FILES_DIRECTORY).join(entry.sha256());
+    if let Some(extension) = entry.path().extension() {
+        file.set_extension(extension);
+    }
+    file.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use crate::database::catalog_entry::CatalogEntry;
+
+    use super::CatalogBundle;
+
+    #[test]
+    fn catalog_entries_keep_the_original_paths_without_files() {
+        let directory = TempDir::new().unwrap();
+        let entries = vec![CatalogEntry::new(
+            "AB".to_owned(),
+            "/card/a.jpeg".to_owned(),
+        )];
+
+        CatalogBundle::write(directory.path(), &entries, Some(3), false).unwrap();
+        let bundle = CatalogBundle::read(directory.path()).unwrap();
+
+        assert_eq!(Some(3), bundle.last_operation);
+        assert_eq!(entries, bundle.catalog_entries(directory.path()).unwrap());
+    }
+
+    #[test]
+    fn catalog_entries_point_to_the_bundled_files() {
+        let directory = TempDir::new().unwrap();
+        let file: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        let entry = CatalogEntry::try_from(&file).unwrap();
+
+        CatalogBundle::write(directory.path(), &[entry], None, true).unwrap();
+        let entries = CatalogBundle::read(directory.path())
+            .unwrap()
+            .catalog_entries(directory.path())
+            .unwrap();
+
+        assert!(entries[0]
+            .path()
+            .starts_with(directory.path().join("files")));
+        assert_eq!("jpeg", entries[0].path().extension().unwrap());
+    }
+}
@@ -1,12 +1,36 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io::BufRead,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use clap::{ArgMatches, Command};
-use eyre::Result;
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use clap::{arg, Arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::repository::Repository;
 
 pub(crate) trait SubApplication {
     fn name(&self) -> &'static str;
     fn command(&self) -> Command;
-    fn handle(&self, matches: &ArgMatches) -> Result<()>;
+    /// Handles the command against the repository located from the global
+    /// arguments, along with its database connection.
+    fn handle(&self, matches: &ArgMatches, repository: &Repository) -> Result<()>;
+
+    /// Whether the command diagnoses a repository whose configuration or database
+    /// may be broken, so that they are neither loaded nor opened before it runs.
+    fn diagnoses(&self) -> bool {
+        false
+    }
+
+    /// The long flags remembered from the previous run for `--again`, such as
+    /// `jobs` and the filters. The commands remembering none, the read-only ones,
+    /// are not recorded.
+    fn remembered(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 pub(crate) struct SubCommandHolder {
@@ -25,21 +49,559 @@ impl SubCommandHolder {
         self
     }
 
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.sub_commands.contains_key(name)
+    }
+
+    pub(crate) fn diagnoses(&self, name: &str) -> bool {
+        self.sub_commands.get(name).is_some_and(|c| c.diagnoses())
+    }
+
+    pub(crate) fn remembered(&self, name: &str) -> &'static [&'static str] {
+        self.sub_commands
+            .get(name)
+            .map(|c| c.remembered())
+            .unwrap_or_default()
+    }
+
     pub(crate) fn enrich_command(&self, mut command: Command) -> Command {
         for sub_command in self.sub_commands.values() {
-            command = command.subcommand(sub_command.command());
+            // Lets the arguments given on the command line override the configured defaults.
+            command = command.subcommand(sub_command.command().args_override_self(true));
         }
         command
     }
 
-    pub(crate) fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
+    pub(crate) fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
         let sub_command = sub_matches.subcommand();
         match sub_command {
             Some((name, sub_matches)) => match self.sub_commands.get(name) {
-                Some(command) => command.handle(sub_matches),
+                Some(command) => command.handle(sub_matches, repository),
                 None => unreachable!("Unsupported subcommand `{name}`"),
             },
             None => unreachable!("Missing subcommand."),
         }
     }
 }
+
+/// Returns the position of the subcommand name in `args`, skipping the binary name
+/// and the options of `command` along with their values.
+pub(crate) fn subcommand_position(command: &Command, args: &[OsString]) -> Option<usize> {
+    let mut position = 1;
+    while position < args.len() {
+        let arg = args[position].to_string_lossy();
+        match arg.strip_prefix("--") {
+            Some(long) if !long.contains('=') && takes_value(command, long) => position += 2,
+            Some(_) => position += 1,
+            None => return Some(position),
+        }
+    }
+    None
+}
+
+/// The arguments of the long flags among `args`, given to `command`, along with
+/// their values. The other flags and the positional arguments are left out.
+pub(crate) fn flag_arguments(command: &Command, args: &[OsString], longs: &[&str]) -> Vec<String> {
+    let mut flags = vec![];
+    let mut args = args.iter().map(|a| a.to_string_lossy().to_string());
+    while let Some(arg) = args.next() {
+        let Some(long) = arg.strip_prefix("--") else {
+            continue;
+        };
+        let (long, inline) = match long.split_once('=') {
+            Some((long, _)) => (long, true),
+            None => (long, false),
+        };
+        let value = if !inline && takes_value(command, long) {
+            args.next()
+        } else {
+            None
+        };
+        if longs.contains(&long) {
+            flags.push(arg.clone());
+            flags.extend(value);
+        }
+    }
+    flags
+}
+
+/// The flag making a subcommand read its targets from the standard input.
+pub(crate) fn stdin_arg() -> Arg {
+    arg!(--stdin "Reads the target paths or sha256 digests from the standard input, one per line")
+}
+
+/// The flag skipping the confirmation token of large permanent deletions.
+pub(crate) fn confirm_arg() -> Arg {
+    arg!(--"i-know-what-i-am-doing" "Deletes without asking for the confirmation token, whatever the number of files")
+}
+
+/// A token for the deletion of the files, hard to type by accident or from a script
+/// written beforehand.
+pub(crate) fn confirmation_token(files: usize) -> String {
+    format!(
+        "delete-{}-{:04}",
+        files,
+        Local::now().timestamp_subsec_nanos() % 10_000
+    )
+}
+
+/// Asks to type the token on the reader before deleting the files permanently.
+pub(crate) fn confirm_deletion(
+    files: usize,
+    bytes: u64,
+    token: &str,
+    mut reader: impl BufRead,
+) -> Result<()> {
+    println!(
+        "This permanently deletes {} files ({} bytes). Type {} to confirm:",
+        files, bytes, token
+    );
+    let mut typed = String::new();
+    reader.read_line(&mut typed)?;
+    if typed.trim() == token {
+        Ok(())
+    } else {
+        Err(eyre!("Confirmation token mismatch, nothing was deleted"))
+    }
+}
+
+/// The flag skipping the confirmation of the files moved to the trash.
+pub(crate) fn yes_arg() -> Arg {
+    arg!(--yes "Moves the files to the trash without asking for confirmation")
+}
+
+/// Asks the question on the reader, true when answered y or yes.
+pub(crate) fn confirm(question: &str, mut reader: impl BufRead) -> Result<bool> {
+    println!("{} [y/N]", question);
+    let mut answer = String::new();
+    reader.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// A picture designated on the standard input of a pipeline stage, by path or by
+/// sha256 digest.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Target {
+    Hash(String),
+    Path(PathBuf),
+}
+
+impl Target {
+    fn parse(line: &str) -> Self {
+        if line.len() == 64 && line.chars().all(|c| c.is_ascii_hexdigit()) {
+            Self::Hash(line.to_uppercase())
+        } else {
+            Self::Path(PathBuf::from(line))
+        }
+    }
+
+    pub(crate) fn matches(&self, sha256: &str, path: &Path) -> bool {
+        match self {
+            Self::Hash(hash) => hash.eq_ignore_ascii_case(sha256),
+            Self::Path(target) => target == path,
+        }
+    }
+
+    /// Resolves a path target with `resolve`, leaving hashes untouched.
+    pub(crate) fn map_path(self, resolve: impl Fn(PathBuf) -> Result<PathBuf>) -> Result<Self> {
+        match self {
+            Self::Path(path) => Ok(Self::Path(resolve(path)?)),
+            hash => Ok(hash),
+        }
+    }
+}
+
+/// Reads the non blank lines of the reader as targets.
+pub(crate) fn read_targets(reader: impl BufRead) -> Result<Vec<Target>> {
+    let mut targets = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            targets.push(Target::parse(line));
+        }
+    }
+    Ok(targets)
+}
+
+/// Parses a byte size such as `700MB`, `23GB` or `4GiB`. Units are decimal (`KB`,
+/// `MB`, `GB`, `TB`) or binary (`KiB`, `MiB`, `GiB`, `TiB`); no unit means bytes.
+pub(crate) fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        "TIB" => 1 << 40,
+        _ => return Err(format!("Unknown size unit `{}`", unit)),
+    };
+    number
+        .parse::<f64>()
+        .map(|n| (n * multiplier as f64) as u64)
+        .map_err(|_| format!("Invalid size `{}`", size))
+}
+
+/// Parses a duration such as `2h`, `45m` or `1h30m`. Units are `s`, `m`, `h` and
+/// `d`.
+pub(crate) fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration `{}`, expected e.g. 2h or 1h30m", duration);
+    let mut seconds = 0;
+    let mut rest = duration.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number = rest[..split].parse::<u64>().map_err(|_| invalid())?;
+        let unit = rest[split..].chars().next().expect("found");
+        seconds += number
+            * match unit {
+                's' => 1,
+                'm' => 60,
+                'h' => 3_600,
+                'd' => 86_400,
+                _ => return Err(invalid()),
+            };
+        rest = &rest[split + unit.len_utf8()..];
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parses an altitude in meters such as `100` or `100m`, or in feet such as
+/// `330ft`, into meters, negative below the sea level.
+pub(crate) fn parse_altitude(altitude: &str) -> Result<f64, String> {
+    parse_measure(altitude, &[("m", 1.0), ("ft", 0.3048)]).ok_or_else(|| {
+        format!(
+            "Invalid altitude `{}`, expected e.g. 100m or 330ft",
+            altitude
+        )
+    })
+}
+
+/// Parses a speed in km/h such as `30` or `30km/h`, or in `mph` or knots such as
+/// `15kn`, into km/h.
+pub(crate) fn parse_speed(speed: &str) -> Result<f64, String> {
+    parse_measure(
+        speed,
+        &[
+            ("km/h", 1.0),
+            ("kmh", 1.0),
+            ("mph", 1.609_344),
+            ("kn", 1.852),
+        ],
+    )
+    .ok_or_else(|| format!("Invalid speed `{}`, expected e.g. 30km/h or 20mph", speed))
+}
+
+/// The number, possibly followed by one of the units, times the factor of its unit.
+fn parse_measure(measure: &str, units: &[(&str, f64)]) -> Option<f64> {
+    let measure = measure.trim().to_lowercase();
+    let (number, factor) = units
+        .iter()
+        .find_map(|(unit, factor)| Some((measure.strip_suffix(unit)?, *factor)))
+        .unwrap_or((&measure, 1.0));
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(|n| n * factor)
+}
+
+/// Parses a date such as `2024-07-15`, `2024-07` or `2024` (their first day),
+/// `15/07/2024` (`07/15/2024` in `en_US` locales), `today`, `yesterday`,
+/// `last-week`, `last-month`, `last-year`, or an age such as `3d`, `2w`, `6m` or
+/// `1y`.
+pub(crate) fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    parse_date_on(date, Local::now().date_naive(), month_first_locale(), false)
+}
+
+/// Parses a date as [`parse_date`] does, as the inclusive end of a range: `2024-07`
+/// and `2024` are their last day.
+pub(crate) fn parse_date_end(date: &str) -> Result<NaiveDate, String> {
+    parse_date_on(date, Local::now().date_naive(), month_first_locale(), true)
+}
+
+/// Parses the date, the partial ones resolving to their last day rather than their
+/// first when `end`.
+fn parse_date_on(
+    date: &str,
+    today: NaiveDate,
+    month_first: bool,
+    end: bool,
+) -> Result<NaiveDate, String> {
+    let invalid = || {
+        format!(
+            "Invalid date `{}`, expected e.g. 2024-07-15, 2024-07, yesterday, last-week or 3d",
+            date
+        )
+    };
+    let date = date.trim().to_lowercase();
+    let ago = |days: u64, months: u32| {
+        today
+            .checked_sub_days(Days::new(days))
+            .and_then(|d| d.checked_sub_months(Months::new(months)))
+            .ok_or_else(invalid)
+    };
+    match date.as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return ago(1, 0),
+        "last-week" => return ago(7, 0),
+        "last-month" => return ago(0, 1),
+        "last-year" => return ago(0, 12),
+        _ => {}
+    }
+    if let Some(unit) = date.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        let number = date[..date.len() - 1]
+            .parse::<u32>()
+            .map_err(|_| invalid())?;
+        return match unit {
+            'd' => ago(number.into(), 0),
+            'w' => ago(u64::from(number) * 7, 0),
+            'm' => ago(0, number),
+            'y' => ago(0, number.checked_mul(12).ok_or_else(invalid)?),
+            _ => Err(invalid()),
+        };
+    }
+    let parts = date
+        .split(['-', '/', '.'])
+        .map(|p| p.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<u32>, String>>()?;
+    // The months of the partial dates, which the end of a range covers whole.
+    let (year, month, day, months) = match (parts.as_slice(), date.contains('-')) {
+        ([year], _) => (*year, 1, 1, 12),
+        ([year, month], true) => (*year, *month, 1, 1),
+        ([year, month, day], true) => (*year, *month, *day, 0),
+        ([first, second, year], false) if month_first => (*year, *first, *second, 0),
+        ([first, second, year], false) => (*year, *second, *first, 0),
+        _ => return Err(invalid()),
+    };
+    i32::try_from(year)
+        .ok()
+        .and_then(|year| NaiveDate::from_ymd_opt(year, month, day))
+        .filter(|d| d.year() >= 1000)
+        .and_then(|d| match (end, months) {
+            (true, 1..) => d.checked_add_months(Months::new(months))?.pred_opt(),
+            _ => Some(d),
+        })
+        .ok_or_else(invalid)
+}
+
+/// True when the locale of the user writes the month before the day.
+fn month_first_locale() -> bool {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|locale| locale.starts_with("en_US"))
+}
+
+fn takes_value(command: &Command, long: &str) -> bool {
+    command
+        .get_arguments()
+        .any(|a| a.get_long() == Some(long) && a.get_action().takes_values())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use chrono::NaiveDate;
+    use clap::{Arg, ArgAction, Command};
+
+    use super::{
+        confirm, confirm_deletion, confirmation_token, flag_arguments, parse_altitude,
+        parse_date_on, parse_duration, parse_size, parse_speed, read_targets, subcommand_position,
+        Target,
+    };
+
+    #[test]
+    fn confirm_deletion_requires_the_exact_token() {
+        let token = confirmation_token(120);
+
+        assert!(token.starts_with("delete-120-"));
+        assert!(confirm_deletion(120, 0, &token, format!("{}\n", token).as_bytes()).is_ok());
+        assert!(confirm_deletion(120, 0, &token, "yes\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn confirm_only_accepts_yes() {
+        assert!(confirm("Move?", "y\n".as_bytes()).unwrap());
+        assert!(confirm("Move?", "YES\n".as_bytes()).unwrap());
+        assert!(!confirm("Move?", "\n".as_bytes()).unwrap());
+        assert!(!confirm("Move?", "".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn read_targets_distinguishes_hashes_from_paths() {
+        let hash = "9ad609d6722147ac7d9ad368f55f743af0329eb258df7e507bd1b22ff01355eb";
+        let targets = read_targets(format!("{}\n\n/a/b.jpeg\n", hash).as_bytes()).unwrap();
+
+        assert_eq!(
+            vec![
+                Target::Hash(hash.to_uppercase()),
+                Target::Path(PathBuf::from("/a/b.jpeg"))
+            ],
+            targets
+        );
+        assert!(targets[0].matches(hash, Path::new("/c.jpeg")));
+        assert!(targets[1].matches("AB", Path::new("/a/b.jpeg")));
+    }
+
+    #[test]
+    fn parse_size_reads_decimal_and_binary_units() {
+        assert_eq!(Ok(23_000_000_000), parse_size("23GB"));
+        assert_eq!(Ok(4_700_000_000), parse_size("4.7GB"));
+        assert_eq!(Ok(2 << 30), parse_size("2GiB"));
+        assert_eq!(Ok(512), parse_size("512"));
+    }
+
+    #[test]
+    fn parse_duration_adds_the_units() {
+        assert_eq!(Ok(Duration::from_secs(5_400)), parse_duration("1h30m"));
+        assert_eq!(Ok(Duration::from_secs(45)), parse_duration("45s"));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("2w").is_err());
+    }
+
+    #[test]
+    fn parse_altitude_and_speed_convert_the_units() {
+        assert_eq!(Ok(100.0), parse_altitude("100m"));
+        assert_eq!(Ok(-20.0), parse_altitude("-20"));
+        assert!((parse_altitude("1000ft").unwrap() - 304.8).abs() < 1e-9);
+        assert!(parse_altitude("high").is_err());
+        assert_eq!(Ok(30.0), parse_speed("30km/h"));
+        assert!((parse_speed("10kn").unwrap() - 18.52).abs() < 1e-9);
+        assert!(parse_speed("10m").is_err());
+    }
+
+    #[test]
+    fn parse_date_accepts_partial_and_relative_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 31).unwrap();
+        let parse = |date| {
+            parse_date_on(date, today, false, false)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!("2024-07-15", parse("2024-07-15"));
+        assert_eq!("2024-07-01", parse("2024-07"));
+        assert_eq!("2023-01-01", parse("2023"));
+        assert_eq!("2024-07-30", parse("yesterday"));
+        assert_eq!("2024-07-24", parse("last-week"));
+        assert_eq!("2024-07-28", parse("3d"));
+        assert_eq!("2024-06-30", parse("1m"));
+        assert_eq!("2024-07-08", parse("08/07/2024"));
+        assert_eq!(
+            "2024-08-07",
+            parse_date_on("08/07/2024", today, true, false)
+                .unwrap()
+                .to_string()
+        );
+        assert!(parse_date_on("2024-13", today, false, false).is_err());
+        assert!(parse_date_on("3x", today, false, false).is_err());
+        assert!(parse_date_on("24", today, false, false).is_err());
+    }
+
+    #[test]
+    fn parse_date_end_resolves_partial_dates_to_their_last_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 31).unwrap();
+        let parse = |date| parse_date_on(date, today, false, true).unwrap().to_string();
+
+        assert_eq!("2024-07-31", parse("2024-07"));
+        assert_eq!("2024-02-29", parse("2024-02"));
+        assert_eq!("2023-12-31", parse("2023"));
+        assert_eq!("2024-07-15", parse("2024-07-15"));
+        assert_eq!("2024-07-30", parse("yesterday"));
+        assert!(parse_date_on("2024-13", today, false, true).is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_unknown_units() {
+        assert_eq!(Err("Unknown size unit `XB`".to_string()), parse_size("1XB"));
+    }
+
+    fn given_a_command() -> Command {
+        Command::new("root")
+            .arg(Arg::new("profile").long("profile"))
+            .arg(
+                Arg::new("verbose")
+                    .long("verbose")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("test"))
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn subcommand_position_skips_the_options_and_their_values() {
+        assert_eq!(
+            Some(4),
+            subcommand_position(
+                &given_a_command(),
+                &args(&["root", "--profile", "test", "--verbose", "test"])
+            )
+        );
+    }
+
+    #[test]
+    fn subcommand_position_skips_options_with_inline_values() {
+        assert_eq!(
+            Some(2),
+            subcommand_position(&given_a_command(), &args(&["root", "--profile=a", "test"]))
+        );
+    }
+
+    #[test]
+    fn flag_arguments_keeps_the_flags_along_with_their_values() {
+        let command = Command::new("test")
+            .arg(Arg::new("jobs").long("jobs"))
+            .arg(Arg::new("yes").long("yes").action(ArgAction::SetTrue))
+            .arg(Arg::new("exclude").long("exclude"))
+            .arg(Arg::new("PATH"));
+
+        assert_eq!(
+            vec!["--jobs", "4", "--exclude=*.gif"],
+            flag_arguments(
+                &command,
+                &args(&[
+                    "--yes",
+                    "--jobs",
+                    "4",
+                    "/card",
+                    "--exclude=*.gif",
+                    "--other",
+                    "x"
+                ]),
+                &["jobs", "exclude"]
+            )
+        );
+    }
+
+    #[test]
+    fn subcommand_position_is_none_without_subcommand() {
+        assert_eq!(
+            None,
+            subcommand_position(&given_a_command(), &args(&["root", "--verbose"]))
+        );
+    }
+}
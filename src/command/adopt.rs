@@ -0,0 +1,250 @@
+use std::{
+    collections::HashSet,
+    env::current_dir,
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::Result;
+use walkdir::WalkDir;
+
+use crate::{
+    clapext::SubApplication,
+    command::catalog::is_hidden_file_name,
+    config::current_user,
+    database::{
+        common::sha256_digest,
+        layout_deviation::record_deviations,
+        library::{foreach_entry, persist_library_entries, LibraryFilter},
+        library_entry::LibraryEntry,
+    },
+    media,
+    metadata::MetadataBackend,
+    naming::layout::{matching_layouts, Layout, LayoutDetection},
+    repository::Repository,
+};
+
+const ADOPT: &str = "adopt";
+
+pub(crate) struct Adopt;
+
+impl SubApplication for Adopt {
+    fn name(&self) -> &'static str {
+        ADOPT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Registers the pictures of a hand-organized repository tree in place and detects its layout")
+            .arg(arg!(--"dry-run" "Only reports the pictures to register and the layout"))
+            .arg(
+                arg!(--sample <COUNT> "The number of library paths the layout is detected on")
+                    .value_parser(value_parser!(usize))
+                    .default_value("1000"),
+            )
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        let mut connection = repository.open_database()?;
+        let dry_run = sub_matches.get_flag("dry-run");
+
+        let mut library = vec![];
+        foreach_entry(&connection, &LibraryFilter::default(), |e| {
+            library.push((e.sha256().to_owned(), e.path().to_owned()));
+            Ok(())
+        })?;
+        let imported_by = current_user()?;
+        let adopted = untracked_pictures(
+            &current_dir()?,
+            &library,
+            config.metadata().backend().as_ref(),
+        )?
+        .into_iter()
+        .map(|e| e.with_imported_by(imported_by.clone()))
+        .collect::<Vec<_>>();
+        if dry_run {
+            println!("Would register {} pictures", adopted.len());
+        } else {
+            println!(
+                "Registered {} pictures",
+                persist_library_entries(&mut connection, &adopted)?
+            );
+        }
+        library.extend(
+            adopted
+                .iter()
+                .map(|e| (e.sha256().to_owned(), e.path().to_owned())),
+        );
+
+        let mut paths = library.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>();
+        paths.sort();
+        let sample = *sub_matches.get_one::<usize>("sample").expect("defaulted");
+        let detection = LayoutDetection::sample(&paths, sample);
+        for (layout, count) in detection.counts() {
+            println!(
+                "{:>5.1}% {}",
+                100.0 * count as f64 / detection.sampled() as f64,
+                layout
+            );
+        }
+        let deviations = match detection.dominant() {
+            Some(Layout::Date(date_layout)) => {
+                println!(
+                    "Proposed layout: [library] date_layout = \"{}\"{}",
+                    date_layout,
+                    if Some(date_layout) == config.library_naming().date_layout() {
+                        ", as configured"
+                    } else {
+                        ""
+                    }
+                );
+                layout_deviations(&library, Layout::Date(date_layout))
+            }
+            Some(Layout::Event) => {
+                println!("Proposed layout: year then event name directories, which relayout doesn't produce; no deviation recorded");
+                vec![]
+            }
+            None => {
+                println!("No layout detected, the pictures are not in year directories");
+                vec![]
+            }
+        };
+        if dry_run {
+            println!("{} pictures deviate from the layout", deviations.len());
+        } else {
+            println!(
+                "Recorded {} pictures deviating from the layout, which relayout --deviations moves",
+                record_deviations(&mut connection, &deviations)?
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The library entries of the pictures under the root that the library doesn't
+/// track yet, at their current path. The contents already in the library, under
+/// another path, are left out.
+fn untracked_pictures(
+    root: &Path,
+    library: &[(String, PathBuf)],
+    backend: &dyn MetadataBackend,
+) -> Result<Vec<LibraryEntry>> {
+    let tracked = library.iter().map(|(_, p)| p).collect::<HashSet<_>>();
+    let mut hashes = library
+        .iter()
+        .map(|(h, _)| h.clone())
+        .collect::<HashSet<_>>();
+    let mut entries = vec![];
+    for path in WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !is_hidden_file_name(e.file_name()))
+        .filter_map(|e| e.ok().map(|f| f.into_path()))
+        .filter(|p| p.is_file())
+    {
+        let relative = path.strip_prefix(root)?.to_path_buf();
+        if tracked.contains(&relative) || media::detect(&path)?.is_none() {
+            continue;
+        }
+        let sha256 = sha256_digest(&path)?;
+        if !hashes.insert(sha256.clone()) {
+            println!("Skipping {}, already in the library", relative.display());
+            continue;
+        }
+        let original_name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string());
+        entries.push(
+            LibraryEntry::new(sha256, path)
+                .refreshed(backend)?
+                .with_path(relative)
+                .with_original_name(original_name),
+        );
+    }
+    Ok(entries)
+}
+
+/// The contents whose directory doesn't match the dominant layout, with the
+/// layouts it matches instead.
+fn layout_deviations(library: &[(String, PathBuf)], dominant: Layout) -> Vec<(String, String)> {
+    library
+        .iter()
+        .filter_map(|(sha256, path)| {
+            let layouts = matching_layouts(path);
+            (!layouts.contains(&dominant)).then(|| {
+                let names = layouts.iter().map(Layout::to_string).collect::<Vec<_>>();
+                (
+                    sha256.clone(),
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(",")
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        database::common::sha256_digest,
+        metadata::Exif,
+        naming::{layout::Layout, DateLayout},
+    };
+
+    use super::{layout_deviations, untracked_pictures};
+
+    #[test]
+    fn layout_deviations_lists_the_pictures_outside_the_dominant_layout() {
+        let library = [
+            ("AB", "2023/05/08/a.jpg"),
+            ("CD", "2023/11/12/b.jpg"),
+            ("EF", "2023/5/8/c.jpg"),
+            ("GH", "2023/Rome/d.jpg"),
+            ("IJ", "misc/e.jpg"),
+        ]
+        .map(|(h, p)| (h.to_string(), PathBuf::from(p)));
+
+        assert_eq!(
+            vec![
+                ("EF".to_string(), "unpadded".to_string()),
+                ("GH".to_string(), "event".to_string()),
+                ("IJ".to_string(), "none".to_string()),
+            ],
+            layout_deviations(&library, Layout::Date(DateLayout::Padded))
+        );
+    }
+
+    #[test]
+    fn untracked_pictures_skips_the_tracked_paths_and_contents() {
+        let root = tempfile::TempDir::new().unwrap();
+        let picture = fs::read("resources/test/kami_neko.jpeg").unwrap();
+        fs::create_dir_all(root.path().join("2023/Rome")).unwrap();
+        fs::create_dir_all(root.path().join(".photo_works")).unwrap();
+        fs::write(root.path().join("2023/Rome/a.jpg"), &picture).unwrap();
+        fs::write(root.path().join("2023/Rome/copy.jpg"), &picture).unwrap();
+        fs::write(root.path().join(".photo_works/b.jpg"), &picture).unwrap();
+        fs::write(root.path().join("2023/Rome/notes.txt"), "notes").unwrap();
+
+        let entries = untracked_pictures(root.path(), &[], &Exif).unwrap();
+        assert_eq!(
+            vec![PathBuf::from("2023/Rome/a.jpg")],
+            entries.iter().map(|e| e.path().clone()).collect::<Vec<_>>()
+        );
+
+        let tracked = [(
+            sha256_digest(&root.path().join("2023/Rome/a.jpg")).unwrap(),
+            PathBuf::from("2023/Rome/a.jpg"),
+        )];
+        assert!(untracked_pictures(root.path(), &tracked, &Exif)
+            .unwrap()
+            .is_empty());
+    }
+}
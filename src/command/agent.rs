@@ -0,0 +1,86 @@
+use std::{
+    io::{stdout, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+use walkdir::WalkDir;
+
+use crate::{
+    clapext::SubApplication, command::catalog::is_hidden_file_name,
+    database::common::sha256_digest, remote::AgentRecord, repository::Repository,
+};
+
+const AGENT: &str = "agent";
+
+pub(crate) struct Agent;
+
+impl SubApplication for Agent {
+    fn name(&self) -> &'static str {
+        AGENT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Hashes a directory on this host for a remote catalog, streaming one JSON line per file")
+            .arg(arg!(<PATH> "The directory to hash"))
+            .arg_required_else_help(true)
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, _: &Repository) -> Result<()> {
+        let path = sub_matches.get_one::<String>("PATH").expect("required");
+        let mut output = stdout().lock();
+        for file in WalkDir::new(path)
+            .into_iter()
+            .filter_entry(|e| !is_hidden_file_name(e.file_name()))
+            .filter_map(|e| e.ok().map(|f| f.into_path()))
+            .filter(|p| p.is_file())
+        {
+            match record(&file) {
+                Ok(record) => writeln!(output, "{}", serde_json::to_string(&record)?)?,
+                Err(e) => eprintln!("Failed to process {}: {}", file.display(), e),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn record(file: &Path) -> Result<AgentRecord> {
+    let metadata = file.metadata()?;
+    Ok(AgentRecord {
+        path: file.to_string_lossy().to_string(),
+        sha256: sha256_digest(file)?,
+        size: metadata.len(),
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{clapext::SubApplication, database::common::sha256_digest};
+
+    use super::{record, Agent};
+
+    #[test]
+    fn command_is_consistent() {
+        Agent.command().debug_assert();
+    }
+
+    #[test]
+    fn record_hashes_and_stats_the_file() {
+        let file: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+
+        let record = record(&file).unwrap();
+
+        assert_eq!(sha256_digest(&file).unwrap(), record.sha256);
+        assert_eq!(file.metadata().unwrap().len(), record.size);
+    }
+}
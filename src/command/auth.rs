@@ -0,0 +1,62 @@
+use clap::{arg, ArgMatches, Command};
+use dialoguer::Password;
+use eyre::Result;
+
+use crate::{clapext::SubApplication, repository::Repository, secrets};
+
+const AUTH: &str = "auth";
+
+pub(crate) struct Auth;
+
+impl SubApplication for Auth {
+    fn name(&self) -> &'static str {
+        AUTH
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Manages the secrets used by photo_works in the OS keyring")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("set")
+                    .about("Stores a secret, e.g. `s3` or `database`, prompting for its value.")
+                    .arg(arg!(<NAME> "The name of the secret")),
+                Command::new("status")
+                    .about("Reports whether a secret is available from the environment or the keyring.")
+                    .arg(arg!(<NAME> "The name of the secret")),
+                Command::new("remove")
+                    .about("Removes a secret from the keyring.")
+                    .arg(arg!(<NAME> "The name of the secret")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, _: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("set", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                let value = Password::new()
+                    .with_prompt(format!("Secret for {}", name))
+                    .interact()?;
+                secrets::store(name, &value)?;
+                println!("Stored secret {}", name);
+                Ok(())
+            }
+            Some(("status", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                match secrets::secret(name)? {
+                    Some(_) => println!("Secret {} is set", name),
+                    None => println!("Secret {} is not set", name),
+                }
+                Ok(())
+            }
+            Some(("remove", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                secrets::remove(name)?;
+                println!("Removed secret {}", name);
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
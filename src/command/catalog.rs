@@ -1,17 +1,69 @@
-use std::{ffi::OsStr, fs::canonicalize, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fmt::{self, Display},
+    fs::{canonicalize, read_to_string},
+    path::{absolute, Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
 
-use clap::{arg, ArgMatches, Command};
-use eyre::Result;
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use eyre::{eyre, Result};
+use glob::{MatchOptions, Pattern};
 use rusqlite::Connection;
 use walkdir::WalkDir;
 
 use crate::{
+    bundle::CatalogBundle,
     clapext::SubApplication,
-    database::{catalog::persist_catalog_entries, catalog_entry::CatalogEntry},
+    config::current_user,
+    database::{
+        catalog::{
+            merge_catalog_entries, persist_catalog_entries, persist_catalog_root,
+            save_catalog_stats, select_catalog_stats, select_cataloged_hashes,
+            update_catalog_entries,
+        },
+        catalog_entry::CatalogEntry,
+        fingerprint::{save_fingerprints, select_fingerprints, Fingerprint},
+        library::select_library_hashes,
+        operation::{
+            assign_operation, last_operation, previous_operation, select_catalog_since,
+            start_operation,
+        },
+        pair::record_pairs,
+        problem::{persist_problems, Problem},
+    },
+    fsext::{
+        managed::ManagedPaths,
+        source::{ensure_outside_sources, SourceSnapshot},
+    },
+    media::{self, pair::find_pairs, validation, MediaType},
+    progress::{file_size, Progress},
+    remote::RemoteSource,
+    repository::Repository,
 };
 
 const CATALOG: &str = "catalog";
 
+/// The image formats cataloged by default, along with the camera raw formats.
+const IMAGE_TYPES: [MediaType; 13] = [
+    media::JPEG,
+    media::PNG,
+    media::GIF,
+    media::WEBP,
+    media::BMP,
+    media::TIFF,
+    media::CR2,
+    media::CR3,
+    media::RAF,
+    media::ORF,
+    media::RW2,
+    media::HEIC,
+    media::AVIF,
+];
+
 pub(crate) struct Catalog;
 
 impl SubApplication for Catalog {
@@ -22,86 +74,1084 @@ impl SubApplication for Catalog {
     fn command(&self) -> Command {
         Command::new(self.name())
             .about("Catalogs a directory in a photo_works database")
-            .arg(arg!(<PATH> "The path to catalog"))
+            .arg(arg!(<PATH> "The path to catalog, or ssh://[user@]host[:port]/path for a remote directory"))
+            .arg(arg!(--"validate-images" "Scans the images structure and flags the corrupt ones"))
+            .arg(
+                arg!(--"include-ext" <EXT> "Also catalogs the files of the extension, e.g. mp4, besides the image formats")
+                    .action(ArgAction::Append)
+                    .value_delimiter(','),
+            )
+            .arg(
+                arg!(--"exclude-ext" <EXT> "Skips the files of the extension, e.g. gif")
+                    .action(ArgAction::Append)
+                    .value_delimiter(','),
+            )
+            .arg(
+                arg!(--exclude <GLOB> "Skips the paths matching the pattern, in the syntax of the .photo_worksignore file, e.g. @eaDir/ or Thumbs.db")
+                    .action(ArgAction::Append),
+            )
+            .arg(arg!(--"verify-source-untouched" "Verifies the modification times of the cataloged files did not change"))
+            .arg(arg!(--agent "Hashes remote files with photo_works agent on the host instead of sha256sum"))
+            .arg(arg!(--rehash "Hashes every file, even those whose device, inode, size and modification time are unchanged"))
+            .arg(arg!(--incremental "Also reuses the hash cataloged under a path while its size and modification time are unchanged, for cards whose inodes change with each mount").conflicts_with("rehash"))
+            .arg(
+                arg!(--jobs <JOBS> "The number of files hashed in parallel")
+                    .value_parser(value_parser!(usize))
+                    .default_value("4"),
+            )
+            .args_conflicts_with_subcommands(true)
+            .subcommand_negates_reqs(true)
+            .subcommands([
+                Command::new("export")
+                    .about("Writes the entries cataloged after an operation to a bundle directory.")
+                    .arg(arg!(<BUNDLE> "The bundle directory"))
+                    .arg(
+                        arg!(--since <OPERATION> "Only exports the entries of the later operations")
+                            .value_parser(clap::value_parser!(i64))
+                            .default_value("0"),
+                    )
+                    .arg(arg!(--"with-files" "Copies the cataloged files in the bundle")),
+                Command::new("apply")
+                    .about("Catalogs the entries of a bundle exported by another repository.")
+                    .arg(arg!(<BUNDLE> "The bundle directory")),
+            ])
             .arg_required_else_help(true)
     }
 
-    fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
-        let path = canonicalize(
-            sub_matches
-                .get_one::<String>("PATH")
-                .expect("required")
-                .as_str(),
+    fn remembered(&self) -> &'static [&'static str] {
+        &["jobs", "include-ext", "exclude-ext", "exclude"]
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("export", sub_matches)) => return export_bundle(sub_matches, repository),
+            Some(("apply", sub_matches)) => return apply_bundle(sub_matches, repository),
+            _ => {}
+        }
+        let path = sub_matches.get_one::<String>("PATH").expect("required");
+        if let Some(remote) = RemoteSource::parse(path)? {
+            let remote = remote.with_agent(sub_matches.get_flag("agent"));
+            repository.enter()?;
+            let _lock = repository.lock()?;
+            let mut connection = repository.open_database()?;
+
+            println!("Cataloging {}", remote.root_url());
+
+            println!(
+                "Cataloged {} remote pictures",
+                catalog_remote(&mut connection, &remote)?
+            );
+            return Ok(());
+        }
+        let path = canonicalize(path)?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        let inbox = repository.root().join(config.inbox().path());
+        let skipped = skipped_managed_directories(
+            &path,
+            &repository.managed_directories()?,
+            &canonicalize(&inbox).unwrap_or(inbox),
         )?;
-        let db_path: PathBuf = [".photo_works", "db.db3"].iter().collect();
-        let connection = Connection::open(db_path)?;
+        for directory in &skipped {
+            println!("Skipping {}, managed by photo_works", directory.display());
+        }
+        let sources = vec![path.clone()];
+        let db_path = repository.db_path();
+        if !skipped.iter().any(|d| db_path.starts_with(d)) {
+            ensure_outside_sources(&db_path, &sources)?;
+        }
+        let snapshot = if sub_matches.get_flag("verify-source-untouched") {
+            Some(SourceSnapshot::take_excluding(&sources, &skipped)?)
+        } else {
+            None
+        };
+        let mut connection = repository.open_database()?;
 
         println!("Cataloging {}", path.to_string_lossy());
 
-        Ok(println!(
+        println!(
             "Cataloged {} pictures",
-            catalog(connection, &path)?
-        ))
+            catalog(
+                &mut connection,
+                &path,
+                sub_matches.get_flag("validate-images"),
+                sub_matches.get_flag("rehash"),
+                sub_matches.get_flag("incremental"),
+                *sub_matches.get_one::<usize>("jobs").expect("defaulted"),
+                &FileFilter::read(sub_matches, &path)?.with_managed(
+                    skipped
+                        .into_iter()
+                        .fold(repository.managed_paths()?, ManagedPaths::with_directory)
+                ),
+            )?
+        );
+        match snapshot {
+            Some(snapshot) => snapshot.verify_untouched(&sources),
+            None => Ok(()),
+        }
     }
 }
 
-fn catalog(mut connection: Connection, path: &PathBuf) -> Result<usize> {
-    let entries = WalkDir::new(&PathBuf::from(path))
+fn export_bundle(sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+    let directory = absolute(sub_matches.get_one::<String>("BUNDLE").expect("required"))?;
+    let since = *sub_matches.get_one::<i64>("since").expect("defaulted");
+    repository.enter()?;
+    let connection = repository.open_database()?;
+
+    let entries = select_catalog_since(&connection, since)?;
+    let last_operation = last_operation(&connection)?;
+    CatalogBundle::write(
+        &directory,
+        &entries,
+        last_operation,
+        sub_matches.get_flag("with-files"),
+    )?;
+    println!(
+        "Exported {} entries to {}. Next time, export with --since {}",
+        entries.len(),
+        directory.display(),
+        last_operation.unwrap_or_default()
+    );
+    Ok(())
+}
+
+fn apply_bundle(sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+    let directory = canonicalize(sub_matches.get_one::<String>("BUNDLE").expect("required"))?;
+    repository.enter()?;
+    let _lock = repository.lock()?;
+    let mut connection = repository.open_database()?;
+
+    let entries = CatalogBundle::read(&directory)?.catalog_entries(&directory)?;
+    let operation = start_operation(
+        &connection,
+        "apply",
+        Some(&directory.to_string_lossy()),
+        current_user()?.as_deref(),
+    )?;
+    let count = merge_catalog_entries(&mut connection, &entries)?;
+    assign_operation(&connection, operation)?;
+    println!(
+        "Cataloged {} new entries of {} in operation {}",
+        count,
+        entries.len(),
+        operation
+    );
+    Ok(())
+}
+
+/// The managed directories under the cataloged path, which the catalog skips.
+/// Fails when the path is inside one of them, the inbox excepted: cataloging the
+/// library or the trash would let prune trash the files they duplicate.
+fn skipped_managed_directories(
+    path: &Path,
+    managed: &[PathBuf],
+    inbox: &Path,
+) -> Result<Vec<PathBuf>> {
+    if !path.starts_with(inbox) {
+        if let Some(directory) = managed.iter().find(|d| path.starts_with(d)) {
+            return Err(eyre!(
+                "Refusing to catalog {} inside {}, managed by photo_works",
+                path.display(),
+                directory.display()
+            ));
+        }
+    }
+    Ok(managed
+        .iter()
+        .filter(|d| d.starts_with(path))
+        .cloned()
+        .collect())
+}
+
+/// Catalogs the files of the directory, hashing them over `jobs` threads. The
+/// files whose fingerprint was recorded by a previous catalog reuse its hash
+/// instead of being read again, unless `rehash` is set. With `incremental`, so do
+/// the files whose path was cataloged with the same size and modification time.
+pub(crate) fn catalog(
+    connection: &mut Connection,
+    path: &PathBuf,
+    validate_images: bool,
+    rehash: bool,
+    incremental: bool,
+    jobs: usize,
+    filter: &FileFilter,
+) -> Result<usize> {
+    let known = KnownHashes {
+        fingerprints: if rehash {
+            HashMap::new()
+        } else {
+            select_fingerprints(connection)?
+        },
+        stats: if incremental {
+            select_catalog_stats(connection, &path.join("").to_string_lossy())?
+        } else {
+            HashMap::new()
+        },
+    };
+    let paths = WalkDir::new(PathBuf::from(path))
         .into_iter()
-        .filter_entry(|e| !is_hidden_file_name(e.file_name()))
+        .filter_entry(|e| {
+            e.depth() == 0
+                || (!is_hidden_file_name(e.file_name())
+                    && !filter.managed.contains(e.path())
+                    && !filter.ignored.is_ignored(
+                        e.path().strip_prefix(path).unwrap_or(e.path()),
+                        e.file_type().is_dir(),
+                    ))
+        })
         .filter_map(|e| e.ok().map(|f| f.into_path()))
         .filter(|p| p.is_file())
-        .map(|entry_path| {
-            let catalog_entry = (&entry_path).try_into();
-            if catalog_entry.is_err() {
-                println!("Failed to process {}", entry_path.display())
+        .collect::<Vec<PathBuf>>();
+    let (paths, skipped): (Vec<PathBuf>, Vec<PathBuf>) =
+        paths.into_iter().partition(|p| filter.accepts(p));
+    if !skipped.is_empty() {
+        println!(
+            "Skipped {} files of other extensions, pass --include-ext to catalog them",
+            skipped.len()
+        );
+    }
+    let mut fingerprints = vec![];
+    let mut stats = vec![];
+    let mut reused = 0;
+    let mut entries = vec![];
+    let hashed = {
+        let progress = Progress::start(
+            "Cataloging",
+            paths.len() as u64,
+            paths.iter().map(|p| file_size(p)).sum(),
+        );
+        hash_files(&paths, &known, jobs, &progress)
+    };
+    for (entry_path, hashed) in paths.iter().zip(hashed) {
+        match hashed {
+            Ok(Hashed {
+                entry,
+                fingerprint,
+                reused: known,
+            }) => {
+                if known {
+                    reused += 1;
+                }
+                if let Some(fingerprint) = fingerprint {
+                    fingerprints.push((fingerprint, entry.sha256().to_owned()));
+                    stats.push((
+                        entry.path().to_string_lossy().to_string(),
+                        fingerprint.size(),
+                        fingerprint.modified(),
+                    ));
+                }
+                entries.push(entry);
+            }
+            Err(_) => println!("Failed to process {}", entry_path.display()),
+        }
+    }
+    if reused > 0 {
+        println!("Reused the known hashes of {} unchanged files", reused);
+    }
+    save_fingerprints(connection, &fingerprints)?;
+    let pairs = find_pairs(&entries.iter().map(|e| e.path()).collect::<Vec<PathBuf>>())
+        .into_iter()
+        .map(|(raw, companion)| {
+            (
+                entries[raw].sha256().to_owned(),
+                entries[companion].sha256().to_owned(),
+            )
+        })
+        .collect::<Vec<(String, String)>>();
+    let paired = record_pairs(connection, &pairs)?;
+    if paired > 0 {
+        println!("Paired {} RAW pictures with the JPEG of their shot", paired);
+    }
+    let previous = previous_operation(connection, CATALOG, &path.to_string_lossy())?;
+    let (summary, entries, changed) = compare_with_catalog(
+        entries,
+        &select_cataloged_hashes(connection, &path.join("").to_string_lossy())?,
+        &select_library_hashes(connection)?,
+    );
+    if let Some((_, started_at)) = previous {
+        println!(
+            "Since the catalog of {}: {}",
+            started_at.format("%Y-%m-%d %H:%M"),
+            summary
+        );
+    }
+    persist_catalog_root(connection, path)?;
+    if validate_images {
+        let mut problems = find_corrupt_images(&entries);
+        problems.extend(find_corrupt_images(&changed));
+        println!("Flagged {} corrupt images", problems.len());
+        persist_problems(connection, &problems)?;
+    }
+    let updated = update_catalog_entries(connection, &changed)?;
+    let count = persist_as_operation(connection, &path.to_string_lossy(), &entries)?;
+    save_catalog_stats(connection, &stats)?;
+    Ok(count + updated)
+}
+
+/// The hashes a catalog reuses instead of reading the files again.
+#[derive(Default)]
+struct KnownHashes {
+    fingerprints: HashMap<Fingerprint, String>,
+    /// The `(size, modified, hash)` cataloged under each path.
+    stats: HashMap<String, (u64, i64, String)>,
+}
+
+impl KnownHashes {
+    fn get(&self, path: &Path, fingerprint: &Fingerprint) -> Option<&String> {
+        self.fingerprints.get(fingerprint).or_else(|| {
+            self.stats
+                .get(&*path.to_string_lossy())
+                .filter(|(size, modified, _)| {
+                    *size == fingerprint.size() && *modified == fingerprint.modified()
+                })
+                .map(|(_, _, hash)| hash)
+        })
+    }
+}
+
+/// A cataloged file with the fingerprint its hash is recorded under.
+struct Hashed {
+    entry: CatalogEntry,
+    fingerprint: Option<Fingerprint>,
+    /// Whether the hash of the fingerprint was reused rather than computed.
+    reused: bool,
+}
+
+/// Hashes the files over the jobs, in the order of the paths. Each job takes the
+/// next file left, so that a few large videos don't hold the other jobs back.
+fn hash_files(
+    paths: &[PathBuf],
+    known: &KnownHashes,
+    jobs: usize,
+    progress: &Progress,
+) -> Vec<Result<Hashed>> {
+    let next = AtomicUsize::new(0);
+    let mut hashed = thread::scope(|scope| {
+        (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashed = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            break hashed;
+                        };
+                        hashed.push((index, hash_file(path, known)));
+                        progress.inc(file_size(path));
+                    }
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("hashing job panicked"))
+            .collect::<Vec<_>>()
+    });
+    hashed.sort_by_key(|(index, _)| *index);
+    hashed.into_iter().map(|(_, hashed)| hashed).collect()
+}
+
+/// Hashes the file, unless it has a `known` hash.
+fn hash_file(path: &PathBuf, known: &KnownHashes) -> Result<Hashed> {
+    let fingerprint = Fingerprint::read(path).ok().flatten();
+    Ok(match fingerprint.and_then(|f| known.get(path, &f)) {
+        Some(sha256) => Hashed {
+            entry: CatalogEntry::hashed(path, sha256.clone())?,
+            fingerprint,
+            reused: true,
+        },
+        None => Hashed {
+            entry: path.try_into()?,
+            fingerprint,
+            reused: false,
+        },
+    })
+}
+
+/// How the files of a source compare with what the repository knew of it.
+#[derive(Debug, Default, PartialEq)]
+struct IngestSummary {
+    /// Paths first seen, with a content the library doesn't have.
+    new: usize,
+    /// Files whose content is in the library already.
+    imported: usize,
+    /// Paths cataloged before with the same content, not imported yet.
+    pending: usize,
+    /// Paths cataloged before with another content.
+    changed: usize,
+}
+
+impl Display for IngestSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} new, {} previously imported, {} already cataloged, {} changed since last time",
+            thousands(self.new),
+            thousands(self.imported),
+            thousands(self.pending),
+            thousands(self.changed)
+        )
+    }
+}
+
+/// Compares the files of a source with the `cataloged` hashes of its paths and the
+/// `imported` library hashes. Returns the summary, the entries to catalog and the
+/// cataloged entries whose content changed.
+fn compare_with_catalog(
+    entries: Vec<CatalogEntry>,
+    cataloged: &HashMap<String, String>,
+    imported: &HashSet<String>,
+) -> (IngestSummary, Vec<CatalogEntry>, Vec<CatalogEntry>) {
+    let mut summary = IngestSummary::default();
+    let mut new = vec![];
+    let mut changed = vec![];
+    for entry in entries {
+        match cataloged.get(&*entry.path().to_string_lossy()) {
+            Some(hash) if hash != entry.sha256() => {
+                summary.changed += 1;
+                changed.push(entry);
+            }
+            Some(_) if imported.contains(entry.sha256()) => summary.imported += 1,
+            Some(_) => summary.pending += 1,
+            None => {
+                if imported.contains(entry.sha256()) {
+                    summary.imported += 1;
+                } else {
+                    summary.new += 1;
+                }
+                new.push(entry);
+            }
+        }
+    }
+    (summary, new, changed)
+}
+
+/// The number with its thousands separated by commas, e.g. 4,801.
+fn thousands(number: usize) -> String {
+    let digits = number.to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Catalogs the files of a remote host, hashed on the host. Their catalog paths
+/// keep the `ssh://` scheme marking the remote volume.
+fn catalog_remote(connection: &mut Connection, remote: &RemoteSource) -> Result<usize> {
+    let entries = remote.catalog_entries()?;
+    persist_catalog_root(connection, &PathBuf::from(remote.root_url()))?;
+    persist_as_operation(connection, &remote.root_url(), &entries)
+}
+
+/// Persists the entries as a new catalog operation, which `catalog export --since`
+/// uses to select the entries to transfer.
+fn persist_as_operation(
+    connection: &mut Connection,
+    path: &str,
+    entries: &Vec<CatalogEntry>,
+) -> Result<usize> {
+    let operation = start_operation(connection, CATALOG, Some(path), current_user()?.as_deref())?;
+    let count = persist_catalog_entries(connection, entries)?;
+    assign_operation(connection, operation)?;
+    println!("Recorded as operation {}", operation);
+    Ok(count)
+}
+
+fn find_corrupt_images(entries: &[CatalogEntry]) -> Vec<Problem> {
+    entries
+        .iter()
+        .filter_map(|entry| match validate_image(&entry.path()) {
+            Ok(None) => None,
+            Ok(Some(description)) | Err(description) => {
+                println!("Corrupt image {}: {}", entry.path().display(), description);
+                Some(Problem::new(
+                    entry.sha256().to_owned(),
+                    entry.path().to_string_lossy().to_string(),
+                    description,
+                ))
             }
-            catalog_entry
         })
-        .filter_map(|e: Result<CatalogEntry, eyre::Error>| e.ok())
-        .collect::<Vec<CatalogEntry>>();
-    persist_catalog_entries(&mut connection, &entries)
+        .collect()
+}
+
+fn validate_image(path: &Path) -> Result<Option<String>, String> {
+    match media::detect(path).map_err(|e| e.to_string())? {
+        Some(media_type) => validation::validate(path, media_type).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// The files to catalog: those of the image formats and of the included
+/// extensions, but none of the excluded extensions, which ignore case, nor of the
+/// ignored paths and the paths managed by photo_works.
+#[derive(Debug, Default)]
+pub(crate) struct FileFilter {
+    included: Vec<String>,
+    excluded: Vec<String>,
+    ignored: IgnoreRules,
+    managed: ManagedPaths,
+}
+
+impl FileFilter {
+    /// The filter of the arguments, along with the `.photo_worksignore` file of
+    /// the cataloged directory.
+    fn read(sub_matches: &ArgMatches, root: &Path) -> Result<Self> {
+        let values = |id: &str| {
+            sub_matches
+                .get_many::<String>(id)
+                .unwrap_or_default()
+                .cloned()
+                .collect::<Vec<String>>()
+        };
+        Ok(Self::default()
+            .with_included(&values("include-ext"))
+            .with_excluded(&values("exclude-ext"))
+            .with_ignored(IgnoreRules::load(root, &values("exclude"))?))
+    }
+
+    pub(crate) fn with_included(mut self, extensions: &[String]) -> Self {
+        self.included = normalize_extensions(extensions);
+        self
+    }
+
+    pub(crate) fn with_excluded(mut self, extensions: &[String]) -> Self {
+        self.excluded = normalize_extensions(extensions);
+        self
+    }
+
+    pub(crate) fn with_ignored(mut self, ignored: IgnoreRules) -> Self {
+        self.ignored = ignored;
+        self
+    }
+
+    pub(crate) fn with_managed(mut self, managed: ManagedPaths) -> Self {
+        self.managed = managed;
+        self
+    }
+
+    /// Whether the file is cataloged, from its extension or, without one, from
+    /// the image type of its content.
+    pub(crate) fn accepts(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension() else {
+            return media::detect(path).is_ok_and(|t| t.is_some_and(|t| IMAGE_TYPES.contains(&t)));
+        };
+        let lowercase = extension.to_string_lossy().to_lowercase();
+        if self.excluded.contains(&lowercase) {
+            return false;
+        }
+        self.included.contains(&lowercase)
+            || media::is_raw(path)
+            || IMAGE_TYPES.iter().any(|t| t.accepts_extension(extension))
+    }
+}
+
+/// The name of the file listing the paths a catalog skips, at the root of the
+/// cataloged directory.
+const IGNORE_FILE: &str = ".photo_worksignore";
+
+/// The paths skipped by a catalog, in the gitignore syntax: a pattern without a
+/// slash matches a name at any depth, one with a slash matches the path from the
+/// cataloged directory, a trailing slash only matches directories and a leading
+/// `!` includes again what an earlier pattern ignored. The last matching pattern
+/// wins, and the files of an ignored directory are never visited.
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+#[derive(Debug)]
+struct IgnoreRule {
+    pattern: Pattern,
+    negated: bool,
+    directory_only: bool,
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    /// The rules of the `.photo_worksignore` file of the directory, when it has
+    /// one, followed by the `excludes`.
+    pub(crate) fn load(directory: &Path, excludes: &[String]) -> Result<Self> {
+        let file = directory.join(IGNORE_FILE);
+        let content = if file.is_file() {
+            read_to_string(&file)?
+        } else {
+            String::new()
+        };
+        content
+            .lines()
+            .chain(excludes.iter().map(String::as_str))
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| IgnoreRule::from_str(line).map_err(|e| eyre!(e)))
+            .collect::<Result<Vec<IgnoreRule>>>()
+            .map(|rules| Self { rules })
+    }
+
+    /// True when the path, relative to the cataloged directory, is ignored.
+    fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let name = relative.file_name().unwrap_or_default();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.directory_only)
+                    && if rule.anchored {
+                        rule.pattern.matches_path_with(relative, options)
+                    } else {
+                        rule.pattern.matches_with(&name.to_string_lossy(), options)
+                    }
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+impl FromStr for IgnoreRule {
+    type Err = String;
+
+    fn from_str(line: &str) -> std::result::Result<Self, Self::Err> {
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = Pattern::new(line.trim_start_matches('/'))
+            .map_err(|e| format!("Invalid ignore pattern `{}`: {}", line, e))?;
+        Ok(Self {
+            pattern,
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+}
+
+/// The extensions in lowercase, without their leading dot.
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|e| e.trim_start_matches('.').to_lowercase())
+        .collect()
 }
 
 /// Returns true when a file_name starts with '.'
-fn is_hidden_file_name(file_name: &OsStr) -> bool {
+pub(crate) fn is_hidden_file_name(file_name: &OsStr) -> bool {
     let bytes = file_name.as_encoded_bytes();
     bytes.len() >= 2 && bytes[0] == b'.' && bytes[1] != b'.'
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::command::catalog::is_hidden_file_name;
+    use crate::{
+        command::catalog::{
+            catalog, compare_with_catalog, find_corrupt_images, hash_files, is_hidden_file_name,
+            skipped_managed_directories, thousands, FileFilter, IgnoreRules, IngestSummary,
+            KnownHashes,
+        },
+        database::{
+            self,
+            catalog_entry::CatalogEntry,
+            fingerprint::{save_fingerprints, Fingerprint},
+            test_utils::new_database,
+        },
+        fsext::{managed::ManagedPaths, source::SourceSnapshot},
+        progress::Progress,
+    };
+    use std::collections::{HashMap, HashSet};
     use std::ffi::OsStr;
+    use std::fs::{copy, read, write};
     use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn find_corrupt_images_flags_a_truncated_jpeg() {
+        let directory = TempDir::new().unwrap();
+        let bytes = read(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+        )
+        .unwrap();
+        let truncated = directory.path().join("truncated.jpeg");
+        write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        let entries = vec![
+            CatalogEntry::try_from(&truncated).unwrap(),
+            CatalogEntry::try_from(&PathBuf::from("resources/test/kami_neko.jpeg")).unwrap(),
+            CatalogEntry::try_from(&PathBuf::from("Cargo.toml")).unwrap(),
+        ];
+
+        let problems = find_corrupt_images(&entries);
+
+        assert_eq!(1, problems.len());
+        assert_eq!(entries[0].path().to_string_lossy(), problems[0].path());
+    }
+
+    #[test]
+    fn catalog_leaves_the_source_untouched() {
+        let directory = TempDir::new().unwrap();
+        copy(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+            directory.path().join("kami_neko.jpeg"),
+        )
+        .unwrap();
+        let sources = vec![directory.path().to_path_buf()];
+        let snapshot = SourceSnapshot::take(&sources).unwrap();
+
+        catalog(
+            &mut new_database(),
+            &sources[0],
+            true,
+            false,
+            false,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+
+        assert!(snapshot.changes(&sources).unwrap().is_empty());
+    }
+
+    #[test]
+    fn catalog_reuses_the_hash_of_unchanged_files_unless_rehashing() {
+        let directory = TempDir::new().unwrap();
+        let source = directory.path().join("source");
+        std::fs::create_dir(&source).unwrap();
+        write(source.join("a.jpg"), "content").unwrap();
+        let db = directory.path().join("db.db3");
+        let mut connection = database::open(&db).unwrap();
+        let fingerprint = Fingerprint::read(&source.join("a.jpg")).unwrap().unwrap();
+        save_fingerprints(&mut connection, &[(fingerprint, "KNOWN".to_string())]).unwrap();
+
+        catalog(
+            &mut connection,
+            &source,
+            false,
+            false,
+            false,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+        let hash = |connection: &rusqlite::Connection| {
+            connection
+                .query_row("SELECT hash FROM catalog", [], |r| r.get::<_, String>(0))
+                .unwrap()
+        };
+        let mut connection = database::open(&db).unwrap();
+        assert_eq!("KNOWN", hash(&connection));
+
+        connection.execute("DELETE FROM catalog", []).unwrap();
+        catalog(
+            &mut connection,
+            &source,
+            false,
+            true,
+            false,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+        assert_ne!("KNOWN", hash(&database::open(&db).unwrap()));
+    }
+
+    #[test]
+    fn catalog_incremental_reuses_the_hash_of_an_unchanged_path() {
+        let directory = TempDir::new().unwrap();
+        let source = directory.path().join("source");
+        std::fs::create_dir(&source).unwrap();
+        write(source.join("a.jpg"), "content").unwrap();
+        let db = directory.path().join("db.db3");
+        catalog(
+            &mut database::open(&db).unwrap(),
+            &source,
+            false,
+            false,
+            false,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+        // A remounted card gives its files new inodes.
+        let mut connection = database::open(&db).unwrap();
+        connection
+            .execute_batch("DELETE FROM fingerprint; UPDATE catalog SET hash = 'KNOWN';")
+            .unwrap();
+        let hash = || {
+            database::open(&db)
+                .unwrap()
+                .query_row("SELECT hash FROM catalog", [], |r| r.get::<_, String>(0))
+                .unwrap()
+        };
+
+        catalog(
+            &mut connection,
+            &source,
+            false,
+            false,
+            true,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+        assert_eq!("KNOWN", hash());
+
+        database::open(&db)
+            .unwrap()
+            .execute("DELETE FROM fingerprint", [])
+            .unwrap();
+        catalog(
+            &mut database::open(&db).unwrap(),
+            &source,
+            false,
+            false,
+            false,
+            2,
+            &FileFilter::default(),
+        )
+        .unwrap();
+        assert_ne!("KNOWN", hash());
+    }
+
+    #[test]
+    fn hash_files_keeps_the_order_of_the_paths() {
+        let directory = TempDir::new().unwrap();
+        let paths = (0..7)
+            .map(|i| {
+                let path = directory.path().join(format!("{}.txt", i));
+                write(&path, i.to_string()).unwrap();
+                path
+            })
+            .chain([directory.path().join("missing.txt")])
+            .collect::<Vec<PathBuf>>();
+
+        let hashed = hash_files(
+            &paths,
+            &KnownHashes::default(),
+            3,
+            &Progress::start("Cataloging", 8, 0),
+        );
+
+        assert_eq!(8, hashed.len());
+        for (path, hashed) in paths.iter().zip(&hashed[..7]) {
+            let expected = CatalogEntry::try_from(path).unwrap();
+            assert_eq!(expected, hashed.as_ref().unwrap().entry);
+        }
+        assert!(hashed[7].is_err());
+    }
+
+    #[test]
+    fn compare_with_catalog_sorts_the_files_by_what_was_known() {
+        let entry = |hash: &str, path: &str| CatalogEntry::new(hash.to_string(), path.to_string());
+        let cataloged = HashMap::from([
+            ("/card/1.jpg".to_string(), "1".to_string()),
+            ("/card/2.jpg".to_string(), "2".to_string()),
+            ("/card/3.jpg".to_string(), "3".to_string()),
+        ]);
+        let imported = HashSet::from(["1".to_string(), "5".to_string()]);
+
+        let (summary, new, changed) = compare_with_catalog(
+            vec![
+                entry("1", "/card/1.jpg"),
+                entry("2", "/card/2.jpg"),
+                entry("4", "/card/3.jpg"),
+                entry("5", "/card/5.jpg"),
+                entry("6", "/card/6.jpg"),
+            ],
+            &cataloged,
+            &imported,
+        );
+
+        assert_eq!(
+            IngestSummary {
+                new: 1,
+                imported: 2,
+                pending: 1,
+                changed: 1
+            },
+            summary
+        );
+        assert_eq!(
+            vec![entry("5", "/card/5.jpg"), entry("6", "/card/6.jpg")],
+            new
+        );
+        assert_eq!(vec![entry("4", "/card/3.jpg")], changed);
+        assert_eq!(
+            "1 new, 2 previously imported, 1 already cataloged, 1 changed since last time",
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn extension_filter_accepts_the_images_and_the_included_extensions() {
+        let filter = FileFilter::default()
+            .with_included(&["MP4".to_string()])
+            .with_excluded(&[".gif".to_string()]);
+
+        assert!(filter.accepts(Path::new("a/IMG_0001.JPG")));
+        assert!(filter.accepts(Path::new("a/IMG_0001.CR2")));
+        assert!(filter.accepts(Path::new("a/clip.mp4")));
+        assert!(!filter.accepts(Path::new("a/animation.gif")));
+        assert!(!filter.accepts(Path::new("a/IMG_0001.xmp")));
+        assert!(!filter.accepts(Path::new("a/notes")));
+        assert!(FileFilter::default().accepts(Path::new("a/animation.gif")));
+    }
+
+    #[test]
+    fn extension_filter_accepts_the_images_without_extension_from_their_content() {
+        let directory = TempDir::new().unwrap();
+        let picture = directory.path().join("export_0001");
+        let notes = directory.path().join("notes");
+        copy(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+            &picture,
+        )
+        .unwrap();
+        write(&notes, "not a picture").unwrap();
+        assert!(FileFilter::default().accepts(&picture));
+        assert!(!FileFilter::default().accepts(&notes));
+    }
+
+    #[test]
+    fn ignore_rules_follow_the_gitignore_syntax() {
+        let rules = IgnoreRules::load(
+            Path::new("/missing"),
+            &[
+                "# vendor caches".to_string(),
+                "Thumbs.db".to_string(),
+                "@eaDir/".to_string(),
+                "/exports/*".to_string(),
+                "*.jpg".to_string(),
+                "!keep/*.jpg".to_string(),
+            ],
+        )
+        .unwrap();
+
+        assert!(rules.is_ignored(Path::new("2015/Thumbs.db"), false));
+        assert!(rules.is_ignored(Path::new("2015/@eaDir"), true));
+        assert!(!rules.is_ignored(Path::new("2015/@eaDir"), false));
+        assert!(rules.is_ignored(Path::new("exports/a"), true));
+        assert!(!rules.is_ignored(Path::new("2015/exports/a"), true));
+        assert!(rules.is_ignored(Path::new("2015/a.jpg"), false));
+        assert!(!rules.is_ignored(Path::new("keep/a.jpg"), false));
+        assert!(!rules.is_ignored(Path::new("2015/a.png"), false));
+    }
+
+    #[test]
+    fn catalog_skips_the_ignored_paths() {
+        let directory = TempDir::new().unwrap();
+        let source = directory.path().join("source");
+        for file in ["a.jpg", "@eaDir/a.jpg", "b/Thumbs.jpg"] {
+            let file = source.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            write(&file, file.to_string_lossy().as_bytes()).unwrap();
+        }
+        write(source.join(".photo_worksignore"), "@eaDir/\n").unwrap();
+        let mut connection = new_database();
+        let filter = FileFilter::default()
+            .with_ignored(IgnoreRules::load(&source, &["Thumbs.*".to_string()]).unwrap());
+
+        assert_eq!(
+            1,
+            catalog(&mut connection, &source, false, false, false, 2, &filter).unwrap()
+        );
+    }
+
+    #[test]
+    fn catalog_skips_the_managed_directories() {
+        let directory = TempDir::new().unwrap();
+        let photos = directory.path().join("photos");
+        for file in ["a.jpg", "library/2024/a.jpg", "trash/b.jpg"] {
+            let file = photos.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            write(&file, file.to_string_lossy().as_bytes()).unwrap();
+        }
+        let managed = vec![photos.join("library"), photos.join("trash")];
+        let inbox = photos.join("library/.inbox");
+
+        let skipped = skipped_managed_directories(&photos, &managed, &inbox).unwrap();
+        assert_eq!(managed, skipped);
+        assert!(
+            skipped_managed_directories(&photos.join("library/2024"), &managed, &inbox).is_err()
+        );
+        assert!(skipped_managed_directories(&photos.join("trash"), &managed, &inbox).is_err());
+        assert!(
+            skipped_managed_directories(&inbox.join("card"), &managed, &inbox)
+                .unwrap()
+                .is_empty()
+        );
+
+        let filter = FileFilter::default().with_managed(
+            skipped
+                .into_iter()
+                .fold(ManagedPaths::default(), ManagedPaths::with_directory),
+        );
+        assert_eq!(
+            1,
+            catalog(
+                &mut new_database(),
+                &photos,
+                false,
+                false,
+                false,
+                2,
+                &filter
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn thousands_separates_the_groups_of_digits() {
+        assert_eq!("0", thousands(0));
+        assert_eq!("312", thousands(312));
+        assert_eq!("4,801", thousands(4801));
+        assert_eq!("1,234,567", thousands(1_234_567));
+    }
 
     #[test]
     fn is_hidden_file_name_is_false_for_empty_string() {
-        assert!(!is_hidden_file_name(&OsStr::from_bytes(&[])))
+        assert!(!is_hidden_file_name(OsStr::from_bytes(&[])))
     }
 
     #[test]
     fn is_hidden_file_name_is_false_for_dot() {
-        assert!(!is_hidden_file_name(&OsStr::from_bytes(&[b'.'])))
+        assert!(!is_hidden_file_name(OsStr::from_bytes(b".")))
     }
 
     #[test]
     fn is_hidden_file_name_is_false_for_single_non_dot() {
-        assert!(!is_hidden_file_name(&OsStr::from_bytes(&[b' '])))
+        assert!(!is_hidden_file_name(OsStr::from_bytes(b" ")))
     }
 
     #[test]
     fn is_hidden_file_name_is_false_for_dot_dot() {
-        assert!(!is_hidden_file_name(&OsStr::from_bytes(&[b'.', b'.'])))
+        assert!(!is_hidden_file_name(OsStr::from_bytes(b"..")))
     }
 
     #[test]
     fn is_hidden_file_name_is_false_for_string_not_starting_with_dot() {
-        assert!(!is_hidden_file_name(&OsStr::from_bytes(&[b' ', b'.'])))
+        assert!(!is_hidden_file_name(OsStr::from_bytes(b" .")))
     }
 
     #[test]
     fn is_hidden_file_name_is_true_for_string_starting_with_dot() {
-        assert!(is_hidden_file_name(&OsStr::from_bytes(&[b'.', b' ', b'a'])))
+        assert!(is_hidden_file_name(OsStr::from_bytes(b". a")))
     }
 }
@@ -1,16 +1,47 @@
-use std::{path::PathBuf, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{metadata, File},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read},
+    path::{absolute, Component, Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use clap::{ArgMatches, Command};
-use eyre::{eyre, Result};
+use chrono::{Local, NaiveDate};
+use clap::{arg, ArgAction, ArgMatches, Command};
+use eyre::Result;
+use glob::Pattern;
 use rusqlite::Connection;
+use walkdir::WalkDir;
 
 use crate::{
-    clapext::SubApplication,
-    database::{self, common::sha256_digest},
+    clapext::{parse_date, parse_date_end, parse_duration, SubApplication},
+    command::{
+        catalog::is_hidden_file_name,
+        tag::{query_arg, query_filter, saved_arg},
+    },
+    database::{
+        catalog::{find_imported_copies, foreach_duplicate},
+        catalog_entry::CatalogEntry,
+        check_finding::{clear_findings, record_finding, select_findings},
+        check_progress::{save_position, select_position},
+        common::sha256_digest,
+        library::{remove_library_entries, update_library_paths, LibraryFilter},
+        library_root::select_library_roots,
+        pair::select_partners,
+    },
+    error::{Error, ErrorCode, Failures},
+    fsext::{managed::ManagedPaths, source::open_read_only},
+    progress::{file_size, print_line, Progress},
+    report::duplicates::DuplicatesHtml,
+    repository::Repository,
+    style::{bold, Status},
 };
 
 const CHECK: &str = "check";
 
+/// The number of pictures checked between two rolling summaries.
+const SUMMARY_INTERVAL: usize = 1000;
+
 pub(crate) struct Check;
 
 impl SubApplication for Check {
@@ -24,23 +55,117 @@ impl SubApplication for Check {
             .subcommand_required(true)
             .arg_required_else_help(true)
             .subcommands([
-                Command::new("library").about("Verify the integrity of the library."),
-                Command::new("catalog").about("Verify the integrity of the catalog."),
-                Command::new("duplicates").about("Reports duplicate pictures in catalog."),
+                Command::new("library")
+                    .about("Verify the integrity of the library.")
+                    .arg(
+                        arg!(--year <YEAR> "Only verifies the pictures taken during the year")
+                            .value_parser(clap::value_parser!(i32)),
+                    )
+                    .arg(arg!(--"path-prefix" <PREFIX> "Only verifies the pictures under the library path"))
+                    .arg(arg!(--after <DATE> "Only verifies the pictures taken on or after the date, e.g. 2024-07 or last-month").value_parser(parse_date))
+                    .arg(arg!(--before <DATE> "Only verifies the pictures taken on or before the date, e.g. 2024-07-15 or 3d").value_parser(parse_date_end))
+                    .arg(arg!(--tag <TAG> "Only verifies the pictures with the tag, e.g. wedding2019, repeated to require several").action(ArgAction::Append))
+                    .arg(query_arg())
+                    .arg(saved_arg())
+                    .arg(arg!(--renames "Matches the missing pictures to the untracked files of the library by content instead of verifying the pictures"))
+                    .arg(arg!(--"fix-renames" "Updates the paths of the renamed pictures in the database").requires("renames"))
+                    .arg(max_duration_arg().conflicts_with("renames")),
+                Command::new("orphans")
+                    .about("Reports library rows pointing outside the library or to temporary and trashed files.")
+                    .arg(arg!(--fix "Removes the orphan rows from the library, leaving the files alone")),
+                Command::new("catalog")
+                    .about("Verify the integrity of the catalog.")
+                    .arg(max_duration_arg()),
+                Command::new("duplicates")
+                    .about("Reports duplicate pictures in catalog.")
+                    .arg(
+                        arg!(--html <FILE> "Writes a report showing the copies of each duplicate side by side")
+                            .value_parser(clap::value_parser!(PathBuf)),
+                    ),
                 Command::new("imported").about("Reports catalog entries already in the library."),
+                Command::new("deep-compare")
+                    .about("Compares the bytes of a sample of the cataloged sources with their library copies, when both exist.")
+                    .arg(
+                        arg!(--sample <COUNT> "The number of sources compared, all of them when 0")
+                            .value_parser(clap::value_parser!(usize))
+                            .default_value("50"),
+                    ),
+                Command::new("problems").about("Reports catalog entries flagged as corrupt."),
+                Command::new("findings")
+                    .about("Reports the pictures the last library and catalog checks found missing or corrupt, even when interrupted.")
+                    .arg(
+                        arg!([CHECK] "Only reports the findings of the check")
+                            .value_parser(["library", "catalog"]),
+                    ),
             ])
     }
 
-    fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
-        let db_path: PathBuf = [".photo_works", "db.db3"].iter().collect();
-        let connection = database::open(&db_path)?;
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let html = sub_matches
+            .subcommand_matches("duplicates")
+            .and_then(|m| m.get_one::<PathBuf>("html"))
+            .map(absolute)
+            .transpose()?;
+        repository.enter()?;
+        let mut connection = repository.open_database()?;
 
         match sub_matches.subcommand() {
-            Some((name, _sub_matches)) => match name {
-                "library" => check_library_integrity(&connection),
-                "catalog" => check_catalog_integrity(&connection),
-                "duplicates" => check_catalog_duplicates(&connection),
+            Some((name, sub_matches)) => match name {
+                "library" => {
+                    let query =
+                        query_filter(sub_matches, &connection)?.relative_to(repository.root());
+                    let mut filter = LibraryFilter {
+                        year: sub_matches.get_one::<i32>("year").copied().or(query.year),
+                        path_prefix: sub_matches
+                            .get_one::<String>("path-prefix")
+                            .cloned()
+                            .or(query.path_prefix),
+                        after: sub_matches
+                            .get_one::<NaiveDate>("after")
+                            .copied()
+                            .or(query.after),
+                        before: sub_matches
+                            .get_one::<NaiveDate>("before")
+                            .copied()
+                            .or(query.before),
+                        ..query
+                    };
+                    filter.tags.extend(
+                        sub_matches
+                            .get_many::<String>("tag")
+                            .unwrap_or_default()
+                            .cloned(),
+                    );
+                    if sub_matches.get_flag("renames") {
+                        let _lock = repository.lock()?;
+                        check_library_renames(
+                            &mut connection,
+                            repository.root(),
+                            &repository.managed_paths()?,
+                            &filter,
+                            sub_matches.get_flag("fix-renames"),
+                        )
+                    } else {
+                        check_library_integrity(&connection, &filter, max_duration(sub_matches))
+                    }
+                }
+                "orphans" => {
+                    let fix = sub_matches.get_flag("fix");
+                    let _lock = if fix { Some(repository.lock()?) } else { None };
+                    check_library_orphans(&mut connection, repository.root(), fix)
+                }
+                "catalog" => check_catalog_integrity(&connection, max_duration(sub_matches)),
+                "duplicates" => check_catalog_duplicates(&connection, html.as_deref()),
                 "imported" => check_imported_library_entries(&connection),
+                "deep-compare" => deep_compare(
+                    &connection,
+                    *sub_matches.get_one::<usize>("sample").expect("defaulted"),
+                ),
+                "problems" => check_catalog_problems(&connection),
+                "findings" => check_findings(
+                    &connection,
+                    sub_matches.get_one::<String>("CHECK").map(String::as_str),
+                ),
                 _ => unreachable!("Unknown subcommand"),
             },
             None => unreachable!("Missing subcommand."),
@@ -48,103 +173,668 @@ impl SubApplication for Check {
     }
 }
 
-fn check_catalog_integrity(connection: &Connection) -> Result<()> {
-    println!("Checking catalog images",);
+fn max_duration_arg() -> clap::Arg {
+    arg!(--"max-duration" <DURATION> "Stops after the duration, e.g. 2h, and resumes from there at the next run")
+        .value_parser(parse_duration)
+}
+
+fn max_duration(sub_matches: &ArgMatches) -> Option<Duration> {
+    sub_matches.get_one::<Duration>("max-duration").copied()
+}
+
+fn check_catalog_integrity(connection: &Connection, max_duration: Option<Duration>) -> Result<()> {
+    println!("{}", bold("Checking catalog images"));
     let catalog_check_start = Instant::now();
 
-    let result = crate::database::catalog::foreach_entry(connection, |e| {
-        if e.sha256().to_string() == sha256_digest(&e.path())? {
-            Ok(())
+    let mut remote = 0;
+    let mut entries = vec![];
+    crate::database::catalog::foreach_entry(connection, |e| {
+        if e.is_remote() {
+            remote += 1;
         } else {
-            Err(eyre!(
-                "Failed catalog check for {}",
-                &e.path().to_string_lossy().to_string()
-            ))
+            entries.push((e.sha256().to_owned(), e.path()));
         }
+        Ok(())
     })?;
-    Ok(println!(
-        "Checked {} pictures in {} seconds",
+    let result = verify_digests(connection, "catalog", entries, max_duration)?;
+    println!(
+        "Checked {} pictures in {} seconds, skipped {} remote pictures",
         result,
-        catalog_check_start.elapsed().as_secs()
-    ))
+        catalog_check_start.elapsed().as_secs(),
+        remote
+    );
+    Ok(())
 }
 
-fn check_library_integrity(connection: &Connection) -> Result<()> {
-    println!("Checking library images");
+fn check_library_integrity(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    max_duration: Option<Duration>,
+) -> Result<()> {
+    println!("{}", bold("Checking library images"));
     let library_check_start = Instant::now();
 
-    let result = crate::database::library::foreach_entry(connection, |e| {
-        if e.sha256().to_string() == sha256_digest(&e.path())? {
-            Ok(())
-        } else {
-            Err(eyre!(
-                "Failed library check for {}",
-                &e.path().to_string_lossy().to_string()
-            ))
-        }
+    let mut entries = vec![];
+    crate::database::library::foreach_entry(connection, filter, |e| {
+        entries.push((e.sha256().to_owned(), e.path().to_owned()));
+        Ok(())
     })?;
-    Ok(println!(
+    let result = verify_digests(connection, "library", entries, max_duration)?;
+    println!(
         "Checked {} pictures in {} seconds",
         result,
         library_check_start.elapsed().as_secs()
-    ))
+    );
+    Ok(())
+}
+
+/// Verifies the digests of the `(hash, path)` entries in path order. With a time
+/// budget, the check starts after the position saved by the previous run and
+/// saves the position reached when the budget runs out, clearing it after a
+/// complete pass. The findings are recorded as they are found and a summary is
+/// printed every `SUMMARY_INTERVAL` pictures, along with the position, so that
+/// an interrupted check leaves a partial report. Returns the number of checked
+/// entries.
+fn verify_digests(
+    connection: &Connection,
+    name: &str,
+    mut entries: Vec<(String, PathBuf)>,
+    max_duration: Option<Duration>,
+) -> Result<usize> {
+    let start = Instant::now();
+    entries.sort_by_key(|(_, path)| path.to_string_lossy().to_string());
+    let resumed = match max_duration {
+        Some(_) => select_position(connection, name)?,
+        None => None,
+    };
+    match resumed {
+        Some(position) => {
+            println!("Resuming after {}", position);
+            entries.retain(|(_, path)| *path.to_string_lossy() > *position);
+        }
+        None => {
+            clear_findings(connection, name)?;
+        }
+    }
+    let mut count = 0;
+    let mut errors = vec![];
+    let mut statuses = HashMap::new();
+    let progress = Progress::start(
+        format!("Checking {}", name),
+        entries.len() as u64,
+        entries.iter().map(|(_, path)| file_size(path)).sum(),
+    );
+    for (sha256, path) in &entries {
+        if max_duration.is_some_and(|d| start.elapsed() >= d) {
+            break;
+        }
+        let status = match sha256_digest(path) {
+            Ok(digest) if digest == *sha256 => Status::Ok,
+            Ok(_) => {
+                errors.push(
+                    Error::new(
+                        ErrorCode::HashMismatch,
+                        format!("Failed {} check for {}", name, path.to_string_lossy()),
+                    )
+                    .into(),
+                );
+                Status::Corrupt
+            }
+            Err(e) => {
+                let missing = e.kind() == ErrorKind::NotFound;
+                errors.push(e.into());
+                if missing {
+                    Status::Missing
+                } else {
+                    Status::Failed
+                }
+            }
+        };
+        progress.inc(file_size(path));
+        if status != Status::Ok {
+            print_line(format!("{} {}", status, path.display()));
+            record_finding(
+                connection,
+                name,
+                &path.to_string_lossy(),
+                sha256,
+                status.label(),
+            )?;
+        }
+        *statuses.entry(status).or_insert(0) += 1;
+        count += 1;
+        if count % SUMMARY_INTERVAL == 0 && count < entries.len() {
+            print_line(format!(
+                "Checked {} of {}: {}",
+                count,
+                entries.len(),
+                summary(&statuses)
+            ));
+            if max_duration.is_some() {
+                save_position(connection, name, Some(&path.to_string_lossy()))?;
+            }
+        }
+    }
+    drop(progress);
+    println!("{}", summary(&statuses));
+    if max_duration.is_some() {
+        if count == entries.len() {
+            save_position(connection, name, None)?;
+        } else if let Some((_, path)) = count.checked_sub(1).map(|i| &entries[i]) {
+            let position = path.to_string_lossy();
+            println!("Out of time, the next run resumes after {}", position);
+            save_position(connection, name, Some(&position))?;
+        }
+    }
+    if errors.is_empty() {
+        Ok(count)
+    } else {
+        Err(Failures(errors).into())
+    }
+}
+
+/// The number of pictures of each status, e.g. `98 OK, 1 MISSING, 1 CORRUPT`.
+fn summary(statuses: &HashMap<Status, usize>) -> String {
+    let mut summary = vec![Status::Ok, Status::Missing, Status::Corrupt];
+    if statuses.contains_key(&Status::Failed) {
+        summary.push(Status::Failed);
+    }
+    summary
+        .iter()
+        .map(|s| format!("{} {}", statuses.get(s).unwrap_or(&0), s))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn check_findings(connection: &Connection, name: Option<&str>) -> Result<()> {
+    println!("{}", bold("Checking findings"));
+    let findings = select_findings(connection, name)?;
+    for finding in &findings {
+        println!(
+            "{} {} ({} check, {})",
+            finding.status,
+            finding.path.display(),
+            finding.name,
+            finding.found_at.format("%Y-%m-%d %H:%M")
+        );
+    }
+    println!("Found {} pictures missing or corrupt", findings.len());
+    Ok(())
+}
+
+/// A library picture found under another path than the recorded one.
+#[derive(Debug, PartialEq)]
+struct Rename {
+    sha256: String,
+    from: PathBuf,
+    to: PathBuf,
+}
+
+fn check_library_renames(
+    connection: &mut Connection,
+    root: &Path,
+    managed: &ManagedPaths,
+    filter: &LibraryFilter,
+    fix: bool,
+) -> Result<()> {
+    println!("{}", bold("Checking library renames"));
+    let renames = find_renames(connection, root, managed, filter)?;
+    for rename in &renames {
+        println!("{} -> {}", rename.from.display(), rename.to.display());
+    }
+    if fix {
+        let moves = renames
+            .into_iter()
+            .map(|r| (r.sha256, r.to))
+            .collect::<Vec<(String, PathBuf)>>();
+        println!(
+            "Updated {} library paths",
+            update_library_paths(connection, &moves)?
+        );
+    } else {
+        println!(
+            "{} renamed pictures found. Update their paths with --fix-renames",
+            renames.len()
+        );
+    }
+    Ok(())
+}
+
+/// Matches the library entries whose file is missing to the files of the library
+/// tree that are not in the database, by content. The managed paths are skipped.
+fn find_renames(
+    connection: &Connection,
+    root: &Path,
+    managed: &ManagedPaths,
+    filter: &LibraryFilter,
+) -> Result<Vec<Rename>> {
+    let mut tracked = HashSet::new();
+    crate::database::library::foreach_entry(connection, &LibraryFilter::default(), |e| {
+        tracked.insert(e.path().to_owned());
+        Ok(())
+    })?;
+    let mut missing = HashMap::new();
+    crate::database::library::foreach_entry(connection, filter, |e| {
+        if !root.join(e.path()).exists() {
+            missing.insert(e.sha256().to_owned(), e.path().to_owned());
+        }
+        Ok(())
+    })?;
+    if missing.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut renames = vec![];
+    for path in WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0 || (!is_hidden_file_name(e.file_name()) && !managed.contains(e.path()))
+        })
+        .filter_map(|e| e.ok().map(|f| f.into_path()))
+        .filter(|p| p.is_file())
+    {
+        let relative = path.strip_prefix(root)?.to_path_buf();
+        if tracked.contains(&relative) {
+            continue;
+        }
+        let sha256 = sha256_digest(&path)?;
+        if let Some(from) = missing.remove(&sha256) {
+            renames.push(Rename {
+                sha256,
+                from,
+                to: relative,
+            });
+        }
+    }
+    renames.sort_by(|a, b| a.from.cmp(&b.from));
+    Ok(renames)
 }
 
-fn check_catalog_duplicates(connection: &Connection) -> Result<()> {
-    println!("Checking catalog duplicates");
+/// Paths of the files left by interrupted copies or moved to the trash, which no
+/// library row should point to.
+const ORPHAN_PATTERNS: &[&str] = &["*.part", "*.tmp", "*~", ".trash/*", "*/.trash/*"];
+
+fn check_library_orphans(connection: &mut Connection, root: &Path, fix: bool) -> Result<()> {
+    println!("{}", bold("Checking library orphans"));
+    // The named roots relative to the repository root are under it already.
+    let mut roots = select_library_roots(connection)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .filter(|path| path.is_absolute())
+        .collect::<Vec<PathBuf>>();
+    roots.push(root.to_path_buf());
+    let orphans = find_orphans(connection, &roots)?;
+    for (_, path) in &orphans {
+        println!("{}", path.display());
+    }
+    if fix {
+        let hashes = orphans
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect::<Vec<String>>();
+        println!(
+            "Removed {} orphan rows",
+            remove_library_entries(connection, &hashes)?
+        );
+    } else {
+        println!(
+            "{} orphan rows found. Remove them with --fix",
+            orphans.len()
+        );
+    }
+    Ok(())
+}
+
+/// The library entries whose path escapes the roots of the library or matches one
+/// of the `ORPHAN_PATTERNS`.
+fn find_orphans(connection: &Connection, roots: &[PathBuf]) -> Result<Vec<(String, PathBuf)>> {
+    let patterns = ORPHAN_PATTERNS
+        .iter()
+        .map(|p| Pattern::new(p))
+        .collect::<Result<Vec<Pattern>, _>>()?;
+    let mut orphans = vec![];
+    crate::database::library::foreach_entry(connection, &LibraryFilter::default(), |e| {
+        let path = e.path();
+        let outside = (path.is_absolute() && !roots.iter().any(|r| path.starts_with(r)))
+            || path.components().any(|c| c == Component::ParentDir);
+        if outside || patterns.iter().any(|p| p.matches_path(path)) {
+            orphans.push((e.sha256().to_owned(), path.to_owned()));
+        }
+        Ok(())
+    })?;
+    orphans.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(orphans)
+}
+
+fn check_catalog_duplicates(connection: &Connection, html: Option<&Path>) -> Result<()> {
+    println!("{}", bold("Checking catalog duplicates"));
     let catalog_check_start = Instant::now();
 
-    let result = crate::database::catalog::find_duplicates(connection)?;
-    if result.len() == 0 {
-        Ok(println!(
+    let mut report = match html {
+        Some(html) => Some(DuplicatesHtml::start(BufWriter::new(File::create(html)?))?),
+        None => None,
+    };
+    let partners = select_partners(connection)?;
+    let count = foreach_duplicate(connection, |hash, copies| {
+        match partners.get(hash) {
+            Some(partner) => println!("{} (paired with {}):", hash, partner),
+            None => println!("{}:", hash),
+        }
+        for copy in &copies {
+            println!("{}", copy.path().display());
+        }
+        match report.as_mut() {
+            Some(report) => report.add(hash, &copies),
+            None => Ok(()),
+        }
+    })?;
+    if let (Some(report), Some(html)) = (report, html) {
+        report.finish()?;
+        println!("Wrote the duplicates report to {}", html.display());
+    }
+    if !partners.is_empty() {
+        println!(
+            "{} JPEG and RAW pairs of a same shot, which are not duplicates.",
+            partners.len() / 2
+        );
+    }
+    if count == 0 {
+        println!(
             "No duplicates found. {} seconds.",
             catalog_check_start.elapsed().as_secs()
-        ))
+        );
     } else {
-        Ok(println!(
-            "{} duplicates found. {} seconds. Paths:\n{}",
-            result.len(),
-            catalog_check_start.elapsed().as_secs(),
-            result
-                .iter()
-                .map(|(key, values)| format!(
-                    "{}:\n{}",
-                    key,
-                    values
-                        .iter()
-                        .map(|e| e.path().to_string_lossy().to_string())
-                        .collect::<Vec<String>>()
-                        .join("\n")
-                ))
-                .collect::<Vec<String>>()
-                .join("\n")
-        ))
+        println!(
+            "{} duplicates found. {} seconds.",
+            count,
+            catalog_check_start.elapsed().as_secs()
+        );
+    }
+    Ok(())
+}
+
+fn deep_compare(connection: &Connection, sample: usize) -> Result<()> {
+    println!(
+        "{}",
+        bold("Comparing the bytes of cataloged sources and library copies")
+    );
+    let start = Instant::now();
+
+    let copies = find_imported_copies(connection)?
+        .into_iter()
+        .filter(|(source, library)| {
+            !source.is_remote() && source.path().is_file() && library.is_file()
+        })
+        .collect::<Vec<(CatalogEntry, PathBuf)>>();
+    let mut count = 0;
+    let mut errors = vec![];
+    for (source, library) in sampled(
+        &copies,
+        sample,
+        Local::now().timestamp_subsec_nanos() as usize,
+    ) {
+        if !same_content(&source.path(), library)? {
+            println!("{} {}", Status::Corrupt, library.display());
+            errors.push(
+                Error::new(
+                    ErrorCode::ContentMismatch,
+                    format!(
+                        "{} differs from {}",
+                        source.path().display(),
+                        library.display()
+                    ),
+                )
+                .into(),
+            );
+        }
+        count += 1;
+    }
+    println!(
+        "Compared {} of the {} copies in {} seconds",
+        count,
+        copies.len(),
+        start.elapsed().as_secs()
+    );
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Failures(errors).into())
+    }
+}
+
+/// Up to `size` items spread evenly over the items, from a varying offset so that
+/// successive runs cover other items. All the items when `size` is 0.
+fn sampled<T>(items: &[T], size: usize, seed: usize) -> impl Iterator<Item = &T> {
+    let step = match size {
+        0 => 1,
+        size => items.len().div_ceil(size).max(1),
+    };
+    items.iter().skip(seed % step).step_by(step)
+}
+
+/// Returns true when the files have the same bytes.
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    if metadata(a)?.len() != metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut a = BufReader::new(open_read_only(a)?);
+    let mut b = BufReader::new(open_read_only(b)?);
+    loop {
+        let chunk = a.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(b.fill_buf()?.is_empty());
+        }
+        let length = chunk.len();
+        let mut other = vec![0; length];
+        if b.read_exact(&mut other).is_err() || chunk != other.as_slice() {
+            return Ok(false);
+        }
+        a.consume(length);
     }
 }
 
 fn check_imported_library_entries(connection: &Connection) -> Result<()> {
-    println!("Checking already imported entries still in catalog");
+    println!(
+        "{}",
+        bold("Checking already imported entries still in catalog")
+    );
     let catalog_check_start = Instant::now();
 
     let result = crate::database::catalog::find_already_imported(connection)?;
-    if result.len() == 0 {
-        Ok(println!(
+    if result.is_empty() {
+        println!(
             "No duplicate entries between library and catalog. {} seconds.",
             catalog_check_start.elapsed().as_secs()
-        ))
+        );
+        Ok(())
     } else {
-        Ok(println!(
+        println!(
             "{} entries found in both catalog and library. {} seconds. Paths:\n{}",
             result.len(),
             catalog_check_start.elapsed().as_secs(),
             result
                 .iter()
-                .map(|c| format!("{}: {}", c.sha256(), c.path().to_string_lossy().to_string()))
+                .map(|c| format!("{}: {}", c.sha256(), c.path().to_string_lossy()))
                 .collect::<Vec<String>>()
                 .join("\n")
-        ))
+        );
+        Ok(())
     }
 }
 
+fn check_catalog_problems(connection: &Connection) -> Result<()> {
+    println!("{}", bold("Checking catalog entries flagged as corrupt"));
+
+    let result = crate::database::problem::select_problems(connection)?;
+    if result.is_empty() {
+        println!("No corrupt entries found.");
+    } else {
+        println!(
+            "{} corrupt entries found. Paths:\n{}",
+            result.len(),
+            result
+                .iter()
+                .map(|p| format!("{} {}: {}", Status::Corrupt, p.path(), p.description()))
+                .collect::<Vec<String>>()
+                .join("\n")
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::{
+        fs::{copy, create_dir_all},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use tempfile::TempDir;
+
+    use crate::{
+        database::{
+            check_finding::{record_finding, select_findings},
+            check_progress::{save_position, select_position},
+            common::sha256_digest,
+            library::LibraryFilter,
+            library_entry::LibraryEntry,
+            test_utils::{new_database, new_database_containing_library_entries},
+        },
+        fsext::managed::ManagedPaths,
+    };
+
+    use super::{find_orphans, find_renames, same_content, sampled, verify_digests, Rename};
+
+    #[test]
+    fn sampled_spreads_the_sample_from_the_offset() {
+        let items = (0..10).collect::<Vec<i32>>();
+
+        assert_eq!(
+            vec![&1, &4, &7],
+            sampled(&items, 4, 4).collect::<Vec<&i32>>()
+        );
+        assert_eq!(10, sampled(&items, 0, 7).count());
+        assert_eq!(10, sampled(&items, 20, 3).count());
+    }
+
+    #[test]
+    fn same_content_compares_the_bytes() {
+        let directory = TempDir::new().unwrap();
+        let picture: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        let copied = directory.path().join("copy.jpeg");
+        copy(&picture, &copied).unwrap();
+        let mut altered = std::fs::read(&picture).unwrap();
+        *altered.last_mut().unwrap() ^= 1;
+        let altered_path = directory.path().join("altered.jpeg");
+        std::fs::write(&altered_path, altered).unwrap();
+
+        assert!(same_content(&picture, &copied).unwrap());
+        assert!(!same_content(&picture, &altered_path).unwrap());
+    }
+
+    #[test]
+    fn verify_digests_resumes_after_the_saved_position() {
+        let connection = new_database();
+        let picture: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        let sha256 = sha256_digest(&picture).unwrap();
+        let entries = vec![
+            (sha256.clone(), PathBuf::from("missing.jpeg")),
+            (sha256, picture),
+        ];
+        save_position(&connection, "library", Some("missing.jpeg")).unwrap();
+
+        assert_eq!(
+            0,
+            verify_digests(
+                &connection,
+                "library",
+                entries.clone(),
+                Some(Duration::ZERO)
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Some("missing.jpeg".to_string()),
+            select_position(&connection, "library").unwrap()
+        );
+        assert_eq!(
+            1,
+            verify_digests(&connection, "library", entries.clone(), Some(Duration::MAX)).unwrap()
+        );
+        assert_eq!(None, select_position(&connection, "library").unwrap());
+        assert!(verify_digests(&connection, "library", entries, None).is_err());
+    }
+
+    #[test]
+    fn verify_digests_records_the_findings_of_the_pass() {
+        let connection = new_database();
+        record_finding(&connection, "library", "fixed.jpeg", "AB", "MISSING").unwrap();
+        let entries = vec![("AB".to_string(), PathBuf::from("missing.jpeg"))];
+
+        assert!(verify_digests(&connection, "library", entries, None).is_err());
+
+        let findings = select_findings(&connection, Some("library")).unwrap();
+        assert_eq!(
+            vec![(PathBuf::from("missing.jpeg"), "MISSING".to_string())],
+            findings
+                .into_iter()
+                .map(|f| (f.path, f.status))
+                .collect::<Vec<(PathBuf, String)>>()
+        );
+    }
+
+    #[test]
+    fn find_renames_matches_missing_entries_to_untracked_files() {
+        let root = TempDir::new().unwrap();
+        let picture: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        create_dir_all(root.path().join("2024")).unwrap();
+        copy(&picture, root.path().join("2024").join("neko.jpeg")).unwrap();
+        create_dir_all(root.path().join("trash/2023")).unwrap();
+        copy(&picture, root.path().join("trash/2023/kami_neko.jpeg")).unwrap();
+        let sha256 = sha256_digest(&picture).unwrap();
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new(sha256.clone(), PathBuf::from("2023/kami_neko.jpeg")),
+            LibraryEntry::new("AB".to_string(), PathBuf::from("2023/gone.jpeg")),
+        ]);
+
+        assert_eq!(
+            vec![Rename {
+                sha256,
+                from: PathBuf::from("2023/kami_neko.jpeg"),
+                to: PathBuf::from("2024/neko.jpeg"),
+            }],
+            find_renames(
+                &connection,
+                root.path(),
+                &ManagedPaths::default().with_directory(root.path().join("trash")),
+                &LibraryFilter::default()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn find_orphans_flags_temporary_trashed_and_outside_paths() {
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/8/a.jpeg")),
+            LibraryEntry::new("2".to_string(), PathBuf::from("2023/5/8/b.jpeg.part")),
+            LibraryEntry::new("3".to_string(), PathBuf::from(".trash/8/c.jpeg")),
+            LibraryEntry::new("4".to_string(), PathBuf::from("../d.jpeg")),
+            LibraryEntry::new("5".to_string(), PathBuf::from("/tmp/e.jpeg")),
+            LibraryEntry::new("6".to_string(), PathBuf::from("/photos/2023/f.jpeg")),
+            LibraryEntry::new("7".to_string(), PathBuf::from("/mnt/drone/2023/g.mp4")),
+        ]);
+
+        assert_eq!(
+            vec!["5", "4", "3", "2"],
+            find_orphans(
+                &connection,
+                &[PathBuf::from("/photos"), PathBuf::from("/mnt/drone")]
+            )
+            .unwrap()
+            .iter()
+            .map(|(hash, _)| hash.as_str())
+            .collect::<Vec<&str>>()
+        );
+    }
+}
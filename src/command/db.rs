@@ -0,0 +1,179 @@
+use std::{
+    fs::{copy, remove_file},
+    process::Command as Process,
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Context, Result};
+
+use crate::{
+    clapext::SubApplication,
+    database::{
+        schema::{current_schema, Schema},
+        snapshot::{list_snapshots, restore},
+    },
+    repository::Repository,
+};
+
+const DB: &str = "db";
+
+pub(crate) struct Db;
+
+impl SubApplication for Db {
+    fn name(&self) -> &'static str {
+        DB
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Describes the photo_works database and restores its snapshots")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("schema")
+                    .about("Prints the documented schema of the database.")
+                    .arg(arg!(--json "Prints the schema as JSON")),
+                Command::new("browse")
+                    .about("Opens the database read-only in an external SQLite tool. The v_photos, v_duplicates and v_pending_import views are the friendliest entry points.")
+                    .arg(
+                        arg!(--tool <TOOL> "The SQLite tool to open")
+                            .value_parser(["sqlite3", "sqlitebrowser"])
+                            .default_value("sqlite3"),
+                    ),
+                Command::new("snapshots")
+                    .about("Lists the snapshots taken before the mutating commands, oldest first."),
+                Command::new("rollback")
+                    .about("Replaces the database with a snapshot, after snapshotting the current database.")
+                    .arg(arg!(--to <SNAPSHOT> "The name of the snapshot").required(true)),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("schema", sub_matches)) => {
+                let schema = current_schema()?;
+                if sub_matches.get_flag("json") {
+                    println!("{}", serde_json::to_string_pretty(&schema)?);
+                } else {
+                    print!("{}", describe(&schema));
+                }
+                Ok(())
+            }
+            Some(("browse", sub_matches)) => {
+                let tool = sub_matches.get_one::<String>("tool").expect("defaulted");
+                let status = Process::new(tool)
+                    .args(read_only_args(tool))
+                    .arg(repository.db_path())
+                    .status()
+                    .wrap_err_with(|| format!("Failed to run {}", tool))?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(eyre!("{} failed: {}", tool, status))
+                }
+            }
+            Some(("snapshots", _)) => {
+                for snapshot in list_snapshots(&repository.snapshots_path())? {
+                    println!(
+                        "{}",
+                        snapshot.file_name().unwrap_or_default().to_string_lossy()
+                    );
+                }
+                Ok(())
+            }
+            Some(("rollback", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("to").expect("required");
+                let snapshot = repository.snapshots_path().join(name);
+                if !snapshot.is_file() {
+                    return Err(eyre!("No snapshot {}", name));
+                }
+                // Locking snapshots the current database, which may rotate out the
+                // snapshot restored.
+                let staged = repository.db_path().with_extension("rollback");
+                copy(&snapshot, &staged)?;
+                let _lock = repository.lock()?;
+                repository.close_database()?;
+                restore(&repository.db_path(), &staged)?;
+                remove_file(&staged)?;
+                println!("Restored the database from {}", snapshot.display());
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
+
+/// The arguments opening a database read-only with each supported tool.
+fn read_only_args(tool: &str) -> &'static [&'static str] {
+    match tool {
+        "sqlite3" => &["-readonly"],
+        "sqlitebrowser" => &["--read-only"],
+        _ => unreachable!("Unknown tool"),
+    }
+}
+
+fn describe(schema: &Schema) -> String {
+    let mut description = format!("Schema version {}\n", schema.version);
+    for table in &schema.tables {
+        description.push_str(&format!(
+            "\n{}: {}\n",
+            table.name,
+            table.description.unwrap_or_default()
+        ));
+        for column in &table.columns {
+            description.push_str(&format!(
+                "  {} {}{}: {}\n",
+                column.name,
+                column.sql_type,
+                if column.primary_key {
+                    " PRIMARY KEY"
+                } else if column.nullable {
+                    ""
+                } else {
+                    " NOT NULL"
+                },
+                column.description.unwrap_or_default()
+            ));
+        }
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clapext::SubApplication,
+        database::schema::{Column, Schema, Table},
+    };
+
+    use super::{describe, Db};
+
+    #[test]
+    fn command_is_consistent() {
+        Db.command().debug_assert();
+    }
+
+    #[test]
+    fn describe_lists_the_columns() {
+        let schema = Schema {
+            version: 1,
+            tables: vec![Table {
+                name: "catalog".to_string(),
+                kind: "table".to_string(),
+                description: Some("Cataloged files."),
+                columns: vec![Column {
+                    name: "path".to_string(),
+                    sql_type: "TEXT".to_string(),
+                    nullable: true,
+                    primary_key: true,
+                    description: Some("Path of the file."),
+                }],
+            }],
+        };
+
+        assert_eq!(
+            "Schema version 1\n\ncatalog: Cataloged files.\n  path TEXT PRIMARY KEY: Path of the file.\n",
+            describe(&schema)
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    fmt::Display,
+    fs::{metadata, remove_file, File},
+    path::Path,
+    process::{Command as Process, Stdio},
+};
+
+use clap::{ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::{
+    clapext::SubApplication,
+    config::RepositoryConfig,
+    database::{connect, schema_version},
+    metadata::{exiftool::ExifTool, Backend},
+    repository::Repository,
+    trash::LEGACY_TRASH,
+};
+
+const DOCTOR: &str = "doctor";
+
+/// Below this free space, imports are likely to fail midway.
+const LOW_DISK_SPACE: u64 = 1 << 30;
+
+pub(crate) struct Doctor;
+
+impl SubApplication for Doctor {
+    fn name(&self) -> &'static str {
+        DOCTOR
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name()).about("Diagnoses the setup of the repository and suggests fixes")
+    }
+
+    fn diagnoses(&self) -> bool {
+        true
+    }
+
+    fn handle(&self, _: &ArgMatches, repository: &Repository) -> Result<()> {
+        let diagnostics = diagnose(repository);
+        for diagnostic in &diagnostics {
+            println!("{}", diagnostic);
+        }
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.status == Status::Error)
+            .count();
+        if errors == 0 {
+            println!("No problem found.");
+            Ok(())
+        } else {
+            Err(eyre!("{} problems found", errors))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The outcome of one check, with the fix to apply when it did not pass.
+#[derive(Debug)]
+struct Diagnostic {
+    check: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Diagnostic {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn error(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Error,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        };
+        write!(f, "[{}] {}: {}", status, self.check, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n    fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+fn diagnose(repository: &Repository) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![check_database(&repository.db_path())];
+    let config = match repository.config() {
+        Ok(config) => {
+            diagnostics.push(Diagnostic::ok("config", "valid"));
+            Some(config)
+        }
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(
+                "config",
+                format!("{:#}", e),
+                "Fix the syntax of .photo_works/config.toml",
+            ));
+            None
+        }
+    };
+    diagnostics.push(check_writable("library root", repository.root()));
+    diagnostics.push(check_disk_space(repository.root()));
+    if let Some(config) = config {
+        diagnostics.push(check_trash(
+            &repository.root().join(config.trash().path()),
+            &repository.root().join(LEGACY_TRASH),
+        ));
+        diagnostics.extend(check_tools(&config));
+        diagnostics.extend(check_metadata_backend(&config));
+    }
+    diagnostics
+}
+
+fn check_database(db: &Path) -> Diagnostic {
+    const CHECK: &str = "database";
+    if !db.exists() {
+        return Diagnostic::error(
+            CHECK,
+            format!("{} not found", db.display()),
+            "Run `photo_works init <PATH>` or select the repository with --profile",
+        );
+    }
+    let version = connect(&db.to_path_buf()).and_then(|mut c| schema_version(&mut c));
+    match version {
+        Ok((Some(applied), latest)) if applied == latest => {
+            Diagnostic::ok(CHECK, format!("schema version {}", applied))
+        }
+        Ok((Some(applied), latest)) if applied > latest => Diagnostic::error(
+            CHECK,
+            format!("schema version {} is newer than {}", applied, latest),
+            "Upgrade photo_works",
+        ),
+        Ok((applied, latest)) => Diagnostic::warning(
+            CHECK,
+            format!(
+                "schema version {} is older than {}",
+                applied.unwrap_or_default(),
+                latest
+            ),
+            "Run any command to migrate the database",
+        ),
+        Err(e) => Diagnostic::error(
+            CHECK,
+            format!("{:#}", e),
+            "Check the permissions of the database and its encryption key",
+        ),
+    }
+}
+
+fn check_writable(check: &'static str, directory: &Path) -> Diagnostic {
+    if !directory.is_dir() {
+        return Diagnostic::error(
+            check,
+            format!("{} is not a directory", directory.display()),
+            "Create the directory or fix the profile path",
+        );
+    }
+    let probe = directory.join(".photo_works_doctor");
+    match File::create(&probe).and_then(|_| remove_file(&probe)) {
+        Ok(()) => Diagnostic::ok(check, format!("{} is writable", directory.display())),
+        Err(e) => Diagnostic::error(
+            check,
+            format!("{} is not writable: {}", directory.display(), e),
+            format!("Grant write access to {}", directory.display()),
+        ),
+    }
+}
+
+/// Checks the configured trash, warning about the files left in the trash of the
+/// earlier versions.
+fn check_trash(trash: &Path, legacy: &Path) -> Diagnostic {
+    const CHECK: &str = "trash";
+    if legacy != trash && legacy.is_dir() {
+        return Diagnostic::warning(
+            CHECK,
+            format!("{} is no longer the trash", legacy.display()),
+            format!(
+                "Move its files to {} or set [trash] path = \"{}\"",
+                trash.display(),
+                LEGACY_TRASH
+            ),
+        );
+    }
+    match metadata(trash) {
+        Ok(_) => check_writable(CHECK, trash),
+        Err(_) => Diagnostic::ok(
+            CHECK,
+            format!("{} will be created by prune", trash.display()),
+        ),
+    }
+}
+
+fn check_disk_space(root: &Path) -> Diagnostic {
+    const CHECK: &str = "disk space";
+    match fs2::available_space(root) {
+        Ok(available) if available < LOW_DISK_SPACE => Diagnostic::warning(
+            CHECK,
+            format!("{} MB available", available >> 20),
+            "Free some space before importing",
+        ),
+        Ok(available) => Diagnostic::ok(CHECK, format!("{} MB available", available >> 20)),
+        Err(e) => Diagnostic::warning(
+            CHECK,
+            format!("unknown: {}", e),
+            "Check the filesystem of the repository",
+        ),
+    }
+}
+
+/// The external tools required by the repository configuration.
+fn check_tools(config: &RepositoryConfig) -> Vec<Diagnostic> {
+    config
+        .encryption()
+        .map(|e| e.tool())
+        .into_iter()
+        .map(|tool| {
+            let found = Process::new(tool)
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok();
+            if found {
+                Diagnostic::ok("external tool", format!("{} found", tool))
+            } else {
+                Diagnostic::error(
+                    "external tool",
+                    format!("{} not found", tool),
+                    format!("Install {} or add it to the PATH", tool),
+                )
+            }
+        })
+        .collect()
+}
+
+/// exiftool is required when configured as the metadata backend, optional otherwise.
+fn check_metadata_backend(config: &RepositoryConfig) -> Option<Diagnostic> {
+    const CHECK: &str = "metadata backend";
+    match (config.metadata().kind(), ExifTool::available()) {
+        (Backend::Exif, _) => None,
+        (_, true) => Some(Diagnostic::ok(CHECK, "exiftool found")),
+        (Backend::ExifTool, false) => Some(Diagnostic::error(
+            CHECK,
+            "exiftool not found",
+            "Install exiftool or set [metadata] backend = \"exif\"",
+        )),
+        (Backend::Auto, false) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+
+    use tempfile::TempDir;
+
+    use crate::database::open;
+
+    use super::{check_database, check_trash, check_writable, Status};
+
+    #[test]
+    fn check_database_reports_a_missing_database() {
+        let directory = TempDir::new().unwrap();
+        let diagnostic = check_database(&directory.path().join("db.db3"));
+        assert_eq!(Status::Error, diagnostic.status);
+    }
+
+    #[test]
+    fn check_database_accepts_a_migrated_database() {
+        let directory = TempDir::new().unwrap();
+        let db = directory.path().join("db.db3");
+        open(&db).unwrap();
+        assert_eq!(Status::Ok, check_database(&db).status);
+    }
+
+    #[test]
+    fn check_writable_reports_a_missing_directory() {
+        let directory = TempDir::new().unwrap();
+        let diagnostic = check_writable("library root", &directory.path().join("missing"));
+        assert_eq!(Status::Error, diagnostic.status);
+    }
+
+    #[test]
+    fn check_trash_accepts_a_missing_trash() {
+        let directory = TempDir::new().unwrap();
+        let trash = directory.path().join(".photo_works/trash");
+        let legacy = directory.path().join(".trash");
+        assert_eq!(Status::Ok, check_trash(&trash, &legacy).status);
+        create_dir_all(&trash).unwrap();
+        assert_eq!(Status::Ok, check_trash(&trash, &legacy).status);
+    }
+
+    #[test]
+    fn check_trash_warns_about_the_legacy_trash() {
+        let directory = TempDir::new().unwrap();
+        let legacy = directory.path().join(".trash");
+        create_dir_all(&legacy).unwrap();
+        assert_eq!(
+            Status::Warning,
+            check_trash(&directory.path().join(".photo_works/trash"), &legacy).status
+        );
+        assert_eq!(Status::Ok, check_trash(&legacy, &legacy).status);
+    }
+}
@@ -0,0 +1,245 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::{
+    clapext::{parse_date, parse_date_end, SubApplication},
+    database::{
+        event::{name_event, select_events, NamedEvent},
+        library::{foreach_entry, LibraryFilter},
+        tag::add_tag,
+    },
+    repository::Repository,
+};
+
+const EVENTS: &str = "events";
+
+/// The prefix of the tags naming the events.
+const EVENT_TAG_PREFIX: &str = "event/";
+
+pub(crate) struct Events;
+
+impl SubApplication for Events {
+    fn name(&self) -> &'static str {
+        EVENTS
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Clusters the library pictures into shooting events and names them")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("list")
+                    .about("Lists the events, a new one starting after each gap between captures longer than the gap.")
+                    .arg(
+                        arg!(--gap <HOURS> "The hours without capture that start a new event")
+                            .value_parser(value_parser!(u32))
+                            .default_value("12"),
+                    ),
+                Command::new("name")
+                    .about("Names the event of a range of capture dates.")
+                    .arg(arg!(<RANGE> "The first and last capture dates, e.g. 2024-07-12..14, 2024-07-30..08-02 or 2024-07-12"))
+                    .arg(arg!(<NAME> "The name of the event, e.g. \"Lake trip\""))
+                    .arg(arg!(--tag "Also tags the pictures of the range with event/<NAME>")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("list", sub_matches)) => {
+                repository.enter()?;
+                let config = repository.config()?;
+                let connection = repository.open_database()?;
+                let backend = config.metadata().backend();
+                let mut captures = vec![];
+                foreach_entry(&connection, &LibraryFilter::default(), |e| {
+                    if let Some(date) = e.original_date() {
+                        captures.push(
+                            backend
+                                .original_datetime(e.path())
+                                .ok()
+                                .filter(|t| t.date() == date)
+                                .unwrap_or(date.and_time(NaiveTime::MIN)),
+                        );
+                    }
+                    Ok(())
+                })?;
+                let gap = *sub_matches.get_one::<u32>("gap").expect("defaulted");
+                let events = select_events(&connection)?;
+                let clusters = clusters(captures, Duration::hours(gap.into()));
+                for cluster in &clusters {
+                    let name = events
+                        .iter()
+                        .find(|e| {
+                            e.contains(cluster.start.date()) && e.contains(cluster.end.date())
+                        })
+                        .map(|e| e.name.as_str())
+                        .unwrap_or_default();
+                    println!(
+                        "{} .. {} {:>6} pictures  {}",
+                        cluster.start.format("%Y-%m-%d %H:%M"),
+                        cluster.end.format("%Y-%m-%d %H:%M"),
+                        cluster.count,
+                        name
+                    );
+                }
+                println!("{} events", clusters.len());
+                Ok(())
+            }
+            Some(("name", sub_matches)) => {
+                let (start, end) =
+                    parse_range(sub_matches.get_one::<String>("RANGE").expect("required"))
+                        .map_err(|e| eyre!(e))?;
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                repository.enter()?;
+                let _lock = repository.lock()?;
+                let mut connection = repository.open_database()?;
+                let event = NamedEvent {
+                    start,
+                    end,
+                    name: name.to_owned(),
+                };
+                name_event(&connection, &event)?;
+                println!("Named {} .. {} {}", start, end, name);
+                if sub_matches.get_flag("tag") {
+                    let mut hashes = vec![];
+                    foreach_entry(&connection, &LibraryFilter::default(), |e| {
+                        if e.original_date().is_some_and(|d| event.contains(d)) {
+                            hashes.push(e.sha256().to_owned());
+                        }
+                        Ok(())
+                    })?;
+                    let tag = format!("{}{}", EVENT_TAG_PREFIX, name);
+                    println!(
+                        "Tagged {} of the {} pictures of the event with {}",
+                        add_tag(&mut connection, &tag, &hashes)?,
+                        hashes.len(),
+                        tag
+                    );
+                }
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
+
+/// Consecutive captures without a gap longer than the event gap.
+#[derive(Debug, PartialEq)]
+struct Cluster {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    count: usize,
+}
+
+/// The events of the capture times, oldest first.
+fn clusters(mut captures: Vec<NaiveDateTime>, gap: Duration) -> Vec<Cluster> {
+    captures.sort();
+    let mut clusters: Vec<Cluster> = vec![];
+    for capture in captures {
+        match clusters.last_mut() {
+            Some(cluster) if capture - cluster.end <= gap => {
+                cluster.end = capture;
+                cluster.count += 1;
+            }
+            _ => clusters.push(Cluster {
+                start: capture,
+                end: capture,
+                count: 1,
+            }),
+        }
+    }
+    clusters
+}
+
+/// The first and last dates of `2024-07-12..14`, `2024-07-30..08-02`,
+/// `2024-12-30..2025-01-02` or a single date, `2024-07` spanning the month. The
+/// end takes the year and month it omits from the start.
+fn parse_range(range: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let Some((start, end)) = range.split_once("..") else {
+        return Ok((parse_date(range)?, parse_date_end(range)?));
+    };
+    let start = parse_date(start)?;
+    let invalid = || format!("Invalid end of range {}", range);
+    let end = match end.split('-').collect::<Vec<_>>().as_slice() {
+        [day] if day.len() <= 2 => start
+            .with_day(day.parse().map_err(|_| invalid())?)
+            .ok_or_else(invalid)?,
+        [month, day] if month.len() <= 2 => NaiveDate::from_ymd_opt(
+            start.year(),
+            month.parse().map_err(|_| invalid())?,
+            day.parse().map_err(|_| invalid())?,
+        )
+        .ok_or_else(invalid)?,
+        _ => parse_date_end(end)?,
+    };
+    if end < start {
+        return Err(format!("The range {} ends before it starts", range));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+    use super::{clusters, parse_range, Cluster};
+
+    fn at(timestamp: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn clusters_start_a_new_event_after_a_gap() {
+        let captures = vec![
+            at("2024-07-13 09:00"),
+            at("2024-07-12 18:00"),
+            at("2024-07-14 08:00"),
+            at("2024-07-20 10:00"),
+        ];
+        assert_eq!(
+            vec![
+                Cluster {
+                    start: at("2024-07-12 18:00"),
+                    end: at("2024-07-13 09:00"),
+                    count: 2
+                },
+                Cluster {
+                    start: at("2024-07-14 08:00"),
+                    end: at("2024-07-14 08:00"),
+                    count: 1
+                },
+                Cluster {
+                    start: at("2024-07-20 10:00"),
+                    end: at("2024-07-20 10:00"),
+                    count: 1
+                },
+            ],
+            clusters(captures, Duration::hours(16))
+        );
+    }
+
+    #[test]
+    fn parse_range_completes_the_end_from_the_start() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            Ok((date(2024, 7, 12), date(2024, 7, 14))),
+            parse_range("2024-07-12..14")
+        );
+        assert_eq!(
+            Ok((date(2024, 7, 30), date(2024, 8, 2))),
+            parse_range("2024-07-30..08-02")
+        );
+        assert_eq!(
+            Ok((date(2024, 12, 30), date(2025, 1, 2))),
+            parse_range("2024-12-30..2025-01-02")
+        );
+        assert_eq!(
+            Ok((date(2024, 7, 12), date(2024, 7, 12))),
+            parse_range("2024-07-12")
+        );
+        assert!(parse_range("2024-07-12..10").is_err());
+        assert!(parse_range("2024-07-12..32").is_err());
+    }
+}
@@ -0,0 +1,679 @@
+use std::{
+    collections::HashSet,
+    fs::{copy, create_dir_all, metadata, read_to_string, remove_file, File},
+    io::{stdin, BufWriter, Write},
+    path::{absolute, Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+use walkdir::WalkDir;
+
+use crate::{
+    clapext::{
+        parse_date, parse_date_end, parse_size, read_targets, stdin_arg, SubApplication, Target,
+    },
+    command::tag::{query_arg, query_filter, saved_arg},
+    database::{
+        common::sha256_digest,
+        library::{foreach_entry, LibraryFilter},
+        library_entry::LibraryEntry,
+        stack::select_stacks,
+        sync_state::record_synced,
+    },
+    encryption::Encryption,
+    naming::LibraryNaming,
+    privacy::{PrivacyConfig, Redaction},
+    repository::Repository,
+    style::Status,
+};
+
+pub(crate) const EXPORT: &str = "export";
+
+/// The name of the manifest written in each export folder.
+const MANIFEST: &str = "MANIFEST.sha256";
+
+pub(crate) struct Export;
+
+impl SubApplication for Export {
+    fn name(&self) -> &'static str {
+        EXPORT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Copies library pictures to a destination folder")
+            .arg(arg!(<DEST> "The folder to export to"))
+            .arg(
+                arg!(--year <YEAR> "Only exports the pictures taken during the year")
+                    .value_parser(clap::value_parser!(i32)),
+            )
+            .arg(
+                arg!(--from <DATE> "Only exports the pictures taken on or after the date, e.g. 2023-01-01")
+                    .value_parser(parse_date),
+            )
+            .arg(
+                arg!(--to <DATE> "Only exports the pictures taken on or before the date, e.g. 2023-12-31 or 2023-12")
+                    .value_parser(parse_date_end),
+            )
+            .arg(arg!(--"path-prefix" <PREFIX> "Only exports the pictures under the library path"))
+            .arg(query_arg())
+            .arg(saved_arg())
+            .arg(
+                arg!(--split <SIZE> "Splits the export in numbered folders each smaller than SIZE (e.g. 23GB)")
+                    .value_parser(parse_size),
+            )
+            .arg(arg!(--flatten "Copies the pictures directly in the destination folder rather than in their library directories, suffixing the names that collide"))
+            .arg(arg!(--encrypt "Encrypts the exported files with the tool of the repository configuration"))
+            .arg(arg!(--stacks "Also exports the other frames of the brackets and panoramas of the selected pictures"))
+            .arg(stdin_arg())
+            .arg_required_else_help(true)
+    }
+
+    fn remembered(&self) -> &'static [&'static str] {
+        &["year", "from", "to", "path-prefix"]
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let destination = ExportDestination {
+            path: absolute(sub_matches.get_one::<String>("DEST").expect("required"))?,
+            split: sub_matches.get_one::<u64>("split").copied(),
+            flatten: sub_matches.get_flag("flatten"),
+        };
+        let targets = if sub_matches.get_flag("stdin") {
+            Some(read_targets(stdin().lock())?)
+        } else {
+            None
+        };
+        repository.enter()?;
+        // Library paths are relative to the repository root.
+        let targets = targets
+            .map(|targets| {
+                targets
+                    .into_iter()
+                    .map(|t| {
+                        t.map_path(|p| {
+                            Ok(p.strip_prefix(repository.root())
+                                .map(Path::to_path_buf)
+                                .unwrap_or(p))
+                        })
+                    })
+                    .collect::<Result<Vec<Target>>>()
+            })
+            .transpose()?;
+        let connection = repository.open_database()?;
+        let query = query_filter(sub_matches, &connection)?.relative_to(repository.root());
+        let filter = LibraryFilter {
+            year: sub_matches.get_one::<i32>("year").copied().or(query.year),
+            path_prefix: sub_matches
+                .get_one::<String>("path-prefix")
+                .cloned()
+                .or(query.path_prefix),
+            after: sub_matches
+                .get_one::<NaiveDate>("from")
+                .copied()
+                .or(query.after),
+            before: sub_matches
+                .get_one::<NaiveDate>("to")
+                .copied()
+                .or(query.before),
+            ..query
+        };
+        let (filter, targets) = if sub_matches.get_flag("stacks") {
+            let targets = with_stacks(&connection, &filter, targets.as_deref())?;
+            (LibraryFilter::default(), Some(targets))
+        } else {
+            (filter, targets)
+        };
+        let config = repository.config()?;
+        let encryption = if sub_matches.get_flag("encrypt") {
+            Some(
+                config
+                    .encryption()
+                    .ok_or(eyre!("No [encryption] in the repository configuration."))?,
+            )
+        } else {
+            None
+        };
+
+        println!("Exporting to {}", destination.path.display());
+
+        let (exported, skipped) = export(
+            &connection,
+            &filter,
+            targets.as_deref(),
+            &destination,
+            encryption,
+            config.privacy(),
+        )?;
+        println!("Exported {} pictures", exported);
+        if skipped > 0 {
+            println!(
+                "Skipped {} pictures taken in privacy zones, in formats that can't be redacted",
+                skipped
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The selected pictures along with the other frames of their brackets and
+/// panoramas, by hash.
+fn with_stacks(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    targets: Option<&[Target]>,
+) -> Result<Vec<Target>> {
+    let mut selected = HashSet::new();
+    foreach_entry(connection, filter, |entry| {
+        if targets.is_none_or(|targets| {
+            targets
+                .iter()
+                .any(|t| t.matches(entry.sha256(), entry.path()))
+        }) {
+            selected.insert(entry.sha256().to_owned());
+        }
+        Ok(())
+    })?;
+    for stack in select_stacks(connection)?.into_values() {
+        if stack.hashes.iter().any(|h| selected.contains(h)) {
+            selected.extend(stack.hashes);
+        }
+    }
+    Ok(selected.into_iter().map(Target::Hash).collect())
+}
+
+/// Where and how the pictures are exported.
+struct ExportDestination {
+    path: PathBuf,
+    /// The largest size of the numbered folders the export is split in, if any.
+    split: Option<u64>,
+    /// Whether the pictures are copied directly in the folder rather than in their
+    /// library directories.
+    flatten: bool,
+}
+
+/// A library entry along with the size of its file and its path in the export.
+struct ExportedFile {
+    entry: LibraryEntry,
+    size: u64,
+    name: PathBuf,
+}
+
+/// Exports the selected pictures. Returns the number of pictures exported and the
+/// number of those skipped as their coordinates can't be redacted.
+fn export(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    targets: Option<&[Target]>,
+    destination: &ExportDestination,
+    encryption: Option<&Encryption>,
+    privacy: &PrivacyConfig,
+) -> Result<(usize, usize)> {
+    let mut files = vec![];
+    foreach_entry(connection, filter, |entry| {
+        if let Some(targets) = targets {
+            if !targets
+                .iter()
+                .any(|t| t.matches(entry.sha256(), entry.path()))
+            {
+                return Ok(());
+            }
+        }
+        let size = metadata(entry.path())?.len();
+        let name = entry.path().to_owned();
+        files.push(ExportedFile { entry, size, name });
+        Ok(())
+    })?;
+    files.sort_by(|a, b| a.entry.path().cmp(b.entry.path()));
+    if destination.flatten {
+        flatten(&mut files)?;
+    }
+    let selected = files.len();
+    let mut synced = vec![];
+    match destination.split {
+        Some(limit) => {
+            for (index, chunk) in split_in_chunks(files, limit)?.iter().enumerate() {
+                let chunk_destination = destination.path.join(format!("part-{:03}", index + 1));
+                println!(
+                    "Exporting {} pictures into {}",
+                    chunk.len(),
+                    chunk_destination.display()
+                );
+                synced.extend(export_files(
+                    chunk,
+                    &chunk_destination,
+                    encryption,
+                    privacy,
+                )?);
+            }
+        }
+        None => synced = export_files(&files, &destination.path, encryption, privacy)?,
+    }
+    record_synced(
+        connection,
+        &format!("{}:{}", EXPORT, destination.path.display()),
+        &synced,
+    )?;
+    Ok((synced.len(), selected - synced.len()))
+}
+
+/// Names the files after their file name alone, suffixed `_1`, `_2`... when it is
+/// taken by a previous file.
+fn flatten(files: &mut [ExportedFile]) -> Result<()> {
+    let naming = LibraryNaming::default();
+    let mut taken = HashSet::new();
+    for file in files {
+        let path = file.entry.path();
+        let stem = path.file_stem().ok_or(eyre!("Expected a file stem"))?;
+        let extension = path.extension().unwrap_or_default();
+        for suffix in naming.suffixes(file.entry.sha256()) {
+            let name = PathBuf::from(naming.file_name(Path::new(""), stem, &suffix, extension)?);
+            if taken.insert(name.clone()) {
+                file.name = name;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Partitions the files in consecutive chunks whose total size is under the limit.
+fn split_in_chunks(files: Vec<ExportedFile>, limit: u64) -> Result<Vec<Vec<ExportedFile>>> {
+    let mut chunks: Vec<Vec<ExportedFile>> = vec![];
+    let mut chunk_size = 0;
+    for file in files {
+        if file.size > limit {
+            return Err(eyre!(
+                "{} is larger than the split size.",
+                file.entry.path().display()
+            ));
+        }
+        match chunks.last_mut() {
+            Some(chunk) if chunk_size + file.size <= limit => {
+                chunk_size += file.size;
+                chunk.push(file);
+            }
+            _ => {
+                chunk_size = file.size;
+                chunks.push(vec![file]);
+            }
+        }
+    }
+    Ok(chunks)
+}
+
+/// Copies the files under the destination, at their export path, along with
+/// a `MANIFEST.sha256` that `sha256sum -c` can verify. Encrypted files get the
+/// extension of the encryption tool while the manifest keeps their plain digest.
+/// Returns the `(hash, digest of the exported file)` of the files.
+fn export_files(
+    files: &[ExportedFile],
+    destination: &Path,
+    encryption: Option<&Encryption>,
+    privacy: &PrivacyConfig,
+) -> Result<Vec<(String, String)>> {
+    create_dir_all(destination)?;
+    let mut synced = vec![];
+    let mut manifest = BufWriter::new(File::create(destination.join(MANIFEST))?);
+    for ExportedFile { entry, name, .. } in files {
+        let target: PathBuf = destination.join(name);
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        // The pictures taken inside a privacy zone are exported redacted, under
+        // the digest of the redacted copy.
+        let redacted = privacy.zone_of(entry.path()).is_some();
+        let exported_hash = if redacted {
+            copy(entry.path(), &target)?;
+            match privacy.redact(&target)? {
+                Redaction::Unsupported(zone) => {
+                    remove_file(&target)?;
+                    println!(
+                        "{} {} (taken in the privacy zone {}, in a format that can't be redacted)",
+                        Status::Skipped,
+                        entry.path().display(),
+                        zone
+                    );
+                    continue;
+                }
+                Redaction::Redacted(zone) => println!(
+                    "{} {} (privacy zone {})",
+                    Status::Redacted,
+                    entry.path().display(),
+                    zone
+                ),
+                Redaction::Unchanged => (),
+            }
+            sha256_digest(&target)?
+        } else {
+            entry.sha256().to_owned()
+        };
+        let synced_hash = match encryption {
+            Some(encryption) => {
+                let encrypted = encryption.encrypted_path(&target);
+                if redacted {
+                    encryption.encrypt(&target, &encrypted)?;
+                    remove_file(&target)?;
+                } else {
+                    encryption.encrypt(entry.path(), &encrypted)?;
+                }
+                sha256_digest(&encrypted)?
+            }
+            None => {
+                if !redacted {
+                    copy(entry.path(), &target)?;
+                }
+                exported_hash.clone()
+            }
+        };
+        synced.push((entry.sha256().to_owned(), synced_hash));
+        writeln!(
+            manifest,
+            "{}  {}",
+            exported_hash.to_lowercase(),
+            name.display()
+        )?;
+    }
+    manifest.flush()?;
+    Ok(synced)
+}
+
+/// The path of the content exported to the destination, from the manifests of the
+/// destination and of its split folders. The file itself may have been encrypted.
+pub(crate) fn find_exported(destination: &Path, hash: &str) -> Result<Option<PathBuf>> {
+    for manifest in WalkDir::new(destination)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == MANIFEST)
+    {
+        let directory = manifest
+            .path()
+            .parent()
+            .expect("manifest is in a directory");
+        for line in read_to_string(manifest.path())?.lines() {
+            if let Some((digest, file)) = line.split_once("  ") {
+                if digest.eq_ignore_ascii_case(hash) {
+                    return Ok(Some(directory.join(file)));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, read_to_string, write},
+        path::PathBuf,
+    };
+
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    use crate::{
+        clapext::Target,
+        database::{
+            library::LibraryFilter,
+            library_entry::LibraryEntry,
+            stack::{record_stacks, Stack},
+            sync_state::sync_status,
+            test_utils::new_database_containing_library_entries,
+        },
+        media::stack::StackKind,
+        privacy::{
+            test_utils::{
+                given_a_jpeg_taken_at_the_eiffel_tower, given_a_tiff_taken_at_the_eiffel_tower,
+            },
+            PrivacyConfig,
+        },
+    };
+
+    use super::{
+        export, find_exported, split_in_chunks, with_stacks, ExportDestination, ExportedFile,
+    };
+
+    fn given_a_file(hash: &str, size: u64) -> ExportedFile {
+        ExportedFile {
+            entry: LibraryEntry::new(hash.to_string(), PathBuf::from(hash)),
+            size,
+            name: PathBuf::from(hash),
+        }
+    }
+
+    #[test]
+    fn split_in_chunks_keeps_each_chunk_under_the_limit() {
+        let files = vec![
+            given_a_file("1", 6),
+            given_a_file("2", 4),
+            given_a_file("3", 5),
+        ];
+
+        let chunks = split_in_chunks(files, 10).unwrap();
+
+        assert_eq!(
+            vec![vec!["1", "2"], vec!["3"]],
+            chunks
+                .iter()
+                .map(|c| c.iter().map(|f| f.entry.sha256()).collect::<Vec<&str>>())
+                .collect::<Vec<Vec<&str>>>()
+        );
+    }
+
+    #[test]
+    fn with_stacks_adds_the_other_frames() {
+        let entries = ["A", "B", "C", "D"]
+            .map(|hash| LibraryEntry::new(hash.to_string(), PathBuf::from(hash)))
+            .into_iter()
+            .collect::<Vec<LibraryEntry>>();
+        let mut connection = new_database_containing_library_entries(&entries);
+        record_stacks(
+            &mut connection,
+            &[Stack {
+                kind: StackKind::Bracket,
+                hashes: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            }],
+        )
+        .unwrap();
+
+        let mut hashes = with_stacks(
+            &connection,
+            &LibraryFilter::default(),
+            Some(&[Target::Hash("B".to_string())]),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|t| match t {
+            Target::Hash(hash) => hash,
+            Target::Path(_) => unreachable!("hashes only"),
+        })
+        .collect::<Vec<String>>();
+        hashes.sort();
+
+        assert_eq!(vec!["A", "B", "C"], hashes);
+    }
+
+    #[test]
+    fn split_in_chunks_fails_when_a_file_is_larger_than_the_limit() {
+        assert!(split_in_chunks(vec![given_a_file("1", 11)], 10).is_err());
+    }
+
+    #[test]
+    fn export_copies_the_files_with_a_manifest() {
+        let destination = TempDir::new().unwrap();
+        let entries = vec![LibraryEntry::new(
+            "ABCD".to_string(),
+            ["resources", "test", "kami_neko.jpeg"].iter().collect(),
+        )];
+        let connection = new_database_containing_library_entries(&entries);
+
+        let count = export(
+            &connection,
+            &LibraryFilter::default(),
+            None,
+            &ExportDestination {
+                path: destination.path().to_owned(),
+                split: Some(1 << 30),
+                flatten: false,
+            },
+            None,
+            &PrivacyConfig::default(),
+        )
+        .unwrap();
+
+        let chunk = destination.path().join("part-001");
+        assert_eq!((1, 0), count);
+        assert!(chunk.join(entries[0].path()).exists());
+        assert_eq!(
+            "abcd  resources/test/kami_neko.jpeg\n",
+            read_to_string(chunk.join("MANIFEST.sha256")).unwrap()
+        );
+        assert_eq!(
+            Some(chunk.join(entries[0].path())),
+            find_exported(destination.path(), "ABCD").unwrap()
+        );
+        assert_eq!(None, find_exported(destination.path(), "EF01").unwrap());
+    }
+
+    #[test]
+    fn export_only_copies_the_targets() {
+        let destination = TempDir::new().unwrap();
+        let entries = vec![
+            LibraryEntry::new(
+                "ABCD".to_string(),
+                ["resources", "test", "kami_neko.jpeg"].iter().collect(),
+            ),
+            LibraryEntry::new(
+                "EF01".to_string(),
+                ["resources", "test", "no_original_date.jpeg"]
+                    .iter()
+                    .collect(),
+            ),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+
+        let count = export(
+            &connection,
+            &LibraryFilter::default(),
+            Some(&[Target::Path(entries[1].path().to_path_buf())]),
+            &ExportDestination {
+                path: destination.path().to_owned(),
+                split: None,
+                flatten: false,
+            },
+            None,
+            &PrivacyConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!((1, 0), count);
+        assert!(!destination.path().join(entries[0].path()).exists());
+        assert!(destination.path().join(entries[1].path()).exists());
+        let status = sync_status(&connection, &[]).unwrap();
+        assert_eq!(
+            format!("export:{}", destination.path().display()),
+            status[0].target
+        );
+        assert_eq!((1, 1), (status[0].synced, status[0].pending));
+    }
+
+    #[test]
+    fn export_flattens_the_pictures_of_the_date_range() {
+        let library = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let entry = |hash: &str, path: &str, year: i32| {
+            let path = library.path().join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(&path, hash).unwrap();
+            LibraryEntry::new(hash.to_string(), path)
+                .with_original_date(NaiveDate::from_ymd_opt(year, 5, 8))
+        };
+        let entries = vec![
+            entry("A1", "2023/05/08/a.jpg", 2023),
+            entry("B2", "2023/05/09/a.jpg", 2023),
+            entry("C3", "2022/05/08/c.jpg", 2022),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let filter = LibraryFilter {
+            after: NaiveDate::from_ymd_opt(2023, 1, 1),
+            before: NaiveDate::from_ymd_opt(2023, 12, 31),
+            ..Default::default()
+        };
+
+        let count = export(
+            &connection,
+            &filter,
+            None,
+            &ExportDestination {
+                path: destination.path().to_owned(),
+                split: None,
+                flatten: true,
+            },
+            None,
+            &PrivacyConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!((2, 0), count);
+        assert_eq!(
+            "A1",
+            read_to_string(destination.path().join("a.jpg")).unwrap()
+        );
+        assert_eq!(
+            "B2",
+            read_to_string(destination.path().join("a_1.jpg")).unwrap()
+        );
+        assert!(!destination.path().join("c.jpg").exists());
+        assert_eq!(
+            "a1  a.jpg\nb2  a_1.jpg\n",
+            read_to_string(destination.path().join("MANIFEST.sha256")).unwrap()
+        );
+    }
+
+    #[test]
+    fn export_counts_apart_the_pictures_it_cannot_redact() {
+        let library = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        write(
+            library.path().join("a.jpg"),
+            given_a_jpeg_taken_at_the_eiffel_tower(),
+        )
+        .unwrap();
+        write(
+            library.path().join("b.tif"),
+            given_a_tiff_taken_at_the_eiffel_tower(),
+        )
+        .unwrap();
+        let entries = ["a.jpg", "b.tif"]
+            .map(|name| LibraryEntry::new(name.to_string(), library.path().join(name)))
+            .into_iter()
+            .collect::<Vec<LibraryEntry>>();
+        let connection = new_database_containing_library_entries(&entries);
+        let privacy: PrivacyConfig = toml::from_str(
+            "[[zones]]\nname = \"home\"\nlatitude = 48.8584\nlongitude = 2.2945\nradius = 500",
+        )
+        .unwrap();
+
+        let count = export(
+            &connection,
+            &LibraryFilter::default(),
+            None,
+            &ExportDestination {
+                path: destination.path().to_owned(),
+                split: None,
+                flatten: true,
+            },
+            None,
+            &privacy,
+        )
+        .unwrap();
+
+        assert_eq!((1, 1), count);
+        assert!(destination.path().join("a.jpg").exists());
+        assert!(!destination.path().join("b.tif").exists());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::{parse_date, SubApplication},
+    database::{
+        library::{foreach_entry, LibraryFilter},
+        library_history::select_library_at,
+    },
+    repository::Repository,
+    style::Status,
+};
+
+const HISTORY: &str = "history";
+
+pub(crate) struct History;
+
+impl SubApplication for History {
+    fn name(&self) -> &'static str {
+        HISTORY
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Shows the past states of the library")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([Command::new("at")
+                .about("Lists the library files at the end of a date, with what became of them since.")
+                .arg(arg!(<DATE> "The date, e.g. 2024-01-01").value_parser(parse_date))
+                .arg(arg!(--path <PREFIX> "Only lists the files whose path then started with the prefix, e.g. 2023/"))])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        if let Some(("at", sub_matches)) = sub_matches.subcommand() {
+            let date = *sub_matches.get_one::<NaiveDate>("DATE").expect("required");
+            repository.enter()?;
+            let connection = repository.open_database()?;
+
+            let mut current = HashMap::new();
+            foreach_entry(&connection, &LibraryFilter::default(), |e| {
+                current.insert(e.sha256().to_owned(), e.path().to_owned());
+                Ok(())
+            })?;
+            let entries = select_library_at(
+                &connection,
+                date,
+                sub_matches.get_one::<String>("path").map(String::as_str),
+            )?;
+            let (mut removed, mut moved) = (0, 0);
+            for (hash, path) in &entries {
+                match current.get(hash) {
+                    None => {
+                        removed += 1;
+                        println!(
+                            "{} {} {}, removed since",
+                            Status::Missing,
+                            path.display(),
+                            hash
+                        );
+                    }
+                    Some(now) if now != path => {
+                        moved += 1;
+                        println!("{} {}, now at {}", path.display(), hash, now.display());
+                    }
+                    Some(_) => println!("{} {}", path.display(), hash),
+                }
+            }
+            println!(
+                "{} library files at the end of {}, {} removed and {} moved since",
+                entries.len(),
+                date,
+                removed,
+                moved
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,22 +1,58 @@
 use std::{
-    fs::{copy, create_dir_all},
-    path::PathBuf,
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    fs::{copy, create_dir_all, metadata, write},
+    io::stdin,
+    path::{absolute, Path, PathBuf},
+    str::FromStr,
 };
 
+use chrono::NaiveDate;
 use clap::{arg, ArgMatches, Command};
 use eyre::{eyre, Result};
+use glob::Pattern;
+use indicatif::HumanBytes;
 use rusqlite::Connection;
 
 use crate::{
-    clapext::SubApplication,
+    clapext::{parse_date, read_targets, stdin_arg, SubApplication, Target},
+    command::{
+        stack::detect_stacks,
+        tag::{derive_rules, derive_tags, folder_tags},
+    },
+    config::{current_user, RepositoryConfig},
     database::{
-        self, catalog::select_from_catalog, common::sha256_digest,
-        library::persist_library_entries, library_entry::LibraryEntry,
+        catalog::{select_catalog_roots, select_from_catalog},
+        catalog_entry::CatalogEntry,
+        common::sha256_digest,
+        library::{persist_library_entries, select_library_hashes, select_library_path},
+        library_entry::LibraryEntry,
+        library_root::persist_library_roots,
+        operation::select_paths_cataloged_since,
+        pair::select_partners,
+        problem::{persist_problems, Problem},
+        sidecar::record_sidecars,
+        tag::add_tag,
+        trashed::{select_discarded, Trashed},
+    },
+    fsext::{
+        health::DestinationHealth,
+        source::{ensure_outside_sources, SourceSnapshot},
     },
+    media::{self, sidecar::Sidecar},
+    metadata::{DateSource, MetadataBackend},
+    progress::{file_size, print_line, Progress},
+    repository::Repository,
+    rules::{evaluate, ImportRule, RuleAction},
+    style::Status,
+    thumbnail::{thumbs_arg, ThumbnailMode, ThumbnailWorker},
 };
 
 const IMPORT: &str = "import";
 
+/// Number of imported files between two checks of the library filesystem.
+const HEALTH_CHECK_INTERVAL: usize = 10;
+
 pub(crate) struct Import;
 
 impl SubApplication for Import {
@@ -27,66 +63,870 @@ impl SubApplication for Import {
     fn command(&self) -> Command {
         Command::new(self.name())
             .about("Imports cataloged pictures that are not already in the library")
-            .arg(arg!(<PATH_PREFIX> "The prefix to the path queried in the catalog"))
+            .arg(
+                arg!([PATH_PREFIX] "The prefix to the path queried in the catalog")
+                    .required_unless_present("stdin"),
+            )
+            .arg(
+                arg!(--priority <RULE> "Imports the matching pictures first (ext:<EXT>, path:<GLOB> or camera:<MODEL>), in the order given")
+                    .value_parser(PriorityRule::from_str)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                arg!(--exclude <GLOB> "Skips the cataloged files whose path matches the pattern, e.g. */rejects/* or *.gif")
+                    .value_parser(Pattern::new)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                arg!(--"cataloged-since" <DATE> "Only imports the files cataloged on or after the date, e.g. yesterday, last-week or 2024-07-15")
+                    .value_parser(parse_date),
+            )
+            .arg(arg!(--"verify-source-untouched" "Verifies the modification times of the imported sources did not change"))
+            .arg(arg!(--"dry-run" "Only reports the pictures to import and their library paths, without copying or recording them"))
+            .arg(arg!(--preview "Prints the table of the cataloged pictures and their library paths, as laid out and renamed on collisions, without copying or recording them"))
+            .arg(arg!(--csv <FILE> "Writes the preview as CSV to the file").value_parser(clap::value_parser!(PathBuf)).requires("preview"))
+            .arg(arg!(--"reimport-trashed" "Imports the pictures whose content was moved to the trash before, from the trash when it still holds them"))
+            .arg(thumbs_arg())
+            .arg(stdin_arg())
             .arg_required_else_help(true)
     }
 
-    fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
+    fn remembered(&self) -> &'static [&'static str] {
+        &["exclude", "cataloged-since"]
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let targets = if sub_matches.get_flag("stdin") {
+            Some(
+                read_targets(stdin().lock())?
+                    .into_iter()
+                    .map(|t| t.map_path(|p| Ok(absolute(p)?)))
+                    .collect::<Result<Vec<Target>>>()?,
+            )
+        } else {
+            None
+        };
         let prefix = sub_matches
             .get_one::<String>("PATH_PREFIX")
-            .expect("required")
-            .as_str();
-        let db_path: PathBuf = [".photo_works", "db.db3"].iter().collect();
-        let connection = database::open(&db_path)?;
+            .map(|p| p.as_str())
+            .unwrap_or_default();
+        let priorities = sub_matches
+            .get_many::<PriorityRule>("priority")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<PriorityRule>>();
+        let thumbs = sub_matches
+            .get_one::<String>("thumbs")
+            .expect("defaulted")
+            .parse::<ThumbnailMode>()?;
+        let mut excludes = sub_matches
+            .get_many::<Pattern>("exclude")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<Pattern>>();
+        repository.enter()?;
+        // The dry runs write nothing, not even the snapshot taken with the lock.
+        let dry_run = sub_matches.get_flag("dry-run") || sub_matches.get_flag("preview");
+        let _lock = if dry_run {
+            None
+        } else {
+            Some(repository.lock()?)
+        };
+        let config = repository.config()?;
+        // The files dropped in the inbox wait for inbox approve.
+        if let Ok(inbox) = repository.root().join(config.inbox().path()).canonicalize() {
+            excludes.push(Pattern::new(&format!(
+                "{}/*",
+                Pattern::escape(&inbox.to_string_lossy())
+            ))?);
+        }
+        let mut connection = repository.open_database()?;
+        let sources = source_roots(&connection, prefix)?;
+        let snapshot = if sub_matches.get_flag("verify-source-untouched") {
+            Some(SourceSnapshot::take(&sources)?)
+        } else {
+            None
+        };
 
         println!(
             "Importing from catalog images where path starts with {}",
             &prefix
         );
 
-        Ok(println!(
+        let derive_rules = derive_rules(&config)?;
+        let entries = select_entries(
+            &connection,
+            prefix,
+            targets.as_deref(),
+            &excludes,
+            sub_matches.get_one::<NaiveDate>("cataloged-since").copied(),
+        )?;
+        let trashed = select_discarded(&connection)?;
+        let (entries, discarded) = partition_trashed(entries, &trashed);
+        let reimport_trashed = sub_matches.get_flag("reimport-trashed");
+        let (entries, restored) = if reimport_trashed {
+            restore_from_trash(entries, discarded, &trashed)
+        } else {
+            for entry in &discarded {
+                let trashed = &trashed[entry.sha256()];
+                println!(
+                    "{} {} (moved to the trash on {} from {})",
+                    Status::Skipped,
+                    entry.path().display(),
+                    trashed.trashed_at.format("%Y-%m-%d"),
+                    trashed.original_path.display()
+                );
+            }
+            if !discarded.is_empty() {
+                println!(
+                    "Skipping {} pictures moved to the trash before, run import --reimport-trashed to import them again",
+                    discarded.len()
+                );
+            }
+            (entries, vec![])
+        };
+        if dry_run {
+            let plans = plan_import(
+                &connection,
+                entries,
+                &priorities,
+                &config,
+                repository.root(),
+                &sources,
+            )?;
+            if let Some(file) = sub_matches.get_one::<PathBuf>("csv") {
+                write(file, to_csv(&plans))?;
+            } else if sub_matches.get_flag("preview") {
+                print!("{}", to_table(&plans));
+            } else {
+                for plan in &plans {
+                    println!("{}", plan);
+                }
+            }
+            let imported = plans.iter().filter(|p| p.destination().is_some());
+            println!(
+                "Would import {} pictures, {}",
+                imported.clone().count(),
+                HumanBytes(imported.map(|p| p.bytes).sum())
+            );
+            return Ok(());
+        }
+        let health = DestinationHealth::new(repository.root(), Path::new(".photo_works"))?;
+        let worker = match thumbs {
+            ThumbnailMode::Inline => Some(ThumbnailWorker::start(repository.thumbnails_path())),
+            _ => None,
+        };
+        println!(
             "Imported {} pictures",
-            import(connection, prefix)?
-        ))
+            import(
+                &mut connection,
+                entries,
+                &priorities,
+                &config,
+                &health,
+                &sources,
+                worker.as_ref()
+            )?
+        );
+        if !restored.is_empty() {
+            let imported = select_library_hashes(&connection)?;
+            let bin = repository.trash()?;
+            for (_, path) in restored.iter().filter(|(hash, _)| imported.contains(hash)) {
+                bin.delete(&connection, path)?;
+            }
+        }
+        match (worker, thumbs) {
+            (Some(worker), _) => {
+                let (generated, deferred) = worker.finish();
+                println!("Generated {} thumbnails", generated);
+                if deferred > 0 {
+                    println!(
+                        "Run photo_works thumbnails for the {} pictures imported faster than their thumbnails",
+                        deferred
+                    );
+                }
+            }
+            (None, ThumbnailMode::Defer) => {
+                println!("Run photo_works thumbnails to generate the thumbnails")
+            }
+            (None, _) => {}
+        }
+        if !derive_rules.is_empty() {
+            let count = derive_tags(&mut connection, &derive_rules)?;
+            println!("Derived {} tags", count);
+        }
+        match snapshot {
+            Some(snapshot) => snapshot.verify_untouched(&sources),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits the entries whose content was discarded to the trash from the others.
+fn partition_trashed(
+    entries: Vec<CatalogEntry>,
+    trashed: &HashMap<String, Trashed>,
+) -> (Vec<CatalogEntry>, Vec<CatalogEntry>) {
+    entries
+        .into_iter()
+        .partition(|e| !trashed.contains_key(e.sha256()))
+}
+
+/// Adds the discarded entries back to the entries to import, read from the trash
+/// when it still holds their file since it is local. Returns the entries along
+/// with the `(hash, trash path)` of the ones read from the trash.
+fn restore_from_trash(
+    mut entries: Vec<CatalogEntry>,
+    discarded: Vec<CatalogEntry>,
+    trashed: &HashMap<String, Trashed>,
+) -> (Vec<CatalogEntry>, Vec<(String, PathBuf)>) {
+    let mut restored = vec![];
+    for entry in discarded {
+        let path = &trashed[entry.sha256()].path;
+        if path.is_file() {
+            entries.push(CatalogEntry::new(
+                entry.sha256().to_owned(),
+                path.to_string_lossy().to_string(),
+            ));
+            restored.push((entry.sha256().to_owned(), path.to_owned()));
+        } else {
+            entries.push(entry);
+        }
+    }
+    (entries, restored)
+}
+
+/// A rule ordering the import queue: entries matching earlier rules are imported first.
+#[derive(Clone, Debug)]
+pub(crate) enum PriorityRule {
+    Extension(String),
+    Path(Pattern),
+    Camera(String),
+}
+
+impl FromStr for PriorityRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> std::result::Result<Self, Self::Err> {
+        match rule.split_once(':') {
+            Some(("ext", extension)) => Ok(Self::Extension(extension.to_lowercase())),
+            Some(("path", pattern)) => Pattern::new(pattern)
+                .map(Self::Path)
+                .map_err(|e| e.to_string()),
+            Some(("camera", model)) => Ok(Self::Camera(model.to_lowercase())),
+            _ => Err(format!(
+                "Invalid priority rule `{}`, expected ext:<EXT>, path:<GLOB> or camera:<MODEL>",
+                rule
+            )),
+        }
+    }
+}
+
+impl PriorityRule {
+    fn matches(&self, entry: &CatalogEntry, metadata: &dyn MetadataBackend) -> bool {
+        match self {
+            Self::Extension(extension) => entry
+                .path()
+                .extension()
+                .is_some_and(|e| e.to_string_lossy().to_lowercase() == *extension),
+            Self::Path(pattern) => pattern.matches_path(&entry.path()),
+            Self::Camera(model) => metadata
+                .camera_model(&entry.path())
+                .is_some_and(|m| m.to_lowercase().contains(model.as_str())),
+        }
+    }
+}
+
+/// Orders the entries by the first priority rule they match, unmatched entries last.
+fn prioritize(
+    entries: Vec<CatalogEntry>,
+    rules: &[PriorityRule],
+    metadata: &dyn MetadataBackend,
+) -> Vec<(usize, CatalogEntry)> {
+    let mut prioritized = entries
+        .into_iter()
+        .map(|e| {
+            (
+                rules
+                    .iter()
+                    .position(|r| r.matches(&e, metadata))
+                    .unwrap_or(rules.len()),
+                e,
+            )
+        })
+        .collect::<Vec<(usize, CatalogEntry)>>();
+    prioritized.sort_by_key(|(priority, _)| *priority);
+    prioritized
+}
+
+/// The catalog roots holding the entries of the path prefix.
+fn source_roots(connection: &Connection, path_prefix: &str) -> Result<Vec<PathBuf>> {
+    Ok(select_catalog_roots(connection)?
+        .into_iter()
+        .filter(|root| {
+            let root = root.to_string_lossy();
+            root.starts_with(path_prefix) || path_prefix.starts_with(root.as_ref())
+        })
+        .collect())
+}
+
+/// The catalog entries of the path prefix, restricted to the targets and without
+/// the entries matching an exclusion pattern.
+fn select_entries(
+    connection: &Connection,
+    path_prefix: &str,
+    targets: Option<&[Target]>,
+    excludes: &[Pattern],
+    cataloged_since: Option<NaiveDate>,
+) -> Result<Vec<CatalogEntry>> {
+    let paths = cataloged_since
+        .map(|date| select_paths_cataloged_since(connection, date))
+        .transpose()?;
+    select_from_catalog(connection, path_prefix, |e| {
+        targets.is_none_or(|targets| targets.iter().any(|t| t.matches(e.sha256(), &e.path())))
+            && paths
+                .as_ref()
+                .is_none_or(|paths| paths.contains(e.path().to_string_lossy().as_ref()))
+            && !excludes.iter().any(|p| p.matches_path(&e.path()))
+    })
+}
+
+/// Imports the catalog entries, checking periodically that the library is still
+/// on the expected filesystem. When it is not, the entries already copied are
+/// persisted before aborting so that a later import resumes after them.
+pub(crate) fn import(
+    connection: &mut Connection,
+    entries: Vec<CatalogEntry>,
+    priorities: &[PriorityRule],
+    config: &RepositoryConfig,
+    health: &DestinationHealth,
+    sources: &[PathBuf],
+    thumbnails: Option<&ThumbnailWorker>,
+) -> Result<usize> {
+    let roots = config
+        .roots()
+        .iter()
+        .map(|(name, path)| (name.to_owned(), path.to_owned()))
+        .collect::<Vec<(String, PathBuf)>>();
+    persist_library_roots(connection, &roots)?;
+    let backend = config.metadata().backend();
+    let imported_by = current_user()?;
+    let folder_tags = folder_tags(config, sources)?;
+    let entries = prioritize(entries, priorities, backend.as_ref());
+    let total = entries.len();
+    let mut library_entries = vec![];
+    let mut tags = vec![];
+    let mut sidecars = vec![];
+    let partners = select_partners(connection)?;
+    let mut folders = HashMap::new();
+    let local = entries.iter().filter(|(_, e)| !e.is_remote());
+    let progress = Progress::start(
+        "Importing",
+        local.clone().count() as u64,
+        local.map(|(_, e)| file_size(&e.path())).sum(),
+    );
+    for (index, (priority, e)) in entries.iter().enumerate() {
+        if e.is_remote() {
+            print_line(format!(
+                "[{}/{}] Skipping remote {}",
+                index + 1,
+                total,
+                e.path().display()
+            ));
+            continue;
+        }
+        progress.inc(file_size(&e.path()));
+        let healthy = if index % HEALTH_CHECK_INTERVAL == 0 {
+            health.check()
+        } else {
+            Ok(())
+        }
+        .and_then(|_| health.check_space(metadata(e.path()).map(|m| m.len()).unwrap_or_default()));
+        if let Err(error) = healthy {
+            let imported = persist_imports(connection, &library_entries, &tags, &sidecars)?;
+            return Err(eyre!(
+                "{}. Stopped after importing {} pictures, run the import again to resume.",
+                error,
+                imported
+            ));
+        }
+        let position = position(index, total, *priority, priorities);
+        let mime = media::detect(&e.path()).ok().flatten().map(|t| t.mime());
+        let rule = evaluate(config.rules(), &e.path(), mime, backend.as_ref());
+        match rule.map(|r| r.action()) {
+            Some(RuleAction::Skip) => {
+                print_line(format!(
+                    "{} {} {} (rule {})",
+                    position,
+                    Status::Skipped,
+                    e.path().display(),
+                    rule_name(rule)
+                ));
+                continue;
+            }
+            Some(RuleAction::Quarantine) => {
+                print_line(format!(
+                    "{} {} {} (rule {})",
+                    position,
+                    Status::Quarantined,
+                    e.path().display(),
+                    rule_name(rule)
+                ));
+                persist_problems(
+                    connection,
+                    &[Problem::new(
+                        e.sha256().to_owned(),
+                        e.path().to_string_lossy().to_string(),
+                        format!("Quarantined by rule {}", rule_name(rule)),
+                    )],
+                )?;
+                continue;
+            }
+            _ => (),
+        }
+        let destination = rule
+            .map(|r| r.destination(config.roots()))
+            .transpose()?
+            .flatten();
+        let imported = LibraryEntry::from_catalog_entry(
+            e,
+            config.library_naming(),
+            backend.as_ref(),
+            destination.as_deref(),
+        )
+        .and_then(|p| {
+            let p = match partner_folder(connection, &partners, &folders, p.sha256())? {
+                Some(folder) if p.path().parent() != Some(&folder) => {
+                    p.placed_in(&folder, config.library_naming(), &HashSet::new())?
+                        .0
+                }
+                _ => p,
+            };
+            ensure_outside_sources(&health.root().join(p.path()), sources)?;
+            print_line(format!(
+                "{} Importing {} into {}{}",
+                position,
+                e.path().display(),
+                p.path().display(),
+                dated_by_fallback(&p)
+            ));
+            try_copy_catalog_entry(&e.path(), p)
+        });
+        match imported {
+            Ok(library_entry) => {
+                if let Some(folder) = library_entry.path().parent() {
+                    folders.insert(library_entry.sha256().to_owned(), folder.to_owned());
+                }
+                for tag in rule.map(|r| r.tags()).unwrap_or_default() {
+                    tags.push((tag.to_owned(), library_entry.sha256().to_owned()));
+                }
+                for tag in folder_tags.iter().flat_map(|f| f.tags(&e.path())) {
+                    tags.push((tag, library_entry.sha256().to_owned()));
+                }
+                if let Some(sidecar) = Sidecar::find(&e.path()) {
+                    match copy_sidecar(&sidecar, library_entry.path()) {
+                        Ok(path) => sidecars.push((library_entry.sha256().to_owned(), path)),
+                        Err(e) => print_line(format!("{} {} {}", position, Status::Failed, e)),
+                    }
+                }
+                if let Some(worker) = thumbnails {
+                    worker.submit(library_entry.path(), library_entry.sha256());
+                }
+                library_entries.push(library_entry.with_imported_by(imported_by.clone()))
+            }
+            Err(e) => print_line(format!("{} {} {}", position, Status::Failed, e)),
+        }
+    }
+    drop(progress);
+    let imported = persist_imports(connection, &library_entries, &tags, &sidecars)?;
+    let stacked = detect_stacks(connection, backend.as_ref(), &library_entries)?;
+    if stacked > 0 {
+        println!("Stacked {} pictures in brackets and panoramas", stacked);
+    }
+    Ok(imported)
+}
+
+/// What import would do with a cataloged picture.
+#[derive(Debug, PartialEq)]
+enum Decision {
+    /// Copies the picture into the library path, suffixed when the name is taken,
+    /// or records the file of the library path holding the same content.
+    Import {
+        path: PathBuf,
+        renamed: bool,
+        existing: bool,
+        sidecar: bool,
+        /// The fallback of the configuration dating the picture, if not its metadata.
+        dated_by: Option<String>,
+    },
+    /// Leaves out the picture of a remote source.
+    Remote,
+    /// Leaves out the picture, by the rule.
+    Skip(String),
+    /// Leaves the picture in quarantine, by the rule.
+    Quarantine(String),
+    /// Fails to lay out the picture, for the reason.
+    Fail(String),
+}
+
+/// The decision of import for a cataloged picture, in the import order.
+#[derive(Debug, PartialEq)]
+struct PlannedImport {
+    position: String,
+    source: PathBuf,
+    decision: Decision,
+    bytes: u64,
+}
+
+impl PlannedImport {
+    /// The library path the picture would be imported into.
+    fn destination(&self) -> Option<&Path> {
+        match &self.decision {
+            Decision::Import { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The name of the decision and its note, such as the rule or the error.
+    fn outcome(&self) -> (&'static str, String) {
+        match &self.decision {
+            Decision::Import {
+                renamed,
+                existing,
+                sidecar,
+                dated_by,
+                ..
+            } => {
+                let name = match (existing, renamed) {
+                    (true, _) => "existing",
+                    (_, true) => "rename",
+                    _ => "import",
+                };
+                let mut notes = vec![];
+                if *sidecar {
+                    notes.push("with its sidecar".to_string());
+                }
+                if let Some(source) = dated_by {
+                    notes.push(format!("dated by {}", source));
+                }
+                (name, notes.join(", "))
+            }
+            Decision::Remote => ("remote", String::new()),
+            Decision::Skip(rule) => ("skip", format!("rule {}", rule)),
+            Decision::Quarantine(rule) => ("quarantine", format!("rule {}", rule)),
+            Decision::Fail(error) => ("fail", error.clone()),
+        }
     }
 }
 
-fn import(mut connection: Connection, path_prefix: &str) -> Result<usize> {
-    let library_entries = select_from_catalog(&connection, path_prefix)?
+impl Display for PlannedImport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let source = self.source.display();
+        match &self.decision {
+            Decision::Import {
+                path,
+                renamed,
+                existing,
+                sidecar,
+                dated_by,
+            } => {
+                let note = if *existing {
+                    ", which already holds the same content"
+                } else if *renamed {
+                    ", renamed as the name is taken"
+                } else {
+                    ""
+                };
+                let sidecar = if *sidecar { ", with its sidecar" } else { "" };
+                let dated_by = dated_by
+                    .as_ref()
+                    .map(|s| format!(" (dated by {})", s))
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "{} Would import {} into {}{}{}{}",
+                    self.position,
+                    source,
+                    path.display(),
+                    note,
+                    sidecar,
+                    dated_by
+                )
+            }
+            Decision::Remote => write!(f, "{} Skipping remote {}", self.position, source),
+            Decision::Skip(rule) => write!(
+                f,
+                "{} {} {} (rule {})",
+                self.position,
+                Status::Skipped,
+                source,
+                rule
+            ),
+            Decision::Quarantine(rule) => write!(
+                f,
+                "{} {} {} (rule {})",
+                self.position,
+                Status::Quarantined,
+                source,
+                rule
+            ),
+            Decision::Fail(error) => write!(f, "{} {} {}", self.position, Status::Failed, error),
+        }
+    }
+}
+
+/// Lays out the entries as import would, including the names suffixed to avoid a
+/// collision, without writing to the library or the database. Returns the decision
+/// for each entry, in the import order.
+fn plan_import(
+    connection: &Connection,
+    entries: Vec<CatalogEntry>,
+    priorities: &[PriorityRule],
+    config: &RepositoryConfig,
+    root: &Path,
+    sources: &[PathBuf],
+) -> Result<Vec<PlannedImport>> {
+    let backend = config.metadata().backend();
+    let entries = prioritize(entries, priorities, backend.as_ref());
+    let total = entries.len();
+    let mut planned = HashSet::new();
+    let partners = select_partners(connection)?;
+    let mut folders = HashMap::new();
+    let mut plans = vec![];
+    for (index, (priority, e)) in entries.iter().enumerate() {
+        let plan = |decision, bytes| PlannedImport {
+            position: position(index, total, *priority, priorities),
+            source: e.path(),
+            decision,
+            bytes,
+        };
+        if e.is_remote() {
+            plans.push(plan(Decision::Remote, 0));
+            continue;
+        }
+        let mime = media::detect(&e.path()).ok().flatten().map(|t| t.mime());
+        let rule = evaluate(config.rules(), &e.path(), mime, backend.as_ref());
+        match rule.map(|r| r.action()) {
+            Some(RuleAction::Skip) => {
+                plans.push(plan(Decision::Skip(rule_name(rule).to_owned()), 0));
+                continue;
+            }
+            Some(RuleAction::Quarantine) => {
+                plans.push(plan(Decision::Quarantine(rule_name(rule).to_owned()), 0));
+                continue;
+            }
+            _ => (),
+        }
+        let destination = rule
+            .map(|r| r.destination(config.roots()))
+            .transpose()?
+            .flatten();
+        let resolved = LibraryEntry::planned_from_catalog_entry(
+            e,
+            config.library_naming(),
+            backend.as_ref(),
+            destination.as_deref(),
+            &planned,
+        )
+        .and_then(|(p, renamed)| {
+            let (p, renamed) = match partner_folder(connection, &partners, &folders, p.sha256())? {
+                Some(folder) if p.path().parent() != Some(&folder) => {
+                    p.placed_in(&folder, config.library_naming(), &planned)?
+                }
+                _ => (p, renamed),
+            };
+            ensure_outside_sources(&root.join(p.path()), sources)?;
+            Ok((p, renamed))
+        });
+        match resolved {
+            Ok((library_entry, renamed)) => {
+                if let Some(folder) = library_entry.path().parent() {
+                    folders.insert(library_entry.sha256().to_owned(), folder.to_owned());
+                }
+                planned.insert(library_entry.path().to_owned());
+                let decision = Decision::Import {
+                    path: library_entry.path().to_owned(),
+                    renamed,
+                    existing: library_entry.path().exists(),
+                    sidecar: Sidecar::find(&e.path()).is_some(),
+                    dated_by: fallback_date_source(&library_entry),
+                };
+                plans.push(plan(decision, file_size(&e.path())));
+            }
+            Err(e) => plans.push(plan(Decision::Fail(e.to_string()), 0)),
+        }
+    }
+    Ok(plans)
+}
+
+/// The preview of the planned imports, in aligned columns.
+fn to_table(plans: &[PlannedImport]) -> String {
+    let rows = plans
         .iter()
-        .map(|e| LibraryEntry::try_from(e).and_then(|p| try_copy_catalog_entry(&e.path(), p)))
-        .filter_map(|r| match r {
-            Ok(library_entry) => Some(library_entry),
-            Err(e) => {
-                println!("{}", e);
-                None
+        .map(|p| {
+            let (decision, note) = p.outcome();
+            [
+                p.source.display().to_string(),
+                p.destination()
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                decision.to_string(),
+                note,
+            ]
+        })
+        .collect::<Vec<[String; 4]>>();
+    let header = ["Source", "Destination", "Decision", "Note"].map(str::to_string);
+    let widths = std::iter::once(&header)
+        .chain(&rows)
+        .fold([0; 3], |mut widths, row| {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
             }
+            widths
+        });
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|[source, destination, decision, note]| {
+            let line = format!(
+                "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+                source,
+                destination,
+                decision,
+                note,
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2]
+            );
+            line.trim_end().to_string() + "\n"
         })
-        .collect::<Vec<LibraryEntry>>();
-    persist_library_entries(&mut connection, &library_entries)
+        .collect()
+}
+
+/// The preview of the planned imports as CSV, quoting the fields as needed.
+fn to_csv(plans: &[PlannedImport]) -> String {
+    let field = |text: &str| {
+        if text.contains([',', '"', '\n']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    };
+    let mut csv = "source,destination,decision,note\n".to_string();
+    for plan in plans {
+        let (decision, note) = plan.outcome();
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            field(&plan.source.to_string_lossy()),
+            field(
+                &plan
+                    .destination()
+                    .map(|d| d.to_string_lossy())
+                    .unwrap_or_default()
+            ),
+            decision,
+            field(&note)
+        ));
+    }
+    csv
+}
+
+/// The note of the pictures dated by a fallback of the configuration rather than
+/// by their metadata.
+fn dated_by_fallback(entry: &LibraryEntry) -> String {
+    fallback_date_source(entry)
+        .map(|source| format!(" (dated by {})", source))
+        .unwrap_or_default()
+}
+
+/// The fallback of the configuration dating the picture, if not its metadata.
+fn fallback_date_source(entry: &LibraryEntry) -> Option<String> {
+    entry
+        .date_source()
+        .filter(|source| *source != DateSource::Metadata.to_string())
+        .map(str::to_owned)
+}
+
+/// The library folder of the other picture of the shot, imported by this run or
+/// before, which the picture joins.
+fn partner_folder(
+    connection: &Connection,
+    partners: &HashMap<String, String>,
+    folders: &HashMap<String, PathBuf>,
+    hash: &str,
+) -> Result<Option<PathBuf>> {
+    let Some(partner) = partners.get(hash) else {
+        return Ok(None);
+    };
+    if let Some(folder) = folders.get(partner) {
+        return Ok(Some(folder.to_owned()));
+    }
+    Ok(select_library_path(connection, partner)?
+        .and_then(|path| path.parent().map(Path::to_path_buf)))
+}
+
+/// The position of the entry in the import queue, e.g. `[3/120, priority 1]`.
+fn position(index: usize, total: usize, priority: usize, priorities: &[PriorityRule]) -> String {
+    if priority < priorities.len() {
+        format!("[{}/{}, priority {}]", index + 1, total, priority + 1)
+    } else {
+        format!("[{}/{}]", index + 1, total)
+    }
+}
+
+fn rule_name(rule: Option<&ImportRule>) -> &str {
+    rule.map(|r| r.name()).unwrap_or_default()
+}
+
+/// Persists the imported entries along with the `(tag, hash)` tags of their rules
+/// and the `(hash, path)` sidecars copied with them.
+fn persist_imports(
+    connection: &mut Connection,
+    library_entries: &Vec<LibraryEntry>,
+    tags: &[(String, String)],
+    sidecars: &[(String, PathBuf)],
+) -> Result<usize> {
+    let imported = persist_library_entries(connection, library_entries)?;
+    for (tag, hash) in tags {
+        add_tag(connection, tag, std::slice::from_ref(hash))?;
+    }
+    record_sidecars(connection, sidecars)?;
+    Ok(imported)
 }
 
 fn try_copy_catalog_entry(path: &PathBuf, library_entry: LibraryEntry) -> Result<LibraryEntry> {
-    println!(
-        "Importing {} into {}",
-        path.display(),
-        library_entry.path().display()
-    );
     let exists = library_entry.path().exists();
-    if exists {
-        Err(eyre!("{} already exists.", library_entry.path().display()))
-    } else {
+    if !exists {
         copy_catalog_entry(path, library_entry)
+    } else if sha256_digest(library_entry.path())? == library_entry.sha256() {
+        print_line(format!(
+            "{} already holds the same content, recording it without copying.",
+            library_entry.path().display()
+        ));
+        Ok(library_entry)
+    } else {
+        Err(eyre!("{} already exists.", library_entry.path().display()))
+    }
+}
+
+/// Copies the sidecar next to the library file, unless a sidecar of the same
+/// content is there already, and returns its path.
+fn copy_sidecar(sidecar: &Sidecar, library_path: &Path) -> Result<PathBuf> {
+    let path = sidecar.path_for(library_path);
+    if !path.exists() {
+        copy(sidecar.path(), &path)?;
+    } else if sha256_digest(&path)? != sha256_digest(sidecar.path())? {
+        return Err(eyre!("{} already exists.", path.display()));
     }
+    Ok(path)
 }
 
 fn copy_catalog_entry(from: &PathBuf, library_entry: LibraryEntry) -> Result<LibraryEntry> {
     if let Some(dirname) = &library_entry.path().parent() {
-        create_dir_all(&dirname)?;
+        create_dir_all(dirname)?;
     }
     copy(from, library_entry.path())?;
-    let copy_sha256 = sha256_digest(&library_entry.path())?;
-    if &library_entry.sha256() != &copy_sha256 {
+    let copy_sha256 = sha256_digest(library_entry.path())?;
+    if library_entry.sha256() != copy_sha256 {
         Err(eyre!(
             "{} sha256 does not match copied {}. Aborting.",
             from.display(),
@@ -103,12 +943,217 @@ mod tests {
 
     use serial_test::serial;
 
+    use std::str::FromStr;
+
     use crate::{
+        clapext::Target,
         command::import::try_copy_catalog_entry,
-        database::{catalog_entry::CatalogEntry, library_entry::LibraryEntry},
+        database::{
+            catalog_entry::CatalogEntry, common::sha256_digest, library_entry::LibraryEntry,
+            test_utils::new_database_containing_catalog_entries, trashed::Trashed,
+        },
+        metadata::Exif,
+    };
+
+    use super::{
+        copy_catalog_entry, partition_trashed, prioritize, restore_from_trash, select_entries,
+        to_csv, to_table, Decision, PlannedImport, PriorityRule,
     };
 
-    use super::copy_catalog_entry;
+    fn given_planned_imports() -> Vec<PlannedImport> {
+        let plan = |source: &str, decision| PlannedImport {
+            position: "[1/3]".to_string(),
+            source: PathBuf::from(source),
+            decision,
+            bytes: 10,
+        };
+        vec![
+            plan(
+                "/sd/Rome, Italy/a.jpg",
+                Decision::Import {
+                    path: PathBuf::from("2023/05/08/a_1.jpg"),
+                    renamed: true,
+                    existing: false,
+                    sidecar: true,
+                    dated_by: None,
+                },
+            ),
+            plan("/sd/b.gif", Decision::Skip("gifs".to_string())),
+        ]
+    }
+
+    #[test]
+    fn to_table_aligns_the_preview() {
+        assert_eq!(
+            "Source                 Destination         Decision  Note\n\
+             /sd/Rome, Italy/a.jpg  2023/05/08/a_1.jpg  rename    with its sidecar\n\
+             /sd/b.gif              -                   skip      rule gifs\n",
+            to_table(&given_planned_imports())
+        );
+    }
+
+    #[test]
+    fn to_csv_quotes_the_paths_with_commas() {
+        assert_eq!(
+            "source,destination,decision,note\n\
+             \"/sd/Rome, Italy/a.jpg\",2023/05/08/a_1.jpg,rename,with its sidecar\n\
+             /sd/b.gif,,skip,rule gifs\n",
+            to_csv(&given_planned_imports())
+        );
+    }
+
+    #[test]
+    fn restore_from_trash_reads_the_file_left_in_the_trash() {
+        let trash = tempfile::tempdir().unwrap();
+        let kept = trash.path().join("a.jpg");
+        std::fs::write(&kept, "a").unwrap();
+        let trashed_at = chrono::NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let trashed = std::collections::HashMap::from([
+            (
+                "1".to_string(),
+                Trashed {
+                    path: kept.clone(),
+                    original_path: PathBuf::from("library/a.jpg"),
+                    trashed_at,
+                },
+            ),
+            (
+                "2".to_string(),
+                Trashed {
+                    path: trash.path().join("b.jpg"),
+                    original_path: PathBuf::from("library/b.jpg"),
+                    trashed_at,
+                },
+            ),
+        ]);
+        let (entries, discarded) = partition_trashed(
+            vec![
+                CatalogEntry::new("1".to_string(), "/card/a.jpg".to_string()),
+                CatalogEntry::new("2".to_string(), "/card/b.jpg".to_string()),
+                CatalogEntry::new("3".to_string(), "/card/c.jpg".to_string()),
+            ],
+            &trashed,
+        );
+        assert_eq!(
+            vec![CatalogEntry::new(
+                "3".to_string(),
+                "/card/c.jpg".to_string()
+            )],
+            entries
+        );
+
+        let (entries, restored) = restore_from_trash(entries, discarded, &trashed);
+
+        assert_eq!(
+            vec![
+                CatalogEntry::new("3".to_string(), "/card/c.jpg".to_string()),
+                CatalogEntry::new("1".to_string(), kept.to_string_lossy().to_string()),
+                CatalogEntry::new("2".to_string(), "/card/b.jpg".to_string()),
+            ],
+            entries
+        );
+        assert_eq!(vec![("1".to_string(), kept)], restored);
+    }
+
+    #[test]
+    fn select_entries_skips_the_excluded_paths() {
+        let connection = new_database_containing_catalog_entries(&vec![
+            CatalogEntry::new("1".to_string(), "/card/rejects/a.jpg".to_string()),
+            CatalogEntry::new("2".to_string(), "/card/b.gif".to_string()),
+            CatalogEntry::new("3".to_string(), "/card/c.jpg".to_string()),
+        ]);
+        let excludes = vec![
+            glob::Pattern::new("*/rejects/*").unwrap(),
+            glob::Pattern::new("*.gif").unwrap(),
+        ];
+
+        assert_eq!(
+            vec!["3"],
+            select_entries(&connection, "/card", None, &excludes, None)
+                .unwrap()
+                .iter()
+                .map(|e| e.sha256())
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn select_entries_selects_the_copy_left_by_the_exclusions() {
+        let connection = new_database_containing_catalog_entries(&vec![
+            CatalogEntry::new("1".to_string(), "/card/rejects/a.jpg".to_string()),
+            CatalogEntry::new("1".to_string(), "/card/keep/a.jpg".to_string()),
+        ]);
+        let excludes = vec![glob::Pattern::new("*/rejects/*").unwrap()];
+
+        assert_eq!(
+            vec![CatalogEntry::new(
+                "1".to_string(),
+                "/card/keep/a.jpg".to_string()
+            )],
+            select_entries(&connection, "/card", None, &excludes, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn select_entries_selects_the_targeted_copy() {
+        let connection = new_database_containing_catalog_entries(&vec![
+            CatalogEntry::new("1".to_string(), "/card/other/a.jpg".to_string()),
+            CatalogEntry::new("1".to_string(), "/card/keep/a.jpg".to_string()),
+        ]);
+        let targets = vec![Target::Path(PathBuf::from("/card/keep/a.jpg"))];
+
+        assert_eq!(
+            vec![CatalogEntry::new(
+                "1".to_string(),
+                "/card/keep/a.jpg".to_string()
+            )],
+            select_entries(&connection, "/card", Some(&targets), &[], None).unwrap()
+        );
+    }
+
+    #[test]
+    fn priority_rule_rejects_unknown_kinds() {
+        assert!(PriorityRule::from_str("size:10").is_err());
+    }
+
+    #[test]
+    fn prioritize_orders_entries_by_first_matching_rule() {
+        let entries = vec![
+            CatalogEntry::new("1".to_string(), "/card/Screenshots/a.png".to_string()),
+            CatalogEntry::new("2".to_string(), "/card/DCIM/b.jpg".to_string()),
+            CatalogEntry::new("3".to_string(), "/card/DCIM/c.CR2".to_string()),
+        ];
+        let rules = vec![
+            PriorityRule::from_str("ext:cr2").unwrap(),
+            PriorityRule::from_str("path:*/DCIM/*").unwrap(),
+        ];
+
+        let prioritized = prioritize(entries, &rules, &Exif);
+
+        assert_eq!(
+            vec![(0, "3"), (1, "2"), (2, "1")],
+            prioritized
+                .iter()
+                .map(|(p, e)| (*p, e.sha256()))
+                .collect::<Vec<(usize, &str)>>()
+        );
+    }
+
+    #[test]
+    fn prioritize_keeps_the_catalog_order_without_rules() {
+        let entries = vec![
+            CatalogEntry::new("1".to_string(), "/card/a.png".to_string()),
+            CatalogEntry::new("2".to_string(), "/card/b.jpg".to_string()),
+        ];
+
+        let prioritized = prioritize(entries, &[], &Exif);
+
+        assert_eq!("1", prioritized[0].1.sha256());
+        assert_eq!("2", prioritized[1].1.sha256());
+    }
 
     #[test]
     #[serial]
@@ -142,7 +1187,7 @@ mod tests {
             error,
             format!(
                 "Cargo.toml sha256 does not match copied {}. Aborting.",
-                actual_library_entry.path().to_string_lossy().to_string()
+                actual_library_entry.path().to_string_lossy()
             )
         );
     }
@@ -170,6 +1215,14 @@ mod tests {
         assert_eq!(error, "Cargo.toml already exists.");
     }
 
+    #[test]
+    fn try_copy_catalog_entry_records_an_existing_identical_file() {
+        let from = &PathBuf::from("Cargo.toml");
+        let to = LibraryEntry::new(sha256_digest(from).unwrap(), PathBuf::from("Cargo.toml"));
+
+        assert!(try_copy_catalog_entry(from, to).is_ok());
+    }
+
     fn given_a_path_for_an_image_with_original_date() -> PathBuf {
         ["resources", "test", "kami_neko.jpeg"].iter().collect()
     }
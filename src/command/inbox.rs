@@ -0,0 +1,194 @@
+use std::{
+    fs::{create_dir_all, remove_file},
+    path::Path,
+    thread::sleep,
+    time::Duration,
+};
+
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::{eyre, Result};
+use glob::Pattern;
+
+use crate::{
+    clapext::SubApplication,
+    command::{
+        catalog::{catalog, FileFilter, IgnoreRules},
+        import::import,
+    },
+    database::{
+        catalog::remove_catalog_entries,
+        catalog_entry::CatalogEntry,
+        library::select_library_hashes,
+        operation::{select_staged_batches, select_staged_entries},
+    },
+    fsext::{health::DestinationHealth, remove_empty_ancestors},
+    repository::Repository,
+};
+
+const INBOX: &str = "inbox";
+
+pub(crate) struct Inbox;
+
+impl SubApplication for Inbox {
+    fn name(&self) -> &'static str {
+        INBOX
+    }
+
+    fn command(&self) -> Command {
+        let batch =
+            || arg!(<BATCH> "The batch, as listed by inbox list").value_parser(value_parser!(i64));
+        Command::new(self.name())
+            .about("Stages the pictures dropped in the [inbox] folder for review before import")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("scan")
+                    .about("Catalogs the new files of the inbox as a batch.")
+                    .arg(
+                        arg!(--watch <SECONDS> "Scans the inbox again every SECONDS until interrupted")
+                            .value_parser(value_parser!(u64)),
+                    )
+                    .arg(
+                        arg!(--jobs <JOBS> "The number of files hashed in parallel")
+                            .value_parser(value_parser!(usize))
+                            .default_value("4"),
+                    ),
+                Command::new("list").about("Lists the staged batches, oldest first."),
+                Command::new("approve")
+                    .about("Imports the files of the batch into the library and removes them from the inbox.")
+                    .arg(batch()),
+                Command::new("reject")
+                    .about("Moves the files of the batch to the trash.")
+                    .arg(batch())
+                    .arg(arg!(--path <GLOB> "Only rejects the files whose path in the inbox matches the pattern, e.g. */screenshots/*").value_parser(Pattern::new)),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        repository.enter()?;
+        let config = repository.config()?;
+        let inbox = repository.root().join(config.inbox().path());
+        create_dir_all(&inbox)?;
+        let inbox = inbox.canonicalize()?;
+        let prefix = inbox.join("").to_string_lossy().to_string();
+        match name {
+            "scan" => {
+                let watch = sub_matches.get_one::<u64>("watch");
+                let jobs = *sub_matches.get_one::<usize>("jobs").expect("defaulted");
+                loop {
+                    {
+                        let _lock = repository.lock()?;
+                        let staged = catalog(
+                            &mut *repository.open_database()?,
+                            &inbox,
+                            false,
+                            false,
+                            false,
+                            jobs,
+                            &FileFilter::default().with_ignored(IgnoreRules::load(&inbox, &[])?),
+                        )?;
+                        println!("Staged {} files from {}", staged, inbox.display());
+                    }
+                    match watch {
+                        Some(seconds) => sleep(Duration::from_secs(*seconds)),
+                        None => return Ok(()),
+                    }
+                }
+            }
+            "list" => {
+                let batches = select_staged_batches(&*repository.open_database()?, &prefix)?;
+                for batch in &batches {
+                    println!(
+                        "{:>6} {} {:>6} files, {} in the library already",
+                        batch.operation,
+                        batch.started_at.format("%Y-%m-%d %H:%M"),
+                        batch.files,
+                        batch.imported
+                    );
+                }
+                println!("{} batches to review", batches.len());
+            }
+            "approve" => {
+                let _lock = repository.lock()?;
+                let entries = batch_entries(repository, &prefix, sub_matches)?;
+                let imported = select_library_hashes(&*repository.open_database()?)?;
+                let pending = entries
+                    .iter()
+                    .filter(|e| !imported.contains(e.sha256()))
+                    .map(|e| {
+                        CatalogEntry::new(
+                            e.sha256().to_owned(),
+                            e.path().to_string_lossy().to_string(),
+                        )
+                    })
+                    .collect::<Vec<CatalogEntry>>();
+                let health = DestinationHealth::new(repository.root(), Path::new(".photo_works"))?;
+                println!(
+                    "Imported {} pictures",
+                    import(
+                        &mut *repository.open_database()?,
+                        pending,
+                        &[],
+                        &config,
+                        &health,
+                        std::slice::from_ref(&inbox),
+                        None
+                    )?
+                );
+                let imported = select_library_hashes(&*repository.open_database()?)?;
+                let (done, failed): (Vec<CatalogEntry>, Vec<CatalogEntry>) = entries
+                    .into_iter()
+                    .partition(|e| imported.contains(e.sha256()));
+                for entry in &done {
+                    remove_file(entry.path())?;
+                    remove_empty_ancestors(&entry.path(), std::slice::from_ref(&inbox))?;
+                }
+                remove_catalog_entries(&mut *repository.open_database()?, &done)?;
+                println!(
+                    "Removed {} files from the inbox, {} left in the batch",
+                    done.len(),
+                    failed.len()
+                );
+            }
+            "reject" => {
+                let _lock = repository.lock()?;
+                let pattern = sub_matches.get_one::<Pattern>("path");
+                let rejected = batch_entries(repository, &prefix, sub_matches)?
+                    .into_iter()
+                    .filter(|e| {
+                        pattern.is_none_or(|p| {
+                            e.path()
+                                .strip_prefix(&inbox)
+                                .is_ok_and(|relative| p.matches_path(relative))
+                        })
+                    })
+                    .collect::<Vec<CatalogEntry>>();
+                let bin = repository.trash()?;
+                let mut connection = repository.open_database()?;
+                for entry in &rejected {
+                    bin.move_in(&connection, entry)?;
+                    remove_empty_ancestors(&entry.path(), std::slice::from_ref(&inbox))?;
+                }
+                remove_catalog_entries(&mut connection, &rejected)?;
+                println!("Moved {} files to the trash", rejected.len());
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
+
+/// The staged entries of the `BATCH` argument, failing for an unknown batch.
+fn batch_entries(
+    repository: &Repository,
+    prefix: &str,
+    sub_matches: &ArgMatches,
+) -> Result<Vec<CatalogEntry>> {
+    let batch = *sub_matches.get_one::<i64>("BATCH").expect("required");
+    let entries = select_staged_entries(&*repository.open_database()?, prefix, batch)?;
+    if entries.is_empty() {
+        return Err(eyre!("No file staged in batch {}", batch));
+    }
+    Ok(entries)
+}
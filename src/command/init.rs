@@ -1,9 +1,12 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use clap::{arg, ArgMatches, Command};
 use eyre::Result;
 
-use crate::{clapext::SubApplication, database::open};
+use crate::{clapext::SubApplication, database::open, repository::Repository};
 
 const INIT: &str = "init";
 
@@ -21,31 +24,53 @@ impl SubApplication for Init {
             .arg_required_else_help(true)
     }
 
-    fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
         let path = sub_matches
             .get_one::<String>("PATH")
             .expect("required")
             .as_str();
         println!("Initializing {}", path);
 
-        Ok(println!("Initialized in {:?}", init(path)?))
+        println!(
+            "Initialized in {:?}",
+            init(Path::new(path), repository.db().map(Path::to_path_buf))?
+        );
+        Ok(())
     }
 }
 
-fn init(parent_path: &str) -> Result<PathBuf> {
-    let mut path: PathBuf = [parent_path, ".photo_works"].iter().collect();
-
-    fs::create_dir_all(&path)?;
-    path.push("db.db3");
+/// Creates the `.photo_works` directory of the repository and its database, the
+/// one of `--db` or `PHOTO_WORKS_DB` when given. Returns the database path.
+fn init(root: &Path, db: Option<PathBuf>) -> Result<PathBuf> {
+    fs::create_dir_all(root.join(".photo_works"))?;
+    let path = Repository::new(root.to_owned()).with_db(db).db_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
     open(&path)?;
     Ok(path)
 }
 
 #[cfg(test)]
 mod tests {
+    use tempfile::TempDir;
+
     use crate::{command::init::INIT, SubApplication};
 
-    use super::Init;
+    use super::{init, Init};
+
+    #[test]
+    fn init_creates_the_database_of_the_override() {
+        let directory = TempDir::new().unwrap();
+        let root = directory.path().join("repository");
+        let db = directory.path().join("elsewhere").join("photos.db3");
+
+        assert_eq!(db, init(&root, Some(db.clone())).unwrap());
+
+        assert!(db.is_file());
+        assert!(root.join(".photo_works").is_dir());
+        assert!(!root.join(".photo_works").join("db.db3").exists());
+    }
 
     #[test]
     fn command_is_consistent() {
@@ -0,0 +1,482 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{create_dir_all, read_dir, remove_dir, rename},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    database::{
+        common::sha256_digest,
+        library::{foreach_entry, move_library_directories, move_library_entries, LibraryFilter},
+    },
+    fsext::remove_empty_ancestors,
+    media::sidecar::Sidecar,
+    naming::{layout::padded_directory, template::PathTemplate, DateLayout, LibraryNaming},
+    repository::Repository,
+};
+
+const LIBRARY: &str = "library";
+
+pub(crate) struct Library;
+
+impl SubApplication for Library {
+    fn name(&self) -> &'static str {
+        LIBRARY
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Maintains the directory tree of the library")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(
+                Command::new("repath")
+                    .about("Renames the unpadded date directories such as 2023/5/8 to 2023/05/08, which sort in file managers, and rewrites the library paths in one transaction")
+                    .arg(arg!(--"dry-run" "Only reports the renames")),
+            )
+            .subcommand(
+                Command::new("reorganize")
+                    .about("Moves the library pictures of the date directories to the ones of another layout or path template, verifying their content after each move, and rewrites the library paths in one transaction")
+                    .arg(arg!(--layout <LAYOUT> "The date layout: unpadded, padded or month-names").value_parser(DateLayout::from_str))
+                    .arg(
+                        arg!(--template <TEMPLATE> "The path template, e.g. {year}/{year}-{month:02}")
+                            .value_parser(PathTemplate::from_str)
+                            .conflicts_with("layout"),
+                    )
+                    .arg(arg!(--"dry-run" "Only reports the moves")),
+            )
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("repath", sub_matches)) => {
+                repository.enter()?;
+                let _lock = repository.lock()?;
+                let mut connection = repository.open_database()?;
+
+                let renames = repath_plan(&connection)?;
+                if sub_matches.get_flag("dry-run") {
+                    for (from, to, count) in &renames {
+                        println!(
+                            "{} -> {} ({} pictures)",
+                            from.display(),
+                            to.display(),
+                            count
+                        );
+                    }
+                    println!("{} directories would be renamed", renames.len());
+                    return Ok(());
+                }
+                let moved = repath(&mut connection, repository.root(), &renames)?;
+                println!(
+                    "Renamed {} directories, moving {} pictures",
+                    renames.len(),
+                    moved
+                );
+                Ok(())
+            }
+            Some(("reorganize", sub_matches)) => {
+                repository.enter()?;
+                let _lock = repository.lock()?;
+                let config = repository.config()?;
+                let mut connection = repository.open_database()?;
+
+                let current = config.library_naming();
+                let naming = match (
+                    sub_matches.get_one::<DateLayout>("layout"),
+                    sub_matches.get_one::<PathTemplate>("template"),
+                ) {
+                    (Some(layout), _) => current.clone().with_date_layout(*layout),
+                    (_, Some(template)) => current.clone().with_path_template(template.clone()),
+                    (None, None) => current.clone(),
+                };
+                let moves = reorganize_plan(&connection, current, &naming)?;
+                if sub_matches.get_flag("dry-run") {
+                    for (_, from, to) in &moves {
+                        println!("{} -> {}", from.display(), to.display());
+                    }
+                    println!("{} pictures would move", moves.len());
+                    return Ok(());
+                }
+                let moved = reorganize(&mut connection, repository.root(), &moves)?;
+                println!("Moved {} pictures", moved);
+                if &naming != current {
+                    println!("Set the layout in the [library] table of the configuration for the next imports");
+                }
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
+
+/// The unpadded date directories of the library pictures, with their padded
+/// directory and the number of pictures they hold.
+fn repath_plan(connection: &Connection) -> Result<Vec<(PathBuf, PathBuf, usize)>> {
+    let mut directories: BTreeMap<PathBuf, (PathBuf, usize)> = BTreeMap::new();
+    foreach_entry(connection, &LibraryFilter::default(), |entry| {
+        if let Some(directory) = entry.path().parent() {
+            if let Some(padded) = padded_directory(directory) {
+                directories
+                    .entry(directory.to_owned())
+                    .or_insert((padded, 0))
+                    .1 += 1;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(directories
+        .into_iter()
+        .map(|(from, (to, count))| (from, to, count))
+        .collect())
+}
+
+/// Moves the files of the directories into their padded directory, then the
+/// library paths in one transaction. Nothing moves when a file of a padded
+/// directory exists already, and the moves are undone when one fails or the paths
+/// can't be updated. Returns the number of pictures moved.
+fn repath(
+    connection: &mut Connection,
+    root: &Path,
+    renames: &[(PathBuf, PathBuf, usize)],
+) -> Result<usize> {
+    let mut moves = vec![];
+    for (from, to, _) in renames {
+        for child in read_dir(root.join(from))? {
+            let name = child?.file_name();
+            let target = root.join(to).join(&name);
+            if target.exists() {
+                return Err(eyre!(
+                    "{} exists already, nothing was moved",
+                    target.display()
+                ));
+            }
+            moves.push((root.join(from).join(&name), target));
+        }
+    }
+    let mut done = vec![];
+    let moved = moves
+        .iter()
+        .try_for_each(|(from, to)| {
+            if let Some(parent) = to.parent() {
+                create_dir_all(parent)?;
+            }
+            rename(from, to)?;
+            done.push((from, to));
+            Ok(())
+        })
+        .and_then(|_| {
+            let directories = renames
+                .iter()
+                .map(|(from, to, _)| (from.clone(), to.clone()))
+                .collect::<Vec<(PathBuf, PathBuf)>>();
+            move_library_directories(connection, &directories)
+        });
+    match moved {
+        Ok(count) => {
+            let roots = [root.to_path_buf()];
+            for (from, _, _) in renames {
+                remove_dir(root.join(from))?;
+                remove_empty_ancestors(&root.join(from), &roots)?;
+            }
+            Ok(count)
+        }
+        Err(e) => {
+            for (from, to) in done.into_iter().rev() {
+                rename(to, from)?;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// The library pictures of the date directories of the current naming, or of any
+/// layout, that are not in the directory of their date under the new naming,
+/// with their new path in the same sub-root, suffixed when the name is taken.
+fn reorganize_plan(
+    connection: &Connection,
+    current: &LibraryNaming,
+    naming: &LibraryNaming,
+) -> Result<Vec<(String, PathBuf, PathBuf)>> {
+    let mut entries = vec![];
+    foreach_entry(connection, &LibraryFilter::default(), |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+    let mut planned = entries
+        .iter()
+        .map(|e| e.path().to_owned())
+        .collect::<HashSet<PathBuf>>();
+    let mut moves = vec![];
+    for entry in entries {
+        let Some(date) = entry.original_date() else {
+            continue;
+        };
+        let in_place = entry.path().parent().is_some_and(|parent| {
+            naming
+                .date_directory_root(entry.path(), date)
+                .is_some_and(|root| parent == root.join(naming.directory(date)))
+        });
+        if in_place {
+            continue;
+        }
+        let Some(root) = current.date_directory_root(entry.path(), date) else {
+            continue;
+        };
+        let (sha256, from) = (entry.sha256().to_owned(), entry.path().to_owned());
+        let (entry, _) = entry.placed_in(&root.join(naming.directory(date)), naming, &planned)?;
+        planned.insert(entry.path().to_owned());
+        moves.push((sha256, from, entry.path().to_owned()));
+    }
+    Ok(moves)
+}
+
+/// Moves the pictures, along with their sidecars, and checks their content after
+/// each move, then moves their library entries in one transaction. The moves are
+/// undone when one fails, a picture doesn't hold its content or the entries can't
+/// be updated. Returns the number of pictures moved.
+fn reorganize(
+    connection: &mut Connection,
+    root: &Path,
+    moves: &[(String, PathBuf, PathBuf)],
+) -> Result<usize> {
+    let mut done = vec![];
+    let mut entries = vec![];
+    let mut sidecars = vec![];
+    let moved = moves
+        .iter()
+        .try_for_each(|(sha256, from, to)| {
+            let (source, target) = (root.join(from), root.join(to));
+            let sidecar = Sidecar::find(&source);
+            for path in
+                std::iter::once(target.clone()).chain(sidecar.as_ref().map(|s| s.path_for(&target)))
+            {
+                if path.exists() {
+                    return Err(eyre!("{} exists already", path.display()));
+                }
+            }
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent)?;
+            }
+            rename(&source, &target)?;
+            done.push((source, target.clone()));
+            if sha256_digest(&target)? != *sha256 {
+                return Err(eyre!(
+                    "{} does not hold the content recorded for {}",
+                    to.display(),
+                    from.display()
+                ));
+            }
+            if let Some(sidecar) = sidecar {
+                let moved = sidecar.path_for(&target);
+                rename(sidecar.path(), &moved)?;
+                done.push((sidecar.path().to_owned(), moved));
+                sidecars.push((sha256.clone(), sidecar.path_for(to)));
+            }
+            entries.push((sha256.clone(), to.clone()));
+            Ok(())
+        })
+        .and_then(|_| move_library_entries(connection, &entries, &sidecars));
+    match moved {
+        Ok(count) => {
+            let roots = [root.to_path_buf()];
+            for (_, from, _) in moves {
+                remove_empty_ancestors(&root.join(from), &roots)?;
+            }
+            Ok(count)
+        }
+        Err(e) => {
+            for (from, to) in done.into_iter().rev() {
+                rename(to, from)?;
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write},
+        path::PathBuf,
+    };
+
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    use crate::{
+        config::RepositoryConfig,
+        database::{
+            common::sha256_digest,
+            library_entry::LibraryEntry,
+            test_utils::{library_contains, new_database_containing_library_entries},
+        },
+        naming::template::PathTemplate,
+    };
+
+    use super::{reorganize, reorganize_plan, repath, repath_plan};
+
+    fn dated(sha256: &str, path: &str, day: u32) -> LibraryEntry {
+        LibraryEntry::new(sha256.to_string(), PathBuf::from(path))
+            .with_original_date(NaiveDate::from_ymd_opt(1999, 5, day))
+    }
+
+    #[test]
+    fn repath_pads_the_date_directories() {
+        let root = TempDir::new().unwrap();
+        for file in ["2023/5/8/a.jpg", "2023/5/8/a.xmp", "2023/11/12/b.jpg"] {
+            create_dir_all(root.path().join(file).parent().unwrap()).unwrap();
+            write(root.path().join(file), file).unwrap();
+        }
+        let mut connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/8/a.jpg")),
+            LibraryEntry::new("2".to_string(), PathBuf::from("2023/11/12/b.jpg")),
+        ]);
+
+        let renames = repath_plan(&connection).unwrap();
+        assert_eq!(
+            vec![(PathBuf::from("2023/5/8"), PathBuf::from("2023/05/08"), 1)],
+            renames
+        );
+
+        assert_eq!(1, repath(&mut connection, root.path(), &renames).unwrap());
+
+        assert!(root.path().join("2023/05/08/a.jpg").is_file());
+        assert!(root.path().join("2023/05/08/a.xmp").is_file());
+        assert!(!root.path().join("2023/5").exists());
+        assert!(root.path().join("2023/11/12/b.jpg").is_file());
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new("1".to_string(), PathBuf::from("2023/05/08/a.jpg"))
+        ));
+    }
+
+    #[test]
+    fn repath_moves_nothing_when_a_padded_file_exists() {
+        let root = TempDir::new().unwrap();
+        for file in ["2023/5/8/a.jpg", "2023/5/9/b.jpg", "2023/05/09/b.jpg"] {
+            create_dir_all(root.path().join(file).parent().unwrap()).unwrap();
+            write(root.path().join(file), file).unwrap();
+        }
+        let mut connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/8/a.jpg")),
+            LibraryEntry::new("2".to_string(), PathBuf::from("2023/5/9/b.jpg")),
+        ]);
+
+        let renames = repath_plan(&connection).unwrap();
+
+        assert!(repath(&mut connection, root.path(), &renames).is_err());
+        assert!(root.path().join("2023/5/8/a.jpg").is_file());
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/8/a.jpg"))
+        ));
+    }
+
+    #[test]
+    fn reorganize_plan_moves_the_date_directories_to_the_template() {
+        let connection = new_database_containing_library_entries(&vec![
+            dated("1", "1999/05/08/a.jpeg", 8),
+            dated("2", "1999/5/9/a.jpeg", 9),
+            dated("3", "drone/1999/05/08/d.mp4", 8),
+            dated("4", "misc/c.jpeg", 8),
+            dated("5", "1999/1999-05/e.jpeg", 8),
+        ]);
+        let config: RepositoryConfig =
+            toml::from_str("[library]\ndate_layout = \"padded\"").unwrap();
+        let naming = config
+            .library_naming()
+            .clone()
+            .with_path_template("{year}/{year}-{month:02}".parse::<PathTemplate>().unwrap());
+
+        assert_eq!(
+            vec![
+                (
+                    "1".to_string(),
+                    PathBuf::from("1999/05/08/a.jpeg"),
+                    PathBuf::from("1999/1999-05/a.jpeg")
+                ),
+                (
+                    "2".to_string(),
+                    PathBuf::from("1999/5/9/a.jpeg"),
+                    PathBuf::from("1999/1999-05/a_1.jpeg")
+                ),
+                (
+                    "3".to_string(),
+                    PathBuf::from("drone/1999/05/08/d.mp4"),
+                    PathBuf::from("drone/1999/1999-05/d.mp4")
+                ),
+            ],
+            reorganize_plan(&connection, config.library_naming(), &naming).unwrap()
+        );
+    }
+
+    #[test]
+    fn reorganize_moves_the_pictures_and_their_sidecars() {
+        let root = TempDir::new().unwrap();
+        create_dir_all(root.path().join("1999/05/08")).unwrap();
+        write(root.path().join("1999/05/08/a.CR2"), "raw").unwrap();
+        write(root.path().join("1999/05/08/a.CR2.xmp"), "edits").unwrap();
+        let sha256 = sha256_digest(&root.path().join("1999/05/08/a.CR2")).unwrap();
+        let mut connection = new_database_containing_library_entries(&vec![LibraryEntry::new(
+            sha256.clone(),
+            PathBuf::from("1999/05/08/a.CR2"),
+        )]);
+        let moves = [(
+            sha256.clone(),
+            PathBuf::from("1999/05/08/a.CR2"),
+            PathBuf::from("1999/1999-05/a.CR2"),
+        )];
+
+        assert_eq!(1, reorganize(&mut connection, root.path(), &moves).unwrap());
+
+        assert!(root.path().join("1999/1999-05/a.CR2").is_file());
+        assert!(root.path().join("1999/1999-05/a.CR2.xmp").is_file());
+        assert!(!root.path().join("1999/05").exists());
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new(sha256, PathBuf::from("1999/1999-05/a.CR2"))
+        ));
+    }
+
+    #[test]
+    fn reorganize_undoes_the_moves_when_a_content_differs() {
+        let root = TempDir::new().unwrap();
+        create_dir_all(root.path().join("1999/05/08")).unwrap();
+        write(root.path().join("1999/05/08/a.jpeg"), "a").unwrap();
+        write(root.path().join("1999/05/08/b.jpeg"), "b").unwrap();
+        let sha256 = sha256_digest(&root.path().join("1999/05/08/a.jpeg")).unwrap();
+        let mut connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new(sha256.clone(), PathBuf::from("1999/05/08/a.jpeg")),
+            LibraryEntry::new("B".to_string(), PathBuf::from("1999/05/08/b.jpeg")),
+        ]);
+        let moves = [
+            (
+                sha256.clone(),
+                PathBuf::from("1999/05/08/a.jpeg"),
+                PathBuf::from("1999/1999-05/a.jpeg"),
+            ),
+            (
+                "B".to_string(),
+                PathBuf::from("1999/05/08/b.jpeg"),
+                PathBuf::from("1999/1999-05/b.jpeg"),
+            ),
+        ];
+
+        assert!(reorganize(&mut connection, root.path(), &moves).is_err());
+
+        assert!(root.path().join("1999/05/08/a.jpeg").is_file());
+        assert!(root.path().join("1999/05/08/b.jpeg").is_file());
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new(sha256, PathBuf::from("1999/05/08/a.jpeg"))
+        ));
+    }
+}
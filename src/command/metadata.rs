@@ -0,0 +1,105 @@
+use std::path::{absolute, PathBuf};
+
+use chrono::NaiveDateTime;
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::{
+    clapext::SubApplication, database::catalog::select_catalog_roots,
+    fsext::source::ensure_outside_sources, repository::Repository,
+};
+
+const METADATA: &str = "metadata";
+
+pub(crate) struct Metadata;
+
+impl SubApplication for Metadata {
+    fn name(&self) -> &'static str {
+        METADATA
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Reads and writes picture metadata with the configured backend")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("show")
+                    .about("Prints the original date and camera model of a file")
+                    .arg(arg!(<FILE> "The file to read").value_parser(value_parser!(PathBuf))),
+                Command::new("set-date")
+                    .about("Writes the original date of a file outside the library and the cataloged directories")
+                    .arg(arg!(<FILE> "The file to write").value_parser(value_parser!(PathBuf)))
+                    .arg(
+                        arg!(<DATETIME> "The date the picture was taken, as YYYY-MM-DDTHH:MM:SS")
+                            .value_parser(value_parser!(NaiveDateTime)),
+                    ),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("show", sub_matches)) => {
+                let file = absolute(sub_matches.get_one::<PathBuf>("FILE").expect("required"))?;
+                repository.enter()?;
+                let config = repository.config()?;
+                let metadata = config.metadata().backend();
+                println!("backend: {}", metadata.name());
+                let fallbacks = config.metadata().date_fallbacks();
+                if !fallbacks.is_empty() {
+                    println!(
+                        "date fallbacks: {}",
+                        fallbacks
+                            .iter()
+                            .map(|f| f.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    );
+                }
+                match metadata.dated(&file) {
+                    Ok((date, source)) => println!("original date: {} ({})", date.date(), source),
+                    Err(e) => println!("original date: {}", e),
+                }
+                println!(
+                    "camera model: {}",
+                    metadata.camera_model(&file).unwrap_or_default()
+                );
+                let telemetry = metadata.telemetry(&file);
+                if let Some(altitude) = telemetry.altitude {
+                    println!("altitude: {:.1} m", altitude);
+                }
+                if let Some(direction) = telemetry.direction {
+                    println!("direction: {:.1}°", direction);
+                }
+                if let Some(speed) = telemetry.speed {
+                    println!("speed: {:.1} km/h", speed);
+                }
+                Ok(())
+            }
+            Some(("set-date", sub_matches)) => {
+                let file = absolute(sub_matches.get_one::<PathBuf>("FILE").expect("required"))?;
+                let date = sub_matches
+                    .get_one::<NaiveDateTime>("DATETIME")
+                    .expect("required");
+                repository.enter()?;
+                let _lock = repository.lock()?;
+                let connection = repository.open_database()?;
+                // The library files must keep their digest and the sources stay read-only.
+                let mut protected = select_catalog_roots(&connection)?;
+                protected.push(repository.root().to_path_buf());
+                ensure_outside_sources(&file, &protected)?;
+                if !file.is_file() {
+                    return Err(eyre!("{} is not a file", file.display()));
+                }
+                repository
+                    .config()?
+                    .metadata()
+                    .backend()
+                    .write_original_date(&file, *date)?;
+                println!("Set the original date of {} to {}", file.display(), date);
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
@@ -1,5 +1,33 @@
+pub(crate) mod adopt;
+pub(crate) mod agent;
+pub(crate) mod auth;
 pub(crate) mod catalog;
 pub(crate) mod check;
+pub(crate) mod db;
+pub(crate) mod doctor;
+pub(crate) mod events;
+pub(crate) mod export;
+pub(crate) mod history;
 pub(crate) mod import;
+pub(crate) mod inbox;
 pub(crate) mod init;
+pub(crate) mod library;
+pub(crate) mod metadata;
+pub(crate) mod protect;
 pub(crate) mod prune;
+pub(crate) mod publish;
+pub(crate) mod query;
+pub(crate) mod refresh_metadata;
+pub(crate) mod relayout;
+pub(crate) mod restore;
+pub(crate) mod review;
+pub(crate) mod rules;
+pub(crate) mod search;
+pub(crate) mod stack;
+pub(crate) mod stats;
+pub(crate) mod sync;
+pub(crate) mod tag;
+pub(crate) mod thumbnails;
+pub(crate) mod trash;
+pub(crate) mod verify_export;
+pub(crate) mod version;
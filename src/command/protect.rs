@@ -0,0 +1,95 @@
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::{stdin_arg, SubApplication},
+    command::tag::{saved_arg, Selection},
+    database::protected::{protect, select_protected, unprotect},
+    repository::Repository,
+};
+
+const PROTECT: &str = "protect";
+
+pub(crate) struct Protect;
+
+impl SubApplication for Protect {
+    fn name(&self) -> &'static str {
+        PROTECT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Protects irreplaceable pictures from prune and trash")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("add")
+                    .about("Protects the pictures of the sha256, in the library or not, or the library pictures matching the selection.")
+                    .arg(
+                        arg!([HASH]... "The sha256 of the pictures")
+                            .required_unless_present_any(["query", "saved", "stdin"]),
+                    )
+                    .arg(arg!(--query <QUERY> "Selects the library pictures matching terms such as year:1950 tag:grandparents"))
+                    .arg(saved_arg())
+                    .arg(stdin_arg()),
+                Command::new("list").about("Lists the protected pictures, oldest first."),
+                Command::new("remove")
+                    .about("Lifts the protection of pictures.")
+                    .arg(arg!(<HASH>... "The sha256 of the pictures")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        let hashes = match name {
+            "add" | "remove" => sub_matches
+                .get_many::<String>("HASH")
+                .unwrap_or_default()
+                .map(|h| h.to_uppercase())
+                .collect::<Vec<String>>(),
+            _ => vec![],
+        };
+        let selection = match name {
+            "add" if hashes.is_empty() => Some(Selection::read(sub_matches)?),
+            _ => None,
+        };
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let mut connection = repository.open_database()?;
+        match (name, selection) {
+            ("add", selection) => {
+                let hashes = match selection {
+                    Some(selection) => selection.hashes(&connection, repository.root())?,
+                    None => hashes,
+                };
+                let count = protect(&mut connection, &hashes)?;
+                println!(
+                    "Protected {} of the {} selected pictures",
+                    count,
+                    hashes.len()
+                );
+            }
+            ("list", _) => {
+                let items = select_protected(&connection)?;
+                for item in &items {
+                    println!(
+                        "{} {} {}",
+                        item.added_at.format("%Y-%m-%d %H:%M"),
+                        item.sha256,
+                        item.path
+                            .as_ref()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or("(not in the library)".to_string())
+                    );
+                }
+                println!("{} protected pictures", items.len());
+            }
+            ("remove", _) => {
+                let count = unprotect(&mut connection, &hashes)?;
+                println!("Lifted the protection of {} pictures", count);
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
@@ -1,16 +1,43 @@
-use std::{fs::remove_file, path::PathBuf, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    env::current_dir,
+    io::stdin,
+    path::{absolute, Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
 
-use clap::{ArgMatches, Command};
+use chrono::NaiveDate;
+use clap::{arg, ArgMatches, Command};
 use eyre::{eyre, Result};
+use glob::Pattern;
+use indicatif::HumanBytes;
 use rusqlite::Connection;
 
 use crate::{
-    clapext::SubApplication,
+    clapext::{confirm, confirm_arg, parse_date, yes_arg, SubApplication},
+    command::trash::empty_trash,
     database::{
         self,
-        catalog::{find_already_imported, find_duplicates},
+        catalog::{
+            find_already_imported, foreach_duplicate, select_catalog_copies, select_catalog_roots,
+        },
         catalog_entry::CatalogEntry,
+        common::sha256_digest,
+        library::select_library_path,
+        pair::select_partners,
+        protected::protected_hashes,
+        trashed::select_trashed_files,
+    },
+    fsext::remove_empty_ancestors,
+    media::{
+        is_raw,
+        pair::{find_pairs, shot},
     },
+    progress::file_size,
+    repository::Repository,
+    style::{bold, Status},
+    trash::{SystemTrash, TrashBin},
 };
 
 const PRUNE: &str = "prune";
@@ -28,131 +55,534 @@ impl SubApplication for Prune {
             .subcommand_required(true)
             .arg_required_else_help(true)
             .subcommands([
-                Command::new("duplicates")
-                    .about("Moves duplicate pictures found in catalog to the trash."),
-                Command::new("imported")
-                    .about("Moves catalog entries already in the library to the trash."),
+                prune_args(
+                    Command::new("duplicates")
+                        .about("Moves duplicate pictures found in catalog to the trash."),
+                ),
+                prune_args(
+                    Command::new("imported")
+                        .about("Moves catalog entries already in the library to the trash.")
+                        .arg(arg!(--verify "Re-hashes the library copies first, keeping the entries whose copy is altered or missing")),
+                ),
+                prune_args(Command::new("pairs").about(
+                    "Moves the in-camera companions of RAW pictures, sharing their directory and name, to the trash.",
+                )),
+                Command::new("gc")
+                    .about("Deletes the files trashed before the date permanently, except the protected ones, asking for a confirmation token above the [delete] limits.")
+                    .arg(
+                        arg!(--"older-than" <DATE> "The date the deleted files were trashed before, e.g. 30d or last-month")
+                            .required(true)
+                            .value_parser(parse_date),
+                    )
+                    .arg(confirm_arg()),
+                Command::new("restore")
+                    .about("Moves the trashed files back to where they were pruned from, and into the catalog.")
+                    .arg(arg!(<TARGET> "The sha256 of the content, or the original or trash path of a file")),
             ])
     }
 
-    fn handle(&self, sub_matches: &ArgMatches) -> Result<()> {
-        let db_path: PathBuf = [".photo_works", "db.db3"].iter().collect();
-        let mut connection = database::open(&db_path)?;
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        // The paths are relative to the directory the command runs in.
+        let restored = sub_matches
+            .subcommand_matches("restore")
+            .map(|m| {
+                let target = m.get_one::<String>("TARGET").expect("required");
+                absolute(target).map(|path| (target.clone(), path))
+            })
+            .transpose()?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let keep_rules = repository
+            .config()?
+            .prune()
+            .keep()
+            .iter()
+            .map(|rule| KeepRule::from_str(rule).map_err(|e| eyre!(e)))
+            .collect::<Result<Vec<KeepRule>>>()?;
+        if let Some(sub_matches) = sub_matches.subcommand_matches("gc") {
+            return empty_trash(
+                repository,
+                sub_matches.get_one::<NaiveDate>("older-than").copied(),
+                sub_matches.get_flag("i-know-what-i-am-doing"),
+            );
+        }
+        let bin = repository.trash()?;
+        let mut connection = repository.open_database()?;
 
+        if let Some((target, path)) = restored {
+            return restore_trashed(&mut connection, &bin, &target, &path);
+        }
         match sub_matches.subcommand() {
-            Some((name, _sub_matches)) => match name {
-                "duplicates" => prune_catalog_duplicates(&mut connection),
-                "imported" => prune_imported_catalog_entries(&mut connection),
-                _ => unreachable!("Unknown subcommand"),
-            },
+            Some((name, sub_matches)) => {
+                let trash = Trash {
+                    confirmation: if sub_matches.get_flag("dry-run") {
+                        Confirmation::DryRun
+                    } else if sub_matches.get_flag("yes") {
+                        Confirmation::Yes
+                    } else {
+                        Confirmation::Ask
+                    },
+                    cleanup_dirs: sub_matches.get_flag("cleanup-dirs"),
+                    bin: if sub_matches.get_flag("system-trash") {
+                        bin.with_system_trash(SystemTrash::locate()?)
+                    } else {
+                        bin
+                    },
+                };
+                match name {
+                    "duplicates" => prune_catalog_duplicates(&mut connection, &keep_rules, trash),
+                    "imported" => prune_imported_catalog_entries(
+                        &mut connection,
+                        trash,
+                        sub_matches.get_flag("verify").then_some(repository.root()),
+                    ),
+                    "pairs" => prune_raw_companions(&mut connection, trash),
+                    _ => unreachable!("Unknown subcommand"),
+                }
+            }
             None => unreachable!("Missing subcommand."),
         }
     }
 }
 
-fn prune_catalog_duplicates(mut connection: &mut Connection) -> Result<()> {
-    println!("Pruning catalog duplicates");
+fn prune_args(command: Command) -> Command {
+    command
+        .arg(arg!(--"cleanup-dirs" "Removes the directories left empty by the pruned files"))
+        .arg(arg!(--"dry-run" "Only lists the files that would be moved to the trash"))
+        .arg(arg!(--"system-trash" "Moves the files to the trash of the desktop instead of the repository trash"))
+        .arg(yes_arg())
+}
+
+/// How the files to prune are confirmed before they are moved to the trash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Confirmation {
+    /// Only lists the files.
+    DryRun,
+    /// Asks on the standard input, showing the number and size of the files.
+    Ask,
+    Yes,
+}
+
+/// How the pruned files are moved to the trash.
+#[derive(Debug)]
+struct Trash {
+    confirmation: Confirmation,
+    cleanup_dirs: bool,
+    bin: TrashBin,
+}
+
+impl Trash {
+    /// Moves the entries to the trash and out of the catalog once confirmed,
+    /// describing them as `what`, e.g. duplicates, and `redundant` when another
+    /// copy of their contents is kept. Returns the number of entries moved, none
+    /// when they were only listed or the move was declined.
+    fn apply(
+        &self,
+        connection: &mut Connection,
+        entries: &[CatalogEntry],
+        what: &str,
+        redundant: bool,
+    ) -> Result<Option<usize>> {
+        let summary = format!(
+            "{} {} ({})",
+            entries.len(),
+            what,
+            HumanBytes(entries.iter().map(|e| file_size(&e.path())).sum())
+        );
+        match self.confirmation {
+            _ if entries.is_empty() => {}
+            Confirmation::DryRun => {
+                for entry in entries {
+                    println!("Would trash {}", entry.path().display());
+                }
+                println!("Would move {} to the trash.", summary);
+                return Ok(None);
+            }
+            Confirmation::Ask => {
+                if !confirm(&format!("Move {} to the trash?", summary), stdin().lock())? {
+                    println!("Nothing was moved to the trash.");
+                    return Ok(None);
+                }
+            }
+            Confirmation::Yes => {}
+        }
+        for entry in entries {
+            if redundant {
+                self.bin.move_in_redundant(connection, entry)?
+            } else {
+                self.bin.move_in(connection, entry)?
+            }
+        }
+        database::catalog::remove_catalog_entries(connection, entries)?;
+        if self.cleanup_dirs {
+            cleanup_empty_directories(connection, entries)?;
+        }
+        Ok(Some(entries.len()))
+    }
+}
+
+/// A rule of the `[prune] keep` configuration: the copy of duplicates matching the
+/// earliest rule is kept.
+#[derive(Debug)]
+enum KeepRule {
+    Raw,
+    Extension(String),
+    Path(Pattern),
+}
+
+impl FromStr for KeepRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> std::result::Result<Self, Self::Err> {
+        match rule.split_once(':') {
+            Some(("format", "raw")) => Ok(Self::Raw),
+            Some(("ext", extension)) => Ok(Self::Extension(extension.to_lowercase())),
+            Some(("path", pattern)) => Pattern::new(pattern)
+                .map(Self::Path)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!(
+                "Invalid keep rule `{}`, expected format:raw, ext:<EXT> or path:<GLOB>",
+                rule
+            )),
+        }
+    }
+}
+
+impl KeepRule {
+    fn matches(&self, entry: &CatalogEntry) -> bool {
+        match self {
+            Self::Raw => is_raw(&entry.path()),
+            Self::Extension(extension) => entry
+                .path()
+                .extension()
+                .is_some_and(|e| e.to_string_lossy().to_lowercase() == *extension),
+            Self::Path(pattern) => pattern.matches_path(&entry.path()),
+        }
+    }
+}
+
+/// The position of the first rule the entry matches, unmatched entries last.
+fn keep_rank(entry: &CatalogEntry, rules: &[KeepRule]) -> usize {
+    rules
+        .iter()
+        .position(|r| r.matches(entry))
+        .unwrap_or(rules.len())
+}
+
+fn prune_catalog_duplicates(
+    connection: &mut Connection,
+    keep_rules: &[KeepRule],
+    trash: Trash,
+) -> Result<()> {
+    println!("{}", bold("Pruning catalog duplicates"));
     let catalog_prune_start = Instant::now();
 
-    let duplicates = find_duplicates(connection)?;
-    if duplicates.len() == 0 {
-        Ok(println!(
+    // Local copies are kept first, remote entries are never trashed.
+    let mut pruned = vec![];
+    let duplicates = foreach_duplicate(connection, |_, mut copies| {
+        copies.sort_by_key(|e| (e.is_remote(), keep_rank(e, keep_rules)));
+        pruned.extend(copies.into_iter().skip(1).filter(|e| !e.is_remote()));
+        Ok(())
+    })?;
+    if duplicates == 0 {
+        println!(
             "No duplicates found. {} seconds.",
             catalog_prune_start.elapsed().as_secs()
-        ))
+        );
+        Ok(())
     } else {
-        let mut count = 0;
-        for dupes in duplicates.values() {
-            for duplicate in dupes.iter().skip(1) {
-                count += 1;
-                move_to_trash(duplicate)?
-            }
+        let pruned = without_protected(connection, with_partners(connection, pruned)?)?;
+        if let Some(count) = trash.apply(connection, &pruned, "duplicates", true)? {
+            println!(
+                "{} duplicates moved to trash. {} seconds.",
+                count,
+                catalog_prune_start.elapsed().as_secs(),
+            );
         }
-        database::catalog::remove_catalog_entries(
-            &mut connection,
-            &duplicates
-                .into_values()
-                .flat_map(|v| v.into_iter().skip(1))
-                .collect(),
-        )?;
-        Ok(println!(
-            "{} duplicates moved to trash. {} seconds.",
-            count,
-            catalog_prune_start.elapsed().as_secs(),
-        ))
+        Ok(())
     }
 }
 
-fn prune_imported_catalog_entries(mut connection: &mut Connection) -> Result<()> {
-    println!("Pruning imported catalog entries");
+/// Prunes the catalog entries whose content is in the library, re-hashing the
+/// library copies under `verified_root` when given.
+fn prune_imported_catalog_entries(
+    connection: &mut Connection,
+    trash: Trash,
+    verified_root: Option<&Path>,
+) -> Result<()> {
+    println!("{}", bold("Pruning imported catalog entries"));
     let catalog_prune_start = Instant::now();
 
-    let already_imported = find_already_imported(connection)?;
-    if already_imported.len() == 0 {
-        Ok(println!(
+    let already_imported = find_already_imported(connection)?
+        .into_iter()
+        .filter(|e| !e.is_remote())
+        .collect();
+    let mut already_imported =
+        without_protected(connection, with_partners(connection, already_imported)?)?;
+    if let Some(root) = verified_root {
+        already_imported = with_intact_library_copy(connection, root, already_imported)?;
+    }
+    if already_imported.is_empty() {
+        println!(
             "No imported entries found. {} seconds.",
             catalog_prune_start.elapsed().as_secs()
-        ))
+        );
+        Ok(())
     } else {
-        let mut count = 0;
-        for entry in &already_imported {
-            count += 1;
-            move_to_trash(entry)?
-        }
-        database::catalog::remove_catalog_entries(&mut connection, &already_imported)?;
-        Ok(println!(
-            "{} imported entries moved to trash. {} seconds.",
+        if let Some(count) = trash.apply(connection, &already_imported, "imported entries", true)? {
+            println!(
+                "{} imported entries moved to trash. {} seconds.",
+                count,
+                catalog_prune_start.elapsed().as_secs(),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn prune_raw_companions(connection: &mut Connection, trash: Trash) -> Result<()> {
+    println!("{}", bold("Pruning companions of RAW pictures"));
+    let catalog_prune_start = Instant::now();
+
+    let mut entries = vec![];
+    database::catalog::foreach_entry(connection, |e| {
+        entries.push(e);
+        Ok(())
+    })?;
+    let companions = without_protected(connection, find_raw_companions(entries))?;
+    if let Some(count) =
+        trash.apply(connection, &companions, "companions of RAW pictures", false)?
+    {
+        println!(
+            "{} companions of RAW pictures moved to trash. {} seconds.",
             count,
             catalog_prune_start.elapsed().as_secs(),
-        ))
+        );
     }
+    Ok(())
 }
 
-fn move_to_trash(entry: &CatalogEntry) -> Result<()> {
-    let original_path = entry.path();
-    let trash_path = trash_path(entry)?;
-    let trash_dir = trash_path.parent().ok_or(eyre!("Invalid Directory"))?;
-    std::fs::create_dir_all(trash_dir)?;
-    std::fs::copy(&original_path, trash_path)?;
-    Ok(remove_file(original_path)?)
+/// Moves the trashed files of the content of the target sha256, or moved from or
+/// to the target path, back to where they were pruned from.
+fn restore_trashed(
+    connection: &mut Connection,
+    bin: &TrashBin,
+    target: &str,
+    path: &Path,
+) -> Result<()> {
+    let files = select_trashed_files(connection, &target.to_uppercase(), &path.to_string_lossy())?;
+    if files.is_empty() {
+        return Err(eyre!("Nothing in the trash matches {}", target));
+    }
+    for (hash, trashed) in &files {
+        bin.restore(connection, hash, trashed)?;
+        println!("{} {}", Status::Restored, trashed.original_path.display());
+    }
+    println!("{} files restored from the trash.", files.len());
+    Ok(())
+}
+
+/// The entries whose library copy still has their content, re-hashing each library
+/// file once. The others are kept, as they may hold the last good copy.
+fn with_intact_library_copy(
+    connection: &Connection,
+    root: &Path,
+    entries: Vec<CatalogEntry>,
+) -> Result<Vec<CatalogEntry>> {
+    let mut intact: HashMap<String, bool> = HashMap::new();
+    let mut verified = vec![];
+    let mut kept = 0;
+    for entry in entries {
+        let is_intact = match intact.get(entry.sha256()) {
+            Some(is_intact) => *is_intact,
+            None => {
+                let is_intact = library_copy_is_intact(connection, root, entry.sha256())?;
+                intact.insert(entry.sha256().to_owned(), is_intact);
+                is_intact
+            }
+        };
+        if is_intact {
+            verified.push(entry);
+        } else {
+            println!("{} {}", Status::Skipped, entry.path().display());
+            kept += 1;
+        }
+    }
+    if kept > 0 {
+        println!(
+            "{} entries kept as their library copy is altered or missing.",
+            kept
+        );
+    }
+    Ok(verified)
 }
 
-fn trash_path(entry: &CatalogEntry) -> Result<PathBuf> {
-    let original_path = entry.path();
-    let directory = original_path
-        .parent()
-        .and_then(|p| p.file_name())
-        .ok_or(eyre!("Invalid File"))?;
-    let filename = original_path.file_name().ok_or(eyre!("Invalid File"))?;
-    let mut trash_path = PathBuf::from(".trash");
-    trash_path.push(directory);
-    trash_path.push(filename);
-    Ok(trash_path)
+/// Whether the library file of the content has its hash, reporting it otherwise.
+fn library_copy_is_intact(connection: &Connection, root: &Path, hash: &str) -> Result<bool> {
+    let Some(path) = select_library_path(connection, hash)? else {
+        return Ok(false);
+    };
+    let path = root.join(path);
+    match sha256_digest(&path) {
+        Ok(digest) if digest == hash => Ok(true),
+        Ok(_) => {
+            println!("{} {}", Status::Corrupt, path.display());
+            Ok(false)
+        }
+        Err(_) => {
+            println!("{} {}", Status::Missing, path.display());
+            Ok(false)
+        }
+    }
+}
+
+/// The entries along with the other pictures of their shot, the JPEG of a pruned
+/// RAW picture or the RAW picture of a pruned JPEG, so that the pairs are pruned
+/// together. A partner is only added while another copy of its content is kept,
+/// in the library or in the catalog.
+fn with_partners(
+    connection: &Connection,
+    mut entries: Vec<CatalogEntry>,
+) -> Result<Vec<CatalogEntry>> {
+    let partners = select_partners(connection)?;
+    let mut pruned = entries
+        .iter()
+        .map(|e| e.path())
+        .collect::<HashSet<PathBuf>>();
+    let mut added = vec![];
+    for entry in &entries {
+        let Some(partner) = partners.get(entry.sha256()) else {
+            continue;
+        };
+        let in_library = select_library_path(connection, partner)?.is_some();
+        let copies = select_catalog_copies(connection, partner)?;
+        let mut taken = HashSet::new();
+        for (index, copy) in copies.iter().enumerate() {
+            if copy.is_remote()
+                || shot(&copy.path()) != shot(&entry.path())
+                || pruned.contains(&copy.path())
+            {
+                continue;
+            }
+            let kept = in_library
+                || copies.iter().any(|c| {
+                    c.path() != copy.path() && !c.is_same_file(copy) && !pruned.contains(&c.path())
+                });
+            if kept {
+                pruned.insert(copy.path());
+                taken.insert(index);
+            }
+        }
+        added.extend(
+            copies
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| taken.contains(index))
+                .map(|(_, copy)| copy),
+        );
+    }
+    if !added.is_empty() {
+        println!(
+            "{} pictures of the same shots added, pairs are pruned together.",
+            added.len()
+        );
+    }
+    entries.extend(added);
+    Ok(entries)
+}
+
+/// The entries whose content is not protected, reporting the protected ones kept.
+fn without_protected(
+    connection: &Connection,
+    entries: Vec<CatalogEntry>,
+) -> Result<Vec<CatalogEntry>> {
+    let protected = protected_hashes(connection)?;
+    let (kept, pruned): (Vec<CatalogEntry>, Vec<CatalogEntry>) = entries
+        .into_iter()
+        .partition(|e| protected.contains(e.sha256()));
+    for entry in &kept {
+        println!("{} {}", Status::Protected, entry.path().display());
+    }
+    if !kept.is_empty() {
+        println!("{} protected entries kept.", kept.len());
+    }
+    Ok(pruned)
+}
+
+/// The local entries sharing their directory and name with a RAW picture, e.g. the
+/// JPEG a camera writes along with the RAW of the same shot. RAW pictures are
+/// never companions.
+fn find_raw_companions(entries: Vec<CatalogEntry>) -> Vec<CatalogEntry> {
+    let companions = find_pairs(&entries.iter().map(|e| e.path()).collect::<Vec<PathBuf>>())
+        .into_iter()
+        .map(|(_, companion)| companion)
+        .collect::<HashSet<usize>>();
+    let mut companions = entries
+        .into_iter()
+        .enumerate()
+        .filter(|(index, e)| companions.contains(index) && !e.is_remote())
+        .map(|(_, e)| e)
+        .collect::<Vec<CatalogEntry>>();
+    companions.sort_by_key(|e| e.path());
+    companions
+}
+
+/// Removes the directories emptied by the prune, restricted to the cataloged
+/// roots and the repository.
+fn cleanup_empty_directories(connection: &Connection, pruned: &[CatalogEntry]) -> Result<()> {
+    let mut roots = select_catalog_roots(connection)?;
+    roots.push(current_dir()?);
+    let mut count = 0;
+    for entry in pruned {
+        count += remove_empty_ancestors(&entry.path(), &roots)?.len();
+    }
+    println!("{} empty directories removed.", count);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs::remove_dir_all;
+    use std::{
+        fs::{copy, create_dir_all, write, File},
+        path::PathBuf,
+    };
 
-    use tempfile::NamedTempFile;
+    use tempfile::{NamedTempFile, TempDir};
 
     use crate::{
         command::prune::prune_catalog_duplicates,
         database::{
+            catalog::persist_catalog_root,
             catalog_entry::CatalogEntry,
+            common::sha256_digest,
             library_entry::LibraryEntry,
+            pair::record_pairs,
+            protected::protect,
             test_utils::{
                 catalog_contains, library_contains,
                 new_database_containing_catalog_and_library_entries,
                 new_database_containing_catalog_entries,
             },
         },
+        trash::TrashBin,
     };
 
-    use super::{move_to_trash, prune_imported_catalog_entries, trash_path};
+    use super::{
+        find_raw_companions, keep_rank, prune_imported_catalog_entries, Confirmation, KeepRule,
+        Trash,
+    };
+
+    fn trash(bin: &TempDir, cleanup_dirs: bool) -> Trash {
+        Trash {
+            confirmation: Confirmation::Yes,
+            cleanup_dirs,
+            bin: TrashBin::new(bin.path().to_owned()),
+        }
+    }
 
     #[test]
     fn prune_catalog_duplicates_entries_removes_the_entry() {
+        let bin = TempDir::new().unwrap();
         let catalog_entry1 = NamedTempFile::new().unwrap();
         let catalog_entry2 = NamedTempFile::new().unwrap();
         let catalog_entry3 = NamedTempFile::new().unwrap();
@@ -172,7 +602,7 @@ mod tests {
             ),
         ];
         let mut connection = new_database_containing_catalog_entries(&entries);
-        prune_catalog_duplicates(&mut connection).unwrap();
+        prune_catalog_duplicates(&mut connection, &[], trash(&bin, false)).unwrap();
 
         assert!(!catalog_contains(&mut connection, &entries[2]));
 
@@ -180,8 +610,75 @@ mod tests {
         assert!(catalog_contains(&mut connection, &entries[1]));
     }
 
+    #[test]
+    fn prune_catalog_duplicates_keeps_the_protected_contents() {
+        let bin = TempDir::new().unwrap();
+        let catalog_entry1 = NamedTempFile::new().unwrap();
+        let catalog_entry2 = NamedTempFile::new().unwrap();
+        let entries = vec![
+            CatalogEntry::new(
+                "1234".to_string(),
+                catalog_entry1.path().to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                "1234".to_string(),
+                catalog_entry2.path().to_string_lossy().to_string(),
+            ),
+        ];
+        let mut connection = new_database_containing_catalog_entries(&entries);
+        protect(&mut connection, &["1234".to_string()]).unwrap();
+
+        prune_catalog_duplicates(&mut connection, &[], trash(&bin, false)).unwrap();
+
+        assert!(catalog_contains(&mut connection, &entries[0]));
+        assert!(catalog_contains(&mut connection, &entries[1]));
+        assert!(catalog_entry2.path().exists());
+    }
+
+    #[test]
+    fn keep_rank_orders_the_copies_by_first_matching_rule() {
+        let rules = vec![
+            "format:raw".parse::<KeepRule>().unwrap(),
+            "path:/card/*".parse::<KeepRule>().unwrap(),
+        ];
+        let rank = |path: &str| {
+            keep_rank(
+                &CatalogEntry::new("1".to_string(), path.to_string()),
+                &rules,
+            )
+        };
+
+        assert_eq!(0, rank("/card/a.nef"));
+        assert_eq!(1, rank("/card/a.jpg"));
+        assert_eq!(2, rank("/backup/a.jpg"));
+    }
+
+    #[test]
+    fn keep_rule_rejects_unknown_formats() {
+        assert!("format:gif".parse::<KeepRule>().is_err());
+    }
+
+    #[test]
+    fn find_raw_companions_never_includes_the_raw() {
+        let entries = vec![
+            CatalogEntry::new("1".to_string(), "/card/IMG_0001.CR2".to_string()),
+            CatalogEntry::new("2".to_string(), "/card/IMG_0001.JPG".to_string()),
+            CatalogEntry::new("3".to_string(), "/card/IMG_0002.JPG".to_string()),
+            CatalogEntry::new("4".to_string(), "/other/IMG_0001.JPG".to_string()),
+        ];
+
+        assert_eq!(
+            vec![PathBuf::from("/card/IMG_0001.JPG")],
+            find_raw_companions(entries)
+                .iter()
+                .map(|e| e.path())
+                .collect::<Vec<PathBuf>>()
+        );
+    }
+
     #[test]
     fn prune_imported_catalog_entries_removes_the_entry() {
+        let bin = TempDir::new().unwrap();
         let catalog_entry1 = NamedTempFile::new().unwrap();
         let catalog_entry2 = NamedTempFile::new().unwrap();
         let library_entry1 = NamedTempFile::new().unwrap();
@@ -203,7 +700,7 @@ mod tests {
 
         let mut connection =
             new_database_containing_catalog_and_library_entries(&catalog_entries, &library_entries);
-        prune_imported_catalog_entries(&mut connection).unwrap();
+        prune_imported_catalog_entries(&mut connection, trash(&bin, false), None).unwrap();
 
         assert!(!catalog_contains(&mut connection, &catalog_entries[0]));
 
@@ -212,36 +709,145 @@ mod tests {
     }
 
     #[test]
-    fn trash_path_prefixes_directory() {
-        let entry = CatalogEntry::new("1234".to_string(), "a/b/c.png".to_string());
-        let trash_path = trash_path(&entry).unwrap();
-        assert_eq!(".trash/b/c.png", trash_path.to_string_lossy().to_string());
+    fn prune_imported_catalog_entries_prunes_the_pairs_together() {
+        let bin = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let card = root.path().join("card");
+        let backup = root.path().join("backup");
+        for directory in [&card, &backup] {
+            create_dir_all(directory).unwrap();
+        }
+        let catalog_entries = vec![
+            CatalogEntry::new(
+                "R1".to_string(),
+                card.join("IMG_1.CR2").to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                "J1".to_string(),
+                card.join("IMG_1.JPG").to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                "J1".to_string(),
+                backup.join("IMG_1.JPG").to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                "R2".to_string(),
+                card.join("IMG_2.CR2").to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                "J2".to_string(),
+                card.join("IMG_2.JPG").to_string_lossy().to_string(),
+            ),
+        ];
+        for entry in &catalog_entries {
+            File::create(entry.path()).unwrap();
+        }
+        let library_entries = vec![
+            LibraryEntry::new("R1".to_string(), PathBuf::from("2024/IMG_1.CR2")),
+            LibraryEntry::new("R2".to_string(), PathBuf::from("2024/IMG_2.CR2")),
+        ];
+        let mut connection =
+            new_database_containing_catalog_and_library_entries(&catalog_entries, &library_entries);
+        let pairs = [("R1", "J1"), ("R2", "J2")].map(|(r, j)| (r.to_string(), j.to_string()));
+        record_pairs(&mut connection, &pairs).unwrap();
+
+        prune_imported_catalog_entries(&mut connection, trash(&bin, false), None).unwrap();
+
+        assert!(!catalog_contains(&mut connection, &catalog_entries[0]));
+        assert!(!catalog_contains(&mut connection, &catalog_entries[1]));
+        assert!(!catalog_entries[1].path().exists());
+        assert!(catalog_contains(&mut connection, &catalog_entries[2]));
+        assert!(!catalog_contains(&mut connection, &catalog_entries[3]));
+        assert!(catalog_contains(&mut connection, &catalog_entries[4]));
+        assert!(catalog_entries[4].path().exists());
     }
 
     #[test]
-    fn trash_path_fails_when_no_file_name() {
-        let entry = CatalogEntry::new("1234".to_string(), "..".to_string());
-        let error = trash_path(&entry).err().unwrap().to_string();
-        assert_eq!("Invalid File", error);
+    fn prune_imported_catalog_entries_keeps_the_entries_of_altered_library_copies_when_verifying() {
+        let bin = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let picture = PathBuf::from("resources/test/kami_neko.jpeg");
+        let hash = sha256_digest(&picture).unwrap();
+        write(root.path().join("a.jpeg"), "altered").unwrap();
+        copy(&picture, root.path().join("b.jpeg")).unwrap();
+        let sources = [NamedTempFile::new().unwrap(), NamedTempFile::new().unwrap()];
+        let catalog_entries = vec![
+            CatalogEntry::new(
+                "1234".to_string(),
+                sources[0].path().to_string_lossy().to_string(),
+            ),
+            CatalogEntry::new(
+                hash.clone(),
+                sources[1].path().to_string_lossy().to_string(),
+            ),
+        ];
+        let library_entries = vec![
+            LibraryEntry::new("1234".to_string(), PathBuf::from("a.jpeg")),
+            LibraryEntry::new(hash, PathBuf::from("b.jpeg")),
+        ];
+
+        let mut connection =
+            new_database_containing_catalog_and_library_entries(&catalog_entries, &library_entries);
+        prune_imported_catalog_entries(&mut connection, trash(&bin, false), Some(root.path()))
+            .unwrap();
+
+        assert!(catalog_contains(&mut connection, &catalog_entries[0]));
+        assert!(sources[0].path().exists());
+        assert!(!catalog_contains(&mut connection, &catalog_entries[1]));
     }
 
     #[test]
-    fn trash_path_fails_when_no_parent_directory() {
-        let entry = CatalogEntry::new("1234".to_string(), "c.png".to_string());
-        let error = trash_path(&entry).err().unwrap().to_string();
-        assert_eq!("Invalid File", error);
+    fn prune_imported_catalog_entries_only_lists_the_entries_on_dry_run() {
+        let bin = TempDir::new().unwrap();
+        let catalog_entry = NamedTempFile::new().unwrap();
+        let catalog_entries = vec![CatalogEntry::new(
+            "1234".to_string(),
+            catalog_entry.path().to_string_lossy().to_string(),
+        )];
+        let library_entries = vec![LibraryEntry::new(
+            "1234".to_string(),
+            PathBuf::from("2023/5/18/b.png"),
+        )];
+
+        let mut connection =
+            new_database_containing_catalog_and_library_entries(&catalog_entries, &library_entries);
+        prune_imported_catalog_entries(
+            &mut connection,
+            Trash {
+                confirmation: Confirmation::DryRun,
+                cleanup_dirs: true,
+                bin: TrashBin::new(bin.path().to_owned()),
+            },
+            None,
+        )
+        .unwrap();
+
+        assert!(catalog_entry.path().exists());
+        assert!(catalog_contains(&mut connection, &catalog_entries[0]));
     }
 
     #[test]
-    fn move_to_trash_moves_the_file() {
-        let file = NamedTempFile::new().unwrap();
-        let entry = CatalogEntry::new(
+    fn prune_imported_catalog_entries_removes_emptied_directories_when_cleaning_up() {
+        let bin = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        let directory = root.path().join("a");
+        create_dir_all(&directory).unwrap();
+        let catalog_entries = vec![CatalogEntry::new(
             "1234".to_string(),
-            file.path().to_string_lossy().to_string(),
-        );
-        let trash_path = trash_path(&entry).unwrap();
-        move_to_trash(&entry).unwrap();
-        assert!(trash_path.exists());
-        remove_dir_all(".trash").unwrap();
+            directory.join("b.png").to_string_lossy().to_string(),
+        )];
+        File::create(catalog_entries[0].path()).unwrap();
+        let library_entries = vec![LibraryEntry::new(
+            "1234".to_string(),
+            PathBuf::from("2023/5/18/b.png"),
+        )];
+
+        let mut connection =
+            new_database_containing_catalog_and_library_entries(&catalog_entries, &library_entries);
+        persist_catalog_root(&connection, root.path()).unwrap();
+        prune_imported_catalog_entries(&mut connection, trash(&bin, true), None).unwrap();
+
+        assert!(!directory.exists());
+        assert!(root.path().exists());
     }
 }
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashSet,
+    fs::{copy, create_dir_all, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::{
+    clapext::SubApplication,
+    command::tag::{selection_args, Selection},
+    config::RepositoryConfig,
+    database::{
+        event::select_events,
+        library::{foreach_entry, LibraryFilter},
+        library_entry::LibraryEntry,
+        published::{record_published, select_published, Published},
+        sync_state::record_synced,
+        tag::select_tags,
+    },
+    error::Failures,
+    http::Client,
+    privacy::{PrivacyConfig, Redaction},
+    publish::{Publication, PublishedMetadata, Session},
+    repository::Repository,
+    secrets,
+    style::Status,
+};
+
+pub(crate) const PUBLISH: &str = "publish";
+
+pub(crate) struct Publish;
+
+impl SubApplication for Publish {
+    fn name(&self) -> &'static str {
+        PUBLISH
+    }
+
+    fn command(&self) -> Command {
+        selection_args(
+            Command::new(self.name())
+                .about("Publishes the selected library pictures, with their tags and event names as captions, to a gallery server")
+                .arg(arg!(<TARGET> "The server, configured in the [publish.<TARGET>] table with its secret in publish.<TARGET>"))
+                .arg(arg!(--"dry-run" "Only reports the pictures to upload and to update")),
+        )
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let target = sub_matches.get_one::<String>("TARGET").expect("required");
+        let selection = Selection::read(sub_matches)?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        if config.publisher(target).is_none() {
+            return Err(eyre!(
+                "No [publish.{}] table in the repository configuration",
+                target
+            ));
+        }
+        let connection = repository.open_database()?;
+
+        let hashes = selection
+            .hashes(&connection, repository.root())?
+            .into_iter()
+            .collect::<HashSet<String>>();
+        let tags = select_tags(&connection)?;
+        let events = select_events(&connection)?;
+        let published = select_published(&connection, target)?;
+        let mut pending = vec![];
+        let mut unchanged = 0;
+        foreach_entry(&connection, &LibraryFilter::default(), |e| {
+            if !hashes.contains(e.sha256()) {
+                return Ok(());
+            }
+            let metadata = PublishedMetadata {
+                title: e
+                    .path()
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                caption: e.original_date().and_then(|date| {
+                    events
+                        .iter()
+                        .find(|event| event.contains(date))
+                        .map(|event| event.name.clone())
+                }),
+                tags: tags.get(e.sha256()).cloned().unwrap_or_default(),
+            };
+            let json = serde_json::to_string(&metadata)?;
+            match published.get(e.sha256()) {
+                Some(p) if p.metadata == json => unchanged += 1,
+                p => pending.push((e, metadata, json, p.map(|p| p.remote_id.clone()))),
+            }
+            Ok(())
+        })?;
+
+        if sub_matches.get_flag("dry-run") {
+            for (entry, _, _, remote_id) in &pending {
+                let action = if remote_id.is_some() {
+                    "UPDATE"
+                } else {
+                    "UPLOAD"
+                };
+                println!("{} {}", action, entry.path().display());
+            }
+            println!(
+                "Would publish {} pictures to {}, {} unchanged",
+                pending.len(),
+                target,
+                unchanged
+            );
+            return Ok(());
+        }
+
+        let session = open_session(&config, target)?;
+        let staging = repository.redacted_path();
+        let (mut uploaded, mut updated) = (0, 0);
+        let mut errors = vec![];
+        for (entry, metadata, json, remote_id) in pending {
+            let Some(root) =
+                upload_root(config.privacy(), repository.root(), &staging, entry.path())?
+            else {
+                continue;
+            };
+            let publication = publication(&entry, &metadata);
+            let new = remote_id.is_none();
+            let result = match remote_id {
+                None => session.upload(&root, &publication),
+                Some(id) => session.update(&root, &id, &publication).map(|_| id),
+            };
+            match result {
+                Ok(id) => {
+                    let action = if new {
+                        uploaded += 1;
+                        "UPLOADED"
+                    } else {
+                        updated += 1;
+                        "UPDATED"
+                    };
+                    println!("{} {}", action, entry.path().display());
+                    record_published(
+                        &connection,
+                        target,
+                        entry.sha256(),
+                        &Published {
+                            remote_id: id,
+                            metadata: json,
+                        },
+                    )?;
+                    record_synced(
+                        &connection,
+                        &format!("{}:{}", PUBLISH, target),
+                        &[(entry.sha256().to_owned(), entry.sha256().to_owned())],
+                    )?;
+                }
+                Err(e) => {
+                    println!("FAILED {}", entry.path().display());
+                    errors.push(e);
+                }
+            }
+        }
+        if staging.exists() {
+            remove_dir_all(&staging)?;
+        }
+        println!(
+            "Uploaded {} and updated {} pictures on {}, {} unchanged",
+            uploaded, updated, target, unchanged
+        );
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Failures(errors).into())
+        }
+    }
+}
+
+/// The root the picture is uploaded from: the repository root, or the staging
+/// folder holding its copy redacted by the privacy zones. None when the picture
+/// was taken inside a zone in a format that can't be redacted.
+fn upload_root(
+    privacy: &PrivacyConfig,
+    root: &Path,
+    staging: &Path,
+    path: &Path,
+) -> Result<Option<PathBuf>> {
+    if privacy.zone_of(&root.join(path)).is_none() {
+        return Ok(Some(root.to_owned()));
+    }
+    let redacted = staging.join(path);
+    if let Some(parent) = redacted.parent() {
+        create_dir_all(parent)?;
+    }
+    copy(root.join(path), &redacted)?;
+    match privacy.redact(&redacted)? {
+        Redaction::Unsupported(zone) => {
+            remove_file(&redacted)?;
+            println!(
+                "{} {} (taken in the privacy zone {}, in a format that can't be redacted)",
+                Status::Skipped,
+                path.display(),
+                zone
+            );
+            Ok(None)
+        }
+        Redaction::Redacted(zone) => {
+            println!(
+                "{} {} (privacy zone {})",
+                Status::Redacted,
+                path.display(),
+                zone
+            );
+            Ok(Some(staging.to_owned()))
+        }
+        Redaction::Unchanged => Ok(Some(staging.to_owned())),
+    }
+}
+
+fn publication<'a>(entry: &'a LibraryEntry, metadata: &'a PublishedMetadata) -> Publication<'a> {
+    Publication {
+        sha256: entry.sha256(),
+        path: entry.path(),
+        original_date: entry.original_date(),
+        metadata,
+    }
+}
+/// Opens a session on the configured target, with its `publish.<TARGET>` secret.
+pub(crate) fn open_session<'a>(config: &'a RepositoryConfig, target: &str) -> Result<Session<'a>> {
+    let publisher = config.publisher(target).ok_or_else(|| {
+        eyre!(
+            "No [publish.{}] table in the repository configuration",
+            target
+        )
+    })?;
+    let secret_name = format!("{}.{}", PUBLISH, target);
+    let secret = secrets::secret(&secret_name)?.ok_or_else(|| {
+        eyre!(
+            "Missing secret {0}, run photo_works auth set {0}",
+            secret_name
+        )
+    })?;
+    publisher.connect(secret, Client::new(config.http().clone()))
+}
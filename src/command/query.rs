@@ -0,0 +1,82 @@
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+
+use crate::{
+    clapext::SubApplication,
+    database::{
+        library::LibraryFilter,
+        saved_query::{remove_saved_query, save_query, select_saved_queries},
+    },
+    repository::Repository,
+};
+
+const QUERY: &str = "query";
+
+pub(crate) struct Query;
+
+impl SubApplication for Query {
+    fn name(&self) -> &'static str {
+        QUERY
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Saves query terms under a name, which --saved selects the pictures with")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("save")
+                    .about(
+                        "Saves the query terms under the name, replacing the query saved under it.",
+                    )
+                    .arg(arg!(<NAME> "The name of the query"))
+                    .arg(arg!(<QUERY> "The query terms, e.g. \"year:2024 tag:beach\"")),
+                Command::new("list").about("Lists the saved queries, by name."),
+                Command::new("remove")
+                    .about("Removes a saved query.")
+                    .arg(arg!(<NAME> "The name of the query")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        if name == "save" {
+            let terms = sub_matches.get_one::<String>("QUERY").expect("required");
+            terms.parse::<LibraryFilter>().map_err(|e| eyre!(e))?;
+        }
+        repository.enter()?;
+        let connection = repository.open_database()?;
+        match name {
+            "save" => {
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                let terms = sub_matches.get_one::<String>("QUERY").expect("required");
+                if save_query(&connection, name, terms)? {
+                    println!("Replaced the query saved under {}", name);
+                } else {
+                    println!("Saved the query under {}", name);
+                }
+            }
+            "list" => {
+                let queries = select_saved_queries(&connection)?;
+                for query in &queries {
+                    println!(
+                        "{} {:<16} {}",
+                        query.saved_at.format("%Y-%m-%d %H:%M"),
+                        query.name,
+                        query.terms
+                    );
+                }
+                println!("{} saved queries", queries.len());
+            }
+            "remove" => {
+                let name = sub_matches.get_one::<String>("NAME").expect("required");
+                if !remove_saved_query(&connection, name)? {
+                    return Err(eyre!("No query saved under {}", name));
+                }
+                println!("Removed the query saved under {}", name);
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashSet, thread};
+
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::SubApplication,
+    command::tag::{selection_args, Selection},
+    database::{
+        library::{foreach_entry, update_library_metadata, LibraryFilter},
+        library_entry::LibraryEntry,
+    },
+    metadata::MetadataConfig,
+    repository::Repository,
+};
+
+const REFRESH_METADATA: &str = "refresh-metadata";
+
+pub(crate) struct RefreshMetadata;
+
+impl SubApplication for RefreshMetadata {
+    fn name(&self) -> &'static str {
+        REFRESH_METADATA
+    }
+
+    fn command(&self) -> Command {
+        selection_args(
+            Command::new(self.name())
+                .about("Reads the metadata of the selected library pictures again, without copying them, and records the changes")
+                .arg(arg!(--"dry-run" "Only reports the changed fields"))
+                .arg(
+                    arg!(--jobs <JOBS> "The number of pictures read in parallel")
+                        .value_parser(value_parser!(usize))
+                        .default_value("4"),
+                ),
+        )
+    }
+
+    fn remembered(&self) -> &'static [&'static str] {
+        &["jobs"]
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let selection = Selection::read(sub_matches)?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        let mut connection = repository.open_database()?;
+
+        let hashes = selection
+            .hashes(&connection, repository.root())?
+            .into_iter()
+            .collect::<HashSet<String>>();
+        let mut entries = vec![];
+        foreach_entry(&connection, &LibraryFilter::default(), |e| {
+            if hashes.contains(e.sha256()) {
+                entries.push(e);
+            }
+            Ok(())
+        })?;
+        let jobs = *sub_matches.get_one::<usize>("jobs").expect("defaulted");
+        let mut changed = vec![];
+        for (entry, refreshed) in refresh(&entries, config.metadata(), jobs) {
+            match refreshed {
+                Ok(refreshed) => {
+                    let changes = entry.changes(&refreshed);
+                    if !changes.is_empty() {
+                        println!("{}: {}", entry.path().display(), changes.join(", "));
+                        changed.push(refreshed);
+                    }
+                }
+                Err(e) => println!("{}: {}", entry.path().display(), e),
+            }
+        }
+        if sub_matches.get_flag("dry-run") {
+            println!(
+                "Would update {} of the {} pictures",
+                changed.len(),
+                entries.len()
+            );
+        } else {
+            println!(
+                "Updated {} of the {} pictures",
+                update_library_metadata(&mut connection, &changed)?,
+                entries.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads the metadata of the entries again, spreading them over the jobs.
+fn refresh<'a>(
+    entries: &'a [LibraryEntry],
+    config: &MetadataConfig,
+    jobs: usize,
+) -> Vec<(&'a LibraryEntry, Result<LibraryEntry>)> {
+    let chunk_size = entries.len().div_ceil(jobs.max(1)).max(1);
+    thread::scope(|scope| {
+        entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let backend = config.backend();
+                    chunk
+                        .iter()
+                        .map(|e| (e, e.refreshed(backend.as_ref())))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("refresh job panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{database::library_entry::LibraryEntry, metadata::MetadataConfig};
+
+    use super::refresh;
+
+    #[test]
+    fn refresh_reads_every_entry_in_order() {
+        let picture: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        let entries = (0..5)
+            .map(|i| LibraryEntry::new(i.to_string(), picture.clone()))
+            .collect::<Vec<LibraryEntry>>();
+
+        let refreshed = refresh(&entries, &MetadataConfig::default(), 2);
+
+        assert_eq!(
+            vec!["0", "1", "2", "3", "4"],
+            refreshed
+                .iter()
+                .map(|(e, _)| e.sha256())
+                .collect::<Vec<&str>>()
+        );
+        let (entry, refreshed) = &refreshed[0];
+        assert_eq!(
+            vec![
+                "mime_type: none -> image/jpeg".to_string(),
+                "original_date: none -> 2023-05-18".to_string(),
+                "size: none -> 455033".to_string(),
+                "kind: none -> photo".to_string()
+            ],
+            entry.changes(refreshed.as_ref().unwrap())
+        );
+    }
+}
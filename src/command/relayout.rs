@@ -0,0 +1,182 @@
+use std::{
+    collections::HashSet,
+    env::current_dir,
+    fs::{create_dir_all, rename},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    database::{
+        layout_deviation::{remove_deviation, select_deviations},
+        library::{foreach_entry, update_library_paths, LibraryFilter},
+    },
+    fsext::remove_empty_ancestors,
+    naming::LibraryNaming,
+    repository::Repository,
+};
+
+const RELAYOUT: &str = "relayout";
+
+pub(crate) struct Relayout;
+
+impl SubApplication for Relayout {
+    fn name(&self) -> &'static str {
+        RELAYOUT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Moves the library pictures to the directories of the configured date layout")
+            .arg(arg!(--"dry-run" "Only reports the moves"))
+            .arg(arg!(--deviations "Only moves the pictures recorded as deviating from the layout by adopt"))
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        let mut connection = repository.open_database()?;
+
+        let deviations = if sub_matches.get_flag("deviations") {
+            Some(select_deviations(&connection)?)
+        } else {
+            None
+        };
+        let moves = relayout_moves(&connection, config.library_naming(), deviations.as_ref())?;
+        if sub_matches.get_flag("dry-run") {
+            for (_, from, to) in &moves {
+                println!("{} -> {}", from.display(), to.display());
+            }
+            println!("{} pictures would move", moves.len());
+            return Ok(());
+        }
+        let root = current_dir()?;
+        let mut count = 0;
+        for (sha256, from, to) in moves {
+            if to.exists() {
+                println!("Skipping {}, {} exists", from.display(), to.display());
+                continue;
+            }
+            move_picture(&mut connection, &sha256, &from, &to)?;
+            remove_deviation(&connection, &sha256)?;
+            remove_empty_ancestors(&root.join(&from), std::slice::from_ref(&root))?;
+            count += 1;
+        }
+        println!("Moved {} pictures", count);
+        Ok(())
+    }
+}
+
+/// The library pictures whose directory is not the one of their original date
+/// under the layout, with their new path in the same sub-root. Only the pictures
+/// of the given hashes move when some are given.
+fn relayout_moves(
+    connection: &Connection,
+    naming: &LibraryNaming,
+    only: Option<&HashSet<String>>,
+) -> Result<Vec<(String, PathBuf, PathBuf)>> {
+    let mut moves = vec![];
+    foreach_entry(connection, &LibraryFilter::default(), |entry| {
+        if only.is_some_and(|hashes| !hashes.contains(entry.sha256())) {
+            return Ok(());
+        }
+        if let (Some(date), Some(file_name)) = (entry.original_date(), entry.path().file_name()) {
+            let to = naming
+                .sub_root(entry.path(), date)
+                .join(naming.directory(date))
+                .join(file_name);
+            if &to != entry.path() {
+                moves.push((entry.sha256().to_owned(), entry.path().to_owned(), to));
+            }
+        }
+        Ok(())
+    })?;
+    moves.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(moves)
+}
+
+/// Moves the file and its library row together, so that an interruption leaves
+/// at most one picture for `check library --renames` to find.
+fn move_picture(connection: &mut Connection, sha256: &str, from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent)?;
+    }
+    rename(from, to)?;
+    update_library_paths(connection, &[(sha256.to_owned(), to.to_path_buf())])?;
+    println!("Moved {} to {}", from.display(), to.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use crate::{
+        config::RepositoryConfig,
+        database::{
+            library_entry::LibraryEntry, test_utils::new_database_containing_library_entries,
+        },
+    };
+
+    use super::relayout_moves;
+
+    #[test]
+    fn relayout_moves_the_pictures_out_of_their_date_directory() {
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("AB".to_string(), PathBuf::from("2023/5/8/a.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 8)),
+            LibraryEntry::new("CD".to_string(), PathBuf::from("2023/05/08/b.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 8)),
+            LibraryEntry::new("EF".to_string(), PathBuf::from("misc/c.jpeg")),
+            LibraryEntry::new("GH".to_string(), PathBuf::from("drone/2023/5/8/d.mp4"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 8)),
+        ]);
+        let config: RepositoryConfig =
+            toml::from_str("[library]\ndate_layout = \"padded\"").unwrap();
+
+        assert_eq!(
+            vec![
+                (
+                    "AB".to_string(),
+                    PathBuf::from("2023/5/8/a.jpeg"),
+                    PathBuf::from("2023/05/08/a.jpeg")
+                ),
+                (
+                    "GH".to_string(),
+                    PathBuf::from("drone/2023/5/8/d.mp4"),
+                    PathBuf::from("drone/2023/05/08/d.mp4")
+                )
+            ],
+            relayout_moves(&connection, config.library_naming(), None).unwrap()
+        );
+    }
+
+    #[test]
+    fn relayout_moves_only_the_given_pictures() {
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("AB".to_string(), PathBuf::from("2023/5/8/a.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 8)),
+            LibraryEntry::new("CD".to_string(), PathBuf::from("2023/5/9/b.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 9)),
+        ]);
+        let config: RepositoryConfig =
+            toml::from_str("[library]\ndate_layout = \"padded\"").unwrap();
+        let deviations = ["CD".to_string()].into_iter().collect();
+
+        assert_eq!(
+            vec![(
+                "CD".to_string(),
+                PathBuf::from("2023/5/9/b.jpeg"),
+                PathBuf::from("2023/05/09/b.jpeg")
+            )],
+            relayout_moves(&connection, config.library_naming(), Some(&deviations)).unwrap()
+        );
+    }
+}
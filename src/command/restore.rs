@@ -0,0 +1,350 @@
+use std::{
+    fmt::{self, Display},
+    fs::{copy, create_dir_all, remove_file, rename},
+    path::{absolute, Path, PathBuf},
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    command::{
+        export::{find_exported, EXPORT},
+        publish::{open_session, PUBLISH},
+    },
+    config::RepositoryConfig,
+    database::{
+        common::sha256_digest, library::select_library_path, published::select_published,
+        sync_state::select_holding_targets, trashed::select_trashed,
+    },
+    repository::Repository,
+    style::Status,
+};
+
+const RESTORE: &str = "restore";
+
+pub(crate) struct Restore;
+
+impl SubApplication for Restore {
+    fn name(&self) -> &'static str {
+        RESTORE
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Restores a picture from the first copy found in the library, the trash, the exports or the publishing targets, verifying its sha256")
+            .arg(arg!(<HASH> "The sha256 of the picture"))
+            .arg(arg!(--to <DIR> "The folder to restore into, instead of the library path of the picture"))
+            .arg_required_else_help(true)
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let hash = sub_matches
+            .get_one::<String>("HASH")
+            .expect("required")
+            .to_uppercase();
+        let to = sub_matches
+            .get_one::<String>("to")
+            .map(absolute)
+            .transpose()?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let config = repository.config()?;
+        let connection = repository.open_database()?;
+
+        let sources = sources(&connection, repository.root(), &config, &hash)?;
+        restore(
+            &connection,
+            repository.root(),
+            &config,
+            &hash,
+            to.as_deref(),
+            &sources,
+        )?;
+        Ok(())
+    }
+}
+
+/// A place holding a copy of a content.
+#[derive(Debug, PartialEq)]
+enum Source {
+    Library(PathBuf),
+    Trash(PathBuf),
+    /// A file of an export destination, encrypted when its plain version is missing.
+    Export {
+        target: String,
+        path: PathBuf,
+        encrypted: bool,
+    },
+    Publish {
+        target: String,
+        remote_id: String,
+    },
+}
+
+impl Source {
+    /// The local path of the copy, the plain one for the encrypted exports.
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Library(path) | Self::Trash(path) | Self::Export { path, .. } => Some(path),
+            Self::Publish { .. } => None,
+        }
+    }
+
+    /// Copies the content to the path.
+    fn fetch(&self, config: &RepositoryConfig, to: &Path) -> Result<()> {
+        match self {
+            Self::Library(path) | Self::Trash(path) => copy(path, to).map(|_| ())?,
+            Self::Export {
+                path,
+                encrypted: false,
+                ..
+            } => copy(path, to).map(|_| ())?,
+            Self::Export {
+                path,
+                encrypted: true,
+                ..
+            } => {
+                let encryption = config
+                    .encryption()
+                    .ok_or(eyre!("No [encryption] in the repository configuration."))?;
+                encryption.decrypt(&encryption.encrypted_path(path), to)?
+            }
+            Self::Publish { target, remote_id } => {
+                open_session(config, target)?.download(remote_id, to)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Library(path) => write!(f, "library {}", path.display()),
+            Self::Trash(path) => write!(f, "trash {}", path.display()),
+            Self::Export { target, path, .. } => {
+                write!(f, "{}:{} {}", EXPORT, target, path.display())
+            }
+            Self::Publish { target, remote_id } => {
+                write!(f, "{}:{} {}", PUBLISH, target, remote_id)
+            }
+        }
+    }
+}
+
+/// The known copies of the content, in the order they are tried: the library, the
+/// trash, the export destinations and the publishing targets. The local copies
+/// are only listed when their file exists.
+fn sources(
+    connection: &Connection,
+    root: &Path,
+    config: &RepositoryConfig,
+    hash: &str,
+) -> Result<Vec<Source>> {
+    let mut sources = vec![];
+    if let Some(path) = select_library_path(connection, hash)? {
+        let path = root.join(path);
+        if path.is_file() {
+            sources.push(Source::Library(path));
+        }
+    }
+    if let Some(trashed) = select_trashed(connection)?.remove(hash) {
+        if trashed.path.is_file() {
+            sources.push(Source::Trash(trashed.path));
+        }
+    }
+    let targets = select_holding_targets(connection, hash)?;
+    for destination in targets
+        .iter()
+        .filter_map(|t| t.strip_prefix(&format!("{}:", EXPORT)))
+    {
+        let Some(path) = find_exported(Path::new(destination), hash)? else {
+            continue;
+        };
+        let encrypted = config
+            .encryption()
+            .is_some_and(|e| e.encrypted_path(&path).is_file());
+        if path.is_file() || encrypted {
+            sources.push(Source::Export {
+                target: destination.to_owned(),
+                encrypted: !path.is_file(),
+                path,
+            });
+        }
+    }
+    for target in targets
+        .iter()
+        .filter_map(|t| t.strip_prefix(&format!("{}:", PUBLISH)))
+    {
+        if let Some(published) = select_published(connection, target)?.remove(hash) {
+            sources.push(Source::Publish {
+                target: target.to_owned(),
+                remote_id: published.remote_id,
+            });
+        }
+    }
+    Ok(sources)
+}
+
+/// Copies the content from the first source whose copy has the expected hash, to
+/// its library path or into the `to` folder, and returns where it was restored.
+/// The library file is replaced when altered, while an existing file of the `to`
+/// folder is never overwritten.
+fn restore(
+    connection: &Connection,
+    root: &Path,
+    config: &RepositoryConfig,
+    hash: &str,
+    to: Option<&Path>,
+    sources: &[Source],
+) -> Result<PathBuf> {
+    let library_path = select_library_path(connection, hash)?;
+    if to.is_none() && library_path.is_none() {
+        return Err(eyre!(
+            "{} is not in the library, pass --to to choose where to restore it",
+            hash
+        ));
+    }
+    for source in sources {
+        let destination = match to {
+            Some(directory) => directory.join(
+                library_path
+                    .as_deref()
+                    .or(source.path())
+                    .and_then(Path::file_name)
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| hash.to_owned()),
+            ),
+            None => root.join(library_path.as_ref().expect("in the library")),
+        };
+        if destination.exists() {
+            if sha256_digest(&destination)? == hash {
+                println!("{} is already in place", destination.display());
+                return Ok(destination);
+            } else if to.is_some() {
+                return Err(eyre!("{} exists already", destination.display()));
+            }
+        }
+        if let Some(parent) = destination.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut partial = destination.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let fetched = source
+            .fetch(config, &partial)
+            .and_then(|_| Ok(sha256_digest(&partial)?));
+        match fetched {
+            Ok(digest) if digest == hash => {
+                rename(&partial, &destination)?;
+                println!("{} {} from {}", Status::Ok, destination.display(), source);
+                return Ok(destination);
+            }
+            Ok(_) => println!("{} {}", Status::Corrupt, source),
+            Err(e) => println!("{} {}: {:#}", Status::Failed, source, e),
+        }
+        let _ = remove_file(&partial);
+    }
+    if sources.is_empty() {
+        Err(eyre!("No copy of {} is known", hash))
+    } else {
+        Err(eyre!("No copy of {} could be restored", hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{copy, create_dir_all, read, write},
+        path::{Path, PathBuf},
+    };
+
+    use tempfile::TempDir;
+
+    use crate::{
+        config::RepositoryConfig,
+        database::{
+            common::sha256_digest, library_entry::LibraryEntry, sync_state::record_synced,
+            test_utils::new_database_containing_library_entries, trashed::record_trashed,
+        },
+    };
+
+    use super::{restore, sources, Source};
+
+    #[test]
+    fn restore_takes_the_first_intact_copy() {
+        let picture = Path::new("resources/test/kami_neko.jpeg");
+        let hash = sha256_digest(picture).unwrap();
+        let root = TempDir::new().unwrap();
+        let connection = new_database_containing_library_entries(&vec![LibraryEntry::new(
+            hash.clone(),
+            PathBuf::from("2024/a.jpeg"),
+        )]);
+        let trashed = root.path().join(".photo_works/trash/card/a.jpeg");
+        create_dir_all(trashed.parent().unwrap()).unwrap();
+        write(&trashed, "altered").unwrap();
+        record_trashed(
+            &connection,
+            &hash,
+            &trashed.to_string_lossy(),
+            "/card/a.jpeg",
+            false,
+        )
+        .unwrap();
+        let backup = root.path().join("backup");
+        create_dir_all(backup.join("2024")).unwrap();
+        copy(picture, backup.join("2024/a.jpeg")).unwrap();
+        write(
+            backup.join("MANIFEST.sha256"),
+            format!("{}  2024/a.jpeg\n", hash.to_lowercase()),
+        )
+        .unwrap();
+        record_synced(
+            &connection,
+            &format!("export:{}", backup.display()),
+            &[(hash.clone(), hash.clone())],
+        )
+        .unwrap();
+        let config = RepositoryConfig::default();
+
+        let sources = sources(&connection, root.path(), &config, &hash).unwrap();
+        assert_eq!(
+            vec![
+                Source::Trash(trashed),
+                Source::Export {
+                    target: backup.to_string_lossy().to_string(),
+                    path: backup.join("2024/a.jpeg"),
+                    encrypted: false
+                }
+            ],
+            sources
+        );
+
+        let restored = restore(&connection, root.path(), &config, &hash, None, &sources).unwrap();
+
+        assert_eq!(root.path().join("2024/a.jpeg"), restored);
+        assert_eq!(read(picture).unwrap(), read(&restored).unwrap());
+        assert!(!root.path().join("2024/a.jpeg.part").exists());
+    }
+
+    #[test]
+    fn restore_requires_a_folder_outside_of_the_library() {
+        let connection = new_database_containing_library_entries(&vec![]);
+        let sources = vec![Source::Trash(PathBuf::from(
+            "resources/test/kami_neko.jpeg",
+        ))];
+
+        assert!(restore(
+            &connection,
+            Path::new("/repo"),
+            &RepositoryConfig::default(),
+            "AB",
+            None,
+            &sources
+        )
+        .is_err());
+    }
+}
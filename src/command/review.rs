@@ -0,0 +1,83 @@
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::SubApplication,
+    command::tag::{selection_args, Selection},
+    database::review::{add_to_review, remove_from_review, select_review},
+    repository::Repository,
+};
+
+const REVIEW: &str = "review";
+
+pub(crate) struct Review;
+
+impl SubApplication for Review {
+    fn name(&self) -> &'static str {
+        REVIEW
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Keeps a queue of library pictures to review across culling sessions")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                selection_args(
+                    Command::new("add").about("Queues the selected pictures for review."),
+                ),
+                Command::new("list").about("Lists the queued pictures, oldest first."),
+                Command::new("done")
+                    .about("Removes reviewed pictures from the queue.")
+                    .arg(arg!(<HASH>... "The sha256 of the reviewed pictures")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        let selection = match name {
+            "add" => Some(Selection::read(sub_matches)?),
+            _ => None,
+        };
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let mut connection = repository.open_database()?;
+        match (name, selection) {
+            ("add", Some(selection)) => {
+                let hashes = selection.hashes(&connection, repository.root())?;
+                let count = add_to_review(&mut connection, &hashes)?;
+                println!(
+                    "Queued {} of the {} pictures matching the selection",
+                    count,
+                    hashes.len()
+                );
+            }
+            ("list", _) => {
+                let items = select_review(&connection)?;
+                for item in &items {
+                    println!(
+                        "{} {} {}",
+                        item.added_at.format("%Y-%m-%d %H:%M"),
+                        item.sha256,
+                        item.path
+                            .as_ref()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or("(not in the library)".to_string())
+                    );
+                }
+                println!("{} pictures to review", items.len());
+            }
+            ("done", _) => {
+                let hashes = sub_matches
+                    .get_many::<String>("HASH")
+                    .unwrap_or_default()
+                    .map(|h| h.to_uppercase())
+                    .collect::<Vec<String>>();
+                let count = remove_from_review(&mut connection, &hashes)?;
+                println!("Removed {} pictures from the review queue", count);
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::{absolute, PathBuf};
+
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::SubApplication,
+    database::{catalog_entry::CatalogEntry, library_entry::LibraryEntry},
+    media,
+    repository::Repository,
+    rules::{evaluate, RuleAction},
+};
+
+const RULES: &str = "rules";
+
+pub(crate) struct Rules;
+
+impl SubApplication for Rules {
+    fn name(&self) -> &'static str {
+        RULES
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Inspects the import rules of the configuration")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([Command::new("test")
+                .about("Prints the rule applying to a file and where it would be imported")
+                .arg(arg!(<FILE> "The file to evaluate").value_parser(value_parser!(PathBuf)))])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("test", sub_matches)) => {
+                let file = absolute(sub_matches.get_one::<PathBuf>("FILE").expect("required"))?;
+                repository.enter()?;
+                let config = repository.config()?;
+                let backend = config.metadata().backend();
+                let mime = media::detect(&file)?.map(|t| t.mime());
+                let rule = evaluate(config.rules(), &file, mime, backend.as_ref());
+                match rule {
+                    Some(rule) => {
+                        println!("rule: {}", rule.name());
+                        println!("action: {:?}", rule.action());
+                        println!("tags: {}", rule.tags().join(", "));
+                    }
+                    None => println!("No rule matches {}", file.display()),
+                }
+                if rule.is_none_or(|r| r.action() == RuleAction::Import) {
+                    let entry = LibraryEntry::from_catalog_entry(
+                        &CatalogEntry::try_from(&file)?,
+                        config.library_naming(),
+                        backend.as_ref(),
+                        rule.map(|r| r.destination(config.roots()))
+                            .transpose()?
+                            .flatten()
+                            .as_deref(),
+                    )?;
+                    println!("library path: {}", entry.path().display());
+                }
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
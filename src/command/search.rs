@@ -0,0 +1,199 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use chrono::NaiveDate;
+use clap::{arg, value_parser, ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::{parse_altitude, parse_date, parse_date_end, parse_speed, SubApplication},
+    database::library::{foreach_entry, select_aliases, LibraryFilter},
+    repository::Repository,
+};
+
+const SEARCH: &str = "search";
+
+/// Score of each character of the pattern found in a name.
+const MATCH_SCORE: i64 = 16;
+/// Bonus of a character matched right after the previous one.
+const CONSECUTIVE_BONUS: i64 = 8;
+/// Bonus of a character matched at the start of a word, e.g. after `_` or `.`.
+const BOUNDARY_BONUS: i64 = 12;
+/// Penalty of each character skipped between two matches.
+const GAP_PENALTY: i64 = 1;
+
+pub(crate) struct Search;
+
+impl SubApplication for Search {
+    fn name(&self) -> &'static str {
+        SEARCH
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Searches the library pictures by name")
+            .arg(
+                arg!(--name <PATTERN> "Fuzzy matches the library file names and the names the pictures had before import, e.g. dsc0042")
+                    .required(true),
+            )
+            .arg(
+                arg!(--limit <COUNT> "The number of results")
+                    .value_parser(value_parser!(usize))
+                    .default_value("20"),
+            )
+            .arg(
+                arg!(--after <DATE> "Only searches the pictures taken on or after the date, e.g. 2024-07 or last-year")
+                    .value_parser(parse_date),
+            )
+            .arg(
+                arg!(--before <DATE> "Only searches the pictures taken on or before the date, e.g. 2024-07-15 or 6m")
+                    .value_parser(parse_date_end),
+            )
+            .arg(arg!(--"imported-by" <NAME> "Only searches the pictures imported by the person"))
+            .arg(
+                arg!(--"min-altitude" <ALTITUDE> "Only searches the pictures taken at or above the GPS altitude, e.g. 100m or 330ft")
+                    .value_parser(parse_altitude)
+                    .allow_negative_numbers(true),
+            )
+            .arg(
+                arg!(--"max-altitude" <ALTITUDE> "Only searches the pictures taken at or below the GPS altitude")
+                    .value_parser(parse_altitude)
+                    .allow_negative_numbers(true),
+            )
+            .arg(
+                arg!(--"min-speed" <SPEED> "Only searches the pictures taken at or above the GPS speed, e.g. 30km/h or 20mph")
+                    .value_parser(parse_speed),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let pattern = sub_matches.get_one::<String>("name").expect("required");
+        let limit = *sub_matches.get_one::<usize>("limit").expect("default");
+        repository.enter()?;
+        let connection = repository.open_database()?;
+        let filter = LibraryFilter {
+            after: sub_matches.get_one::<NaiveDate>("after").copied(),
+            before: sub_matches.get_one::<NaiveDate>("before").copied(),
+            imported_by: sub_matches.get_one::<String>("imported-by").cloned(),
+            min_altitude: sub_matches.get_one::<f64>("min-altitude").copied(),
+            max_altitude: sub_matches.get_one::<f64>("max-altitude").copied(),
+            min_speed: sub_matches.get_one::<f64>("min-speed").copied(),
+            ..Default::default()
+        };
+        let mut aliases = select_aliases(&connection)?;
+        if filter != LibraryFilter::default() {
+            let mut hashes = HashSet::new();
+            foreach_entry(&connection, &filter, |e| {
+                hashes.insert(e.sha256().to_owned());
+                Ok(())
+            })?;
+            aliases.retain(|(hash, _, _)| hashes.contains(hash));
+        }
+
+        for (score, hash, path, name) in search(aliases, pattern).into_iter().take(limit) {
+            println!("{:>4} {} {} ({})", score, hash, path.display(), name);
+        }
+        Ok(())
+    }
+}
+
+/// The `(score, hash, path, best matching name)` of the contents with a name
+/// matching the pattern, best first.
+fn search(
+    aliases: Vec<(String, PathBuf, Vec<String>)>,
+    pattern: &str,
+) -> Vec<(i64, String, PathBuf, String)> {
+    let mut results = aliases
+        .into_iter()
+        .filter_map(|(hash, path, names)| {
+            let file_name = path.file_name().map(|n| n.to_string_lossy().to_string());
+            file_name
+                .into_iter()
+                .chain(names)
+                .filter_map(|name| fuzzy_score(pattern, &name).map(|score| (score, name)))
+                .max_by_key(|(score, _)| *score)
+                .map(|(score, name)| (score, hash, path, name))
+        })
+        .collect::<Vec<(i64, String, PathBuf, String)>>();
+    results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.2.cmp(&b.2)));
+    results
+}
+
+/// Scores the name when it holds the characters of the pattern in order, ignoring
+/// case, preferring consecutive characters and word starts. Every start of the
+/// first character is tried, the rest is matched greedily.
+fn fuzzy_score(pattern: &str, name: &str) -> Option<i64> {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<char>>();
+    let name = name.to_lowercase().chars().collect::<Vec<char>>();
+    let first = *pattern.first()?;
+    (0..name.len())
+        .filter(|start| name[*start] == first)
+        .filter_map(|start| {
+            let mut score = 0;
+            let mut previous: Option<usize> = None;
+            let mut position = start;
+            for c in &pattern {
+                let index = position + name[position..].iter().position(|n| n == c)?;
+                score += MATCH_SCORE;
+                match previous {
+                    Some(p) if p + 1 == index => score += CONSECUTIVE_BONUS,
+                    Some(p) => score -= (index - p - 1) as i64 * GAP_PENALTY,
+                    None => (),
+                }
+                if index == 0 || !name[index - 1].is_alphanumeric() {
+                    score += BOUNDARY_BONUS;
+                }
+                previous = Some(index);
+                position = index + 1;
+            }
+            Some(score)
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{fuzzy_score, search};
+
+    #[test]
+    fn fuzzy_score_requires_the_characters_in_order() {
+        assert!(fuzzy_score("dsc42", "DSC_0042.JPG").is_some());
+        assert!(fuzzy_score("dsc42", "IMG_0042.JPG").is_none());
+        assert!(fuzzy_score("", "DSC_0042.JPG").is_none());
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_consecutive_characters() {
+        assert!(
+            fuzzy_score("0042", "DSCF0042.JPG").unwrap()
+                > fuzzy_score("0042", "D0SCF0402.JPG").unwrap()
+        );
+    }
+
+    #[test]
+    fn search_ranks_the_contents_by_their_best_name() {
+        let aliases = vec![
+            (
+                "1".to_string(),
+                PathBuf::from("2024/1/1/a.jpg"),
+                vec!["DSCF0042.JPG".to_string()],
+            ),
+            (
+                "2".to_string(),
+                PathBuf::from("2024/1/1/olddsc00402.jpg"),
+                vec![],
+            ),
+            ("3".to_string(), PathBuf::from("2024/1/1/b.jpg"), vec![]),
+        ];
+
+        assert_eq!(
+            vec![("1", "DSCF0042.JPG"), ("2", "olddsc00402.jpg")],
+            search(aliases, "dsc0042")
+                .iter()
+                .map(|(_, hash, _, name)| (hash.as_str(), name.as_str()))
+                .collect::<Vec<(&str, &str)>>()
+        );
+    }
+}
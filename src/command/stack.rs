@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use clap::{ArgMatches, Command};
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    command::tag::{query_arg, query_filter, saved_arg},
+    database::{
+        library::foreach_entry,
+        library_entry::LibraryEntry,
+        stack::{record_stacks, select_stacks, Stack},
+    },
+    media::stack::{find_stacks, Frame},
+    metadata::MetadataBackend,
+    repository::Repository,
+};
+
+const STACK: &str = "stack";
+
+pub(crate) struct Stacks;
+
+impl SubApplication for Stacks {
+    fn name(&self) -> &'static str {
+        STACK
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Groups the HDR brackets and panorama sweeps, which stats count and export copies as one shot")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("detect")
+                    .about("Finds the brackets and panoramas among the library pictures, from their metadata.")
+                    .arg(query_arg())
+                    .arg(saved_arg()),
+                Command::new("list").about("Lists the brackets and panoramas with their frames."),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("detect", sub_matches)) => {
+                repository.enter()?;
+                let _lock = repository.lock()?;
+                let config = repository.config()?;
+                let mut connection = repository.open_database()?;
+                let filter = query_filter(sub_matches, &connection)?.relative_to(repository.root());
+                let mut entries = vec![];
+                foreach_entry(&connection, &filter, |e| {
+                    entries.push(e);
+                    Ok(())
+                })?;
+                let stacked = detect_stacks(
+                    &mut connection,
+                    config.metadata().backend().as_ref(),
+                    &entries,
+                )?;
+                println!("Stacked {} of the {} pictures", stacked, entries.len());
+                Ok(())
+            }
+            Some(("list", _)) => {
+                repository.enter()?;
+                let connection = repository.open_database()?;
+                let mut paths = HashMap::new();
+                foreach_entry(&connection, &Default::default(), |e| {
+                    paths.insert(e.sha256().to_owned(), e.path().to_owned());
+                    Ok(())
+                })?;
+                let stacks = select_stacks(&connection)?;
+                for stack in stacks.values() {
+                    println!("{} of {} frames", stack.kind, stack.hashes.len());
+                    for hash in &stack.hashes {
+                        match paths.get(hash) {
+                            Some(path) => println!("  {}", path.display()),
+                            None => println!("  {} (not in the library)", hash),
+                        }
+                    }
+                }
+                println!("{} stacks", stacks.len());
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
+
+/// Finds the brackets and panoramas among the entries and records them. Returns
+/// the number of pictures newly stacked.
+pub(crate) fn detect_stacks(
+    connection: &mut Connection,
+    backend: &dyn MetadataBackend,
+    entries: &[LibraryEntry],
+) -> Result<usize> {
+    let (hashes, frames): (Vec<&str>, Vec<Frame>) = entries
+        .iter()
+        .filter_map(|e| {
+            let frame = Frame {
+                datetime: backend.original_datetime(e.path()).ok()?,
+                camera: backend.camera_model(e.path()),
+                exposure_bias: backend.exposure_bias(e.path()),
+                direction: backend.telemetry(e.path()).direction,
+            };
+            Some((e.sha256(), frame))
+        })
+        .unzip();
+    let stacks = find_stacks(&frames)
+        .into_iter()
+        .map(|(kind, frames)| Stack {
+            kind,
+            hashes: frames.iter().map(|i| hashes[*i].to_owned()).collect(),
+        })
+        .collect::<Vec<Stack>>();
+    record_stacks(connection, &stacks)
+}
@@ -0,0 +1,235 @@
+use std::cell::RefMut;
+
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    command::tag::{query_arg, query_filter, saved_arg},
+    database::{
+        library::LibraryFilter,
+        stats::{growth, kind_usage, person_usage, root_usage, Growth},
+    },
+    repository::Repository,
+};
+
+const STATS: &str = "stats";
+
+/// The levels of the sparkline, from the smallest to the largest value.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub(crate) struct Stats;
+
+impl SubApplication for Stats {
+    fn name(&self) -> &'static str {
+        STATS
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Reports statistics about the library")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("growth")
+                    .about(
+                        "Reports the files and bytes imported per month, with cumulative totals.",
+                    )
+                    .arg(arg!(--yearly "Groups the imports per year instead of per month"))
+                    .arg(arg!(--csv "Prints the report as CSV"))
+                    .arg(arg!(--sparkline "Prints a sparkline of the bytes imported per period"))
+                    .arg(query_arg())
+                    .arg(saved_arg()),
+                Command::new("roots")
+                    .about("Reports the files and bytes of each library root.")
+                    .arg(query_arg())
+                    .arg(saved_arg()),
+                Command::new("kinds")
+                    .about(
+                        "Reports the files, shots and bytes of photos, videos, animations and screenshots, a shot counting the frames of a bracket or a panorama as one.",
+                    )
+                    .arg(query_arg())
+                    .arg(saved_arg()),
+                Command::new("people")
+                    .about("Reports the files and bytes imported by each person.")
+                    .arg(query_arg())
+                    .arg(saved_arg()),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("growth", sub_matches)) => {
+                let (connection, filter) = enter(sub_matches, repository)?;
+                let rows = growth(&connection, &filter, sub_matches.get_flag("yearly"))?;
+                if sub_matches.get_flag("csv") {
+                    print!("{}", to_csv(&rows));
+                } else {
+                    print!("{}", to_table(&rows));
+                }
+                if sub_matches.get_flag("sparkline") {
+                    println!("{}", sparkline(&rows));
+                }
+                Ok(())
+            }
+            Some(("roots", sub_matches)) => {
+                let (connection, filter) = enter(sub_matches, repository)?;
+                println!("{:<16} {:>8} {:>16}", "Root", "Files", "Bytes");
+                for usage in root_usage(&connection, &filter)? {
+                    println!(
+                        "{:<16} {:>8} {:>16}",
+                        usage.name.as_deref().unwrap_or("(repository)"),
+                        usage.files,
+                        usage.bytes
+                    );
+                }
+                Ok(())
+            }
+            Some(("kinds", sub_matches)) => {
+                let (connection, filter) = enter(sub_matches, repository)?;
+                println!(
+                    "{:<16} {:>8} {:>8} {:>16}",
+                    "Kind", "Files", "Shots", "Bytes"
+                );
+                for usage in kind_usage(&connection, &filter)? {
+                    println!(
+                        "{:<16} {:>8} {:>8} {:>16}",
+                        usage.kind.as_deref().unwrap_or("(unknown)"),
+                        usage.files,
+                        usage.shots,
+                        usage.bytes
+                    );
+                }
+                Ok(())
+            }
+            Some(("people", sub_matches)) => {
+                let (connection, filter) = enter(sub_matches, repository)?;
+                println!("{:<16} {:>8} {:>16}", "Person", "Files", "Bytes");
+                for usage in person_usage(&connection, &filter)? {
+                    println!(
+                        "{:<16} {:>8} {:>16}",
+                        usage.imported_by.as_deref().unwrap_or("(unknown)"),
+                        usage.files,
+                        usage.bytes
+                    );
+                }
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
+
+/// Enters the repository and opens its database, reading the `--query` or
+/// `--saved` filter of the report.
+fn enter<'a>(
+    sub_matches: &ArgMatches,
+    repository: &'a Repository,
+) -> Result<(RefMut<'a, Connection>, LibraryFilter)> {
+    repository.enter()?;
+    let connection = repository.open_database()?;
+    let filter = query_filter(sub_matches, &connection)?.relative_to(repository.root());
+    Ok((connection, filter))
+}
+
+/// The name of a period in the reports.
+fn period_name(row: &Growth) -> &str {
+    row.period.as_deref().unwrap_or("unknown")
+}
+
+/// The rows along with the cumulative files and bytes up to each of them.
+fn cumulated(rows: &[Growth]) -> Vec<(&Growth, i64, i64)> {
+    rows.iter()
+        .scan((0, 0), |(files, bytes), row| {
+            *files += row.files;
+            *bytes += row.bytes;
+            Some((row, *files, *bytes))
+        })
+        .collect()
+}
+
+fn to_table(rows: &[Growth]) -> String {
+    let mut table = format!(
+        "{:<8} {:>8} {:>16} {:>10} {:>16}\n",
+        "Period", "Files", "Bytes", "Total", "Total bytes"
+    );
+    for (row, files, bytes) in cumulated(rows) {
+        table.push_str(&format!(
+            "{:<8} {:>8} {:>16} {:>10} {:>16}\n",
+            period_name(row),
+            row.files,
+            row.bytes,
+            files,
+            bytes
+        ));
+    }
+    table
+}
+
+fn to_csv(rows: &[Growth]) -> String {
+    let mut csv = "period,files,bytes,total_files,total_bytes\n".to_string();
+    for (row, files, bytes) in cumulated(rows) {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            period_name(row),
+            row.files,
+            row.bytes,
+            files,
+            bytes
+        ));
+    }
+    csv
+}
+
+/// One character per period, scaled on the largest number of bytes.
+fn sparkline(rows: &[Growth]) -> String {
+    let max = rows
+        .iter()
+        .map(|r| r.bytes)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    rows.iter()
+        .map(|r| SPARKS[(r.bytes * (SPARKS.len() as i64 - 1) / max) as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::stats::Growth;
+
+    use super::{sparkline, to_csv};
+
+    fn given_a_growth(period: &str, files: i64, bytes: i64) -> Growth {
+        Growth {
+            period: Some(period.to_string()),
+            files,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn to_csv_adds_the_cumulative_totals() {
+        let rows = vec![
+            given_a_growth("2024-01", 2, 10),
+            given_a_growth("2024-02", 3, 30),
+        ];
+
+        assert_eq!(
+            "period,files,bytes,total_files,total_bytes\n2024-01,2,10,2,10\n2024-02,3,30,5,40\n",
+            to_csv(&rows)
+        );
+    }
+
+    #[test]
+    fn sparkline_scales_on_the_largest_period() {
+        let rows = vec![
+            given_a_growth("2024-01", 1, 0),
+            given_a_growth("2024-02", 1, 35),
+            given_a_growth("2024-03", 1, 70),
+        ];
+
+        assert_eq!("▁▄█", sparkline(&rows));
+    }
+}
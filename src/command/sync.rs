@@ -0,0 +1,78 @@
+use clap::{ArgMatches, Command};
+use eyre::Result;
+use indicatif::HumanBytes;
+
+use crate::{clapext::SubApplication, database::sync_state::sync_status, repository::Repository};
+
+const SYNC: &str = "sync";
+
+pub(crate) struct Sync;
+
+impl SubApplication for Sync {
+    fn name(&self) -> &'static str {
+        SYNC
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Reports what the backup and publishing targets hold of the library")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([Command::new("status").about(
+                "Reports the pictures each export and [publish] target is missing, and when it was last synced.",
+            )])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        match sub_matches.subcommand() {
+            Some(("status", _)) => {
+                repository.enter()?;
+                let config = repository.config()?;
+                let connection = repository.open_database()?;
+                let targets = config
+                    .publishers()
+                    .map(|name| format!("publish:{}", name))
+                    .collect::<Vec<String>>();
+                let statuses = sync_status(&connection, &targets)?;
+                if statuses.is_empty() {
+                    println!("No target was synced yet, run export or publish");
+                    return Ok(());
+                }
+                println!(
+                    "{:<32} {:>8} {:>8} {:>12} {:>8} {:<16} Last synced",
+                    "Target", "Synced", "Pending", "Bytes", "Removed", "Oldest pending"
+                );
+                for status in &statuses {
+                    println!(
+                        "{:<32} {:>8} {:>8} {:>12} {:>8} {:<16} {}",
+                        status.target,
+                        status.synced,
+                        status.pending,
+                        HumanBytes(status.pending_bytes as u64).to_string(),
+                        status.removed,
+                        status
+                            .oldest_pending
+                            .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or("-".to_string()),
+                        status
+                            .last_synced_at
+                            .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or("never".to_string())
+                    );
+                }
+                let lagging = statuses.iter().filter(|s| s.pending > 0).count();
+                if lagging == 0 {
+                    println!("Every target holds the whole library");
+                } else {
+                    println!(
+                        "{} of {} targets lag behind the library",
+                        lagging,
+                        statuses.len()
+                    );
+                }
+                Ok(())
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    io::stdin,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Datelike, NaiveDate};
+use clap::{arg, Arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use glob::Pattern;
+use rusqlite::Connection;
+
+use crate::{
+    clapext::{read_targets, stdin_arg, SubApplication, Target},
+    config::RepositoryConfig,
+    database::{
+        library::{foreach_entry, LibraryFilter},
+        saved_query::select_saved_query,
+        tag::{add_tag, remove_tag, replace_tags_under},
+    },
+    repository::Repository,
+};
+
+const TAG: &str = "tag";
+
+/// The tags maintained by the derivation rules, replaced whenever they are derived.
+const DERIVED_PREFIXES: [&str; 2] = ["decade/", "era/"];
+
+pub(crate) struct Tag;
+
+impl SubApplication for Tag {
+    fn name(&self) -> &'static str {
+        TAG
+    }
+
+    fn command(&self) -> Command {
+        let selection = |command: Command| {
+            selection_args(command.arg(arg!(<TAG> "The tag")))
+                .arg(arg!(--"dry-run" "Only reports the number of pictures matching the selection"))
+        };
+        Command::new(self.name())
+            .about("Tags the library pictures matching a query")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                selection(Command::new("add").about("Adds the tag to the selected pictures.")),
+                selection(
+                    Command::new("remove").about("Removes the tag from the selected pictures."),
+                ),
+                Command::new("derive")
+                    .about("Replaces the decade/ and era/ tags with the ones derived from the original dates by the [tags] derive rules.")
+                    .arg(arg!(--"dry-run" "Only reports the number of derived tags")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        if name == "derive" {
+            repository.enter()?;
+            let _lock = repository.lock()?;
+            let rules = derive_rules(&repository.config()?)?;
+            if rules.is_empty() {
+                return Err(eyre!(
+                    "No rule to derive tags, add some to [tags] derive in the configuration"
+                ));
+            }
+            let mut connection = repository.open_database()?;
+            if sub_matches.get_flag("dry-run") {
+                println!(
+                    "Would derive {} tags",
+                    derived_tags(&connection, &rules)?.len()
+                );
+            } else {
+                println!("Derived {} tags", derive_tags(&mut connection, &rules)?);
+            }
+            return Ok(());
+        }
+        let tag = sub_matches.get_one::<String>("TAG").expect("required");
+        let selection = Selection::read(sub_matches)?;
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let mut connection = repository.open_database()?;
+
+        let hashes = selection.hashes(&connection, repository.root())?;
+        if sub_matches.get_flag("dry-run") {
+            println!(
+                "{} pictures match, would {} tag {}",
+                hashes.len(),
+                name,
+                tag
+            );
+            return Ok(());
+        }
+        let count = match name {
+            "add" => add_tag(&mut connection, tag, &hashes)?,
+            "remove" => remove_tag(&mut connection, tag, &hashes)?,
+            _ => unreachable!("Unknown subcommand"),
+        };
+        println!(
+            "Updated {} of the {} pictures matching the selection",
+            count,
+            hashes.len()
+        );
+        Ok(())
+    }
+}
+
+/// Adds the `--query`, `--saved` and `--stdin` arguments selecting library
+/// pictures.
+pub(crate) fn selection_args(command: Command) -> Command {
+    command
+        .arg(query_arg().required_unless_present_any(["saved", "stdin"]))
+        .arg(saved_arg())
+        .arg(stdin_arg())
+}
+
+/// The `--query` argument, selecting the library pictures with the query terms.
+pub(crate) fn query_arg() -> Arg {
+    arg!(--query <QUERY> "Selects the pictures matching terms such as after:2024-07 before:yesterday year:2024 path:2024/07 tag:beach kind:photo by:anna")
+}
+
+/// The `--saved` argument, selecting the library pictures with the terms of a
+/// query saved by query save.
+pub(crate) fn saved_arg() -> Arg {
+    arg!(--saved <NAME> "Selects the pictures matching the query saved under the name, see query list")
+        .conflicts_with("query")
+}
+
+/// The filter of the `--query` argument, or of the query saved under the name of
+/// the `--saved` argument, selecting every picture without them.
+pub(crate) fn query_filter(
+    sub_matches: &ArgMatches,
+    connection: &Connection,
+) -> Result<LibraryFilter> {
+    filter(
+        connection,
+        sub_matches.get_one::<String>("query").map(String::as_str),
+        sub_matches.get_one::<String>("saved").map(String::as_str),
+    )
+}
+
+/// The filter of the query terms, or of the terms of the saved query.
+fn filter(
+    connection: &Connection,
+    query: Option<&str>,
+    saved: Option<&str>,
+) -> Result<LibraryFilter> {
+    let terms = match saved {
+        Some(name) => Some(
+            select_saved_query(connection, name)?
+                .ok_or(eyre!("No query saved under {}, see query list", name))?,
+        ),
+        None => query.map(str::to_owned),
+    };
+    Ok(terms
+        .map(|terms| terms.parse::<LibraryFilter>().map_err(|e| eyre!(e)))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// The library pictures selected by the `--query`, `--saved` and `--stdin`
+/// arguments.
+pub(crate) struct Selection {
+    query: Option<String>,
+    saved: Option<String>,
+    targets: Option<Vec<Target>>,
+}
+
+impl Selection {
+    /// Reads the arguments, and the standard input, before entering the repository.
+    pub(crate) fn read(sub_matches: &ArgMatches) -> Result<Self> {
+        let query = sub_matches.get_one::<String>("query").cloned();
+        if let Some(query) = &query {
+            query.parse::<LibraryFilter>().map_err(|e| eyre!(e))?;
+        }
+        let saved = sub_matches.get_one::<String>("saved").cloned();
+        let targets = if sub_matches.get_flag("stdin") {
+            Some(read_targets(stdin().lock())?)
+        } else {
+            None
+        };
+        Ok(Self {
+            query,
+            saved,
+            targets,
+        })
+    }
+
+    /// The contents of the selected pictures of the repository.
+    pub(crate) fn hashes(self, connection: &Connection, root: &Path) -> Result<Vec<String>> {
+        let filter =
+            filter(connection, self.query.as_deref(), self.saved.as_deref())?.relative_to(root);
+        // Library paths are relative to the repository root.
+        let targets = self
+            .targets
+            .map(|targets| {
+                targets
+                    .into_iter()
+                    .map(|t| {
+                        t.map_path(|p| Ok(p.strip_prefix(root).map(Path::to_path_buf).unwrap_or(p)))
+                    })
+                    .collect::<Result<Vec<Target>>>()
+            })
+            .transpose()?;
+        select_hashes(connection, &filter, targets.as_deref())
+    }
+}
+
+/// A rule deriving a tag from the original date of the pictures.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DeriveRule {
+    /// `decade/1970s`
+    Decade,
+    /// `era/<NAME>` for the pictures taken from the first to the last year.
+    Era(String, i32, i32),
+}
+
+impl FromStr for DeriveRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid derive rule `{}`, expected decade or era:<NAME>:<FROM>-<TO>",
+                rule
+            )
+        };
+        match rule.split(':').collect::<Vec<&str>>()[..] {
+            ["decade"] => Ok(Self::Decade),
+            ["era", name, years] if !name.is_empty() => {
+                let (from, to) = years.split_once('-').ok_or_else(invalid)?;
+                match (from.parse(), to.parse()) {
+                    (Ok(from), Ok(to)) if from <= to => Ok(Self::Era(name.to_owned(), from, to)),
+                    _ => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl DeriveRule {
+    fn tag(&self, date: NaiveDate) -> Option<String> {
+        match self {
+            Self::Decade => Some(format!(
+                "decade/{}s",
+                date.year() - date.year().rem_euclid(10)
+            )),
+            Self::Era(name, from, to) => (*from..=*to)
+                .contains(&date.year())
+                .then(|| format!("era/{}", name)),
+        }
+    }
+}
+
+/// The derivation rules of the repository configuration.
+pub(crate) fn derive_rules(config: &RepositoryConfig) -> Result<Vec<DeriveRule>> {
+    config
+        .tags()
+        .derive()
+        .iter()
+        .map(|rule| DeriveRule::from_str(rule).map_err(|e| eyre!(e)))
+        .collect()
+}
+
+/// The `folder/<NAME>` tags of the folders a picture was cataloged in, keeping
+/// the events the folder names record once the picture is laid out by date.
+#[derive(Debug)]
+pub(crate) struct FolderTags {
+    roots: Vec<PathBuf>,
+    ignored: Vec<Pattern>,
+}
+
+impl FolderTags {
+    /// The tags of the folders between the closest catalog root and the file, or
+    /// of its parent folder outside of the roots, except the ignored folders.
+    pub(crate) fn tags(&self, path: &Path) -> Vec<String> {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let folders = match self
+            .roots
+            .iter()
+            .filter_map(|root| parent.strip_prefix(root).ok())
+            .min_by_key(|relative| relative.components().count())
+        {
+            Some(relative) => relative.iter().collect::<Vec<_>>(),
+            None => parent.file_name().into_iter().collect(),
+        };
+        folders
+            .into_iter()
+            .map(|name| name.to_string_lossy())
+            .filter(|name| !self.ignored.iter().any(|p| p.matches(name)))
+            .map(|name| format!("folder/{}", name))
+            .collect()
+    }
+}
+
+/// The folder tags of the pictures cataloged under the roots, when the `[tags]`
+/// configuration enables them.
+pub(crate) fn folder_tags(
+    config: &RepositoryConfig,
+    roots: &[PathBuf],
+) -> Result<Option<FolderTags>> {
+    if !config.tags().folders() {
+        return Ok(None);
+    }
+    let ignored = config
+        .tags()
+        .ignore_folders()
+        .iter()
+        .map(|p| Pattern::new(p).map_err(|e| eyre!("Invalid folder pattern `{}`: {}", p, e)))
+        .collect::<Result<Vec<Pattern>>>()?;
+    Ok(Some(FolderTags {
+        roots: roots.to_vec(),
+        ignored,
+    }))
+}
+
+/// The `(hash, name)` tags the rules derive from the original dates of the library.
+fn derived_tags(connection: &Connection, rules: &[DeriveRule]) -> Result<Vec<(String, String)>> {
+    let mut tags = vec![];
+    foreach_entry(connection, &LibraryFilter::default(), |entry| {
+        if let Some(date) = entry.original_date() {
+            tags.extend(
+                rules
+                    .iter()
+                    .filter_map(|r| r.tag(date))
+                    .map(|t| (entry.sha256().to_owned(), t)),
+            );
+        }
+        Ok(())
+    })?;
+    Ok(tags)
+}
+
+/// Replaces the derived tags of the library with the ones of the rules. Returns the
+/// number of derived tags.
+pub(crate) fn derive_tags(connection: &mut Connection, rules: &[DeriveRule]) -> Result<usize> {
+    let tags = derived_tags(connection, rules)?;
+    replace_tags_under(connection, &DERIVED_PREFIXES, &tags)
+}
+
+/// The contents of the library entries matching both the filter and the targets.
+fn select_hashes(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    targets: Option<&[Target]>,
+) -> Result<Vec<String>> {
+    let mut hashes = vec![];
+    foreach_entry(connection, filter, |entry| {
+        if targets.is_none_or(|targets| {
+            targets
+                .iter()
+                .any(|t| t.matches(entry.sha256(), entry.path()))
+        }) {
+            hashes.push(entry.sha256().to_owned());
+        }
+        Ok(())
+    })?;
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use chrono::NaiveDate;
+
+    use crate::{
+        clapext::Target,
+        database::{
+            library::LibraryFilter,
+            library_entry::LibraryEntry,
+            saved_query::save_query,
+            test_utils::{new_database, new_database_containing_library_entries},
+        },
+    };
+
+    use super::{derived_tags, filter, select_hashes, DeriveRule, FolderTags};
+
+    #[test]
+    fn filter_reads_the_terms_of_the_saved_query() {
+        let connection = new_database();
+        save_query(&connection, "beach", "tag:beach year:2024").unwrap();
+
+        let beach = filter(&connection, None, Some("beach")).unwrap();
+
+        assert_eq!(Some(2024), beach.year);
+        assert_eq!(vec!["beach".to_string()], beach.tags);
+        assert!(filter(&connection, None, Some("mountain")).is_err());
+        assert_eq!(
+            LibraryFilter::default(),
+            filter(&connection, None, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn derive_rule_parses_the_eras() {
+        assert_eq!(
+            DeriveRule::Era("childhood".to_string(), 1970, 1985),
+            "era:childhood:1970-1985".parse().unwrap()
+        );
+        assert!("era:childhood:1985-1970".parse::<DeriveRule>().is_err());
+        assert!("century".parse::<DeriveRule>().is_err());
+    }
+
+    #[test]
+    fn folder_tags_name_the_folders_below_the_closest_root() {
+        let folder_tags = FolderTags {
+            roots: vec![PathBuf::from("/photos"), PathBuf::from("/photos/old")],
+            ignored: vec![glob::Pattern::new("DCIM").unwrap()],
+        };
+
+        assert_eq!(
+            vec!["folder/2015 - Rome trip", "folder/day 1"],
+            folder_tags.tags(Path::new("/photos/old/2015 - Rome trip/DCIM/day 1/a.jpg"))
+        );
+        assert!(folder_tags.tags(Path::new("/photos/a.jpg")).is_empty());
+        assert_eq!(
+            vec!["folder/party"],
+            folder_tags.tags(Path::new("/card/party/a.jpg"))
+        );
+    }
+
+    #[test]
+    fn derived_tags_apply_every_rule_to_the_dated_pictures() {
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("AB".to_string(), PathBuf::from("1978/a.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(1978, 6, 1)),
+            LibraryEntry::new("CD".to_string(), PathBuf::from("1991/b.jpeg"))
+                .with_original_date(NaiveDate::from_ymd_opt(1991, 6, 1)),
+            LibraryEntry::new("EF".to_string(), PathBuf::from("misc/c.jpeg")),
+        ]);
+        let rules = vec![
+            DeriveRule::Decade,
+            DeriveRule::Era("childhood".to_string(), 1970, 1985),
+        ];
+
+        assert_eq!(
+            vec![
+                ("AB".to_string(), "decade/1970s".to_string()),
+                ("AB".to_string(), "era/childhood".to_string()),
+                ("CD".to_string(), "decade/1990s".to_string()),
+            ],
+            derived_tags(&connection, &rules).unwrap()
+        );
+    }
+
+    #[test]
+    fn select_hashes_matches_the_filter_and_the_targets() {
+        let entries = vec![
+            LibraryEntry::new("AB".to_string(), PathBuf::from("2024/a.jpeg")),
+            LibraryEntry::new("CD".to_string(), PathBuf::from("2024/b.jpeg")),
+            LibraryEntry::new("EF".to_string(), PathBuf::from("2023/c.jpeg")),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let filter: LibraryFilter = "path:2024".parse().unwrap();
+
+        assert_eq!(
+            vec!["AB", "CD"],
+            select_hashes(&connection, &filter, None).unwrap()
+        );
+        assert_eq!(
+            vec!["CD"],
+            select_hashes(
+                &connection,
+                &filter,
+                Some(&[
+                    Target::Hash("CD".to_string()),
+                    Target::Hash("EF".to_string())
+                ])
+            )
+            .unwrap()
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use clap::{ArgMatches, Command};
+use eyre::Result;
+
+use crate::{
+    clapext::SubApplication,
+    database::library::{foreach_entry, LibraryFilter},
+    repository::Repository,
+    thumbnail::{generate, thumbnail_path},
+};
+
+const THUMBNAILS: &str = "thumbnails";
+
+pub(crate) struct Thumbnails;
+
+impl SubApplication for Thumbnails {
+    fn name(&self) -> &'static str {
+        THUMBNAILS
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name()).about(
+            "Generates the missing thumbnails of the library pictures, deferred by the import",
+        )
+    }
+
+    fn handle(&self, _: &ArgMatches, repository: &Repository) -> Result<()> {
+        repository.enter()?;
+        let connection = repository.open_database()?;
+        let directory = repository.thumbnails_path();
+        let mut generated = 0;
+        let mut missing = 0;
+        foreach_entry(&connection, &LibraryFilter::default(), |e| {
+            let thumbnail = thumbnail_path(&directory, e.sha256());
+            if thumbnail.exists() {
+                return Ok(());
+            }
+            if generate(e.path(), &thumbnail)? {
+                generated += 1;
+            } else {
+                missing += 1;
+            }
+            Ok(())
+        })?;
+        println!(
+            "Generated {} thumbnails, {} pictures have neither an embedded thumbnail nor ImageMagick convert to resize them",
+            generated, missing
+        );
+        Ok(())
+    }
+}
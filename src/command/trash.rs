@@ -0,0 +1,205 @@
+use std::{io::stdin, path::PathBuf};
+
+use chrono::NaiveDate;
+use clap::{arg, ArgMatches, Command};
+use eyre::Result;
+use indicatif::HumanBytes;
+
+use crate::{
+    clapext::{confirm_arg, confirm_deletion, confirmation_token, parse_date, SubApplication},
+    database::{
+        common::sha256_digest, protected::protected_hashes, trashed::select_trashed_before,
+    },
+    repository::Repository,
+};
+
+const TRASH: &str = "trash";
+
+pub(crate) struct Trash;
+
+impl SubApplication for Trash {
+    fn name(&self) -> &'static str {
+        TRASH
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Manages the pictures moved to the trash by prune")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("list").about("Reports the number and size of the trashed files."),
+                Command::new("empty")
+                    .about("Deletes the trashed files permanently, except the protected ones, asking for a confirmation token above the [delete] limits.")
+                    .arg(
+                        arg!(--"older-than" <DATE> "Only deletes the files trashed before the date, e.g. 30d or last-month")
+                            .value_parser(parse_date),
+                    )
+                    .arg(confirm_arg()),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        repository.enter()?;
+        let bin = repository.trash()?;
+        match name {
+            "list" => {
+                let (files, bytes) = usage(&bin.files());
+                println!(
+                    "{} files, {} bytes in {}",
+                    files,
+                    bytes,
+                    bin.directory().display()
+                )
+            }
+            "empty" => {
+                let _lock = repository.lock()?;
+                empty_trash(
+                    repository,
+                    sub_matches.get_one::<NaiveDate>("older-than").copied(),
+                    sub_matches.get_flag("i-know-what-i-am-doing"),
+                )?
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
+
+/// Deletes the trashed files permanently, only the ones the trash index records
+/// as trashed before the date when given, and forgets them. The protected files are kept. The deletions above
+/// the `[delete]` limits require a confirmation token unless `confirmed`.
+pub(crate) fn empty_trash(
+    repository: &Repository,
+    older_than: Option<NaiveDate>,
+    confirmed: bool,
+) -> Result<()> {
+    let bin = repository.trash()?;
+    let connection = repository.open_database()?;
+    let protected = protected_hashes(&connection)?;
+    let trashed_before = older_than
+        .map(|date| select_trashed_before(&connection, date))
+        .transpose()?;
+    let mut deleted = vec![];
+    for (path, size) in bin.files() {
+        if trashed_before
+            .as_ref()
+            .is_some_and(|paths| !paths.contains(&path))
+        {
+            continue;
+        }
+        if !protected.is_empty() && protected.contains(&sha256_digest(&path)?) {
+            println!("Keeping protected {}", path.display());
+        } else {
+            deleted.push((path, size));
+        }
+    }
+    let (files, bytes) = usage(&deleted);
+    if files == 0 {
+        println!("Nothing to delete in the trash");
+        return Ok(());
+    }
+    if repository
+        .config()?
+        .delete()
+        .requires_confirmation(files, bytes)
+        && !confirmed
+    {
+        confirm_deletion(files, bytes, &confirmation_token(files), stdin().lock())?;
+    }
+    for (path, _) in &deleted {
+        bin.delete(&connection, path)?;
+    }
+    println!("Deleted {} files, reclaimed {}", files, HumanBytes(bytes));
+    Ok(())
+}
+
+/// The number and total size of the files.
+fn usage(files: &[(PathBuf, u64)]) -> (usize, u64) {
+    (files.len(), files.iter().map(|(_, size)| size).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, write, File},
+        time::{Duration, SystemTime},
+    };
+
+    use tempfile::TempDir;
+
+    use chrono::{Days, Local};
+
+    use crate::{
+        database::{catalog_entry::CatalogEntry, trashed::select_trashed},
+        repository::Repository,
+        trash::TrashBin,
+    };
+
+    use super::{empty_trash, usage};
+
+    #[test]
+    fn usage_sums_the_trashed_files() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join("a/b")).unwrap();
+        write(directory.path().join("a/b/c.jpeg"), "abc").unwrap();
+        write(directory.path().join("d.jpeg"), "d").unwrap();
+
+        assert_eq!(
+            (2, 4),
+            usage(&TrashBin::new(directory.path().to_owned()).files())
+        );
+    }
+
+    #[test]
+    fn empty_trash_deletes_the_files_trashed_before_the_date() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+        let repository = Repository::new(directory.path().to_owned());
+        let bin = repository.trash().unwrap();
+        let connection = repository.open_database().unwrap();
+        for (hash, name) in [("1", "old.jpeg"), ("2", "new.jpeg")] {
+            let path = directory.path().join("card").join(name);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(&path, name).unwrap();
+            bin.move_in(
+                &connection,
+                &CatalogEntry::new(hash.to_string(), path.to_string_lossy().to_string()),
+            )
+            .unwrap();
+        }
+        let trashed = select_trashed(&connection).unwrap();
+        let old = &trashed["1"].path;
+        connection
+            .execute(
+                "UPDATE trashed SET trashed_at = ?1 WHERE hash = '1'",
+                [Local::now().naive_local() - chrono::Duration::days(40)],
+            )
+            .unwrap();
+        // A picture trashed today may keep the old modification time of its content.
+        File::options()
+            .write(true)
+            .open(&trashed["2"].path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(40 * 24 * 3600))
+            .unwrap();
+        drop(connection);
+
+        empty_trash(
+            &repository,
+            Local::now().date_naive().checked_sub_days(Days::new(30)),
+            false,
+        )
+        .unwrap();
+
+        assert!(!old.exists());
+        assert_eq!(
+            vec!["2"],
+            select_trashed(&repository.open_database().unwrap())
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<String>>()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    fs::read_to_string,
+    path::{absolute, Path},
+};
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use walkdir::WalkDir;
+
+use crate::{
+    clapext::SubApplication,
+    database::common::sha256_digest,
+    encryption::Encryption,
+    error::{Error, ErrorCode, Failures},
+    repository::Repository,
+};
+
+const VERIFY_EXPORT: &str = "verify-export";
+
+pub(crate) struct VerifyExport;
+
+impl SubApplication for VerifyExport {
+    fn name(&self) -> &'static str {
+        VERIFY_EXPORT
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Verifies exported files against their manifests, decrypting encrypted ones")
+            .arg(arg!(<PATH> "The export folder to verify"))
+            .arg_required_else_help(true)
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let path = absolute(sub_matches.get_one::<String>("PATH").expect("required"))?;
+        let config = repository.config()?;
+
+        println!("Verifying export {}", path.display());
+
+        println!(
+            "Verified {} files",
+            verify_export(&path, config.encryption())?
+        );
+        Ok(())
+    }
+}
+
+fn verify_export(path: &Path, encryption: Option<&Encryption>) -> Result<usize> {
+    let mut count = 0;
+    let mut errors = vec![];
+    for manifest in WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == "MANIFEST.sha256")
+    {
+        let directory = manifest
+            .path()
+            .parent()
+            .expect("manifest is in a directory");
+        for line in read_to_string(manifest.path())?.lines() {
+            let (expected, file) = line
+                .split_once("  ")
+                .ok_or(eyre!("Invalid manifest line `{}`", line))?;
+            match exported_sha256(&directory.join(file), encryption) {
+                Ok(actual) if actual.eq_ignore_ascii_case(expected) => count += 1,
+                Ok(_) => errors.push(
+                    Error::new(
+                        ErrorCode::HashMismatch,
+                        format!("Failed export check for {}", file),
+                    )
+                    .into(),
+                ),
+                Err(e) => {
+                    let message = format!("{}: {}", file, e);
+                    errors.push(e.wrap_err(message))
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(count)
+    } else {
+        Err(Failures(errors).into())
+    }
+}
+
+/// The digest of the plain content of an exported file, decrypting its encrypted
+/// version when the file itself is missing.
+fn exported_sha256(path: &Path, encryption: Option<&Encryption>) -> Result<String> {
+    if path.exists() {
+        return Ok(sha256_digest(path)?);
+    }
+    match encryption {
+        Some(encryption) if encryption.encrypted_path(path).exists() => {
+            encryption.decrypted_sha256(&encryption.encrypted_path(path))
+        }
+        _ => Err(eyre!("Missing exported file")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{copy, create_dir_all, write},
+        path::PathBuf,
+    };
+
+    use tempfile::TempDir;
+
+    use crate::database::common::sha256_digest;
+
+    use super::verify_export;
+
+    #[test]
+    fn verify_export_checks_the_manifest() {
+        let export = TempDir::new().unwrap();
+        let file: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        create_dir_all(export.path().join("a")).unwrap();
+        copy(&file, export.path().join("a").join("kami.jpeg")).unwrap();
+        write(
+            export.path().join("MANIFEST.sha256"),
+            format!("{}  a/kami.jpeg\n", sha256_digest(&file).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(1, verify_export(export.path(), None).unwrap());
+    }
+
+    #[test]
+    fn verify_export_reports_altered_files() {
+        let export = TempDir::new().unwrap();
+        write(export.path().join("a.jpeg"), "altered").unwrap();
+        write(export.path().join("MANIFEST.sha256"), "abcd  a.jpeg\n").unwrap();
+
+        assert_eq!(
+            "Failed export check for a.jpeg",
+            verify_export(export.path(), None)
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+}
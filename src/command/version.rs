@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use clap::{arg, ArgMatches, Command};
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+
+use crate::{
+    clapext::SubApplication,
+    database::{
+        library::{foreach_entry, LibraryFilter},
+        library_entry::LibraryEntry,
+        version::{link_versions, select_versions, Version},
+    },
+    media::is_raw,
+    repository::Repository,
+};
+
+const VERSION: &str = "version";
+
+pub(crate) struct Versions;
+
+impl SubApplication for Versions {
+    fn name(&self) -> &'static str {
+        VERSION
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.name())
+            .about("Links the edited versions of the library pictures to their masters")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                Command::new("link")
+                    .about("Records a picture as derived from a master.")
+                    .arg(arg!(<MASTER> "The sha256 of the master"))
+                    .arg(arg!(<DERIVED> "The sha256 of the derived picture"))
+                    .arg(arg!(--tool <TOOL> "The editing tool")),
+                Command::new("detect")
+                    .about("Links the pictures taken the same day as a RAW whose name starts with the RAW name, e.g. IMG_0001-Edit.tif to IMG_0001.CR2.")
+                    .arg(arg!(--"dry-run" "Only prints the detected versions")),
+                Command::new("show")
+                    .about("Prints the version chain of a picture, from its master.")
+                    .arg(arg!(<HASH> "The sha256 of a picture of the chain")),
+            ])
+    }
+
+    fn handle(&self, sub_matches: &ArgMatches, repository: &Repository) -> Result<()> {
+        let (name, sub_matches) = sub_matches.subcommand().expect("required");
+        repository.enter()?;
+        let _lock = repository.lock()?;
+        let mut connection = repository.open_database()?;
+        let hash = |name: &str| {
+            sub_matches
+                .get_one::<String>(name)
+                .expect("required")
+                .to_uppercase()
+        };
+        match name {
+            "link" => {
+                let paths = library_paths(&connection)?;
+                let version = Version {
+                    master: hash("MASTER"),
+                    derived: hash("DERIVED"),
+                    tool: sub_matches.get_one::<String>("tool").cloned(),
+                };
+                for hash in [&version.master, &version.derived] {
+                    if !paths.contains_key(hash) {
+                        return Err(eyre!("{} is not in the library", hash));
+                    }
+                }
+                let count = link_versions(&mut connection, &[version])?;
+                println!("Linked {} versions", count);
+            }
+            "detect" => {
+                let mut entries = vec![];
+                foreach_entry(&connection, &LibraryFilter::default(), |e| {
+                    entries.push(e);
+                    Ok(())
+                })?;
+                let versions = detect_versions(&entries);
+                if sub_matches.get_flag("dry-run") {
+                    for version in &versions {
+                        println!("{} -> {}", version.master, version.derived);
+                    }
+                    println!("Would link {} versions", versions.len());
+                } else {
+                    println!(
+                        "Linked {} of the {} detected versions",
+                        link_versions(&mut connection, &versions)?,
+                        versions.len()
+                    );
+                }
+            }
+            "show" => {
+                let paths = library_paths(&connection)?;
+                let chain = version_chain(&select_versions(&connection)?, &hash("HASH"));
+                for (depth, hash) in chain {
+                    println!(
+                        "{}{} {}",
+                        "  ".repeat(depth),
+                        hash,
+                        paths
+                            .get(&hash)
+                            .map(String::as_str)
+                            .unwrap_or("(not in the library)")
+                    );
+                }
+            }
+            _ => unreachable!("Unknown subcommand"),
+        }
+        Ok(())
+    }
+}
+
+/// The library paths by content.
+fn library_paths(connection: &Connection) -> Result<HashMap<String, String>> {
+    let mut paths = HashMap::new();
+    foreach_entry(connection, &LibraryFilter::default(), |e| {
+        paths.insert(e.sha256().to_owned(), e.path().display().to_string());
+        Ok(())
+    })?;
+    Ok(paths)
+}
+
+/// Links each RAW to the other pictures of the same original date whose name
+/// starts with its name, ignoring case.
+fn detect_versions(entries: &[LibraryEntry]) -> Vec<Version> {
+    let stem = |e: &LibraryEntry| {
+        e.path()
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+    let mut versions = vec![];
+    for master in entries.iter().filter(|e| is_raw(e.path())) {
+        let master_stem = stem(master);
+        versions.extend(
+            entries
+                .iter()
+                .filter(|e| {
+                    !is_raw(e.path())
+                        && e.original_date().is_some()
+                        && e.original_date() == master.original_date()
+                        && stem(e).starts_with(&master_stem)
+                })
+                .map(|derived| Version {
+                    master: master.sha256().to_owned(),
+                    derived: derived.sha256().to_owned(),
+                    tool: None,
+                }),
+        );
+    }
+    versions
+}
+
+/// The contents of the version chain of the content, with their depth from the
+/// master at the top of the chain.
+fn version_chain(versions: &[Version], hash: &str) -> Vec<(usize, String)> {
+    let mut master = hash.to_owned();
+    let mut visited = vec![master.clone()];
+    while let Some(version) = versions.iter().find(|v| v.derived == master) {
+        if visited.contains(&version.master) {
+            break;
+        }
+        master = version.master.clone();
+        visited.push(master.clone());
+    }
+    let mut chain = vec![];
+    let mut pending = vec![(0, master)];
+    while let Some((depth, hash)) = pending.pop() {
+        if chain.iter().any(|(_, h)| *h == hash) {
+            continue;
+        }
+        pending.extend(
+            versions
+                .iter()
+                .rev()
+                .filter(|v| v.master == hash)
+                .map(|v| (depth + 1, v.derived.clone())),
+        );
+        chain.push((depth, hash));
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use crate::database::{library_entry::LibraryEntry, version::Version};
+
+    use super::{detect_versions, version_chain};
+
+    fn given_a_version(master: &str, derived: &str) -> Version {
+        Version {
+            master: master.to_string(),
+            derived: derived.to_string(),
+            tool: None,
+        }
+    }
+
+    #[test]
+    fn detect_versions_links_the_edits_of_the_same_day_to_the_raw() {
+        let date = NaiveDate::from_ymd_opt(2024, 7, 1);
+        let entries = vec![
+            LibraryEntry::new("AB".to_string(), PathBuf::from("2024/7/1/IMG_0001.CR2"))
+                .with_original_date(date),
+            LibraryEntry::new(
+                "CD".to_string(),
+                PathBuf::from("2024/7/1/img_0001-edit.tif"),
+            )
+            .with_original_date(date),
+            LibraryEntry::new("EF".to_string(), PathBuf::from("2024/7/2/IMG_0001.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 7, 2)),
+            LibraryEntry::new("GH".to_string(), PathBuf::from("2024/7/1/IMG_0002.jpg"))
+                .with_original_date(date),
+        ];
+
+        assert_eq!(vec![given_a_version("AB", "CD")], detect_versions(&entries));
+    }
+
+    #[test]
+    fn version_chain_starts_from_the_master() {
+        let versions = vec![
+            given_a_version("AB", "CD"),
+            given_a_version("CD", "EF"),
+            given_a_version("AB", "GH"),
+        ];
+
+        assert_eq!(
+            vec![
+                (0, "AB".to_string()),
+                (1, "CD".to_string()),
+                (2, "EF".to_string()),
+                (1, "GH".to_string())
+            ],
+            version_chain(&versions, "EF")
+        );
+    }
+}
@@ -0,0 +1,643 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    clapext::{parse_duration, parse_size},
+    encryption::Encryption,
+    metadata::MetadataConfig,
+    naming::LibraryNaming,
+    privacy::PrivacyConfig,
+    publish::Publisher,
+    rules::ImportRule,
+};
+
+/// The configuration of the user, shared by all repositories.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct UserConfig {
+    /// The person the imports are attributed to in shared repositories.
+    name: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// A named repository the commands can be run against with `--profile`.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct Profile {
+    path: PathBuf,
+}
+
+impl Profile {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The configuration of a repository, stored in `.photo_works/config.toml`.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct RepositoryConfig {
+    /// Arguments inserted after the command name, keyed by command.
+    #[serde(default)]
+    defaults: HashMap<String, Vec<String>>,
+    /// Pipelines of commands run in sequence, keyed by alias name.
+    #[serde(default)]
+    aliases: HashMap<String, Vec<Vec<String>>>,
+    encryption: Option<Encryption>,
+    #[serde(default)]
+    prune: PruneConfig,
+    #[serde(default)]
+    library: LibraryNaming,
+    #[serde(default)]
+    metadata: MetadataConfig,
+    #[serde(default)]
+    tags: TagsConfig,
+    /// Rules routing, tagging, skipping or quarantining the files at import.
+    #[serde(default)]
+    rules: Vec<ImportRule>,
+    /// Named roots of the library the rules import into, keyed by name, relative
+    /// to the repository root or absolute.
+    #[serde(default)]
+    roots: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    delete: DeleteConfig,
+    #[serde(default)]
+    snapshots: SnapshotsConfig,
+    #[serde(default)]
+    inbox: InboxConfig,
+    #[serde(default)]
+    trash: TrashConfig,
+    /// Gallery servers the pictures are published to, keyed by target name.
+    #[serde(default)]
+    publish: BTreeMap<String, Publisher>,
+    #[serde(default)]
+    http: HttpConfig,
+    #[serde(default)]
+    privacy: PrivacyConfig,
+}
+
+/// The `[prune]` table of the repository configuration.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct PruneConfig {
+    /// Rules choosing the copy of duplicates to keep, e.g. `format:raw`, `ext:jpg` or
+    /// `path:<GLOB>`, in order of preference.
+    #[serde(default)]
+    keep: Vec<String>,
+}
+
+/// The `[delete]` table of the repository configuration: permanent deletions
+/// above either limit require a typed confirmation token.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct DeleteConfig {
+    #[serde(default = "default_confirm_files")]
+    confirm_files: usize,
+    /// A size such as `1GB`.
+    #[serde(default = "default_confirm_size", deserialize_with = "size")]
+    confirm_size: u64,
+}
+
+impl Default for DeleteConfig {
+    fn default() -> Self {
+        Self {
+            confirm_files: default_confirm_files(),
+            confirm_size: default_confirm_size(),
+        }
+    }
+}
+
+impl DeleteConfig {
+    /// Returns true when deleting the files requires a confirmation.
+    pub(crate) fn requires_confirmation(&self, files: usize, bytes: u64) -> bool {
+        files > self.confirm_files || bytes > self.confirm_size
+    }
+}
+
+fn default_confirm_files() -> usize {
+    100
+}
+
+fn default_confirm_size() -> u64 {
+    1_000_000_000
+}
+
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    parse_size(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn optional_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    size(deserializer).map(Some)
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    parse_duration(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+/// The `[http]` table of the repository configuration: how the requests to the
+/// servers are retried, capped and proxied.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct HttpConfig {
+    /// The times a request failing on a network error, a timeout, 429 Too Many
+    /// Requests or a 5xx status is sent again.
+    #[serde(default = "default_http_retries")]
+    retries: u32,
+    /// The wait before the first retry, such as `2s`, doubled at each retry.
+    #[serde(default = "default_http_backoff", deserialize_with = "duration")]
+    backoff: Duration,
+    /// The longest wait between two retries.
+    #[serde(default = "default_http_max_backoff", deserialize_with = "duration")]
+    max_backoff: Duration,
+    /// The bandwidth of each transfer, a size per second such as `2MB`.
+    #[serde(default, deserialize_with = "optional_size")]
+    max_rate: Option<u64>,
+    /// The proxy, e.g. `http://proxy:3128`, instead of the one of the
+    /// environment.
+    proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            retries: default_http_retries(),
+            backoff: default_http_backoff(),
+            max_backoff: default_http_max_backoff(),
+            max_rate: None,
+            proxy: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub(crate) fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The wait before the retry, counted from 0.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    pub(crate) fn max_rate(&self) -> Option<u64> {
+        self.max_rate
+    }
+
+    pub(crate) fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+}
+
+fn default_http_retries() -> u32 {
+    3
+}
+
+fn default_http_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_http_max_backoff() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// The `[snapshots]` table of the repository configuration: the database is
+/// copied before each mutating command.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct SnapshotsConfig {
+    /// The number of copies kept, none when 0.
+    #[serde(default = "default_snapshots_keep")]
+    keep: usize,
+}
+
+impl Default for SnapshotsConfig {
+    fn default() -> Self {
+        Self {
+            keep: default_snapshots_keep(),
+        }
+    }
+}
+
+impl SnapshotsConfig {
+    pub(crate) fn keep(&self) -> usize {
+        self.keep
+    }
+}
+
+fn default_snapshots_keep() -> usize {
+    10
+}
+
+/// The `[inbox]` table of the repository configuration: the drop folder of the
+/// contributions staged for review.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct InboxConfig {
+    /// Relative to the repository root or absolute.
+    #[serde(default = "default_inbox_path")]
+    path: PathBuf,
+}
+
+impl Default for InboxConfig {
+    fn default() -> Self {
+        Self {
+            path: default_inbox_path(),
+        }
+    }
+}
+
+impl InboxConfig {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn default_inbox_path() -> PathBuf {
+    PathBuf::from(".inbox")
+}
+
+/// The `[trash]` table of the repository configuration: the directory the pruned
+/// and rejected files are moved to.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct TrashConfig {
+    /// Relative to the repository root or absolute.
+    #[serde(default = "default_trash_path")]
+    path: PathBuf,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            path: default_trash_path(),
+        }
+    }
+}
+
+impl TrashConfig {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn default_trash_path() -> PathBuf {
+    PathBuf::from(".photo_works/trash")
+}
+
+/// The `[tags]` table of the repository configuration.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct TagsConfig {
+    /// Rules deriving tags from the original dates, e.g. `decade` or
+    /// `era:<NAME>:<FROM>-<TO>`, refreshed after each import.
+    #[serde(default)]
+    derive: Vec<String>,
+    /// Tags the imported pictures with the names of the folders they were
+    /// cataloged in, as `folder/<NAME>`.
+    #[serde(default)]
+    folders: bool,
+    /// Globs of the folder names not worth a tag, e.g. `DCIM` or `*CANON`.
+    #[serde(default)]
+    ignore_folders: Vec<String>,
+}
+
+impl TagsConfig {
+    pub(crate) fn derive(&self) -> &[String] {
+        &self.derive
+    }
+
+    pub(crate) fn folders(&self) -> bool {
+        self.folders
+    }
+
+    pub(crate) fn ignore_folders(&self) -> &[String] {
+        &self.ignore_folders
+    }
+}
+
+impl PruneConfig {
+    pub(crate) fn keep(&self) -> &[String] {
+        &self.keep
+    }
+}
+
+impl RepositoryConfig {
+    /// Loads the configuration. A missing file is an empty configuration.
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            parse(&read_to_string(path)?)
+                .wrap_err_with(|| format!("Invalid configuration {}", path.display()))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub(crate) fn defaults(&self, command: &str) -> &[String] {
+        self.defaults.get(command).map_or(&[], |d| d.as_slice())
+    }
+
+    pub(crate) fn alias(&self, name: &str) -> Option<&[Vec<String>]> {
+        self.aliases.get(name).map(|a| a.as_slice())
+    }
+
+    pub(crate) fn encryption(&self) -> Option<&Encryption> {
+        self.encryption.as_ref()
+    }
+
+    pub(crate) fn prune(&self) -> &PruneConfig {
+        &self.prune
+    }
+
+    pub(crate) fn library_naming(&self) -> &LibraryNaming {
+        &self.library
+    }
+
+    pub(crate) fn metadata(&self) -> &MetadataConfig {
+        &self.metadata
+    }
+
+    pub(crate) fn tags(&self) -> &TagsConfig {
+        &self.tags
+    }
+
+    pub(crate) fn rules(&self) -> &[ImportRule] {
+        &self.rules
+    }
+
+    pub(crate) fn roots(&self) -> &BTreeMap<String, PathBuf> {
+        &self.roots
+    }
+
+    pub(crate) fn delete(&self) -> &DeleteConfig {
+        &self.delete
+    }
+
+    pub(crate) fn snapshots(&self) -> &SnapshotsConfig {
+        &self.snapshots
+    }
+
+    pub(crate) fn inbox(&self) -> &InboxConfig {
+        &self.inbox
+    }
+
+    pub(crate) fn trash(&self) -> &TrashConfig {
+        &self.trash
+    }
+
+    pub(crate) fn http(&self) -> &HttpConfig {
+        &self.http
+    }
+
+    pub(crate) fn privacy(&self) -> &PrivacyConfig {
+        &self.privacy
+    }
+
+    pub(crate) fn publisher(&self, target: &str) -> Option<&Publisher> {
+        self.publish.get(target)
+    }
+
+    /// The names of the configured publishing targets.
+    pub(crate) fn publishers(&self) -> impl Iterator<Item = &String> {
+        self.publish.keys()
+    }
+}
+
+impl UserConfig {
+    /// Loads `$XDG_CONFIG_HOME/photo_works/config.toml`, defaulting to `~/.config`.
+    /// A missing file is an empty configuration.
+    pub(crate) fn load() -> Result<Self> {
+        match user_config_path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub(crate) fn load_from(path: &Path) -> Result<Self> {
+        parse(&read_to_string(path)?)
+            .wrap_err_with(|| format!("Invalid configuration {}", path.display()))
+    }
+
+    pub(crate) fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or(eyre!("Unknown profile `{}`", name))
+    }
+}
+
+/// The person running the command, from `PHOTO_WORKS_USER` or the `name` of the
+/// user configuration.
+pub(crate) fn current_user() -> Result<Option<String>> {
+    match env::var("PHOTO_WORKS_USER") {
+        Ok(name) if !name.is_empty() => Ok(Some(name)),
+        _ => Ok(UserConfig::load()?.name),
+    }
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|h| h.join(".config")))
+        .map(|p| p.join("photo_works").join("config.toml"))
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(content: &str) -> Result<T> {
+    Ok(toml::from_str(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use crate::{
+        encryption::Encryption,
+        metadata::{Backend, DateSource},
+        naming::LibraryNaming,
+    };
+
+    use super::{parse, RepositoryConfig, UserConfig};
+
+    #[test]
+    fn parse_reads_the_encryption() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [encryption]
+            tool = "gpg"
+            recipient = "me@example.com"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&Encryption::Gpg {
+                recipient: "me@example.com".to_string()
+            }),
+            config.encryption()
+        );
+    }
+
+    #[test]
+    fn parse_reads_the_http_backoff() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [http]
+            backoff = "2s"
+            max_backoff = "1m"
+            max_rate = "2MB"
+            "#,
+        )
+        .unwrap();
+
+        let http = config.http();
+        assert_eq!(Duration::from_secs(2), http.backoff(0));
+        assert_eq!(Duration::from_secs(16), http.backoff(3));
+        assert_eq!(Duration::from_secs(60), http.backoff(10));
+        assert_eq!(Some(2_000_000), http.max_rate());
+        assert_eq!(3, http.retries());
+    }
+
+    #[test]
+    fn parse_reads_the_defaults_and_aliases() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [defaults]
+            import = ["--priority", "ext:cr2"]
+
+            [aliases]
+            weekly = [["catalog", "/media/card"], ["import", "/media/card"]]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(["--priority", "ext:cr2"], config.defaults("import"));
+        assert!(config.defaults("catalog").is_empty());
+        assert_eq!(
+            vec![
+                vec!["catalog", "/media/card"],
+                vec!["import", "/media/card"]
+            ],
+            config.alias("weekly").unwrap()
+        );
+        assert_eq!(None, config.alias("daily"));
+    }
+
+    #[test]
+    fn parse_reads_the_prune_keep_rules() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [prune]
+            keep = ["format:raw", "ext:jpg"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(["format:raw", "ext:jpg"], config.prune().keep());
+        assert!(RepositoryConfig::default().prune().keep().is_empty());
+    }
+
+    #[test]
+    fn parse_reads_the_folder_tags() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [tags]
+            folders = true
+            ignore_folders = ["DCIM", "*CANON"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.tags().folders());
+        assert_eq!(["DCIM", "*CANON"], config.tags().ignore_folders());
+        assert!(!RepositoryConfig::default().tags().folders());
+    }
+
+    #[test]
+    fn parse_reads_the_library_naming() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [library]
+            max_file_name_length = 143
+            illegal_characters = "<>:|?*"
+            "#,
+        )
+        .unwrap();
+
+        assert_ne!(&LibraryNaming::default(), config.library_naming());
+    }
+
+    #[test]
+    fn parse_reads_the_delete_limits() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [delete]
+            confirm_size = "10MB"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.delete().requires_confirmation(1, 20_000_000));
+        assert!(!config.delete().requires_confirmation(100, 1_000));
+        assert!(RepositoryConfig::default()
+            .delete()
+            .requires_confirmation(101, 0));
+    }
+
+    #[test]
+    fn parse_reads_the_metadata_backend() {
+        let config: RepositoryConfig = parse(
+            r#"
+            [metadata]
+            backend = "exiftool"
+            date_fallbacks = ["filename", "mtime"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(Backend::ExifTool, config.metadata().kind());
+        assert_eq!(
+            vec![DateSource::FileName, DateSource::MTime],
+            config.metadata().date_fallbacks()
+        );
+        assert_eq!(Backend::Auto, RepositoryConfig::default().metadata().kind());
+    }
+
+    #[test]
+    fn parse_reads_the_profiles() {
+        let config: UserConfig = parse(
+            r#"
+            name = "anna"
+
+            [profiles.family]
+            path = "/photos/family"
+
+            [profiles.client]
+            path = "/photos/client"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Path::new("/photos/client"),
+            config.profile("client").unwrap().path()
+        );
+        assert_eq!(Some("anna".to_string()), config.name);
+    }
+
+    #[test]
+    fn parse_accepts_an_empty_configuration() {
+        assert_eq!(UserConfig::default(), parse("").unwrap());
+    }
+
+    #[test]
+    fn profile_fails_when_unknown() {
+        assert_eq!(
+            "Unknown profile `client`",
+            UserConfig::default()
+                .profile("client")
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use eyre::{eyre, Result};
 use rusqlite::{params, Connection, Params, Statement, Transaction};
 
+use crate::error::{Error, Failures};
+
 use super::catalog_entry::CatalogEntry;
 
 pub(crate) fn persist_catalog_entries(
@@ -18,7 +23,8 @@ pub(crate) fn persist_catalog_entries(
 
 fn catalog_insert_all(transaction: &mut Transaction, entries: &Vec<CatalogEntry>) -> Result<usize> {
     let mut count = 0;
-    let mut statement = transaction.prepare("INSERT INTO catalog (hash, path) values (?1, ?2)")?;
+    let mut statement = transaction
+        .prepare("INSERT INTO catalog (hash, path, device, inode) values (?1, ?2, ?3, ?4)")?;
     for entry in entries {
         count += catalog_insert(&mut statement, entry)?;
     }
@@ -27,74 +33,281 @@ fn catalog_insert_all(transaction: &mut Transaction, entries: &Vec<CatalogEntry>
 
 fn catalog_insert(
     statement: &mut Statement,
-    CatalogEntry { sha256, path }: &CatalogEntry,
+    CatalogEntry {
+        sha256,
+        path,
+        file_id,
+    }: &CatalogEntry,
 ) -> Result<usize> {
     statement
-        .execute([sha256, path])
-        .map_err(|e| eyre!("Failed to insert ({}, {}): {}", sha256, path, e))
+        .execute(params![
+            sha256,
+            path,
+            file_id.map(|(device, _)| device),
+            file_id.map(|(_, inode)| inode)
+        ])
+        .map_err(|e| Error::database(e, format!("Failed to insert ({}, {})", sha256, path)))
+        .map_err(Into::into)
+}
+
+/// Persists the entries whose path is not cataloged yet, returning their count.
+pub(crate) fn merge_catalog_entries(
+    connection: &mut Connection,
+    entries: &[CatalogEntry],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "INSERT OR IGNORE INTO catalog (hash, path, device, inode) values (?1, ?2, ?3, ?4)",
+        )?;
+        for CatalogEntry {
+            sha256,
+            path,
+            file_id,
+        } in entries
+        {
+            count += statement.execute(params![
+                sha256,
+                path,
+                file_id.map(|(device, _)| device),
+                file_id.map(|(_, inode)| inode)
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
 }
 
-pub(crate) fn select_from_catalog(
+/// Updates the content of the entries cataloged under their path, which the next
+/// operation is attributed.
+pub(crate) fn update_catalog_entries(
+    connection: &mut Connection,
+    entries: &[CatalogEntry],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "UPDATE catalog SET hash = ?1, device = ?3, inode = ?4, operation = NULL WHERE path = ?2",
+        )?;
+        for CatalogEntry {
+            sha256,
+            path,
+            file_id,
+        } in entries
+        {
+            count += statement.execute(params![
+                sha256,
+                path,
+                file_id.map(|(device, _)| device),
+                file_id.map(|(_, inode)| inode)
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The hashes of the cataloged paths starting with the prefix, keyed by path.
+pub(crate) fn select_cataloged_hashes(
     connection: &Connection,
     path_prefix: &str,
-) -> Result<Vec<CatalogEntry>> {
-    let mut statement = connection.prepare("SELECT catalog.hash, catalog.path FROM catalog LEFT JOIN library ON catalog.hash = library.hash WHERE catalog.path like ?1 AND library.hash IS NULL GROUP BY catalog.hash")?;
-    query(&mut statement, params!([path_prefix, "%"].join("")))
+) -> Result<HashMap<String, String>> {
+    let mut statement = connection.prepare("SELECT path, hash FROM catalog WHERE path LIKE ?1")?;
+    let hashes = statement
+        .query_map([format!("{}%", path_prefix)], |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })?
+        .collect::<Result<HashMap<String, String>, rusqlite::Error>>()?;
+    Ok(hashes)
+}
+
+/// The `(size, modified, hash)` recorded with the cataloged paths starting with
+/// the prefix, keyed by path.
+pub(crate) fn select_catalog_stats(
+    connection: &Connection,
+    path_prefix: &str,
+) -> Result<HashMap<String, (u64, i64, String)>> {
+    let mut statement = connection.prepare(
+        "SELECT path, size, modified, hash FROM catalog WHERE path LIKE ?1 AND size IS NOT NULL AND modified IS NOT NULL",
+    )?;
+    let stats = statement
+        .query_map([format!("{}%", path_prefix)], |r| {
+            Ok((r.get(0)?, (r.get(1)?, r.get(2)?, r.get(3)?)))
+        })?
+        .collect::<Result<HashMap<String, (u64, i64, String)>, rusqlite::Error>>()?;
+    Ok(stats)
+}
+
+/// Records the size and modification time, in nanoseconds since the epoch, of the
+/// cataloged paths.
+pub(crate) fn save_catalog_stats(
+    connection: &mut Connection,
+    stats: &[(String, u64, i64)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement =
+            transaction.prepare("UPDATE catalog SET size = ?2, modified = ?3 WHERE path = ?1")?;
+        for (path, size, modified) in stats {
+            count += statement.execute(params![path, size, modified])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Records a cataloged directory as a root managed by the repository.
+pub(crate) fn persist_catalog_root(connection: &Connection, path: &Path) -> Result<usize> {
+    connection
+        .execute(
+            "INSERT OR IGNORE INTO catalog_root (path) values (?1)",
+            [path.to_string_lossy()],
+        )
+        .map_err(|e| Error::database(e, format!("Failed to insert root {}", path.display())))
+        .map_err(Into::into)
 }
 
-pub(crate) fn find_duplicates(
+pub(crate) fn select_catalog_roots(connection: &Connection) -> Result<Vec<PathBuf>> {
+    let mut statement = connection.prepare("SELECT path FROM catalog_root")?;
+    let result = statement
+        .query_map([], |r| r.get::<_, String>(0).map(PathBuf::from))?
+        .collect::<Result<Vec<PathBuf>, rusqlite::Error>>()?;
+    Ok(result)
+}
+
+/// The entries of the path prefix whose content is neither in the library nor
+/// with a problem, in hash order. Each content is selected once, as its first
+/// cataloged copy that `accepts`, so that the copies filtered out never hide
+/// the content.
+pub(crate) fn select_from_catalog<F>(
     connection: &Connection,
-) -> Result<HashMap<String, Vec<CatalogEntry>>> {
-    let mut statement = connection.prepare("SELECT catalog.hash, catalog.path FROM catalog WHERE catalog.hash in (SELECT hash FROM catalog GROUP BY hash HAVING COUNT(path) > 1 ORDER BY hash)")?;
-    Ok(query(&mut statement, [])?
-        .into_iter()
-        .fold(HashMap::new(), |mut map, e| {
-            map.entry(e.sha256.to_string())
-                .or_insert_with(|| Vec::new())
-                .push(e);
-            map
-        }))
+    path_prefix: &str,
+    mut accepts: F,
+) -> Result<Vec<CatalogEntry>>
+where
+    F: FnMut(&CatalogEntry) -> bool,
+{
+    let mut statement = connection.prepare("SELECT catalog.hash, catalog.path, catalog.device, catalog.inode FROM catalog LEFT JOIN library ON catalog.hash = library.hash WHERE catalog.path like ?1 AND library.hash IS NULL AND catalog.path NOT IN (SELECT path FROM problem) ORDER BY catalog.hash, catalog.rowid")?;
+    let mut entries: Vec<CatalogEntry> = vec![];
+    for entry in query(&mut statement, params!([path_prefix, "%"].join("")))? {
+        if entries.last().is_none_or(|e| e.sha256 != entry.sha256) && accepts(&entry) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Calls `f` with the copies of each content cataloged more than once, in hash
+/// order. The rows are grouped by SQL and read one content at a time, so that
+/// only the copies of the current content are held in memory. The paths reaching
+/// the same physical file are one copy, listed once under its first path, so that
+/// pruning a duplicate never removes the only copy. Returns the number of
+/// duplicated contents.
+pub(crate) fn foreach_duplicate<F>(connection: &Connection, mut f: F) -> Result<usize>
+where
+    F: FnMut(&str, Vec<CatalogEntry>) -> Result<()>,
+{
+    let mut statement = connection.prepare(
+        "SELECT hash, path, device, inode FROM (
+            SELECT hash, path, device, inode, rowid AS id, COUNT(*) OVER (PARTITION BY hash) AS paths
+            FROM catalog
+        ) WHERE paths > 1 ORDER BY hash, id",
+    )?;
+    let mut rows = statement.query([])?;
+    let mut count = 0;
+    let mut copies: Vec<CatalogEntry> = vec![];
+    let mut flush = |copies: Vec<CatalogEntry>| {
+        if copies.len() > 1 {
+            count += 1;
+            f(&copies[0].sha256.clone(), copies)
+        } else {
+            Ok(())
+        }
+    };
+    while let Some(row) = rows.next()? {
+        let entry = CatalogEntry::try_from(row)?;
+        if copies.first().is_some_and(|c| c.sha256 != entry.sha256) {
+            flush(std::mem::take(&mut copies))?;
+        }
+        if !copies.iter().any(|c| c.is_same_file(&entry)) {
+            copies.push(entry);
+        }
+    }
+    flush(copies)?;
+    Ok(count)
 }
 
 pub(crate) fn foreach_entry<F>(connection: &Connection, mut f: F) -> Result<usize>
 where
     F: FnMut(CatalogEntry) -> Result<()>,
 {
-    let mut statement = connection.prepare("SELECT catalog.hash, catalog.path FROM catalog")?;
+    let mut statement = connection
+        .prepare("SELECT catalog.hash, catalog.path, catalog.device, catalog.inode FROM catalog")?;
     let entries = query(&mut statement, [])?;
     let mut count = 0;
     let mut errors = vec![];
     for entry in entries {
         match f(entry) {
             Ok(()) => count += 1,
-            Err(e) => errors.push(e.to_string()),
+            Err(e) => errors.push(e),
         }
     }
-    if errors.len() == 0 {
+    if errors.is_empty() {
         Ok(count)
     } else {
-        Err(eyre!(errors.join("\n")))
+        Err(Failures(errors).into())
     }
 }
 
+/// The cataloged copies of the content.
+pub(crate) fn select_catalog_copies(
+    connection: &Connection,
+    hash: &str,
+) -> Result<Vec<CatalogEntry>> {
+    let mut statement = connection
+        .prepare("SELECT hash, path, device, inode FROM catalog WHERE hash = ?1 ORDER BY path")?;
+    query(&mut statement, [hash])
+}
+
 pub(crate) fn find_already_imported(connection: &Connection) -> Result<Vec<CatalogEntry>> {
     let mut statement = connection.prepare(
-        "SELECT catalog.hash, catalog.path FROM catalog, library WHERE catalog.hash = library.hash",
+        "SELECT catalog.hash, catalog.path, catalog.device, catalog.inode FROM catalog, library WHERE catalog.hash = library.hash",
     )?;
     query(&mut statement, [])
 }
 
+/// The cataloged copies of the library contents, with the library path of their
+/// content, ordered by content.
+pub(crate) fn find_imported_copies(
+    connection: &Connection,
+) -> Result<Vec<(CatalogEntry, PathBuf)>> {
+    let mut statement = connection.prepare(
+        "SELECT catalog.hash, catalog.path, catalog.device, catalog.inode, library.path FROM catalog, library WHERE catalog.hash = library.hash ORDER BY catalog.hash, catalog.path",
+    )?;
+    let copies = statement
+        .query_map([], |r| {
+            Ok((
+                CatalogEntry::try_from(r)?,
+                PathBuf::from(r.get::<_, String>(4)?),
+            ))
+        })?
+        .collect::<Result<Vec<(CatalogEntry, PathBuf)>, rusqlite::Error>>()?;
+    Ok(copies)
+}
+
 fn query<T: Params>(statement: &mut Statement, params: T) -> Result<Vec<CatalogEntry>> {
     let result = statement
         .query_map(params, |r| CatalogEntry::try_from(r))?
-        .into_iter()
         .collect::<Result<Vec<CatalogEntry>, rusqlite::Error>>()?;
     Ok(result)
 }
 
 pub(crate) fn remove_catalog_entries(
     connection: &mut Connection,
-    entries: &Vec<CatalogEntry>,
+    entries: &[CatalogEntry],
 ) -> Result<usize> {
     let mut transaction = connection.transaction()?;
     let count = catalog_remove_all(&mut transaction, entries)?;
@@ -114,11 +327,11 @@ fn catalog_remove_all(transaction: &mut Transaction, entries: &[CatalogEntry]) -
 
 fn catalog_remove(
     statement: &mut Statement,
-    CatalogEntry { sha256, path }: &CatalogEntry,
+    CatalogEntry { sha256, path, .. }: &CatalogEntry,
 ) -> Result<usize> {
     let count = statement
         .execute([sha256, path])
-        .map_err(|e| eyre!("Failed to remove ({}, {}): {}", sha256, path, e))?;
+        .map_err(|e| Error::database(e, format!("Failed to remove ({}, {})", sha256, path)))?;
     if count == 0 {
         Err(eyre!("Failed to remove ({}, {})", sha256, path))
     } else {
@@ -128,7 +341,7 @@ fn catalog_remove(
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{collections::HashMap, path::PathBuf};
 
     use eyre::eyre;
     use rusqlite::params;
@@ -139,14 +352,15 @@ mod tests {
         },
         library::persist_library_entries,
         library_entry::LibraryEntry,
+        problem::{persist_problems, Problem},
         test_utils::{
             catalog_contains, new_connection, new_database, new_database_containing_catalog_entries,
         },
     };
 
     use super::{
-        find_already_imported, find_duplicates, persist_catalog_entries, select_from_catalog,
-        CatalogEntry,
+        find_already_imported, foreach_duplicate, persist_catalog_entries, persist_catalog_root,
+        select_catalog_roots, select_from_catalog, CatalogEntry,
     };
 
     fn some_entries() -> Vec<CatalogEntry> {
@@ -154,10 +368,12 @@ mod tests {
             CatalogEntry {
                 sha256: "1".to_string(),
                 path: "a/a".to_string(),
+                file_id: None,
             },
             CatalogEntry {
                 sha256: "2".to_string(),
                 path: "a/b".to_string(),
+                file_id: None,
             },
         ]
     }
@@ -221,6 +437,7 @@ mod tests {
             &CatalogEntry {
                 sha256: "2".to_string(),
                 path: "b".to_string(),
+                file_id: None,
             }
         ));
         assert_eq!(
@@ -229,13 +446,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn persist_catalog_root_records_the_root_once() {
+        let connection = new_database();
+
+        persist_catalog_root(&connection, &PathBuf::from("/a")).unwrap();
+        persist_catalog_root(&connection, &PathBuf::from("/a")).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("/a")],
+            select_catalog_roots(&connection).unwrap()
+        );
+    }
+
     #[test]
     fn select_from_catalog_returns_the_catalog_entries() {
         let entries = some_entries();
         let mut connection = new_database();
 
         persist_catalog_entries(&mut connection, &entries).unwrap();
-        let results = select_from_catalog(&connection, "a").unwrap();
+        let results = select_from_catalog(&connection, "a", |_| true).unwrap();
         assert_eq!(entries, results);
     }
 
@@ -247,6 +477,7 @@ mod tests {
         let expected_results = vec![CatalogEntry {
             sha256: "2".to_string(),
             path: "a/b".to_string(),
+            file_id: None,
         }];
         persist_catalog_entries(&mut connection, &entries).unwrap();
         persist_library_entries(
@@ -254,36 +485,76 @@ mod tests {
             &vec![LibraryEntry::new("1".to_string(), PathBuf::from("a/aa"))],
         )
         .unwrap();
-        let results = select_from_catalog(&connection, "a").unwrap();
+        let results = select_from_catalog(&connection, "a", |_| true).unwrap();
         assert_eq!(expected_results, results);
     }
 
+    #[test]
+    fn select_from_catalog_does_not_return_entries_with_problems() {
+        let entries = some_entries();
+        let mut connection = new_database_containing_catalog_entries(&entries);
+        persist_problems(
+            &mut connection,
+            &[Problem::new(
+                "1".to_string(),
+                "a/a".to_string(),
+                "Truncated".to_string(),
+            )],
+        )
+        .unwrap();
+
+        let results = select_from_catalog(&connection, "a", |_| true).unwrap();
+        assert_eq!(
+            vec![CatalogEntry::new("2".to_string(), "a/b".to_string())],
+            results
+        );
+    }
+
     #[test]
     fn select_from_catalog_does_not_return_duplicate_hash_entries() {
         let entries = vec![
             CatalogEntry {
                 sha256: "1".to_string(),
                 path: "a/a".to_string(),
+                file_id: None,
             },
             CatalogEntry {
                 sha256: "1".to_string(),
                 path: "a/b".to_string(),
+                file_id: None,
             },
         ];
         let mut connection = new_database();
 
         persist_catalog_entries(&mut connection, &entries).unwrap();
-        let results = select_from_catalog(&connection, "a").unwrap();
+        let results = select_from_catalog(&connection, "a", |_| true).unwrap();
         assert_eq!(1, results.len());
     }
 
+    #[test]
+    fn select_from_catalog_selects_the_first_accepted_copy() {
+        let connection = new_database_containing_catalog_entries(&vec![
+            CatalogEntry::new("1".to_string(), "a/rejects/a".to_string()),
+            CatalogEntry::new("1".to_string(), "a/keep/a".to_string()),
+            CatalogEntry::new("2".to_string(), "a/rejects/b".to_string()),
+        ]);
+
+        let results =
+            select_from_catalog(&connection, "a", |e| !e.path.contains("rejects")).unwrap();
+
+        assert_eq!(
+            vec![CatalogEntry::new("1".to_string(), "a/keep/a".to_string())],
+            results
+        );
+    }
+
     #[test]
     fn foreach_entry_applies_the_function_to_each_entry() {
         let entries = some_entries();
 
-        let mut connection = new_database_containing_catalog_entries(&entries);
+        let connection = new_database_containing_catalog_entries(&entries);
         let mut entry_hashes = vec![];
-        let iterated_count = foreach_entry(&mut connection, |e| {
+        let iterated_count = foreach_entry(&connection, |e| {
             entry_hashes.push(e.sha256().to_owned());
             Ok(())
         })
@@ -326,7 +597,10 @@ mod tests {
     fn query_returns_error_when_row_cannot_be_converted_to_entry() {
         let connection = new_connection();
         connection
-            .execute("create table catalog (hash integer, path string)", [])
+            .execute(
+                "create table catalog (hash integer, path string, device integer, inode integer)",
+                [],
+            )
             .unwrap();
         connection
             .execute(
@@ -336,7 +610,9 @@ mod tests {
             .unwrap();
 
         let mut statement = connection
-            .prepare("SELECT catalog.hash, catalog.path FROM catalog")
+            .prepare(
+                "SELECT catalog.hash, catalog.path, catalog.device, catalog.inode FROM catalog",
+            )
             .unwrap();
 
         assert_eq!(
@@ -383,8 +659,19 @@ mod tests {
         assert!(find_already_imported(&connection).unwrap().is_empty());
     }
 
+    fn duplicates(connection: &rusqlite::Connection) -> HashMap<String, Vec<CatalogEntry>> {
+        let mut duplicates = HashMap::new();
+        let count = foreach_duplicate(connection, |hash, copies| {
+            duplicates.insert(hash.to_owned(), copies);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, duplicates.len());
+        duplicates
+    }
+
     #[test]
-    fn find_duplicates_retuns_duplicates_grouped_together() {
+    fn foreach_duplicate_groups_the_copies_of_a_content() {
         let mut entries = some_entries();
         entries.push(CatalogEntry::new(
             entries[0].sha256.to_owned(),
@@ -393,7 +680,7 @@ mod tests {
 
         let connection = new_database_containing_catalog_entries(&entries);
 
-        let dupes = find_duplicates(&connection).unwrap();
+        let dupes = duplicates(&connection);
 
         assert_eq!(1, dupes.len());
         assert_eq!(2, dupes.get(&entries[0].sha256).unwrap().len());
@@ -401,6 +688,39 @@ mod tests {
         assert_eq!(entries[2], dupes.get(&entries[0].sha256).unwrap()[1]);
     }
 
+    #[test]
+    fn foreach_duplicate_counts_the_paths_of_the_same_file_once() {
+        let entries = vec![
+            CatalogEntry::new("1".to_string(), "/mnt/a/1.jpg".to_string())
+                .with_file_id(Some((1, 7))),
+            CatalogEntry::new("1".to_string(), "/media/a/1.jpg".to_string())
+                .with_file_id(Some((1, 7))),
+            CatalogEntry::new("2".to_string(), "/mnt/a/2.jpg".to_string())
+                .with_file_id(Some((1, 8))),
+            CatalogEntry::new("2".to_string(), "/media/a/2.jpg".to_string())
+                .with_file_id(Some((1, 8))),
+            CatalogEntry::new("2".to_string(), "/backup/2.jpg".to_string())
+                .with_file_id(Some((2, 8))),
+        ];
+
+        let connection = new_database_containing_catalog_entries(&entries);
+
+        let dupes = duplicates(&connection);
+
+        assert_eq!(1, dupes.len());
+        assert_eq!(
+            vec![&entries[2], &entries[4]],
+            dupes["2"].iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            2,
+            connection
+                .query_row("SELECT count(*) FROM v_duplicates", [], |r| r
+                    .get::<_, i64>(0))
+                .unwrap()
+        );
+    }
+
     #[test]
     fn catalog_remove_all_returns_count_of_deletions() {
         let entries = some_entries();
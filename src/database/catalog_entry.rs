@@ -1,18 +1,32 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use rusqlite::Row;
 
+use crate::remote;
+
 use super::common::sha256_digest;
 
 #[derive(PartialEq, Debug)]
 pub(crate) struct CatalogEntry {
     pub(super) sha256: String,
     pub(super) path: String,
+    /// The device and inode of the file, shared by the paths reaching the same
+    /// physical file through bind mounts or hard links.
+    pub(super) file_id: Option<(u64, u64)>,
 }
 
 impl CatalogEntry {
     pub(crate) fn new(sha256: String, path: String) -> Self {
-        Self { sha256, path }
+        Self {
+            sha256,
+            path,
+            file_id: None,
+        }
+    }
+
+    pub(crate) fn with_file_id(mut self, file_id: Option<(u64, u64)>) -> Self {
+        self.file_id = file_id;
+        self
     }
 
     pub(crate) fn sha256(&self) -> &str {
@@ -22,26 +36,56 @@ impl CatalogEntry {
     pub(crate) fn path(&self) -> PathBuf {
         PathBuf::from(&self.path)
     }
+
+    /// True for entries cataloged on a remote host, which can't be read locally.
+    pub(crate) fn is_remote(&self) -> bool {
+        remote::is_remote(&self.path)
+    }
+
+    /// True when both entries are known to be the same physical file.
+    pub(crate) fn is_same_file(&self, other: &CatalogEntry) -> bool {
+        self.file_id.is_some() && self.file_id == other.file_id
+    }
 }
 
 impl TryFrom<&PathBuf> for CatalogEntry {
     type Error = eyre::Report;
 
     fn try_from(path_buf: &PathBuf) -> std::prelude::v1::Result<Self, Self::Error> {
-        let sha256 = sha256_digest(&path_buf)?;
-        let path = path_buf.canonicalize()?;
-        Ok(Self::new(sha256, path.to_string_lossy().to_string()))
+        Self::hashed(path_buf, sha256_digest(path_buf)?)
+    }
+}
+
+impl CatalogEntry {
+    /// The entry of a file whose content hash is already known.
+    pub(crate) fn hashed(path: &Path, sha256: String) -> eyre::Result<Self> {
+        let path = path.canonicalize()?;
+        Ok(Self::new(sha256, path.to_string_lossy().to_string()).with_file_id(file_id(&path)?))
     }
 }
 
+#[cfg(unix)]
+fn file_id(path: &Path) -> eyre::Result<Option<(u64, u64)>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = path.metadata()?;
+    Ok(Some((metadata.dev(), metadata.ino())))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> eyre::Result<Option<(u64, u64)>> {
+    path.metadata()?;
+    Ok(None)
+}
+
+/// Reads the `hash, path, device, inode` columns of a catalog row.
 impl TryFrom<&Row<'_>> for CatalogEntry {
     type Error = rusqlite::Error;
 
     fn try_from(row: &Row<'_>) -> std::prelude::v1::Result<Self, Self::Error> {
-        Ok(CatalogEntry {
-            sha256: row.get(0)?,
-            path: row.get(1)?,
-        })
+        let device: Option<u64> = row.get(2)?;
+        let inode: Option<u64> = row.get(3)?;
+        Ok(CatalogEntry::new(row.get(0)?, row.get(1)?).with_file_id(device.zip(inode)))
     }
 }
 
@@ -54,7 +98,7 @@ mod tests {
     #[test]
     fn try_from_creates_catalog_entry_from_path() {
         let path: PathBuf = ["Cargo.toml"].iter().collect();
-        let CatalogEntry { sha256, path } = CatalogEntry::try_from(&path).unwrap();
+        let CatalogEntry { sha256, path, .. } = CatalogEntry::try_from(&path).unwrap();
         assert_eq!(sha256, sha256_digest(&PathBuf::from(path)).unwrap());
     }
 
@@ -63,4 +107,23 @@ mod tests {
         let path: PathBuf = ["/tmp"].iter().collect();
         assert!(CatalogEntry::try_from(&path).is_err());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn try_from_recognizes_hard_links_as_the_same_file() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let original = directory.path().join("original.txt");
+        std::fs::write(&original, "content").unwrap();
+        std::fs::hard_link(&original, directory.path().join("link.txt")).unwrap();
+        std::fs::write(directory.path().join("copy.txt"), "content").unwrap();
+
+        let original = CatalogEntry::try_from(&original).unwrap();
+        let link = CatalogEntry::try_from(&directory.path().join("link.txt")).unwrap();
+        let copy = CatalogEntry::try_from(&directory.path().join("copy.txt")).unwrap();
+
+        assert!(original.is_same_file(&link));
+        assert!(!original.is_same_file(&copy));
+        assert!(!CatalogEntry::new("1".to_string(), "a".to_string())
+            .is_same_file(&CatalogEntry::new("1".to_string(), "b".to_string())));
+    }
 }
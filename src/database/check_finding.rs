@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A picture a check found missing, corrupt or unreadable.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Finding {
+    /// The check, library or catalog.
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) hash: String,
+    /// The status label, e.g. CORRUPT.
+    pub(crate) status: String,
+    pub(crate) found_at: NaiveDateTime,
+}
+
+/// Records the finding as soon as it is found, so that an interrupted check still
+/// leaves its findings.
+pub(crate) fn record_finding(
+    connection: &Connection,
+    name: &str,
+    path: &str,
+    hash: &str,
+    status: &str,
+) -> Result<usize> {
+    Ok(connection.execute(
+        "INSERT OR REPLACE INTO check_finding (name, path, hash, status, found_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![name, path, hash, status, Local::now().naive_local()],
+    )?)
+}
+
+/// Forgets the findings of the check, before a new pass.
+pub(crate) fn clear_findings(connection: &Connection, name: &str) -> Result<usize> {
+    Ok(connection.execute("DELETE FROM check_finding WHERE name = ?1", [name])?)
+}
+
+/// The findings of the check, or of every check, in path order.
+pub(crate) fn select_findings(connection: &Connection, name: Option<&str>) -> Result<Vec<Finding>> {
+    let mut statement = connection.prepare(
+        "SELECT name, path, hash, status, found_at FROM check_finding WHERE ?1 IS NULL OR name = ?1 ORDER BY name, path",
+    )?;
+    let findings = statement
+        .query_map([name], |row| {
+            Ok(Finding {
+                name: row.get(0)?,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+                hash: row.get(2)?,
+                status: row.get(3)?,
+                found_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<Finding>, rusqlite::Error>>()?;
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{clear_findings, record_finding, select_findings};
+
+    #[test]
+    fn select_findings_lists_the_findings_of_the_check() {
+        let connection = new_database();
+        record_finding(&connection, "library", "2024/b.jpeg", "AB", "CORRUPT").unwrap();
+        record_finding(&connection, "library", "2024/a.jpeg", "CD", "MISSING").unwrap();
+        record_finding(&connection, "catalog", "/card/c.jpeg", "EF", "MISSING").unwrap();
+
+        let findings = select_findings(&connection, Some("library")).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("2024/a.jpeg"), PathBuf::from("2024/b.jpeg")],
+            findings.into_iter().map(|f| f.path).collect::<Vec<_>>()
+        );
+        assert_eq!(3, select_findings(&connection, None).unwrap().len());
+
+        clear_findings(&connection, "library").unwrap();
+
+        assert_eq!(1, select_findings(&connection, None).unwrap().len());
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::Local;
+use eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// The path the time-boxed check resumes after, none to start over.
+pub(crate) fn select_position(connection: &Connection, name: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row(
+            "SELECT position FROM check_progress WHERE name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Records the path the check reached, or clears it at the end of a complete pass.
+pub(crate) fn save_position(
+    connection: &Connection,
+    name: &str,
+    position: Option<&str>,
+) -> Result<()> {
+    match position {
+        Some(position) => connection.execute(
+            "INSERT OR REPLACE INTO check_progress (name, position, updated_at) VALUES (?1, ?2, ?3)",
+            params![name, position, Local::now().naive_local()],
+        )?,
+        None => connection.execute("DELETE FROM check_progress WHERE name = ?1", [name])?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{save_position, select_position};
+
+    #[test]
+    fn save_position_replaces_then_clears_the_position() {
+        let connection = new_database();
+
+        save_position(&connection, "library", Some("2024/a.jpeg")).unwrap();
+        save_position(&connection, "library", Some("2024/b.jpeg")).unwrap();
+        assert_eq!(
+            Some("2024/b.jpeg".to_string()),
+            select_position(&connection, "library").unwrap()
+        );
+
+        save_position(&connection, "library", None).unwrap();
+        assert_eq!(None, select_position(&connection, "library").unwrap());
+    }
+}
@@ -1,16 +1,20 @@
 use std::{
-    fs::File,
     io::{BufReader, Read},
-    path::PathBuf,
+    path::Path,
 };
 
 use sha2::{Digest, Sha256};
 
+use crate::fsext::source::open_read_only;
+
 /// calculates sha256 digest as lowercase hex string
-pub(crate) fn sha256_digest(path: &PathBuf) -> Result<String, std::io::Error> {
-    let input = File::open(path)?;
-    let mut reader = BufReader::new(input);
+pub(crate) fn sha256_digest(path: &Path) -> Result<String, std::io::Error> {
+    let input = open_read_only(path)?;
+    sha256_digest_reader(BufReader::new(input))
+}
 
+/// calculates sha256 digest of the content of a reader
+pub(crate) fn sha256_digest_reader(mut reader: impl Read) -> Result<String, std::io::Error> {
     let digest = {
         let mut hasher = Sha256::new();
         let mut buffer = [0; 1024];
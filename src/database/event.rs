@@ -0,0 +1,67 @@
+use chrono::NaiveDate;
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A named range of capture dates, both included.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct NamedEvent {
+    pub(crate) start: NaiveDate,
+    pub(crate) end: NaiveDate,
+    pub(crate) name: String,
+}
+
+impl NamedEvent {
+    pub(crate) fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+/// Names the range of dates, renaming the event of the same range.
+pub(crate) fn name_event(connection: &Connection, event: &NamedEvent) -> Result<usize> {
+    Ok(connection.execute(
+        "INSERT OR REPLACE INTO event (start_date, end_date, name) VALUES (?1, ?2, ?3)",
+        params![event.start, event.end, event.name],
+    )?)
+}
+
+/// The named events, oldest first.
+pub(crate) fn select_events(connection: &Connection) -> Result<Vec<NamedEvent>> {
+    let mut statement = connection
+        .prepare("SELECT start_date, end_date, name FROM event ORDER BY start_date, end_date")?;
+    let events = statement
+        .query_map([], |row| {
+            Ok(NamedEvent {
+                start: row.get(0)?,
+                end: row.get(1)?,
+                name: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<NamedEvent>, rusqlite::Error>>()?;
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{name_event, select_events, NamedEvent};
+
+    #[test]
+    fn name_event_renames_the_same_range() {
+        let connection = new_database();
+        let event = |name: &str| NamedEvent {
+            start: NaiveDate::from_ymd_opt(2024, 7, 12).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 7, 14).unwrap(),
+            name: name.to_string(),
+        };
+        name_event(&connection, &event("Lake")).unwrap();
+        name_event(&connection, &event("Lake trip")).unwrap();
+
+        assert_eq!(
+            vec![event("Lake trip")],
+            select_events(&connection).unwrap()
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, path::Path, time::UNIX_EPOCH};
+
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// The device, inode, size and modification time of a file, which match as long
+/// as the file is renamed or moved on its device but not rewritten.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub(crate) struct Fingerprint {
+    device: u64,
+    inode: u64,
+    size: u64,
+    /// Nanoseconds since the epoch.
+    modified: i64,
+}
+
+impl Fingerprint {
+    /// The fingerprint of the file, none where inodes are not available.
+    #[cfg(unix)]
+    pub(crate) fn read(path: &Path) -> Result<Option<Fingerprint>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = path.metadata()?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(Some(Fingerprint {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.len(),
+            modified: i64::try_from(modified.as_nanos())?,
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn read(path: &Path) -> Result<Option<Fingerprint>> {
+        path.metadata()?;
+        Ok(None)
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Nanoseconds since the epoch.
+    pub(crate) fn modified(&self) -> i64 {
+        self.modified
+    }
+}
+
+/// The hashes of the fingerprinted files.
+pub(crate) fn select_fingerprints(connection: &Connection) -> Result<HashMap<Fingerprint, String>> {
+    let mut statement =
+        connection.prepare("SELECT device, inode, size, modified, hash FROM fingerprint")?;
+    let fingerprints = statement
+        .query_map([], |row| {
+            Ok((
+                Fingerprint {
+                    device: row.get(0)?,
+                    inode: row.get(1)?,
+                    size: row.get(2)?,
+                    modified: row.get(3)?,
+                },
+                row.get(4)?,
+            ))
+        })?
+        .collect::<Result<HashMap<Fingerprint, String>, rusqlite::Error>>()?;
+    Ok(fingerprints)
+}
+
+/// Records the hashes of the files, replacing the previous fingerprint of their
+/// inodes.
+pub(crate) fn save_fingerprints(
+    connection: &mut Connection,
+    fingerprints: &[(Fingerprint, String)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "INSERT OR REPLACE INTO fingerprint (device, inode, size, modified, hash) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (fingerprint, hash) in fingerprints {
+            count += statement.execute(params![
+                fingerprint.device,
+                fingerprint.inode,
+                fingerprint.size,
+                fingerprint.modified,
+                hash
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::TempDir;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{save_fingerprints, select_fingerprints, Fingerprint};
+
+    #[cfg(unix)]
+    #[test]
+    fn fingerprint_survives_a_rename_but_not_a_rewrite() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("a.jpeg");
+        write(&path, "content").unwrap();
+        let fingerprint = Fingerprint::read(&path).unwrap();
+        let mut connection = new_database();
+        save_fingerprints(&mut connection, &[(fingerprint.unwrap(), "1".to_string())]).unwrap();
+
+        let renamed = directory.path().join("b.jpeg");
+        std::fs::rename(&path, &renamed).unwrap();
+        let fingerprints = select_fingerprints(&connection).unwrap();
+        assert_eq!(
+            Some(&"1".to_string()),
+            fingerprints.get(&Fingerprint::read(&renamed).unwrap().unwrap())
+        );
+
+        write(&renamed, "other content").unwrap();
+        assert_eq!(
+            None,
+            fingerprints.get(&Fingerprint::read(&renamed).unwrap().unwrap())
+        );
+    }
+}
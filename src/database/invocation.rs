@@ -0,0 +1,64 @@
+use chrono::Local;
+use eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Records the arguments the command was run with, after its name, replacing
+/// those of its previous run.
+pub(crate) fn record_invocation(
+    connection: &Connection,
+    command: &str,
+    arguments: &[String],
+) -> Result<usize> {
+    Ok(connection.execute(
+        "INSERT OR REPLACE INTO invocation (command, arguments, invoked_at) VALUES (?1, ?2, ?3)",
+        params![
+            command,
+            serde_json::to_string(arguments)?,
+            Local::now().naive_local()
+        ],
+    )?)
+}
+
+/// The arguments of the last run of the command.
+pub(crate) fn select_invocation(
+    connection: &Connection,
+    command: &str,
+) -> Result<Option<Vec<String>>> {
+    let arguments = connection
+        .query_row(
+            "SELECT arguments FROM invocation WHERE command = ?1",
+            [command],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    Ok(arguments
+        .map(|arguments| serde_json::from_str(&arguments))
+        .transpose()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{record_invocation, select_invocation};
+
+    #[test]
+    fn select_invocation_returns_the_last_arguments() {
+        let connection = new_database();
+        let arguments = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        record_invocation(&connection, "check", &arguments(&["catalog"])).unwrap();
+        record_invocation(
+            &connection,
+            "check",
+            &arguments(&["library", "--year", "2024"]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(arguments(&["library", "--year", "2024"])),
+            select_invocation(&connection, "check").unwrap()
+        );
+        assert_eq!(None, select_invocation(&connection, "import").unwrap());
+    }
+}
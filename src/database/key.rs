@@ -0,0 +1,43 @@
+use eyre::Result;
+use rusqlite::Connection;
+
+use crate::secrets;
+
+/// Name of the secret holding the key of the database.
+const DATABASE_SECRET: &str = "database";
+
+/// Keys the connection when a key is available for the database. A database
+/// without key stays in plain SQLite format.
+pub(crate) fn unlock(connection: &Connection) -> Result<()> {
+    if let Some(key) = secrets::secret(DATABASE_SECRET)? {
+        connection.pragma_update(None, "key", key)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use rusqlite::Connection;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    use crate::database::open;
+
+    #[test]
+    #[serial]
+    fn open_encrypts_the_database_with_the_key() {
+        let directory = TempDir::new().unwrap();
+        let db = directory.path().join("photo_works.db");
+        env::set_var("PHOTO_WORKS_SECRET_DATABASE", "secret");
+        open(&db).unwrap();
+        env::remove_var("PHOTO_WORKS_SECRET_DATABASE");
+
+        let connection = Connection::open(&db).unwrap();
+        assert!(connection
+            .query_row("SELECT count(*) FROM sqlite_master", [], |r| r
+                .get::<_, i64>(0))
+            .is_err());
+    }
+}
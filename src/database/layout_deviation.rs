@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use chrono::Local;
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// Replaces the recorded deviations with the contents and the layouts their
+/// directory matches, all or none of them.
+pub(crate) fn record_deviations(
+    connection: &mut Connection,
+    deviations: &[(String, String)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        transaction.execute("DELETE FROM layout_deviation", [])?;
+        let mut statement = transaction.prepare(
+            "INSERT INTO layout_deviation (hash, layout, detected_at) VALUES (?1, ?2, ?3)",
+        )?;
+        let now = Local::now().naive_local();
+        for (hash, layout) in deviations {
+            count += statement.execute(params![hash, layout, now])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The hashes of the contents outside the detected layout.
+pub(crate) fn select_deviations(connection: &Connection) -> Result<HashSet<String>> {
+    let mut statement = connection.prepare("SELECT hash FROM layout_deviation")?;
+    let hashes = statement
+        .query_map([], |r| r.get(0))?
+        .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+    Ok(hashes)
+}
+
+/// Forgets the deviation of a content once it is normalized.
+pub(crate) fn remove_deviation(connection: &Connection, hash: &str) -> Result<usize> {
+    Ok(connection.execute(
+        "DELETE FROM layout_deviation WHERE hash = ?1",
+        params![hash],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database_containing_library_entries;
+
+    use super::{record_deviations, remove_deviation, select_deviations};
+
+    #[test]
+    fn record_deviations_replaces_the_previous_detection() {
+        let mut connection = new_database_containing_library_entries(&vec![]);
+        record_deviations(&mut connection, &[("AB".to_string(), "event".to_string())]).unwrap();
+        record_deviations(
+            &mut connection,
+            &[
+                ("CD".to_string(), "none".to_string()),
+                ("EF".to_string(), "unpadded".to_string()),
+            ],
+        )
+        .unwrap();
+        remove_deviation(&connection, "EF").unwrap();
+
+        assert_eq!(
+            ["CD".to_string()]
+                .into_iter()
+                .collect::<std::collections::HashSet<_>>(),
+            select_deviations(&connection).unwrap()
+        );
+    }
+}
@@ -1,5 +1,18 @@
-use eyre::{eyre, Result};
-use rusqlite::{Connection, Statement, Transaction};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Statement, Transaction};
+
+use crate::{
+    clapext::{parse_altitude, parse_date, parse_date_end, parse_speed},
+    error::{Error, Failures},
+    media::MediaKind,
+};
 
 use super::library_entry::LibraryEntry;
 
@@ -16,77 +29,434 @@ pub(crate) fn persist_library_entries(
 
 fn library_insert_all(transaction: &mut Transaction, entries: &Vec<LibraryEntry>) -> Result<usize> {
     let mut count = 0;
-    let mut statement = transaction.prepare("INSERT INTO library (hash, path) values (?1, ?2)")?;
+    let mut statement = transaction.prepare(
+        "INSERT INTO library (hash, path, mime_type, original_date, size, original_name, imported_at, kind, imported_by, date_source, altitude, direction, speed) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+    )?;
+    let imported_at = Local::now().naive_local();
     for entry in entries {
-        count += library_insert(&mut statement, entry)?;
+        count += library_insert(&mut statement, entry, imported_at)?;
     }
     Ok(count)
 }
 
 fn library_insert(
     statement: &mut Statement,
-    LibraryEntry { sha256, path }: &LibraryEntry,
+    LibraryEntry {
+        sha256,
+        path,
+        mime_type,
+        original_date,
+        size,
+        original_name,
+        kind,
+        imported_by,
+        date_source,
+        telemetry,
+    }: &LibraryEntry,
+    imported_at: NaiveDateTime,
 ) -> Result<usize> {
     statement
-        .execute([sha256, &path.to_string_lossy().to_string()])
-        .map_err(|e| eyre!("Failed to insert ({}, {}): {}", sha256, path.display(), e))
+        .execute(params![
+            sha256,
+            path.to_string_lossy(),
+            mime_type,
+            original_date,
+            size,
+            original_name,
+            imported_at,
+            kind,
+            imported_by,
+            date_source,
+            telemetry.altitude,
+            telemetry.direction,
+            telemetry.speed
+        ])
+        .map_err(|e| {
+            Error::database(
+                e,
+                format!("Failed to insert ({}, {})", sha256, path.display()),
+            )
+        })
+        .map_err(Into::into)
+}
+
+/// The hashes of the library contents.
+pub(crate) fn select_library_hashes(connection: &Connection) -> Result<HashSet<String>> {
+    let mut statement = connection.prepare("SELECT hash FROM library")?;
+    let hashes = statement
+        .query_map([], |r| r.get(0))?
+        .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+    Ok(hashes)
+}
+
+/// The library path of the content, relative to the repository root.
+pub(crate) fn select_library_path(connection: &Connection, hash: &str) -> Result<Option<PathBuf>> {
+    Ok(connection
+        .query_row("SELECT path FROM library WHERE hash = ?1", [hash], |row| {
+            row.get::<_, String>(0).map(PathBuf::from)
+        })
+        .optional()?)
+}
+
+/// Moves the library entries to new paths, all or none of them.
+pub(crate) fn update_library_paths(
+    connection: &mut Connection,
+    moves: &[(String, PathBuf)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare("UPDATE library SET path = ?2 WHERE hash = ?1")?;
+        for (sha256, path) in moves {
+            count += statement.execute(params![sha256, path.to_string_lossy()])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Moves the library entries and their sidecars from the directories to the new
+/// ones, e.g. `2023/5/8/a.jpg` to `2023/05/08/a.jpg`, all or none of them. Returns
+/// the number of library entries moved.
+pub(crate) fn move_library_directories(
+    connection: &mut Connection,
+    moves: &[(PathBuf, PathBuf)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut library = transaction.prepare(
+            "UPDATE library SET path = ?2 || substr(path, length(?1) + 1) WHERE substr(path, 1, length(?1)) = ?1",
+        )?;
+        let mut sidecars = transaction.prepare(
+            "UPDATE sidecar SET path = ?2 || substr(path, length(?1) + 1) WHERE substr(path, 1, length(?1)) = ?1",
+        )?;
+        for (from, to) in moves {
+            let from = format!("{}/", from.to_string_lossy());
+            let to = format!("{}/", to.to_string_lossy());
+            count += library.execute([&from, &to])?;
+            sidecars.execute([&from, &to])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Moves the `(hash, path)` library entries and sidecars to their new paths, all
+/// or none of them. Returns the number of library entries moved.
+pub(crate) fn move_library_entries(
+    connection: &mut Connection,
+    entries: &[(String, PathBuf)],
+    sidecars: &[(String, PathBuf)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut library = transaction.prepare("UPDATE library SET path = ?2 WHERE hash = ?1")?;
+        for (sha256, path) in entries {
+            count += library.execute(params![sha256, path.to_string_lossy()])?;
+        }
+        let mut sidecar = transaction.prepare("UPDATE sidecar SET path = ?2 WHERE hash = ?1")?;
+        for (sha256, path) in sidecars {
+            sidecar.execute(params![sha256, path.to_string_lossy()])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Updates the metadata of the library entries, all or none of them.
+pub(crate) fn update_library_metadata(
+    connection: &mut Connection,
+    entries: &[LibraryEntry],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "UPDATE library SET mime_type = ?2, original_date = ?3, size = ?4, kind = ?5, date_source = COALESCE(?6, date_source), altitude = COALESCE(?7, altitude), direction = COALESCE(?8, direction), speed = COALESCE(?9, speed) WHERE hash = ?1",
+        )?;
+        for entry in entries {
+            count += statement.execute(params![
+                entry.sha256,
+                entry.mime_type,
+                entry.original_date,
+                entry.size,
+                entry.kind,
+                entry.date_source,
+                entry.telemetry.altitude,
+                entry.telemetry.direction,
+                entry.telemetry.speed
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Removes the library entries, all or none of them, leaving their files alone.
+pub(crate) fn remove_library_entries(
+    connection: &mut Connection,
+    hashes: &[String],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare("DELETE FROM library WHERE hash = ?1")?;
+        for hash in hashes {
+            count += statement.execute([hash])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Restricts the library entries processed by `foreach_entry`.
+#[derive(Default, Debug, PartialEq)]
+pub(crate) struct LibraryFilter {
+    /// The year of the original date, or of the path for entries without one.
+    pub(crate) year: Option<i32>,
+    pub(crate) path_prefix: Option<String>,
+    /// The first original date included.
+    pub(crate) after: Option<NaiveDate>,
+    /// The last original date included.
+    pub(crate) before: Option<NaiveDate>,
+    /// Tags the entries must all have.
+    pub(crate) tags: Vec<String>,
+    pub(crate) kind: Option<MediaKind>,
+    /// The person who imported the entries.
+    pub(crate) imported_by: Option<String>,
+    /// The lowest GPS altitude included, in meters.
+    pub(crate) min_altitude: Option<f64>,
+    /// The highest GPS altitude included, in meters.
+    pub(crate) max_altitude: Option<f64>,
+    /// The lowest GPS speed included, in km/h.
+    pub(crate) min_speed: Option<f64>,
 }
 
-pub(crate) fn foreach_entry<F>(connection: &Connection, mut f: F) -> Result<usize>
+impl LibraryFilter {
+    /// Makes an absolute or `~` path prefix relative to the repository root, as the
+    /// library paths are.
+    pub(crate) fn relative_to(mut self, root: &Path) -> Self {
+        if let Some(prefix) = &self.path_prefix {
+            let prefix = match prefix.strip_prefix("~/") {
+                Some(rest) => home::home_dir()
+                    .map(|home| home.join(rest))
+                    .unwrap_or_else(|| prefix.into()),
+                None => prefix.into(),
+            };
+            if let Ok(relative) = prefix.strip_prefix(root) {
+                self.path_prefix = Some(relative.to_string_lossy().to_string());
+            }
+        }
+        self
+    }
+
+    /// The library table restricted to the filtered entries, as a subquery named
+    /// `library`, with the values of its parameters.
+    pub(crate) fn library_table(&self) -> (String, Vec<String>) {
+        let (where_clause, values) = self.where_clause();
+        (
+            format!("(SELECT * FROM library{}) AS library", where_clause),
+            values,
+        )
+    }
+
+    fn where_clause(&self) -> (String, Vec<String>) {
+        let mut conditions = vec![];
+        let mut values = vec![];
+        if let Some(year) = self.year {
+            conditions.push(
+                "(original_date LIKE ? OR (original_date IS NULL AND path LIKE ?))".to_string(),
+            );
+            values.push(format!("{:04}-%", year));
+            values.push(format!("{}/%", year));
+        }
+        if let Some(path_prefix) = &self.path_prefix {
+            conditions.push("path LIKE ?".to_string());
+            values.push(format!("{}%", path_prefix));
+        }
+        if let Some(after) = self.after {
+            conditions.push("original_date >= ?".to_string());
+            values.push(after.to_string());
+        }
+        if let Some(before) = self.before {
+            conditions.push("original_date <= ?".to_string());
+            values.push(before.to_string());
+        }
+        if let Some(kind) = self.kind {
+            conditions.push("kind = ?".to_string());
+            values.push(kind.to_string());
+        }
+        if let Some(imported_by) = &self.imported_by {
+            conditions.push("imported_by = ?".to_string());
+            values.push(imported_by.clone());
+        }
+        if let Some(min_altitude) = self.min_altitude {
+            conditions.push("altitude >= ?".to_string());
+            values.push(min_altitude.to_string());
+        }
+        if let Some(max_altitude) = self.max_altitude {
+            conditions.push("altitude <= ?".to_string());
+            values.push(max_altitude.to_string());
+        }
+        if let Some(min_speed) = self.min_speed {
+            conditions.push("speed >= ?".to_string());
+            values.push(min_speed.to_string());
+        }
+        for tag in &self.tags {
+            conditions.push("hash IN (SELECT hash FROM tag WHERE name = ?)".to_string());
+            values.push(tag.clone());
+        }
+        if conditions.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!(" WHERE {}", conditions.join(" AND ")), values)
+        }
+    }
+}
+
+/// Parses a query such as `after:2024-07-01 before:2024-07-15 path:2024 tag:beach kind:photo by:anna`,
+/// the dates in any of the forms of `parse_date` such as `after:last-week`, and
+/// the aerial shots with `min-altitude:100m`, `max-altitude:` and `min-speed:30km/h`.
+impl FromStr for LibraryFilter {
+    type Err = String;
+
+    fn from_str(query: &str) -> std::result::Result<Self, Self::Err> {
+        let mut filter = Self::default();
+        for term in query.split_whitespace() {
+            match term.split_once(':') {
+                Some(("after", value)) => filter.after = Some(parse_date(value)?),
+                Some(("before", value)) => filter.before = Some(parse_date_end(value)?),
+                Some(("year", value)) => {
+                    filter.year = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid year `{}`", value))?,
+                    )
+                }
+                Some(("path", value)) => filter.path_prefix = Some(value.to_string()),
+                Some(("tag", value)) => filter.tags.push(value.to_string()),
+                Some(("kind", value)) => filter.kind = Some(value.parse()?),
+                Some(("by", value)) => filter.imported_by = Some(value.to_string()),
+                Some(("min-altitude", value)) => filter.min_altitude = Some(parse_altitude(value)?),
+                Some(("max-altitude", value)) => filter.max_altitude = Some(parse_altitude(value)?),
+                Some(("min-speed", value)) => filter.min_speed = Some(parse_speed(value)?),
+                _ => {
+                    return Err(format!(
+                        "Invalid query term `{}`, expected after:, before:, year:, path:, tag:, kind:, by:, min-altitude:, max-altitude: or min-speed:",
+                        term
+                    ))
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// The `(hash, path, names)` of the library contents, with the other names they
+/// were known under: their name before import and the names of their cataloged
+/// copies.
+pub(crate) fn select_aliases(
+    connection: &Connection,
+) -> Result<Vec<(String, PathBuf, Vec<String>)>> {
+    let mut statement = connection.prepare(
+        "SELECT library.hash, library.path, library.original_name, catalog.path FROM library LEFT JOIN catalog ON catalog.hash = library.hash ORDER BY library.path",
+    )?;
+    let rows = statement
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    let mut aliases: Vec<(String, PathBuf, Vec<String>)> = vec![];
+    for (hash, path, original_name, cataloged) in rows {
+        if aliases.last().is_none_or(|(h, _, _)| *h != hash) {
+            aliases.push((hash, PathBuf::from(path), vec![]));
+        }
+        let names = &mut aliases.last_mut().expect("pushed").2;
+        let cataloged_name = cataloged.as_deref().and_then(|p| {
+            Path::new(p)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+        });
+        for name in original_name.into_iter().chain(cataloged_name) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+pub(crate) fn foreach_entry<F>(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    mut f: F,
+) -> Result<usize>
 where
     F: FnMut(LibraryEntry) -> Result<()>,
 {
-    let mut query = connection.prepare("SELECT hash, path FROM library")?;
-    let entries = query.query_map([], |r| {
-        Ok(LibraryEntry::new(
-            r.get::<_, String>(0)?,
-            r.get::<_, String>(1)?.into(),
-        ))
-    })?;
+    let (where_clause, values) = filter.where_clause();
+    let mut query = connection.prepare(&format!(
+        "SELECT hash, path, mime_type, original_date, size, kind, altitude, direction, speed FROM library{}",
+        where_clause
+    ))?;
+    let entries = query.query_map(params_from_iter(values), |r| LibraryEntry::try_from(r))?;
     let mut count = 0;
     let mut errors = vec![];
     for entry_mapping_result in entries {
         match entry_mapping_result {
             Ok(entry) => match f(entry) {
                 Ok(()) => count += 1,
-                Err(e) => errors.push(e.to_string()),
+                Err(e) => errors.push(e),
             },
-            Err(e) => errors.push(e.to_string()),
+            Err(e) => errors.push(e.into()),
         }
     }
-    if errors.len() == 0 {
+    if errors.is_empty() {
         Ok(count)
     } else {
-        Err(eyre!(errors.join("\n")))
+        Err(Failures(errors).into())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::NaiveDate;
     use eyre::eyre;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     use rusqlite::params;
 
-    use crate::database::{
-        library::{library_insert_all, LibraryEntry},
-        test_utils::{
-            library_contains, new_connection, new_database, new_database_containing_library_entries,
+    use crate::{
+        database::{
+            catalog_entry::CatalogEntry,
+            library::{library_insert_all, LibraryEntry},
+            test_utils::{
+                library_contains, new_connection, new_database,
+                new_database_containing_catalog_and_library_entries,
+                new_database_containing_library_entries,
+            },
         },
+        media::MediaKind,
+        metadata::Telemetry,
     };
 
-    use super::{foreach_entry, persist_library_entries};
+    use super::{
+        foreach_entry, move_library_directories, move_library_entries, persist_library_entries,
+        remove_library_entries, select_aliases, update_library_paths, LibraryFilter,
+    };
 
     fn some_entries() -> Vec<LibraryEntry> {
         vec![
-            LibraryEntry {
-                sha256: "1".to_string(),
-                path: PathBuf::from("a"),
-            },
-            LibraryEntry {
-                sha256: "2".to_string(),
-                path: PathBuf::from("b"),
-            },
+            LibraryEntry::new("1".to_string(), PathBuf::from("a")),
+            LibraryEntry::new("2".to_string(), PathBuf::from("b"))
+                .with_mime_type(Some("image/jpeg".to_string())),
         ]
     }
 
@@ -153,10 +523,7 @@ mod tests {
 
         assert!(!library_contains(
             &mut connection,
-            &LibraryEntry {
-                sha256: "2".to_string(),
-                path: PathBuf::from("b"),
-            }
+            &LibraryEntry::new("2".to_string(), PathBuf::from("b"))
         ));
         assert_eq!(
             result.err().unwrap().to_string(),
@@ -167,9 +534,9 @@ mod tests {
     #[test]
     fn foreach_entry_applies_the_function_to_each_entry() {
         let entries = some_entries();
-        let mut connection = new_database_containing_library_entries(&entries);
+        let connection = new_database_containing_library_entries(&entries);
         let mut entry_hashes = vec![];
-        let iterated_count = foreach_entry(&mut connection, |e| {
+        let iterated_count = foreach_entry(&connection, &LibraryFilter::default(), |e| {
             entry_hashes.push(e.sha256().to_owned());
             Ok(())
         })
@@ -178,12 +545,308 @@ mod tests {
         assert_eq!(vec!("1", "2"), entry_hashes);
     }
 
+    #[test]
+    fn foreach_entry_reads_the_mime_type() {
+        let connection = new_database_containing_library_entries(&some_entries());
+        let mut entries = vec![];
+        foreach_entry(&connection, &LibraryFilter::default(), |e| {
+            entries.push(e);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(some_entries(), entries);
+    }
+
+    #[test]
+    fn foreach_entry_filters_by_year_of_the_original_date_or_path() {
+        let entries = vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/18/a.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2023, 5, 18)),
+            LibraryEntry::new("2".to_string(), PathBuf::from("2023/6/1/b.jpg")),
+            LibraryEntry::new("3".to_string(), PathBuf::from("2022/6/1/c.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2022, 6, 1)),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let filter = LibraryFilter {
+            year: Some(2023),
+            ..Default::default()
+        };
+        let mut entry_hashes = vec![];
+        foreach_entry(&connection, &filter, |e| {
+            entry_hashes.push(e.sha256().to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec!["1", "2"], entry_hashes);
+    }
+
+    #[test]
+    fn foreach_entry_filters_by_date_range() {
+        let entries = vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("a.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 6, 30)),
+            LibraryEntry::new("2".to_string(), PathBuf::from("b.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 7, 1)),
+            LibraryEntry::new("3".to_string(), PathBuf::from("c.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 7, 15)),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let filter = "after:2024-07-01 before:2024-07-15"
+            .parse::<LibraryFilter>()
+            .unwrap();
+        let mut entry_hashes = vec![];
+        foreach_entry(&connection, &filter, |e| {
+            entry_hashes.push(e.sha256().to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec!["2", "3"], entry_hashes);
+    }
+
+    #[test]
+    fn foreach_entry_filters_before_the_end_of_a_partial_date() {
+        let entries = vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("a.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 7, 31)),
+            LibraryEntry::new("2".to_string(), PathBuf::from("b.jpg"))
+                .with_original_date(NaiveDate::from_ymd_opt(2024, 8, 1)),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let mut entry_hashes = vec![];
+        for query in ["before:2024-07", "before:2024"] {
+            foreach_entry(&connection, &query.parse().unwrap(), |e| {
+                entry_hashes.push(e.sha256().to_owned());
+                Ok(())
+            })
+            .unwrap();
+        }
+        assert_eq!(vec!["1", "1", "2"], entry_hashes);
+    }
+
+    #[test]
+    fn foreach_entry_filters_by_altitude_and_speed() {
+        let at = |altitude, speed| Telemetry {
+            altitude: Some(altitude),
+            direction: None,
+            speed: Some(speed),
+        };
+        let entries = vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("a.jpg"))
+                .with_telemetry(at(12.5, 4.0)),
+            LibraryEntry::new("2".to_string(), PathBuf::from("b.jpg"))
+                .with_telemetry(at(120.0, 40.0)),
+            LibraryEntry::new("3".to_string(), PathBuf::from("c.jpg"))
+                .with_telemetry(at(480.0, 8.0)),
+            LibraryEntry::new("4".to_string(), PathBuf::from("d.jpg")),
+        ];
+        let connection = new_database_containing_library_entries(&entries);
+        let filter = "min-altitude:100m max-altitude:1000ft"
+            .parse::<LibraryFilter>()
+            .unwrap();
+        let mut filtered = vec![];
+        foreach_entry(&connection, &filter, |e| {
+            filtered.push(e);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec![&entries[1]], filtered.iter().collect::<Vec<_>>());
+
+        let filter = "min-speed:30km/h".parse::<LibraryFilter>().unwrap();
+        let mut entry_hashes = vec![];
+        foreach_entry(&connection, &filter, |e| {
+            entry_hashes.push(e.sha256().to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec!["2"], entry_hashes);
+    }
+
+    #[test]
+    fn from_str_parses_the_query_terms() {
+        assert_eq!(
+            LibraryFilter {
+                year: Some(2024),
+                path_prefix: Some("2024/7".to_string()),
+                tags: vec!["beach".to_string()],
+                kind: Some(MediaKind::Animation),
+                imported_by: Some("anna".to_string()),
+                ..Default::default()
+            },
+            "year:2024 path:2024/7 tag:beach kind:animation by:anna"
+                .parse()
+                .unwrap()
+        );
+        assert!("camera:x".parse::<LibraryFilter>().is_err());
+        assert!("kind:gif".parse::<LibraryFilter>().is_err());
+    }
+
+    #[test]
+    fn relative_to_strips_the_repository_root() {
+        let filter = LibraryFilter {
+            path_prefix: Some("/photos/2024".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some("2024".to_string()),
+            filter.relative_to(Path::new("/photos")).path_prefix
+        );
+    }
+
+    #[test]
+    fn move_library_directories_moves_the_entries_and_sidecars_under_them() {
+        let mut connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("1".to_string(), PathBuf::from("2023/5/8/a.jpg")),
+            LibraryEntry::new("2".to_string(), PathBuf::from("2023/5/80/b.jpg")),
+            LibraryEntry::new("3".to_string(), PathBuf::from("drone/2023/5/8/c.mp4")),
+        ]);
+        connection
+            .execute(
+                "INSERT INTO sidecar (hash, path) VALUES ('1', '2023/5/8/a.xmp')",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            1,
+            move_library_directories(
+                &mut connection,
+                &[(PathBuf::from("2023/5/8"), PathBuf::from("2023/05/08"))]
+            )
+            .unwrap()
+        );
+
+        for (hash, path) in [
+            ("1", "2023/05/08/a.jpg"),
+            ("2", "2023/5/80/b.jpg"),
+            ("3", "drone/2023/5/8/c.mp4"),
+        ] {
+            assert!(library_contains(
+                &mut connection,
+                &LibraryEntry::new(hash.to_string(), PathBuf::from(path))
+            ));
+        }
+        assert_eq!(
+            "2023/05/08/a.xmp",
+            connection
+                .query_row("SELECT path FROM sidecar", [], |r| r.get::<_, String>(0))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn move_library_entries_moves_the_entries_and_their_sidecars() {
+        let mut connection = new_database_containing_library_entries(&some_entries());
+        connection
+            .execute(
+                "INSERT INTO sidecar (hash, path) VALUES ('1', 'a.xmp'), ('2', 'b.xmp')",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            1,
+            move_library_entries(
+                &mut connection,
+                &[("1".to_string(), PathBuf::from("2024/a.jpeg"))],
+                &[("1".to_string(), PathBuf::from("2024/a.xmp"))]
+            )
+            .unwrap()
+        );
+
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new("1".to_string(), PathBuf::from("2024/a.jpeg"))
+        ));
+        assert_eq!(
+            vec!["2024/a.xmp".to_string(), "b.xmp".to_string()],
+            connection
+                .prepare("SELECT path FROM sidecar ORDER BY hash")
+                .unwrap()
+                .query_map([], |r| r.get::<_, String>(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn update_library_paths_moves_the_entries() {
+        let mut connection = new_database_containing_library_entries(&some_entries());
+        let moved = PathBuf::from("2024/moved.jpeg");
+
+        assert_eq!(
+            1,
+            update_library_paths(&mut connection, &[("1".to_string(), moved.clone())]).unwrap()
+        );
+        assert!(library_contains(
+            &mut connection,
+            &LibraryEntry::new("1".to_string(), moved)
+        ));
+    }
+
+    #[test]
+    fn remove_library_entries_removes_the_rows() {
+        let entries = some_entries();
+        let mut connection = new_database_containing_library_entries(&entries);
+
+        assert_eq!(
+            1,
+            remove_library_entries(&mut connection, &["1".to_string()]).unwrap()
+        );
+        assert!(!library_contains(&mut connection, &entries[0]));
+        assert!(library_contains(&mut connection, &entries[1]));
+    }
+
+    #[test]
+    fn select_aliases_collects_the_original_and_cataloged_names() {
+        let connection = new_database_containing_catalog_and_library_entries(
+            &vec![
+                CatalogEntry::new("1".to_string(), "/card/DSCF0001.JPG".to_string()),
+                CatalogEntry::new("1".to_string(), "/backup/DSCF0001.JPG".to_string()),
+                CatalogEntry::new("1".to_string(), "/backup/copy.jpg".to_string()),
+            ],
+            &vec![
+                LibraryEntry::new("1".to_string(), PathBuf::from("2024/1/1/a.jpg"))
+                    .with_original_name(Some("DSCF0001.JPG".to_string())),
+                LibraryEntry::new("2".to_string(), PathBuf::from("2024/1/1/b.jpg")),
+            ],
+        );
+
+        assert_eq!(
+            vec![
+                (
+                    "1".to_string(),
+                    PathBuf::from("2024/1/1/a.jpg"),
+                    vec!["DSCF0001.JPG".to_string(), "copy.jpg".to_string()]
+                ),
+                ("2".to_string(), PathBuf::from("2024/1/1/b.jpg"), vec![])
+            ],
+            select_aliases(&connection).unwrap()
+        );
+    }
+
+    #[test]
+    fn foreach_entry_filters_by_path_prefix() {
+        let connection = new_database_containing_library_entries(&some_entries());
+        let filter = LibraryFilter {
+            path_prefix: Some("b".to_string()),
+            ..Default::default()
+        };
+        let mut entry_hashes = vec![];
+        foreach_entry(&connection, &filter, |e| {
+            entry_hashes.push(e.sha256().to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec!["2"], entry_hashes);
+    }
+
     #[test]
     fn foreach_entry_returns_error_when_query_fails() {
         let connection = new_connection();
         assert_eq!(
             "no such table: library",
-            foreach_entry(&connection, |_e| Ok(()))
+            foreach_entry(&connection, &LibraryFilter::default(), |_e| Ok(()))
                 .err()
                 .unwrap()
                 .to_string()
@@ -195,10 +858,12 @@ mod tests {
         let connection = new_database_containing_library_entries(&some_entries());
         assert_eq!(
             "invalid entry",
-            foreach_entry(&connection, |e| if e.sha256() == "1" {
-                Ok(())
-            } else {
-                Err(eyre!("invalid entry"))
+            foreach_entry(&connection, &LibraryFilter::default(), |e| {
+                if e.sha256() == "1" {
+                    Ok(())
+                } else {
+                    Err(eyre!("invalid entry"))
+                }
             })
             .err()
             .unwrap()
@@ -210,7 +875,10 @@ mod tests {
     fn foreach_entry_returns_error_when_row_cannot_be_converted_to_entry() {
         let connection = new_connection();
         connection
-            .execute("create table library (hash integer, path string)", [])
+            .execute(
+                "create table library (hash integer, path string, mime_type string, original_date string, size integer, kind string, altitude real, direction real, speed real)",
+                [],
+            )
             .unwrap();
         connection
             .execute(
@@ -221,7 +889,7 @@ mod tests {
 
         assert_eq!(
             "Invalid column type Integer at index: 0, name: hash",
-            foreach_entry(&connection, |_e| Ok(()))
+            foreach_entry(&connection, &LibraryFilter::default(), |_e| Ok(()))
                 .err()
                 .unwrap()
                 .to_string()
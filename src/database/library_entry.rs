@@ -1,30 +1,116 @@
 use std::{
-    ffi::{OsStr, OsString},
-    path::PathBuf,
+    collections::HashSet,
+    ffi::OsStr,
+    fs::metadata,
+    path::{Path, PathBuf},
 };
 
-use chrono::{Datelike, NaiveDate};
-use exif::Exif;
+use chrono::NaiveDate;
+use rusqlite::Row;
 
-use eyre::{eyre, Context, Error, Result};
+use eyre::{eyre, Error, Result};
 
-use super::catalog_entry::CatalogEntry;
+use crate::{
+    media::{self, MediaType},
+    metadata::{Exif, MetadataBackend, Telemetry},
+    naming::LibraryNaming,
+};
+
+use super::{catalog_entry::CatalogEntry, common::sha256_digest};
 
 #[derive(PartialEq, Debug)]
 pub(crate) struct LibraryEntry {
     pub(super) sha256: String,
     pub(super) path: PathBuf,
+    pub(super) mime_type: Option<String>,
+    pub(super) original_date: Option<NaiveDate>,
+    /// The size of the file in bytes.
+    pub(super) size: Option<u64>,
+    /// The name of the imported file, which the library name may have sanitized.
+    pub(super) original_name: Option<String>,
+    /// The photo, video, animation or screenshot classification.
+    pub(super) kind: Option<String>,
+    /// The person who imported the file.
+    pub(super) imported_by: Option<String>,
+    /// Where the original date was read: metadata, filename or mtime.
+    pub(super) date_source: Option<String>,
+    /// The GPS altitude, direction and speed.
+    pub(super) telemetry: Telemetry,
 }
 
 impl LibraryEntry {
     pub(crate) fn new(sha256: String, path: PathBuf) -> Self {
-        Self { sha256, path }
+        Self {
+            sha256,
+            path,
+            mime_type: None,
+            original_date: None,
+            size: None,
+            original_name: None,
+            kind: None,
+            imported_by: None,
+            date_source: None,
+            telemetry: Telemetry::default(),
+        }
+    }
+
+    pub(crate) fn with_path(mut self, path: PathBuf) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub(crate) fn with_mime_type(mut self, mime_type: Option<String>) -> Self {
+        self.mime_type = mime_type;
+        self
+    }
+
+    pub(crate) fn with_original_date(mut self, original_date: Option<NaiveDate>) -> Self {
+        self.original_date = original_date;
+        self
+    }
+
+    pub(crate) fn with_size(mut self, size: Option<u64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub(crate) fn with_original_name(mut self, original_name: Option<String>) -> Self {
+        self.original_name = original_name;
+        self
+    }
+
+    pub(crate) fn with_kind(mut self, kind: Option<String>) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub(crate) fn with_imported_by(mut self, imported_by: Option<String>) -> Self {
+        self.imported_by = imported_by;
+        self
+    }
+
+    pub(crate) fn with_date_source(mut self, date_source: Option<String>) -> Self {
+        self.date_source = date_source;
+        self
+    }
+
+    pub(crate) fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    pub(crate) fn date_source(&self) -> Option<&str> {
+        self.date_source.as_deref()
     }
 
     pub(crate) fn sha256(&self) -> &str {
         &self.sha256
     }
 
+    pub(crate) fn original_date(&self) -> Option<NaiveDate> {
+        self.original_date
+    }
+
     pub(crate) fn path(&self) -> &PathBuf {
         &self.path
     }
@@ -34,95 +120,261 @@ impl TryFrom<&CatalogEntry> for LibraryEntry {
     type Error = Error;
 
     fn try_from(catalog_entry: &CatalogEntry) -> Result<LibraryEntry> {
-        let exif: Exif = read_exif(&catalog_entry.path())?;
-        let original_date = original_date(&exif)
-            .map_err(|e| eyre!("For {}: {}", catalog_entry.path().display(), e))?;
-
-        Ok(Self::new(
-            catalog_entry.sha256().to_owned(),
-            find_unused_library_path(&catalog_entry.path(), original_date)?,
-        ))
+        Self::from_catalog_entry(catalog_entry, &LibraryNaming::default(), &Exif, None)
     }
 }
 
-fn find_unused_library_path(path: &PathBuf, original_date: NaiveDate) -> Result<PathBuf> {
-    let file_stem = path.file_stem().ok_or(eyre!("Expected a file stem"))?;
-    let extension = path.extension().ok_or(eyre!("Expected a file extension"))?;
-    let date_based_path = date_based_path(original_date);
+impl LibraryEntry {
+    /// The library entry of a cataloged file, named under the naming restrictions
+    /// and dated by the metadata backend, in the date directories of the sub-root
+    /// or of the repository root.
+    pub(crate) fn from_catalog_entry(
+        catalog_entry: &CatalogEntry,
+        naming: &LibraryNaming,
+        backend: &dyn MetadataBackend,
+        sub_root: Option<&Path>,
+    ) -> Result<LibraryEntry> {
+        Self::planned_from_catalog_entry(catalog_entry, naming, backend, sub_root, &HashSet::new())
+            .map(|(entry, _)| entry)
+    }
+
+    /// The library entry of a cataloged file as [from_catalog_entry](Self::from_catalog_entry)
+    /// names it, also avoiding the planned paths that are not copied yet. Tells
+    /// whether the name was suffixed to avoid a collision.
+    pub(crate) fn planned_from_catalog_entry(
+        catalog_entry: &CatalogEntry,
+        naming: &LibraryNaming,
+        backend: &dyn MetadataBackend,
+        sub_root: Option<&Path>,
+        planned: &HashSet<PathBuf>,
+    ) -> Result<(LibraryEntry, bool)> {
+        let media_type = media::detect(&catalog_entry.path())?;
+        let kind = media::classify(&catalog_entry.path(), media_type.map(|t| t.mime()))?;
+        let (original_datetime, date_source) = backend
+            .dated(&catalog_entry.path())
+            .map_err(|e| eyre!("For {}: {}", catalog_entry.path().display(), e))?;
+        let original_date = original_datetime.date();
+
+        let (path, renamed) = find_unused_library_path(
+            catalog_entry,
+            media_type,
+            original_date,
+            naming,
+            sub_root,
+            planned,
+        )?;
+        let entry = Self::new(catalog_entry.sha256().to_owned(), path)
+            .with_mime_type(media_type.map(|t| t.mime().to_owned()))
+            .with_original_date(Some(original_date))
+            .with_date_source(Some(date_source.to_string()))
+            .with_size(metadata(catalog_entry.path()).ok().map(|m| m.len()))
+            .with_telemetry(backend.telemetry(&catalog_entry.path()))
+            .with_kind(kind.map(|k| k.to_string()))
+            .with_original_name(
+                catalog_entry
+                    .path()
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string()),
+            );
+        Ok((entry, renamed))
+    }
 
-    unused_filename(&date_based_path, &file_stem, &extension)
+    /// The entry moved into the directory under the first free name, as a picture
+    /// joins the folder of the other picture of its shot. Tells whether the name
+    /// was suffixed to avoid a collision.
+    pub(crate) fn placed_in(
+        mut self,
+        directory: &Path,
+        naming: &LibraryNaming,
+        planned: &HashSet<PathBuf>,
+    ) -> Result<(LibraryEntry, bool)> {
+        // The stem of the library path went through the file name template already.
+        let file_stem = match self.original_name.as_deref().map(Path::new) {
+            Some(name) => naming.stem(
+                name.file_stem().ok_or(eyre!("Expected a file stem"))?,
+                &self.sha256,
+            ),
+            None => self
+                .path
+                .file_stem()
+                .ok_or(eyre!("Expected a file stem"))?
+                .to_owned(),
+        };
+        let extension = self
+            .path
+            .extension()
+            .ok_or(eyre!("Expected a file extension"))?;
+        let (path, renamed) = unused_filename(
+            directory,
+            &file_stem,
+            extension,
+            &self.sha256,
+            naming,
+            planned,
+        )?;
+        self.path = path;
+        Ok((self, renamed))
+    }
 }
 
-fn unused_filename(base_path: &PathBuf, file_stem: &OsStr, extension: &OsStr) -> Result<PathBuf> {
-    let mut result = base_path.to_owned();
-    result.push(file_stem);
-    result.set_extension(extension);
-    let mut names = (1..).map(|i| build_file_name(file_stem, &("_".to_owned() + &i.to_string())));
-    while result.exists() {
-        if let Some(name) = names.next() {
-            result.set_file_name(name);
-            result.set_extension(extension);
-        } else {
-            return Err(eyre!("Can't find an unused file name in the library."));
+impl LibraryEntry {
+    /// The entry with the metadata read again from its file. The original date, and
+    /// its source, are kept when the backend can't read it anymore, as are the
+    /// telemetry readings.
+    pub(crate) fn refreshed(&self, backend: &dyn MetadataBackend) -> Result<LibraryEntry> {
+        let media_type = media::detect(&self.path)?;
+        let mime_type = media_type.map(|t| t.mime());
+        let dated = backend.dated(&self.path).ok();
+        Ok(Self::new(self.sha256.clone(), self.path.clone())
+            .with_mime_type(mime_type.map(str::to_owned))
+            .with_original_date(dated.map(|(d, _)| d.date()).or(self.original_date))
+            .with_date_source(
+                dated
+                    .map(|(_, source)| source.to_string())
+                    .or(self.date_source.clone()),
+            )
+            .with_size(Some(metadata(&self.path)?.len()))
+            .with_telemetry(backend.telemetry(&self.path).or(self.telemetry))
+            .with_kind(media::classify(&self.path, mime_type)?.map(|k| k.to_string()))
+            .with_original_name(self.original_name.clone()))
+    }
+
+    /// The `field: old -> new` descriptions of the metadata that differ in the
+    /// other entry.
+    pub(crate) fn changes(&self, other: &LibraryEntry) -> Vec<String> {
+        fn change<T: ToString + PartialEq>(
+            name: &str,
+            old: &Option<T>,
+            new: &Option<T>,
+        ) -> Option<String> {
+            let show = |v: &Option<T>| v.as_ref().map_or("none".to_string(), T::to_string);
+            (old != new).then(|| format!("{}: {} -> {}", name, show(old), show(new)))
         }
+        [
+            change("mime_type", &self.mime_type, &other.mime_type),
+            change("original_date", &self.original_date, &other.original_date),
+            change("size", &self.size, &other.size),
+            change("kind", &self.kind, &other.kind),
+            change(
+                "altitude",
+                &self.telemetry.altitude,
+                &other.telemetry.altitude,
+            ),
+            change(
+                "direction",
+                &self.telemetry.direction,
+                &other.telemetry.direction,
+            ),
+            change("speed", &self.telemetry.speed, &other.telemetry.speed),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
-    Ok(result)
 }
 
-fn build_file_name(stem: &OsStr, suffix: &str) -> OsString {
-    let mut result = stem.to_owned();
-    result.push(suffix);
-    result
+/// Reads the `hash, path, mime_type, original_date, size, kind, altitude,
+/// direction, speed` columns of a library row.
+impl TryFrom<&Row<'_>> for LibraryEntry {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &Row<'_>) -> std::result::Result<Self, Self::Error> {
+        Ok(
+            LibraryEntry::new(row.get::<_, String>(0)?, row.get::<_, String>(1)?.into())
+                .with_mime_type(row.get(2)?)
+                .with_original_date(row.get(3)?)
+                .with_size(row.get(4)?)
+                .with_kind(row.get(5)?)
+                .with_telemetry(Telemetry {
+                    altitude: row.get(6)?,
+                    direction: row.get(7)?,
+                    speed: row.get(8)?,
+                }),
+        )
+    }
 }
 
-fn date_based_path(date: NaiveDate) -> PathBuf {
-    [
-        &date.year().to_string(),
-        &date.month().to_string(),
-        &date.day().to_string(),
-    ]
-    .iter()
-    .collect()
+fn find_unused_library_path(
+    catalog_entry: &CatalogEntry,
+    media_type: Option<MediaType>,
+    original_date: NaiveDate,
+    naming: &LibraryNaming,
+    sub_root: Option<&Path>,
+    planned: &HashSet<PathBuf>,
+) -> Result<(PathBuf, bool)> {
+    let path = catalog_entry.path();
+    let file_stem = naming.stem(
+        path.file_stem().ok_or(eyre!("Expected a file stem"))?,
+        catalog_entry.sha256(),
+    );
+    let extension = library_extension(&path, media_type)?;
+    let date_based_path = sub_root
+        .unwrap_or(Path::new(""))
+        .join(naming.directory(original_date));
+
+    unused_filename(
+        &date_based_path,
+        &file_stem,
+        extension,
+        catalog_entry.sha256(),
+        naming,
+        planned,
+    )
 }
 
-fn original_date(exif: &Exif) -> Result<NaiveDate> {
-    if let Some(datetime_field) = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY) {
-        NaiveDate::parse_from_str(
-            &datetime_field
-                .value
-                .display_as(exif::Tag::DateTimeOriginal)
-                .to_string(),
-            "%Y-%m-%d %H:%M:%S",
-        )
-        .wrap_err("Failed to parse DateTimmeOriginal")
-    } else {
-        Err(eyre!("DateTimeOriginal tag not found"))
+/// The extension of the file unless it does not match the detected media type,
+/// in which case the canonical extension of the media type is used.
+fn library_extension(path: &Path, media_type: Option<MediaType>) -> Result<&OsStr> {
+    match (path.extension(), media_type) {
+        (Some(extension), Some(media_type)) if !media_type.accepts_extension(extension) => {
+            Ok(OsStr::new(media_type.extension()))
+        }
+        (Some(extension), _) => Ok(extension),
+        (None, Some(media_type)) => Ok(OsStr::new(media_type.extension())),
+        (None, None) => Err(eyre!("Expected a file extension")),
     }
 }
 
-fn read_exif(path: &PathBuf) -> Result<Exif> {
-    let file = std::fs::File::open(path)?;
-    let mut bufreader = std::io::BufReader::new(&file);
-    let exifreader = exif::Reader::new();
-    exifreader
-        .read_from_container(&mut bufreader)
-        .wrap_err(eyre!("Failed to read exif for {}", path.display()))
+/// The first name of the directory that is free, or that already holds the same
+/// content, which then needs no copy, along with whether it needed a suffix.
+fn unused_filename(
+    base_path: &Path,
+    file_stem: &OsStr,
+    extension: &OsStr,
+    sha256: &str,
+    naming: &LibraryNaming,
+    planned: &HashSet<PathBuf>,
+) -> Result<(PathBuf, bool)> {
+    for suffix in naming.suffixes(sha256) {
+        let result = base_path.join(naming.file_name(base_path, file_stem, &suffix, extension)?);
+        if planned.contains(&result) {
+            continue;
+        }
+        if !result.exists() || sha256_digest(&result)? == sha256 {
+            return Ok((result, !suffix.is_empty()));
+        }
+    }
+    Err(eyre!("Can't find an unused file name in the library."))
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
-        fs::{copy, create_dir_all, remove_dir_all, remove_file, File},
+        collections::HashSet,
+        ffi::OsStr,
+        fs::{copy, create_dir_all, remove_dir_all, remove_file, write, File},
         path::PathBuf,
     };
 
     use chrono::NaiveDate;
+    use tempfile::TempDir;
 
     use serial_test::serial;
 
-    use crate::database::{
-        catalog_entry::CatalogEntry,
-        library_entry::{date_based_path, original_date, read_exif, LibraryEntry},
+    use crate::{
+        database::{catalog_entry::CatalogEntry, library_entry::LibraryEntry},
+        metadata::Exif,
+        naming::LibraryNaming,
     };
 
     #[test]
@@ -144,46 +396,62 @@ mod tests {
     }
 
     #[test]
-    fn read_exif_returns_an_error() {
-        let path: &PathBuf = &given_a_path_for_non_exif_file();
+    fn try_from_records_the_detected_mime_type() {
+        let catalog_entry =
+            CatalogEntry::try_from(&given_a_path_for_an_image_with_original_date()).unwrap();
+        let entry = LibraryEntry::try_from(&catalog_entry).unwrap();
+        assert_eq!(Some("image/jpeg".to_string()), entry.mime_type);
+    }
 
-        assert!(read_exif(path).is_err());
+    #[test]
+    fn try_from_records_the_original_name() {
+        let catalog_entry =
+            CatalogEntry::try_from(&given_a_path_for_an_image_with_original_date()).unwrap();
+        let entry = LibraryEntry::try_from(&catalog_entry).unwrap();
+        assert_eq!(Some("kami_neko.jpeg".to_string()), entry.original_name);
     }
 
     #[test]
-    fn try_from_fails_to_create_library_entry_from_non_exif_file() {
-        let catalog_entry = CatalogEntry::try_from(&given_a_path_for_non_exif_file()).unwrap();
-        assert!(LibraryEntry::try_from(&catalog_entry).is_err());
+    fn try_from_records_the_original_date() {
+        let catalog_entry =
+            CatalogEntry::try_from(&given_a_path_for_an_image_with_original_date()).unwrap();
+        let entry = LibraryEntry::try_from(&catalog_entry).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 5, 18), entry.original_date);
     }
 
     #[test]
-    fn date_based_path_uses_slash_separator() {
-        let date = NaiveDate::from_ymd_opt(2023, 12, 2).unwrap();
-        assert_eq!(
-            [2023.to_string(), 12.to_string(), 2.to_string()]
-                .iter()
-                .collect::<PathBuf>(),
-            date_based_path(date)
-        );
+    fn try_from_corrects_an_extension_not_matching_the_content() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("kami_neko.png");
+        copy(given_a_path_for_an_image_with_original_date(), &path).unwrap();
+
+        let entry = LibraryEntry::try_from(&CatalogEntry::try_from(&path).unwrap()).unwrap();
+
+        assert_eq!(Some(OsStr::new("jpg")), entry.path().extension());
     }
 
     #[test]
-    fn original_date_returns_the_original_naive_date_from_exif() {
-        let path = &given_a_path_for_an_image_with_original_date();
-        let exif = read_exif(path).unwrap();
+    fn try_from_adds_the_extension_of_the_content_when_missing() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("kami_neko");
+        copy(given_a_path_for_an_image_with_original_date(), &path).unwrap();
 
-        assert_eq!(
-            NaiveDate::from_ymd_opt(2023, 5, 18).unwrap(),
-            original_date(&exif).unwrap()
-        );
+        let entry = LibraryEntry::try_from(&CatalogEntry::try_from(&path).unwrap()).unwrap();
+
+        assert_eq!(Some(OsStr::new("jpg")), entry.path().extension());
     }
 
     #[test]
-    fn original_date_returns_error() {
-        let path = &given_a_path_for_an_image_with_no_original_date();
+    fn try_from_fails_to_create_library_entry_without_original_date() {
+        let catalog_entry =
+            CatalogEntry::try_from(&given_a_path_for_an_image_with_no_original_date()).unwrap();
+        assert!(LibraryEntry::try_from(&catalog_entry).is_err());
+    }
 
-        let exif = read_exif(path).unwrap();
-        assert!(original_date(&exif).is_err());
+    #[test]
+    fn try_from_fails_to_create_library_entry_from_non_exif_file() {
+        let catalog_entry = CatalogEntry::try_from(&given_a_path_for_non_exif_file()).unwrap();
+        assert!(LibraryEntry::try_from(&catalog_entry).is_err());
     }
 
     #[test]
@@ -196,7 +464,7 @@ mod tests {
         assert_eq!(
             &[
                 2023.to_string(),
-                5.to_string(),
+                "05".to_string(),
                 18.to_string(),
                 path.file_name().unwrap().to_string_lossy().to_string()
             ]
@@ -212,7 +480,7 @@ mod tests {
         let path = &given_a_path_for_an_image_with_original_date();
         let occupied_path = [
             2023.to_string(),
-            5.to_string(),
+            "05".to_string(),
             18.to_string(),
             path.file_name().unwrap().to_string_lossy().to_string(),
         ]
@@ -220,19 +488,19 @@ mod tests {
         .collect::<PathBuf>();
 
         let _ = remove_dir_all(PathBuf::from(2023.to_string()));
-        create_dir_all(&occupied_path.parent().unwrap()).unwrap();
-        copy(path, &occupied_path).unwrap();
+        create_dir_all(occupied_path.parent().unwrap()).unwrap();
+        write(&occupied_path, b"another picture").unwrap();
 
         let result = LibraryEntry::try_from(&CatalogEntry::try_from(path).unwrap());
 
         assert_eq!(
             &[
                 2023.to_string(),
-                5.to_string(),
+                "05".to_string(),
                 18.to_string(),
                 path.file_stem().unwrap().to_string_lossy().to_string()
                     + "_1."
-                    + &path.extension().unwrap().to_string_lossy().to_string()
+                    + path.extension().unwrap().to_string_lossy().as_ref()
             ]
             .iter()
             .collect::<PathBuf>(),
@@ -240,6 +508,56 @@ mod tests {
         );
     }
 
+    #[test]
+    #[serial]
+    fn library_path_is_the_existing_file_with_the_same_content() {
+        let path = &given_a_path_for_an_image_with_original_date();
+        let occupied_path = [
+            2023.to_string(),
+            "05".to_string(),
+            18.to_string(),
+            path.file_name().unwrap().to_string_lossy().to_string(),
+        ]
+        .iter()
+        .collect::<PathBuf>();
+
+        let _ = remove_dir_all(PathBuf::from(2023.to_string()));
+        create_dir_all(occupied_path.parent().unwrap()).unwrap();
+        copy(path, &occupied_path).unwrap();
+
+        let result = LibraryEntry::try_from(&CatalogEntry::try_from(path).unwrap());
+
+        assert_eq!(&occupied_path, result.unwrap().path());
+        let _ = remove_dir_all(PathBuf::from(2023.to_string()));
+    }
+
+    #[test]
+    #[serial]
+    fn planned_library_path_avoids_the_planned_paths() {
+        let path = &given_a_path_for_an_image_with_original_date();
+        let planned = ["2023", "05", "18", "kami_neko.jpeg"]
+            .iter()
+            .collect::<PathBuf>();
+        let _ = remove_dir_all(PathBuf::from(2023.to_string()));
+
+        let (entry, renamed) = LibraryEntry::planned_from_catalog_entry(
+            &CatalogEntry::try_from(path).unwrap(),
+            &LibraryNaming::default(),
+            &Exif,
+            None,
+            &HashSet::from([planned]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            &["2023", "05", "18", "kami_neko_1.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+            entry.path()
+        );
+        assert!(renamed);
+    }
+
     fn given_a_path_for_an_image_with_original_date() -> PathBuf {
         ["resources", "test", "kami_neko.jpeg"].iter().collect()
     }
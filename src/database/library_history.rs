@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use chrono::{Days, NaiveDate};
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+
+/// The library entries at the end of the date, from the last change of each
+/// content recorded by then. The imports older than the history, whose time is
+/// unknown, count as always there. The path prefix restricts the entries by their
+/// path at that date.
+pub(crate) fn select_library_at(
+    connection: &Connection,
+    date: NaiveDate,
+    path_prefix: Option<&str>,
+) -> Result<Vec<(String, PathBuf)>> {
+    let next_day = date
+        .checked_add_days(Days::new(1))
+        .ok_or(eyre!("Invalid date {}", date))?;
+    let mut statement = connection.prepare(
+        "SELECT hash, path FROM library_history WHERE rowid IN (
+            SELECT max(rowid) FROM library_history
+            WHERE changed_at IS NULL OR changed_at < ?1
+            GROUP BY hash
+        ) AND change <> 'remove' AND path LIKE ?2 ORDER BY path",
+    )?;
+    let entries = statement
+        .query_map(
+            [
+                next_day.to_string(),
+                format!("{}%", path_prefix.unwrap_or_default()),
+            ],
+            |row| Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?))),
+        )?
+        .collect::<Result<Vec<(String, PathBuf)>, rusqlite::Error>>()?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use crate::database::test_utils::new_database;
+
+    use super::select_library_at;
+
+    #[test]
+    fn select_library_at_replays_the_imports_moves_and_removals() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library (hash, path, imported_at) VALUES
+                    ('H1', '2023/a.jpg', '2024-01-05 10:00:00'),
+                    ('H2', '2023/b.jpg', '2024-01-05 10:00:00'),
+                    ('H3', '2023/c.jpg', '2024-03-01 10:00:00');
+                UPDATE library SET path = '2022/b.jpg' WHERE hash = 'H2';
+                DELETE FROM library WHERE hash = 'H1';
+                UPDATE library_history SET changed_at = '2024-02-10 08:00:00' WHERE change <> 'import';",
+            )
+            .unwrap();
+        let at = |date, prefix| {
+            select_library_at(
+                &connection,
+                NaiveDate::from_ymd_opt(2024, 1, date).unwrap(),
+                prefix,
+            )
+            .unwrap()
+        };
+
+        assert!(at(4, None).is_empty());
+        assert_eq!(
+            vec![
+                ("H1".to_string(), PathBuf::from("2023/a.jpg")),
+                ("H2".to_string(), PathBuf::from("2023/b.jpg"))
+            ],
+            at(5, Some("2023/"))
+        );
+        assert_eq!(
+            vec![("H2".to_string(), PathBuf::from("2022/b.jpg"))],
+            select_library_at(
+                &connection,
+                NaiveDate::from_ymd_opt(2024, 2, 20).unwrap(),
+                None
+            )
+            .unwrap()
+        );
+    }
+}
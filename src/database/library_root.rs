@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// Replaces the recorded library roots with the `(name, path)` roots of the
+/// configuration, all or none of them.
+pub(crate) fn persist_library_roots(
+    connection: &mut Connection,
+    roots: &[(String, PathBuf)],
+) -> Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute("DELETE FROM library_root", [])?;
+    {
+        let mut statement =
+            transaction.prepare("INSERT INTO library_root (name, path) VALUES (?1, ?2)")?;
+        for (name, path) in roots {
+            statement.execute(params![name, path.to_string_lossy()])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// The `(name, path)` library roots, by name.
+pub(crate) fn select_library_roots(connection: &Connection) -> Result<Vec<(String, PathBuf)>> {
+    let mut statement = connection.prepare("SELECT name, path FROM library_root ORDER BY name")?;
+    let roots = statement
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                PathBuf::from(r.get::<_, String>(1)?),
+            ))
+        })?
+        .collect::<Result<Vec<(String, PathBuf)>, rusqlite::Error>>()?;
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{persist_library_roots, select_library_roots};
+
+    #[test]
+    fn persist_library_roots_replaces_the_previous_roots() {
+        let mut connection = new_database();
+        let drone = ("drone".to_string(), PathBuf::from("drone"));
+        let scans = ("scans".to_string(), PathBuf::from("/mnt/scans"));
+
+        persist_library_roots(&mut connection, std::slice::from_ref(&drone)).unwrap();
+        persist_library_roots(&mut connection, std::slice::from_ref(&scans)).unwrap();
+
+        assert_eq!(vec![scans], select_library_roots(&connection).unwrap());
+    }
+}
@@ -1,14 +1,41 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use eyre::Result;
-use refinery::{Error, Report};
+use refinery::Report;
 use rusqlite::Connection;
 
 pub(crate) mod catalog;
 pub(crate) mod catalog_entry;
+pub(crate) mod check_finding;
+pub(crate) mod check_progress;
 pub(crate) mod common;
+pub(crate) mod event;
+pub(crate) mod fingerprint;
+pub(crate) mod invocation;
+pub(crate) mod layout_deviation;
 pub(crate) mod library;
 pub(crate) mod library_entry;
+pub(crate) mod library_history;
+pub(crate) mod library_root;
+pub(crate) mod operation;
+pub(crate) mod pair;
+pub(crate) mod problem;
+pub(crate) mod protected;
+pub(crate) mod published;
+pub(crate) mod review;
+pub(crate) mod saved_query;
+pub(crate) mod schema;
+pub(crate) mod sidecar;
+pub(crate) mod snapshot;
+pub(crate) mod stack;
+pub(crate) mod stats;
+pub(crate) mod sync_state;
+pub(crate) mod tag;
+pub(crate) mod trashed;
+pub(crate) mod version;
+
+#[cfg(feature = "sqlcipher")]
+mod key;
 
 #[cfg(test)]
 pub(crate) mod test_utils;
@@ -18,21 +45,49 @@ mod embedded {
     embed_migrations!("./migrations");
 }
 
+/// How long a connection retries while another process holds the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub(crate) fn open(db: &PathBuf) -> Result<Connection> {
-    let mut connection = Connection::open(&db)?;
+    let mut connection = connect(db)?;
     migrate(&mut connection)?;
     Ok(connection)
 }
 
-fn migrate(connection: &mut Connection) -> Result<Report, Error> {
-    embedded::migrations::runner().run(connection)
+/// Opens the database without migrating it.
+pub(crate) fn connect(db: &PathBuf) -> Result<Connection> {
+    let connection = Connection::open(db)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    #[cfg(feature = "sqlcipher")]
+    key::unlock(&connection)?;
+    Ok(connection)
+}
+
+/// The last schema version applied to the database, and the latest one known to
+/// this build.
+pub(crate) fn schema_version(connection: &mut Connection) -> Result<(Option<u32>, u32)> {
+    let runner = embedded::migrations::runner();
+    let applied = runner
+        .get_last_applied_migration(connection)?
+        .map(|m| m.version());
+    let latest = runner
+        .get_migrations()
+        .iter()
+        .map(|m| m.version())
+        .max()
+        .unwrap_or_default();
+    Ok((applied, latest))
+}
+
+fn migrate(connection: &mut Connection) -> Result<Report> {
+    Ok(embedded::migrations::runner().run(connection)?)
 }
 
 #[cfg(test)]
 mod tests {
     use rusqlite::{params, Connection};
 
-    use crate::database::migrate;
+    use crate::database::{migrate, schema_version};
 
     fn table_exists(connection: &mut Connection, table_name: &str) -> bool {
         let mut statement = connection
@@ -57,4 +112,41 @@ mod tests {
         assert!(migrate(&mut connection).is_ok());
         assert!(table_exists(&mut connection, "library"));
     }
+
+    #[test]
+    fn schema_version_is_the_latest_after_migrate() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        let (applied, latest) = schema_version(&mut connection).unwrap();
+        assert_eq!(Some(latest), applied);
+    }
+
+    #[test]
+    fn migrate_creates_views_joining_the_tables() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO catalog (hash, path) VALUES ('H1', '/a'), ('H1', '/b'), ('H2', '/c'), ('H3', '/d');
+                INSERT INTO library (hash, path, original_date) VALUES ('H2', '2023/c', '2023-05-01');
+                INSERT INTO problem (hash, path, description) VALUES ('H3', '/d', 'truncated');",
+            )
+            .unwrap();
+        let count = |view: &str| {
+            connection
+                .query_row(&format!("SELECT count(*) FROM {}", view), [], |r| {
+                    r.get::<_, usize>(0)
+                })
+                .unwrap()
+        };
+
+        assert_eq!(2, count("v_duplicates"));
+        assert_eq!(2, count("v_pending_import"));
+        assert_eq!(
+            "2023",
+            connection
+                .query_row("SELECT year FROM v_photos", [], |r| r.get::<_, String>(0))
+                .unwrap()
+        );
+    }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::catalog_entry::CatalogEntry;
+
+/// Records the start of a command changing the catalog, by the person running it,
+/// and returns its id.
+pub(crate) fn start_operation(
+    connection: &Connection,
+    command: &str,
+    argument: Option<&str>,
+    imported_by: Option<&str>,
+) -> Result<i64> {
+    connection.execute(
+        "INSERT INTO operation (command, argument, started_at, imported_by) VALUES (?1, ?2, ?3, ?4)",
+        params![command, argument, Local::now().naive_local(), imported_by],
+    )?;
+    Ok(connection.last_insert_rowid())
+}
+
+/// Attributes the catalog entries persisted since the last operation to this one.
+pub(crate) fn assign_operation(connection: &Connection, operation: i64) -> Result<usize> {
+    Ok(connection.execute(
+        "UPDATE catalog SET operation = ?1 WHERE operation IS NULL",
+        [operation],
+    )?)
+}
+
+/// The id and start of the latest operation of the command on the argument.
+pub(crate) fn previous_operation(
+    connection: &Connection,
+    command: &str,
+    argument: &str,
+) -> Result<Option<(i64, NaiveDateTime)>> {
+    Ok(connection
+        .query_row(
+            "SELECT id, started_at FROM operation WHERE command = ?1 AND argument = ?2 ORDER BY id DESC LIMIT 1",
+            [command, argument],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?)
+}
+
+pub(crate) fn last_operation(connection: &Connection) -> Result<Option<i64>> {
+    Ok(connection.query_row("SELECT max(id) FROM operation", [], |r| r.get(0))?)
+}
+
+/// The paths cataloged by the operations started on or after the date.
+pub(crate) fn select_paths_cataloged_since(
+    connection: &Connection,
+    date: NaiveDate,
+) -> Result<HashSet<String>> {
+    let mut statement = connection.prepare(
+        "SELECT catalog.path FROM catalog JOIN operation ON catalog.operation = operation.id WHERE operation.started_at >= ?1",
+    )?;
+    let paths = statement
+        .query_map([date.to_string()], |row| row.get(0))?
+        .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+    Ok(paths)
+}
+
+/// The catalog entries added by the operations after `since`.
+pub(crate) fn select_catalog_since(
+    connection: &Connection,
+    since: i64,
+) -> Result<Vec<CatalogEntry>> {
+    let mut statement = connection
+        .prepare("SELECT hash, path, device, inode FROM catalog WHERE operation > ?1 ORDER BY operation, path")?;
+    let entries = statement
+        .query_map([since], |row| CatalogEntry::try_from(row))?
+        .collect::<Result<Vec<CatalogEntry>, rusqlite::Error>>()?;
+    Ok(entries)
+}
+
+/// The files staged by a catalog of the inbox that remain in the catalog.
+#[derive(Debug, PartialEq)]
+pub(crate) struct StagedBatch {
+    pub(crate) operation: i64,
+    pub(crate) started_at: NaiveDateTime,
+    pub(crate) files: usize,
+    /// The files whose content is in the library already.
+    pub(crate) imported: usize,
+}
+
+/// The batches of the catalog entries under the path prefix, oldest first.
+pub(crate) fn select_staged_batches(
+    connection: &Connection,
+    path_prefix: &str,
+) -> Result<Vec<StagedBatch>> {
+    let mut statement = connection.prepare(
+        "SELECT operation.id, operation.started_at, count(*), count(library.hash) FROM catalog JOIN operation ON catalog.operation = operation.id LEFT JOIN library ON catalog.hash = library.hash WHERE substr(catalog.path, 1, length(?1)) = ?1 GROUP BY operation.id ORDER BY operation.id",
+    )?;
+    let batches = statement
+        .query_map([path_prefix], |row| {
+            Ok(StagedBatch {
+                operation: row.get(0)?,
+                started_at: row.get(1)?,
+                files: row.get(2)?,
+                imported: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<StagedBatch>, rusqlite::Error>>()?;
+    Ok(batches)
+}
+
+/// The catalog entries of the operation under the path prefix.
+pub(crate) fn select_staged_entries(
+    connection: &Connection,
+    path_prefix: &str,
+    operation: i64,
+) -> Result<Vec<CatalogEntry>> {
+    let mut statement = connection.prepare(
+        "SELECT hash, path, device, inode FROM catalog WHERE operation = ?2 AND substr(path, 1, length(?1)) = ?1 ORDER BY path",
+    )?;
+    let entries = statement
+        .query_map(params![path_prefix, operation], |row| {
+            CatalogEntry::try_from(row)
+        })?
+        .collect::<Result<Vec<CatalogEntry>, rusqlite::Error>>()?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::{
+        catalog::persist_catalog_entries, catalog_entry::CatalogEntry, test_utils::new_database,
+    };
+
+    use super::{
+        assign_operation, last_operation, previous_operation, select_catalog_since,
+        select_staged_batches, select_staged_entries, start_operation, StagedBatch,
+    };
+
+    #[test]
+    fn select_staged_batches_groups_the_inbox_entries_by_operation() {
+        let mut connection = new_database();
+        let operation = start_operation(&connection, "catalog", Some("/inbox"), None).unwrap();
+        persist_catalog_entries(
+            &mut connection,
+            &vec![
+                CatalogEntry::new("H1".to_owned(), "/inbox/anna/1.jpeg".to_owned()),
+                CatalogEntry::new("H2".to_owned(), "/inbox/anna/2.jpeg".to_owned()),
+                CatalogEntry::new("H3".to_owned(), "/inboxes/3.jpeg".to_owned()),
+            ],
+        )
+        .unwrap();
+        assign_operation(&connection, operation).unwrap();
+        connection
+            .execute(
+                "INSERT INTO library (hash, path) VALUES ('H2', '2024/2.jpeg')",
+                [],
+            )
+            .unwrap();
+
+        let batches = select_staged_batches(&connection, "/inbox/").unwrap();
+        assert_eq!(
+            vec![(operation, 2, 1)],
+            batches
+                .iter()
+                .map(
+                    |StagedBatch {
+                         operation,
+                         files,
+                         imported,
+                         ..
+                     }| (*operation, *files, *imported)
+                )
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["H1", "H2"],
+            select_staged_entries(&connection, "/inbox/", operation)
+                .unwrap()
+                .iter()
+                .map(|e| e.sha256())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn select_catalog_since_returns_the_entries_of_later_operations() {
+        let mut connection = new_database();
+        assert_eq!(None, last_operation(&connection).unwrap());
+
+        let first = start_operation(&connection, "catalog", Some("/a"), None).unwrap();
+        persist_catalog_entries(
+            &mut connection,
+            &vec![CatalogEntry::new("H1".to_owned(), "/a/1.jpeg".to_owned())],
+        )
+        .unwrap();
+        assign_operation(&connection, first).unwrap();
+        let second = start_operation(&connection, "catalog", Some("/b"), Some("anna")).unwrap();
+        persist_catalog_entries(
+            &mut connection,
+            &vec![CatalogEntry::new("H2".to_owned(), "/b/2.jpeg".to_owned())],
+        )
+        .unwrap();
+        assign_operation(&connection, second).unwrap();
+
+        assert_eq!(Some(second), last_operation(&connection).unwrap());
+        assert_eq!(
+            Some(first),
+            previous_operation(&connection, "catalog", "/a")
+                .unwrap()
+                .map(|(id, _)| id)
+        );
+        assert_eq!(
+            None,
+            previous_operation(&connection, "catalog", "/c").unwrap()
+        );
+        assert_eq!(
+            vec![CatalogEntry::new("H2".to_owned(), "/b/2.jpeg".to_owned())],
+            select_catalog_since(&connection, first).unwrap()
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use eyre::Result;
+use rusqlite::Connection;
+
+/// Records the `(raw, companion)` contents of the pairs, the RAW picture and the
+/// JPEG the camera wrote along. Returns the number of new pairs.
+pub(crate) fn record_pairs(
+    connection: &mut Connection,
+    pairs: &[(String, String)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement =
+            transaction.prepare("INSERT OR IGNORE INTO pair (raw, companion) VALUES (?1, ?2)")?;
+        for (raw, companion) in pairs {
+            count += statement.execute([raw, companion])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The partner of each paired content, the companion of a RAW picture and the RAW
+/// picture of a companion.
+pub(crate) fn select_partners(connection: &Connection) -> Result<HashMap<String, String>> {
+    let mut statement = connection.prepare("SELECT raw, companion FROM pair")?;
+    let mut partners = HashMap::new();
+    for pair in statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))? {
+        let (raw, companion): (String, String) = pair?;
+        partners.insert(companion.clone(), raw.clone());
+        partners.insert(raw, companion);
+    }
+    Ok(partners)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{record_pairs, select_partners};
+
+    #[test]
+    fn select_partners_goes_both_ways() {
+        let mut connection = new_database();
+        let pairs = [("RAW".to_string(), "JPEG".to_string())];
+
+        assert_eq!(1, record_pairs(&mut connection, &pairs).unwrap());
+        assert_eq!(0, record_pairs(&mut connection, &pairs).unwrap());
+
+        let partners = select_partners(&connection).unwrap();
+        assert_eq!("JPEG", partners["RAW"]);
+        assert_eq!("RAW", partners["JPEG"]);
+    }
+}
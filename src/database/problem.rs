@@ -0,0 +1,100 @@
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+use crate::error::Error;
+
+/// A cataloged file found to be unusable, e.g. a truncated image.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Problem {
+    pub(super) sha256: String,
+    pub(super) path: String,
+    pub(super) description: String,
+}
+
+impl Problem {
+    pub(crate) fn new(sha256: String, path: String, description: String) -> Self {
+        Self {
+            sha256,
+            path,
+            description,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+pub(crate) fn persist_problems(connection: &mut Connection, problems: &[Problem]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "INSERT OR REPLACE INTO problem (hash, path, description) values (?1, ?2, ?3)",
+        )?;
+        for Problem {
+            sha256,
+            path,
+            description,
+        } in problems
+        {
+            count += statement
+                .execute(params![sha256, path, description])
+                .map_err(|e| {
+                    Error::database(e, format!("Failed to insert problem for {}", path))
+                })?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+pub(crate) fn select_problems(connection: &Connection) -> Result<Vec<Problem>> {
+    let mut statement = connection.prepare("SELECT hash, path, description FROM problem")?;
+    let result = statement
+        .query_map([], |r| Ok(Problem::new(r.get(0)?, r.get(1)?, r.get(2)?)))?
+        .collect::<Result<Vec<Problem>, rusqlite::Error>>()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{persist_problems, select_problems, Problem};
+
+    fn some_problem(description: &str) -> Problem {
+        Problem::new("1".to_string(), "a/a".to_string(), description.to_string())
+    }
+
+    #[test]
+    fn persist_problems_inserts_into_the_problem_table() {
+        let mut connection = new_database();
+
+        assert_eq!(
+            1,
+            persist_problems(&mut connection, &[some_problem("Truncated")]).unwrap()
+        );
+        assert_eq!(
+            vec![some_problem("Truncated")],
+            select_problems(&connection).unwrap()
+        );
+    }
+
+    #[test]
+    fn persist_problems_replaces_the_previous_problem_of_a_path() {
+        let mut connection = new_database();
+
+        persist_problems(&mut connection, &[some_problem("Truncated")]).unwrap();
+        persist_problems(&mut connection, &[some_problem("Invalid marker")]).unwrap();
+
+        assert_eq!(
+            vec![some_problem("Invalid marker")],
+            select_problems(&connection).unwrap()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A content that prune and trash never delete.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ProtectedItem {
+    pub(crate) sha256: String,
+    /// The library path, none when the content is not in the library.
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) added_at: NaiveDateTime,
+}
+
+/// Protects the contents, all or none of them. Returns the number of contents that
+/// were not protected already.
+pub(crate) fn protect(connection: &mut Connection, hashes: &[String]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction
+            .prepare("INSERT OR IGNORE INTO protected (hash, added_at) VALUES (?1, ?2)")?;
+        let now = Local::now().naive_local();
+        for hash in hashes {
+            count += statement.execute(params![hash, now])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Lifts the protection of the contents. Returns the number of contents that were
+/// protected.
+pub(crate) fn unprotect(connection: &mut Connection, hashes: &[String]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare("DELETE FROM protected WHERE hash = ?1")?;
+        for hash in hashes {
+            count += statement.execute(params![hash])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The protected contents, oldest first.
+pub(crate) fn select_protected(connection: &Connection) -> Result<Vec<ProtectedItem>> {
+    let mut statement = connection.prepare(
+        "SELECT protected.hash, library.path, protected.added_at FROM protected LEFT JOIN library ON protected.hash = library.hash ORDER BY protected.added_at, protected.hash",
+    )?;
+    let items = statement
+        .query_map([], |row| {
+            Ok(ProtectedItem {
+                sha256: row.get(0)?,
+                path: row.get::<_, Option<String>>(1)?.map(PathBuf::from),
+                added_at: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<ProtectedItem>, rusqlite::Error>>()?;
+    Ok(items)
+}
+
+/// The protected contents, for the delete paths to leave alone.
+pub(crate) fn protected_hashes(connection: &Connection) -> Result<HashSet<String>> {
+    let mut statement = connection.prepare("SELECT hash FROM protected")?;
+    let hashes = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{protect, protected_hashes, select_protected, unprotect};
+
+    #[test]
+    fn protect_records_the_contents_until_unprotected() {
+        let mut connection = new_database();
+        let hashes = vec!["AB".to_string(), "CD".to_string()];
+
+        assert_eq!(2, protect(&mut connection, &hashes).unwrap());
+        assert_eq!(0, protect(&mut connection, &hashes[..1]).unwrap());
+        assert_eq!(2, select_protected(&connection).unwrap().len());
+
+        assert_eq!(1, unprotect(&mut connection, &hashes[1..]).unwrap());
+        assert_eq!(
+            HashSet::from(["AB".to_string()]),
+            protected_hashes(&connection).unwrap()
+        );
+    }
+}
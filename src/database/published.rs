@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use chrono::Local;
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A content published to a gallery server.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Published {
+    /// The id of the picture on the server.
+    pub(crate) remote_id: String,
+    /// The JSON of the title, caption and tags sent with the picture.
+    pub(crate) metadata: String,
+}
+
+/// The contents published to the target, keyed by hash.
+pub(crate) fn select_published(
+    connection: &Connection,
+    target: &str,
+) -> Result<HashMap<String, Published>> {
+    let mut statement =
+        connection.prepare("SELECT hash, remote_id, metadata FROM published WHERE target = ?1")?;
+    let published = statement
+        .query_map([target], |row| {
+            Ok((
+                row.get(0)?,
+                Published {
+                    remote_id: row.get(1)?,
+                    metadata: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<Result<HashMap<String, Published>, rusqlite::Error>>()?;
+    Ok(published)
+}
+
+/// Records what the target received of the content, replacing the previous
+/// publication.
+pub(crate) fn record_published(
+    connection: &Connection,
+    target: &str,
+    hash: &str,
+    published: &Published,
+) -> Result<usize> {
+    Ok(connection.execute(
+        "INSERT OR REPLACE INTO published (target, hash, remote_id, metadata, published_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            target,
+            hash,
+            published.remote_id,
+            published.metadata,
+            Local::now().naive_local()
+        ],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{record_published, select_published, Published};
+
+    #[test]
+    fn record_published_replaces_the_publication_of_the_target() {
+        let connection = new_database();
+        let published = |remote_id: &str, metadata: &str| Published {
+            remote_id: remote_id.to_string(),
+            metadata: metadata.to_string(),
+        };
+        record_published(&connection, "family", "H1", &published("1", "{}")).unwrap();
+        record_published(
+            &connection,
+            "family",
+            "H1",
+            &published("1", "{\"tags\":[]}"),
+        )
+        .unwrap();
+        record_published(&connection, "public", "H1", &published("9", "{}")).unwrap();
+
+        let family = select_published(&connection, "family").unwrap();
+
+        assert_eq!(1, family.len());
+        assert_eq!(&published("1", "{\"tags\":[]}"), family.get("H1").unwrap());
+    }
+}
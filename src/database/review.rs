@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A library file waiting for review.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ReviewItem {
+    pub(crate) sha256: String,
+    /// The library path, none when the file left the library since.
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) added_at: NaiveDateTime,
+}
+
+/// Queues the contents for review, all or none of them. Returns the number of
+/// contents that were not queued already.
+pub(crate) fn add_to_review(connection: &mut Connection, hashes: &[String]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement =
+            transaction.prepare("INSERT OR IGNORE INTO review (hash, added_at) VALUES (?1, ?2)")?;
+        let now = Local::now().naive_local();
+        for hash in hashes {
+            count += statement.execute(params![hash, now])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Removes the contents from the review queue. Returns the number of contents
+/// that were queued.
+pub(crate) fn remove_from_review(connection: &mut Connection, hashes: &[String]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare("DELETE FROM review WHERE hash = ?1")?;
+        for hash in hashes {
+            count += statement.execute(params![hash])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The review queue, oldest first.
+pub(crate) fn select_review(connection: &Connection) -> Result<Vec<ReviewItem>> {
+    let mut statement = connection.prepare(
+        "SELECT review.hash, library.path, review.added_at FROM review LEFT JOIN library ON review.hash = library.hash ORDER BY review.added_at, review.hash",
+    )?;
+    let items = statement
+        .query_map([], |row| {
+            Ok(ReviewItem {
+                sha256: row.get(0)?,
+                path: row.get::<_, Option<String>>(1)?.map(PathBuf::from),
+                added_at: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<ReviewItem>, rusqlite::Error>>()?;
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::{
+        library_entry::LibraryEntry, test_utils::new_database_containing_library_entries,
+    };
+
+    use super::{add_to_review, remove_from_review, select_review};
+
+    #[test]
+    fn review_queue_lists_the_queued_contents_until_done() {
+        let mut connection = new_database_containing_library_entries(&vec![LibraryEntry::new(
+            "AB".to_string(),
+            PathBuf::from("2024/a.jpeg"),
+        )]);
+        let hashes = vec!["AB".to_string(), "CD".to_string()];
+
+        assert_eq!(2, add_to_review(&mut connection, &hashes).unwrap());
+        assert_eq!(0, add_to_review(&mut connection, &hashes[..1]).unwrap());
+        assert_eq!(
+            vec![Some(PathBuf::from("2024/a.jpeg")), None],
+            select_review(&connection)
+                .unwrap()
+                .into_iter()
+                .map(|i| i.path)
+                .collect::<Vec<Option<PathBuf>>>()
+        );
+
+        assert_eq!(
+            1,
+            remove_from_review(&mut connection, &hashes[1..]).unwrap()
+        );
+        assert_eq!(1, select_review(&connection).unwrap().len());
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// The terms of a query saved under a name.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SavedQuery {
+    pub(crate) name: String,
+    pub(crate) terms: String,
+    pub(crate) saved_at: NaiveDateTime,
+}
+
+/// Saves the query terms under the name, replacing the query saved under it.
+/// Tells whether a query was replaced.
+pub(crate) fn save_query(connection: &Connection, name: &str, terms: &str) -> Result<bool> {
+    let replaced = select_saved_query(connection, name)?.is_some();
+    connection.execute(
+        "INSERT OR REPLACE INTO saved_query (name, terms, saved_at) VALUES (?1, ?2, ?3)",
+        params![name, terms, Local::now().naive_local()],
+    )?;
+    Ok(replaced)
+}
+
+/// The terms of the query saved under the name.
+pub(crate) fn select_saved_query(connection: &Connection, name: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row(
+            "SELECT terms FROM saved_query WHERE name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// The saved queries, by name.
+pub(crate) fn select_saved_queries(connection: &Connection) -> Result<Vec<SavedQuery>> {
+    let mut statement =
+        connection.prepare("SELECT name, terms, saved_at FROM saved_query ORDER BY name")?;
+    let queries = statement
+        .query_map([], |row| {
+            Ok(SavedQuery {
+                name: row.get(0)?,
+                terms: row.get(1)?,
+                saved_at: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<SavedQuery>, rusqlite::Error>>()?;
+    Ok(queries)
+}
+
+/// Removes the query saved under the name. Tells whether there was one.
+pub(crate) fn remove_saved_query(connection: &Connection, name: &str) -> Result<bool> {
+    Ok(connection.execute("DELETE FROM saved_query WHERE name = ?1", [name])? > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{remove_saved_query, save_query, select_saved_queries, select_saved_query};
+
+    #[test]
+    fn save_query_replaces_the_query_of_the_name() {
+        let connection = new_database();
+
+        assert!(!save_query(&connection, "beach", "tag:beach").unwrap());
+        assert!(save_query(&connection, "beach", "tag:beach year:2024").unwrap());
+        save_query(&connection, "anna", "by:anna").unwrap();
+
+        assert_eq!(
+            Some("tag:beach year:2024".to_string()),
+            select_saved_query(&connection, "beach").unwrap()
+        );
+        assert_eq!(
+            vec!["anna", "beach"],
+            select_saved_queries(&connection)
+                .unwrap()
+                .iter()
+                .map(|q| q.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+
+        assert!(remove_saved_query(&connection, "beach").unwrap());
+        assert!(!remove_saved_query(&connection, "beach").unwrap());
+        assert_eq!(None, select_saved_query(&connection, "beach").unwrap());
+    }
+}
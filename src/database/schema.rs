@@ -0,0 +1,557 @@
+use eyre::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::{migrate, schema_version};
+
+/// The name and description of a column.
+type ColumnDocumentation = (&'static str, &'static str);
+/// The name, description and columns of a table or view.
+type TableDocumentation = (&'static str, &'static str, &'static [ColumnDocumentation]);
+
+/// The documentation of the tables and views created by the migrations. The structure itself
+/// comes from the migrated database, the tests ensure every table and column is
+/// documented here.
+const DOCUMENTATION: &[TableDocumentation] = &[
+    (
+        "catalog",
+        "Files found in the cataloged directories, candidates for import.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            ("path", "Absolute path of the file."),
+            ("operation", "The operation that cataloged the file."),
+            (
+                "device",
+                "The device of the file, with the inode identifying the physical file.",
+            ),
+            ("inode", "The inode of the file on its device."),
+            ("size", "The size of the file in bytes, unknown for older catalogs."),
+            (
+                "modified",
+                "The modification time of the file, in nanoseconds since the epoch, unknown for older catalogs.",
+            ),
+        ],
+    ),
+    (
+        "catalog_root",
+        "Directories that were cataloged.",
+        &[("path", "Absolute path of the directory.")],
+    ),
+    (
+        "check_finding",
+        "Pictures the checks found missing, corrupt or unreadable, recorded as they are found.",
+        &[
+            ("name", "The check, library or catalog."),
+            ("path", "Path of the picture."),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest the picture was expected to have.",
+            ),
+            ("status", "MISSING, CORRUPT or FAILED."),
+            ("found_at", "Local time the picture was checked."),
+        ],
+    ),
+    (
+        "check_progress",
+        "Positions the time-boxed checks resume from.",
+        &[
+            ("name", "The check, library or catalog."),
+            ("position", "The last path checked, in path order."),
+            ("updated_at", "Local time the position was recorded."),
+        ],
+    ),
+    (
+        "event",
+        "Named ranges of capture dates, from events name.",
+        &[
+            ("start_date", "First capture date of the event."),
+            ("end_date", "Last capture date of the event, included."),
+            ("name", "The name of the event, e.g. Lake trip."),
+        ],
+    ),
+    (
+        "fingerprint",
+        "Hashes of the cataloged files, reused while their fingerprint matches.",
+        &[
+            ("device", "The device of the file."),
+            ("inode", "The inode of the file on its device."),
+            ("size", "The size of the file in bytes."),
+            (
+                "modified",
+                "The modification time of the file, in nanoseconds since the epoch.",
+            ),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+        ],
+    ),
+    (
+        "invocation",
+        "Arguments of the last run of each command, repeated by --again.",
+        &[
+            ("command", "The command name, e.g. check."),
+            (
+                "arguments",
+                "JSON array of the arguments after the command name.",
+            ),
+            ("invoked_at", "Local time the command was run."),
+        ],
+    ),
+    (
+        "library",
+        "Files imported in the library, one per distinct content.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            ("path", "Path of the file relative to the repository root."),
+            ("mime_type", "Media type detected from the file header."),
+            (
+                "original_date",
+                "Date the picture was taken, from its metadata or a date fallback, as YYYY-MM-DD.",
+            ),
+            (
+                "imported_at",
+                "Local time the file was imported, unknown for older imports.",
+            ),
+            (
+                "imported_by",
+                "The person who imported the file, from PHOTO_WORKS_USER or the user configuration.",
+            ),
+            (
+                "size",
+                "Size of the file in bytes, unknown for older imports.",
+            ),
+            (
+                "original_name",
+                "Name of the imported file before sanitization, unknown for older imports.",
+            ),
+            (
+                "kind",
+                "photo, video, animation or screenshot, unknown for older imports.",
+            ),
+            (
+                "date_source",
+                "Where the original date was read: metadata, filename or mtime, unknown for older imports.",
+            ),
+            (
+                "altitude",
+                "GPS altitude in meters above the sea level, negative below, when the metadata has one.",
+            ),
+            (
+                "direction",
+                "GPS direction the camera faced, in degrees from the north, when the metadata has one.",
+            ),
+            (
+                "speed",
+                "GPS speed of the camera in km/h, when the metadata has one.",
+            ),
+        ],
+    ),
+    (
+        "layout_deviation",
+        "Library files outside the dominant layout detected on adopt, until relayout moves them.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the deviating content.",
+            ),
+            (
+                "layout",
+                "The layouts the directory of the file matches, e.g. event, or none.",
+            ),
+            ("detected_at", "Local time the deviation was detected."),
+        ],
+    ),
+    (
+        "library_history",
+        "Imports, moves and removals of the library files, recorded by triggers, which history at replays.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            (
+                "path",
+                "Path of the file relative to the repository root, after the change.",
+            ),
+            ("change", "import, move or remove."),
+            (
+                "changed_at",
+                "Local time of the change, unknown for the imports older than the history.",
+            ),
+        ],
+    ),
+    (
+        "library_root",
+        "Named roots of the library, from the [roots] configuration at the last import.",
+        &[
+            ("name", "The name the import rules select the root with."),
+            (
+                "path",
+                "Directory of the root, relative to the repository root or absolute.",
+            ),
+        ],
+    ),
+    (
+        "operation",
+        "Commands that changed the catalog, in the order they ran.",
+        &[
+            ("id", "Increasing identifier of the operation."),
+            ("command", "The command, e.g. catalog or apply."),
+            ("argument", "The path or bundle the command ran on."),
+            ("started_at", "Local time the command started."),
+            (
+                "imported_by",
+                "The person who ran the command, from PHOTO_WORKS_USER or the user configuration.",
+            ),
+        ],
+    ),
+    (
+        "pair",
+        "RAW pictures and the JPEG the camera wrote along, found by catalog in a same directory under a same name.",
+        &[
+            (
+                "raw",
+                "Uppercase hexadecimal sha256 digest of the RAW content.",
+            ),
+            (
+                "companion",
+                "Uppercase hexadecimal sha256 digest of the companion content.",
+            ),
+        ],
+    ),
+    (
+        "problem",
+        "Catalog entries flagged as corrupt, excluded from import.",
+        &[
+            ("path", "Absolute path of the cataloged file."),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            ("description", "Why the file is considered corrupt."),
+        ],
+    ),
+    (
+        "protected",
+        "Contents that prune and trash never delete.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the protected content.",
+            ),
+            ("added_at", "Local time the content was protected."),
+        ],
+    ),
+    (
+        "published",
+        "Library files published to the gallery servers of the [publish] configuration.",
+        &[
+            ("target", "The name of the [publish] table of the server."),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the published content.",
+            ),
+            ("remote_id", "The id of the picture on the server."),
+            (
+                "metadata",
+                "The JSON of the title, caption and tags last sent with the picture.",
+            ),
+            ("published_at", "Local time the picture was last sent."),
+        ],
+    ),
+    (
+        "review",
+        "Library files queued for review, until marked done.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the queued content.",
+            ),
+            ("added_at", "Local time the content was queued."),
+        ],
+    ),
+    (
+        "saved_query",
+        "Query terms saved under a name by query save, selected with --saved.",
+        &[
+            ("name", "Name the query is saved under."),
+            ("terms", "Query terms, as passed to --query."),
+            ("saved_at", "Local time the query was last saved."),
+        ],
+    ),
+    (
+        "sidecar",
+        "XMP sidecars copied next to the RAW library files by import.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the RAW content.",
+            ),
+            ("path", "Path of the sidecar relative to the repository root."),
+        ],
+    ),
+    (
+        "stack",
+        "HDR brackets and panorama sweeps, the frames of a same shot taken in a burst.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the frame.",
+            ),
+            (
+                "stack",
+                "Uppercase hexadecimal sha256 digest of the first frame of the stack.",
+            ),
+            ("kind", "bracket or panorama."),
+        ],
+    ),
+    (
+        "sync_state",
+        "Library contents held by each backup or publishing target, for sync status.",
+        &[
+            (
+                "target",
+                "The target, e.g. publish:family or export:/mnt/backup.",
+            ),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the library content.",
+            ),
+            (
+                "synced_hash",
+                "Uppercase hexadecimal sha256 digest of the file the target received, e.g. once encrypted.",
+            ),
+            ("synced_at", "Local time the content was last synced."),
+        ],
+    ),
+    (
+        "tag",
+        "Tags given to library files.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the tagged content.",
+            ),
+            ("name", "The tag."),
+        ],
+    ),
+    (
+        "trashed",
+        "Files moved to the trash by prune and inbox reject, until the trash is emptied.",
+        &[
+            (
+                "path",
+                "Path of the file in the trash, relative to the repository root.",
+            ),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the trashed content.",
+            ),
+            ("original_path", "Path the file was moved from."),
+            ("trashed_at", "Local time the file was moved to the trash."),
+            (
+                "redundant",
+                "1 when prune duplicates trashed the file as a redundant copy of a content it kept.",
+            ),
+        ],
+    ),
+    (
+        "version",
+        "Library files derived from a master file, e.g. edits exported from a RAW.",
+        &[
+            (
+                "master",
+                "Uppercase hexadecimal sha256 digest of the master content.",
+            ),
+            (
+                "derived",
+                "Uppercase hexadecimal sha256 digest of the derived content.",
+            ),
+            ("tool", "The editing tool, unknown for detected versions."),
+            ("created_at", "Local time the versions were linked."),
+        ],
+    ),
+    (
+        "v_duplicates",
+        "Catalog entries sharing their content with other physical files, one path per file.",
+        &[
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            ("path", "Absolute path of the cataloged file."),
+            (
+                "copies",
+                "Number of physical files with this content, paths sharing a device and inode counting once.",
+            ),
+        ],
+    ),
+    (
+        "v_pending_import",
+        "Catalog entries neither in the library nor flagged as corrupt.",
+        &[
+            ("path", "Absolute path of the cataloged file."),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+        ],
+    ),
+    (
+        "v_photos",
+        "Library files with their metadata.",
+        &[
+            ("path", "Path of the file relative to the repository root."),
+            (
+                "hash",
+                "Uppercase hexadecimal sha256 digest of the content.",
+            ),
+            ("mime_type", "Media type detected from the file header."),
+            (
+                "original_date",
+                "Date the picture was taken, as YYYY-MM-DD.",
+            ),
+            ("year", "Year the picture was taken."),
+        ],
+    ),
+];
+
+/// Tables managed by tools rather than by the photo_works migrations.
+const INTERNAL_TABLES: &[&str] = &["refinery_schema_history", "sqlite_sequence"];
+
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct Schema {
+    pub(crate) version: u32,
+    pub(crate) tables: Vec<Table>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct Table {
+    pub(crate) name: String,
+    /// `table`, or `view` for the read-only shapes meant for external tools.
+    pub(crate) kind: String,
+    pub(crate) description: Option<&'static str>,
+    pub(crate) columns: Vec<Column>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct Column {
+    pub(crate) name: String,
+    #[serde(rename = "type")]
+    pub(crate) sql_type: String,
+    pub(crate) nullable: bool,
+    pub(crate) primary_key: bool,
+    pub(crate) description: Option<&'static str>,
+}
+
+/// The schema created by the migrations of this build, with its documentation.
+pub(crate) fn current_schema() -> Result<Schema> {
+    let mut connection = Connection::open_in_memory()?;
+    migrate(&mut connection)?;
+    let (_, version) = schema_version(&mut connection)?;
+    let mut statement = connection.prepare(
+        "SELECT name, type FROM sqlite_master WHERE type IN ('table', 'view') ORDER BY type, name",
+    )?;
+    let names = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<(String, String)>, rusqlite::Error>>()?;
+    let tables = names
+        .into_iter()
+        .filter(|(name, _)| !INTERNAL_TABLES.contains(&name.as_str()))
+        .map(|(name, kind)| table(&connection, name, kind))
+        .collect::<Result<Vec<Table>>>()?;
+    Ok(Schema { version, tables })
+}
+
+fn table(connection: &Connection, name: String, kind: String) -> Result<Table> {
+    let documentation = DOCUMENTATION.iter().find(|(table, _, _)| *table == name);
+    let mut statement = connection.prepare(&format!("PRAGMA table_info({})", name))?;
+    let columns = statement
+        .query_map([], |row| {
+            let column: String = row.get("name")?;
+            let description = documentation.and_then(|(_, _, columns)| {
+                columns
+                    .iter()
+                    .find(|(name, _)| *name == column)
+                    .map(|(_, description)| *description)
+            });
+            Ok(Column {
+                name: column,
+                sql_type: row.get("type")?,
+                nullable: row.get::<_, i64>("notnull")? == 0,
+                primary_key: row.get::<_, i64>("pk")? > 0,
+                description,
+            })
+        })?
+        .collect::<Result<Vec<Column>, rusqlite::Error>>()?;
+    Ok(Table {
+        name,
+        kind,
+        description: documentation.map(|(_, description, _)| *description),
+        columns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_schema, DOCUMENTATION};
+
+    #[test]
+    fn every_table_and_column_is_documented() {
+        let schema = current_schema().unwrap();
+        for table in &schema.tables {
+            assert!(
+                table.description.is_some(),
+                "table {} is not documented",
+                table.name
+            );
+            for column in &table.columns {
+                assert!(
+                    column.description.is_some(),
+                    "column {}.{} is not documented",
+                    table.name,
+                    column.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_documented_table_exists() {
+        let schema = current_schema().unwrap();
+        for (name, _, _) in DOCUMENTATION {
+            assert!(
+                schema.tables.iter().any(|t| t.name == *name),
+                "table {} does not exist",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn current_schema_reads_the_columns() {
+        let schema = current_schema().unwrap();
+        let catalog = schema.tables.iter().find(|t| t.name == "catalog").unwrap();
+        let path = catalog.columns.iter().find(|c| c.name == "path").unwrap();
+
+        assert_eq!("TEXT", path.sql_type);
+        assert!(path.primary_key);
+    }
+
+    #[test]
+    fn current_schema_reads_the_views() {
+        let schema = current_schema().unwrap();
+        let photos = schema.tables.iter().find(|t| t.name == "v_photos").unwrap();
+
+        assert_eq!("view", photos.kind);
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// Records the `(hash, path)` sidecars copied next to the library files of the
+/// contents, replacing those recorded before.
+pub(crate) fn record_sidecars(
+    connection: &Connection,
+    sidecars: &[(String, PathBuf)],
+) -> Result<usize> {
+    let mut statement =
+        connection.prepare("INSERT OR REPLACE INTO sidecar (hash, path) VALUES (?1, ?2)")?;
+    let mut count = 0;
+    for (hash, path) in sidecars {
+        count += statement.execute(params![hash, path.to_string_lossy()])?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::test_utils::new_database;
+
+    use super::record_sidecars;
+
+    #[test]
+    fn record_sidecars_replaces_the_path_of_the_content() {
+        let connection = new_database();
+        let sidecar = |path: &str| ("H1".to_string(), PathBuf::from(path));
+
+        record_sidecars(&connection, &[sidecar("2024/IMG_0001.xmp")]).unwrap();
+        record_sidecars(&connection, &[sidecar("2024/IMG_0001.CR2.xmp")]).unwrap();
+
+        assert_eq!(
+            "2024/IMG_0001.CR2.xmp",
+            connection
+                .query_row("SELECT path FROM sidecar WHERE hash = 'H1'", [], |r| r
+                    .get::<_, String>(
+                    0
+                ))
+                .unwrap()
+        );
+    }
+}
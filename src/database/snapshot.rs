@@ -0,0 +1,100 @@
+use std::{
+    fs::{copy, create_dir_all, read_dir, remove_file, rename},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use eyre::{eyre, Result};
+
+use super::connect;
+
+const SNAPSHOT_EXTENSION: &str = "db3";
+
+/// Writes a consistent copy of the database into the directory, named after the
+/// current time, then removes the oldest copies beyond `keep`. Returns the path of
+/// the copy.
+pub(crate) fn snapshot(db: &PathBuf, directory: &Path, keep: usize) -> Result<PathBuf> {
+    create_dir_all(directory)?;
+    let path = directory.join(format!(
+        "{}.{}",
+        Local::now().format("%Y%m%dT%H%M%S%.3f"),
+        SNAPSHOT_EXTENSION
+    ));
+    connect(db)?.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    let snapshots = list_snapshots(directory)?;
+    for old in &snapshots[..snapshots.len().saturating_sub(keep)] {
+        remove_file(old)?;
+    }
+    Ok(path)
+}
+
+/// The snapshots of the directory, oldest first.
+pub(crate) fn list_snapshots(directory: &Path) -> Result<Vec<PathBuf>> {
+    if !directory.exists() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = read_dir(directory)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<PathBuf>, std::io::Error>>()?
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e == SNAPSHOT_EXTENSION))
+        .collect::<Vec<PathBuf>>();
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Replaces the database with the snapshot. No connection to the database may be
+/// open.
+pub(crate) fn restore(db: &Path, snapshot: &Path) -> Result<()> {
+    if !snapshot.is_file() {
+        return Err(eyre!("No snapshot {}", snapshot.display()));
+    }
+    let restored = db.with_extension("restore");
+    copy(snapshot, &restored)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = db.as_os_str().to_owned();
+        journal.push(suffix);
+        let journal = PathBuf::from(journal);
+        if journal.exists() {
+            remove_file(journal)?;
+        }
+    }
+    Ok(rename(restored, db)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::database::open;
+
+    use super::{list_snapshots, restore, snapshot};
+
+    #[test]
+    fn snapshot_keeps_the_latest_copies_and_restores_them() {
+        let directory = TempDir::new().unwrap();
+        let db = directory.path().join("db.db3");
+        let snapshots = directory.path().join("snapshots");
+        let connection = open(&db).unwrap();
+        connection
+            .execute("INSERT INTO tag (hash, name) VALUES ('AB', 'beach')", [])
+            .unwrap();
+
+        let first = snapshot(&db, &snapshots, 2).unwrap();
+        snapshot(&db, &snapshots, 2).unwrap();
+        let last = snapshot(&db, &snapshots, 2).unwrap();
+        connection.execute("DELETE FROM tag", []).unwrap();
+        drop(connection);
+
+        assert!(!first.exists());
+        assert_eq!(2, list_snapshots(&snapshots).unwrap().len());
+        restore(&db, &last).unwrap();
+        assert_eq!(
+            1,
+            open(&db)
+                .unwrap()
+                .query_row("SELECT count(*) FROM tag", [], |r| r.get::<_, i64>(0))
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+
+use crate::media::stack::StackKind;
+
+/// The frames of a bracket or a panorama, kept as one shot.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Stack {
+    pub(crate) kind: StackKind,
+    /// The digests of the frames, the first frame first.
+    pub(crate) hashes: Vec<String>,
+}
+
+/// Records the stacks of frames, each under the digest of its first frame. The
+/// frames already in a stack stay in it. Returns the number of frames recorded.
+pub(crate) fn record_stacks(connection: &mut Connection, stacks: &[Stack]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction
+            .prepare("INSERT OR IGNORE INTO stack (hash, stack, kind) VALUES (?1, ?2, ?3)")?;
+        for stack in stacks {
+            let Some(first) = stack.hashes.first() else {
+                continue;
+            };
+            for hash in &stack.hashes {
+                count += statement.execute([hash, first, &stack.kind.to_string()])?;
+            }
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// The recorded stacks, by the digest of their first frame.
+pub(crate) fn select_stacks(connection: &Connection) -> Result<BTreeMap<String, Stack>> {
+    let mut statement = connection
+        .prepare("SELECT stack, kind, hash FROM stack ORDER BY stack, hash = stack DESC, rowid")?;
+    let mut stacks: BTreeMap<String, Stack> = BTreeMap::new();
+    for row in statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))? {
+        let (id, kind, hash): (String, String, String) = row?;
+        if !stacks.contains_key(&id) {
+            let kind = kind.parse::<StackKind>().map_err(|e| eyre!(e))?;
+            stacks.insert(
+                id.clone(),
+                Stack {
+                    kind,
+                    hashes: vec![],
+                },
+            );
+        }
+        stacks.get_mut(&id).expect("inserted").hashes.push(hash);
+    }
+    Ok(stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::test_utils::new_database, media::stack::StackKind};
+
+    use super::{record_stacks, select_stacks, Stack};
+
+    #[test]
+    fn select_stacks_lists_the_frames_first_frame_first() {
+        let mut connection = new_database();
+        let stacks = [Stack {
+            kind: StackKind::Bracket,
+            hashes: vec!["B".to_string(), "A".to_string(), "C".to_string()],
+        }];
+
+        assert_eq!(3, record_stacks(&mut connection, &stacks).unwrap());
+        assert_eq!(0, record_stacks(&mut connection, &stacks).unwrap());
+
+        let selected = select_stacks(&connection).unwrap();
+        assert_eq!(1, selected.len());
+        assert_eq!(stacks[0], selected["B"]);
+    }
+}
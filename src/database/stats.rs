@@ -0,0 +1,362 @@
+use eyre::Result;
+use rusqlite::{params_from_iter, Connection};
+
+use super::library::LibraryFilter;
+
+/// The number of files in each state of the repository.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Counts {
+    pub(crate) cataloged: i64,
+    pub(crate) imported: i64,
+    pub(crate) pending_import: i64,
+    /// Catalog entries whose content is already cataloged under another path.
+    pub(crate) duplicate_copies: i64,
+    pub(crate) problems: i64,
+}
+
+pub(crate) fn counts(connection: &Connection) -> Result<Counts> {
+    let count = |query: &str| connection.query_row(query, [], |r| r.get::<_, i64>(0));
+    Ok(Counts {
+        cataloged: count("SELECT count(*) FROM catalog")?,
+        imported: count("SELECT count(*) FROM library")?,
+        pending_import: count("SELECT count(*) FROM v_pending_import")?,
+        duplicate_copies: count("SELECT count(*) - count(DISTINCT hash) FROM v_duplicates")?,
+        problems: count("SELECT count(*) FROM problem")?,
+    })
+}
+
+/// The files imported during a period.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Growth {
+    /// The month as YYYY-MM or the year as YYYY, none for the imports that predate
+    /// the recording of the import time.
+    pub(crate) period: Option<String>,
+    pub(crate) files: i64,
+    /// The bytes of the files whose size is known.
+    pub(crate) bytes: i64,
+}
+
+/// The growth of the filtered library per month, or per year, in chronological
+/// order.
+pub(crate) fn growth(
+    connection: &Connection,
+    filter: &LibraryFilter,
+    yearly: bool,
+) -> Result<Vec<Growth>> {
+    let (library, values) = filter.library_table();
+    let mut statement = connection.prepare(&format!(
+        "SELECT substr(imported_at, 1, {}) AS period, count(*), COALESCE(sum(size), 0) FROM {} GROUP BY period ORDER BY period",
+        if yearly { 4 } else { 7 },
+        library
+    ))?;
+    let rows = statement
+        .query_map(params_from_iter(values), |row| {
+            Ok(Growth {
+                period: row.get(0)?,
+                files: row.get(1)?,
+                bytes: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<Growth>, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+/// The files of a library root.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RootUsage {
+    /// The name of the root, none for the files outside the named roots.
+    pub(crate) name: Option<String>,
+    pub(crate) files: i64,
+    /// The bytes of the files whose size is known.
+    pub(crate) bytes: i64,
+}
+
+/// The files and bytes of the filtered library in each recorded library root,
+/// then in the rest of the library.
+pub(crate) fn root_usage(
+    connection: &Connection,
+    filter: &LibraryFilter,
+) -> Result<Vec<RootUsage>> {
+    let (library, values) = filter.library_table();
+    let mut statement = connection.prepare(&format!(
+        "SELECT library_root.name, count(library.hash), COALESCE(sum(library.size), 0) FROM library_root LEFT JOIN {} ON substr(library.path, 1, length(library_root.path) + 1) = library_root.path || '/' GROUP BY library_root.name ORDER BY library_root.name",
+        library
+    ))?;
+    let mut rows = statement
+        .query_map(params_from_iter(&values), |row| {
+            Ok(RootUsage {
+                name: row.get(0)?,
+                files: row.get(1)?,
+                bytes: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<RootUsage>, rusqlite::Error>>()?;
+    let (files, bytes) = connection.query_row(
+        &format!("SELECT count(*), COALESCE(sum(size), 0) FROM {}", library),
+        params_from_iter(&values),
+        |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)),
+    )?;
+    rows.push(RootUsage {
+        name: None,
+        files: files - rows.iter().map(|r| r.files).sum::<i64>(),
+        bytes: bytes - rows.iter().map(|r| r.bytes).sum::<i64>(),
+    });
+    Ok(rows)
+}
+
+/// The files of a kind.
+#[derive(Debug, PartialEq)]
+pub(crate) struct KindUsage {
+    /// The kind, none for the files imported before the classification.
+    pub(crate) kind: Option<String>,
+    pub(crate) files: i64,
+    /// The files counting the frames of a bracket or a panorama as one.
+    pub(crate) shots: i64,
+    /// The bytes of the files whose size is known.
+    pub(crate) bytes: i64,
+}
+
+/// The files and bytes of each kind of the filtered library.
+pub(crate) fn kind_usage(
+    connection: &Connection,
+    filter: &LibraryFilter,
+) -> Result<Vec<KindUsage>> {
+    let (library, values) = filter.library_table();
+    let mut statement = connection.prepare(&format!(
+        "SELECT library.kind, count(*), count(DISTINCT COALESCE(stack.stack, library.hash)), COALESCE(sum(library.size), 0) FROM {} LEFT JOIN stack ON stack.hash = library.hash GROUP BY library.kind ORDER BY library.kind",
+        library
+    ))?;
+    let rows = statement
+        .query_map(params_from_iter(values), |row| {
+            Ok(KindUsage {
+                kind: row.get(0)?,
+                files: row.get(1)?,
+                shots: row.get(2)?,
+                bytes: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<KindUsage>, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+/// The files imported by a person.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PersonUsage {
+    /// The person, none for the imports not attributed to anyone.
+    pub(crate) imported_by: Option<String>,
+    pub(crate) files: i64,
+    /// The bytes of the files whose size is known.
+    pub(crate) bytes: i64,
+}
+
+/// The files and bytes of the filtered library imported by each person.
+pub(crate) fn person_usage(
+    connection: &Connection,
+    filter: &LibraryFilter,
+) -> Result<Vec<PersonUsage>> {
+    let (library, values) = filter.library_table();
+    let mut statement = connection.prepare(&format!(
+        "SELECT imported_by, count(*), COALESCE(sum(size), 0) FROM {} GROUP BY imported_by ORDER BY imported_by",
+        library
+    ))?;
+    let rows = statement
+        .query_map(params_from_iter(values), |row| {
+            Ok(PersonUsage {
+                imported_by: row.get(0)?,
+                files: row.get(1)?,
+                bytes: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<PersonUsage>, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::{library::LibraryFilter, test_utils::new_database};
+
+    use super::{
+        counts, growth, kind_usage, person_usage, root_usage, Counts, Growth, KindUsage,
+        PersonUsage, RootUsage,
+    };
+
+    #[test]
+    fn person_usage_groups_the_library_by_importer() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library (hash, path, size, imported_by) VALUES ('H1', 'a.jpg', 10, 'anna');
+                INSERT INTO library (hash, path, size, imported_by) VALUES ('H2', 'b.jpg', 1, 'anna');
+                INSERT INTO library (hash, path, size) VALUES ('H3', 'c.jpg', 2);",
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                PersonUsage {
+                    imported_by: None,
+                    files: 1,
+                    bytes: 2
+                },
+                PersonUsage {
+                    imported_by: Some("anna".to_string()),
+                    files: 2,
+                    bytes: 11
+                }
+            ],
+            person_usage(&connection, &LibraryFilter::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn kind_usage_groups_the_library_by_kind() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library (hash, path, size, kind) VALUES ('H1', 'a.gif', 10, 'animation');
+                INSERT INTO library (hash, path, size, kind) VALUES ('H2', 'b.jpg', 1, 'photo');
+                INSERT INTO library (hash, path, size, kind) VALUES ('H3', 'c.jpg', 2, 'photo');
+                INSERT INTO library (hash, path) VALUES ('H4', 'd.jpg');
+                INSERT INTO stack (hash, stack, kind) VALUES ('H2', 'H2', 'bracket');
+                INSERT INTO stack (hash, stack, kind) VALUES ('H3', 'H2', 'bracket');",
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                KindUsage {
+                    kind: None,
+                    files: 1,
+                    shots: 1,
+                    bytes: 0
+                },
+                KindUsage {
+                    kind: Some("animation".to_string()),
+                    files: 1,
+                    shots: 1,
+                    bytes: 10
+                },
+                KindUsage {
+                    kind: Some("photo".to_string()),
+                    files: 2,
+                    shots: 1,
+                    bytes: 3
+                }
+            ],
+            kind_usage(&connection, &LibraryFilter::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn kind_usage_only_counts_the_filtered_library() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library (hash, path, size, kind) VALUES ('H1', 'a.jpg', 10, 'photo');
+                INSERT INTO library (hash, path, size, kind) VALUES ('H2', 'b.jpg', 1, 'photo');
+                INSERT INTO tag (hash, name) VALUES ('H1', 'wedding2019');",
+            )
+            .unwrap();
+        let filter = "tag:wedding2019".parse::<LibraryFilter>().unwrap();
+
+        assert_eq!(
+            vec![KindUsage {
+                kind: Some("photo".to_string()),
+                files: 1,
+                shots: 1,
+                bytes: 10
+            }],
+            kind_usage(&connection, &filter).unwrap()
+        );
+    }
+
+    #[test]
+    fn root_usage_splits_the_library_by_root() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library_root (name, path) VALUES ('drone', 'drone');
+                INSERT INTO library (hash, path, size) VALUES ('H1', 'drone/2024/1/1/a.mp4', 10);
+                INSERT INTO library (hash, path, size) VALUES ('H2', 'drones/2024/1/1/b.jpg', 1);
+                INSERT INTO library (hash, path, size) VALUES ('H3', '2024/1/1/c.jpg', 2);",
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                RootUsage {
+                    name: Some("drone".to_string()),
+                    files: 1,
+                    bytes: 10
+                },
+                RootUsage {
+                    name: None,
+                    files: 2,
+                    bytes: 3
+                }
+            ],
+            root_usage(&connection, &LibraryFilter::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn counts_reads_the_tables_and_views() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO catalog (hash, path) VALUES ('H1', '/a'), ('H1', '/b'), ('H2', '/c'), ('H3', '/d');
+                INSERT INTO library (hash, path) VALUES ('H2', '2023/c');
+                INSERT INTO problem (hash, path, description) VALUES ('H3', '/d', 'truncated');",
+            )
+            .unwrap();
+
+        assert_eq!(
+            Counts {
+                cataloged: 4,
+                imported: 1,
+                pending_import: 2,
+                duplicate_copies: 1,
+                problems: 1,
+            },
+            counts(&connection).unwrap()
+        );
+    }
+
+    #[test]
+    fn growth_groups_the_imports_by_period() {
+        let connection = new_database();
+        connection
+            .execute_batch(
+                "INSERT INTO library (hash, path) VALUES ('H0', '2019/a');
+                INSERT INTO library (hash, path, imported_at, size) VALUES ('H1', '2023/b', '2024-01-05T10:00:00', 10), ('H2', '2023/c', '2024-01-20T10:00:00', 5), ('H3', '2023/d', '2024-03-01T10:00:00', NULL);",
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                Growth {
+                    period: None,
+                    files: 1,
+                    bytes: 0
+                },
+                Growth {
+                    period: Some("2024-01".to_string()),
+                    files: 2,
+                    bytes: 15
+                },
+                Growth {
+                    period: Some("2024-03".to_string()),
+                    files: 1,
+                    bytes: 0
+                },
+            ],
+            growth(&connection, &LibraryFilter::default(), false).unwrap()
+        );
+        assert_eq!(
+            2,
+            growth(&connection, &LibraryFilter::default(), true)
+                .unwrap()
+                .len()
+        );
+    }
+}
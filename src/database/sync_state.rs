@@ -0,0 +1,144 @@
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// How far a backup or publishing target lags behind the library.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SyncStatus {
+    /// The target, e.g. publish:family or export:/mnt/backup.
+    pub(crate) target: String,
+    /// The library contents the target holds.
+    pub(crate) synced: i64,
+    /// The library contents the target is missing.
+    pub(crate) pending: i64,
+    /// The bytes of the pending contents whose size is known.
+    pub(crate) pending_bytes: i64,
+    /// The import time of the oldest pending content, when known.
+    pub(crate) oldest_pending: Option<NaiveDateTime>,
+    /// The contents the target holds that are no longer in the library.
+    pub(crate) removed: i64,
+    /// The last time a content was synced to the target.
+    pub(crate) last_synced_at: Option<NaiveDateTime>,
+}
+
+/// Records the `(hash, synced hash)` contents as held by the target, replacing
+/// their previous sync.
+pub(crate) fn record_synced(
+    connection: &Connection,
+    target: &str,
+    synced: &[(String, String)],
+) -> Result<usize> {
+    let mut statement = connection.prepare(
+        "INSERT OR REPLACE INTO sync_state (target, hash, synced_hash, synced_at) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    let synced_at = Local::now().naive_local();
+    let mut count = 0;
+    for (hash, synced_hash) in synced {
+        count += statement.execute(params![target, hash, synced_hash, synced_at])?;
+    }
+    Ok(count)
+}
+
+/// The targets holding the content, the most recently synced first.
+pub(crate) fn select_holding_targets(connection: &Connection, hash: &str) -> Result<Vec<String>> {
+    let mut statement = connection
+        .prepare("SELECT target FROM sync_state WHERE hash = ?1 ORDER BY synced_at DESC")?;
+    let targets = statement
+        .query_map([hash], |row| row.get(0))?
+        .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+    Ok(targets)
+}
+
+/// The status of the recorded targets along with the other targets, in target
+/// order.
+pub(crate) fn sync_status(connection: &Connection, targets: &[String]) -> Result<Vec<SyncStatus>> {
+    let mut all = connection
+        .prepare("SELECT DISTINCT target FROM sync_state")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+    all.extend(targets.iter().cloned());
+    all.sort();
+    all.dedup();
+    all.into_iter()
+        .map(|target| {
+            let (synced, removed, last_synced_at) = connection.query_row(
+                "SELECT COALESCE(sum(hash IN (SELECT hash FROM library)), 0), COALESCE(sum(hash NOT IN (SELECT hash FROM library)), 0), max(synced_at) FROM sync_state WHERE target = ?1",
+                [&target],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+            let (pending, pending_bytes, oldest_pending) = connection.query_row(
+                "SELECT count(*), COALESCE(sum(size), 0), min(imported_at) FROM library WHERE hash NOT IN (SELECT hash FROM sync_state WHERE target = ?1)",
+                [&target],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+            Ok(SyncStatus {
+                target,
+                synced,
+                pending,
+                pending_bytes,
+                oldest_pending,
+                removed,
+                last_synced_at,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::{
+        library_entry::LibraryEntry, test_utils::new_database_containing_library_entries,
+    };
+
+    use super::{record_synced, select_holding_targets, sync_status};
+
+    #[test]
+    fn sync_status_counts_the_pending_and_removed_contents_of_each_target() {
+        let connection = new_database_containing_library_entries(&vec![
+            LibraryEntry::new("H1".to_string(), PathBuf::from("a.jpeg")),
+            LibraryEntry::new("H2".to_string(), PathBuf::from("b.jpeg")),
+        ]);
+        let synced = |hashes: &[&str]| {
+            hashes
+                .iter()
+                .map(|h| (h.to_string(), h.to_string()))
+                .collect::<Vec<(String, String)>>()
+        };
+        record_synced(&connection, "export:/backup", &synced(&["H1", "H2", "H3"])).unwrap();
+        record_synced(&connection, "publish:family", &synced(&["H1"])).unwrap();
+
+        let status = sync_status(&connection, &["publish:public".to_string()]).unwrap();
+
+        assert_eq!(
+            vec![
+                ("export:/backup", 2, 0, 1),
+                ("publish:family", 1, 1, 0),
+                ("publish:public", 0, 2, 0)
+            ],
+            status
+                .iter()
+                .map(|s| (s.target.as_str(), s.synced, s.pending, s.removed))
+                .collect::<Vec<(&str, i64, i64, i64)>>()
+        );
+        assert!(status[0].last_synced_at.is_some());
+        assert_eq!(None, status[2].last_synced_at);
+    }
+
+    #[test]
+    fn select_holding_targets_lists_the_targets_of_the_content() {
+        let connection = new_database_containing_library_entries(&vec![]);
+        let synced = vec![("H1".to_string(), "E1".to_string())];
+        record_synced(&connection, "export:/backup", &synced).unwrap();
+        record_synced(&connection, "publish:family", &synced).unwrap();
+
+        let mut targets = select_holding_targets(&connection, "H1").unwrap();
+        targets.sort();
+
+        assert_eq!(vec!["export:/backup", "publish:family"], targets);
+        assert!(select_holding_targets(&connection, "H2")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// The tags of each tagged content, in name order, keyed by hash.
+pub(crate) fn select_tags(connection: &Connection) -> Result<HashMap<String, Vec<String>>> {
+    let mut statement = connection.prepare("SELECT hash, name FROM tag ORDER BY name")?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for row in statement.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get(1)?)))? {
+        let (hash, name) = row?;
+        tags.entry(hash).or_default().push(name);
+    }
+    Ok(tags)
+}
+
+/// Tags the contents, all or none of them. Returns the number of newly tagged
+/// contents.
+pub(crate) fn add_tag(connection: &mut Connection, name: &str, hashes: &[String]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement =
+            transaction.prepare("INSERT OR IGNORE INTO tag (hash, name) VALUES (?1, ?2)")?;
+        for hash in hashes {
+            count += statement.execute(params![hash, name])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Untags the contents, all or none of them. Returns the number of contents that
+/// had the tag.
+pub(crate) fn remove_tag(
+    connection: &mut Connection,
+    name: &str,
+    hashes: &[String],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare("DELETE FROM tag WHERE hash = ?1 AND name = ?2")?;
+        for hash in hashes {
+            count += statement.execute(params![hash, name])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// Replaces the tags under the prefixes, e.g. `decade/`, with the given `(hash, name)`
+/// tags, all or none of them. Returns the number of tags under the prefixes
+/// afterwards.
+pub(crate) fn replace_tags_under(
+    connection: &mut Connection,
+    prefixes: &[&str],
+    tags: &[(String, String)],
+) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut delete =
+            transaction.prepare("DELETE FROM tag WHERE substr(name, 1, length(?1)) = ?1")?;
+        for prefix in prefixes {
+            delete.execute(params![prefix])?;
+        }
+        let mut insert =
+            transaction.prepare("INSERT OR IGNORE INTO tag (hash, name) VALUES (?1, ?2)")?;
+        for (hash, name) in tags {
+            count += insert.execute(params![hash, name])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{add_tag, remove_tag, replace_tags_under};
+
+    fn tags(connection: &Connection) -> Vec<String> {
+        let mut statement = connection
+            .prepare("SELECT hash || ' ' || name FROM tag ORDER BY hash, name")
+            .unwrap();
+        statement
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, rusqlite::Error>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn replace_tags_under_keeps_the_other_tags() {
+        let mut connection = new_database();
+        add_tag(&mut connection, "beach", &["AB".to_string()]).unwrap();
+        add_tag(&mut connection, "decade/1970s", &["AB".to_string()]).unwrap();
+
+        assert_eq!(
+            1,
+            replace_tags_under(
+                &mut connection,
+                &["decade/"],
+                &[("CD".to_string(), "decade/1980s".to_string())]
+            )
+            .unwrap()
+        );
+        assert_eq!(vec!["AB beach", "CD decade/1980s"], tags(&connection));
+    }
+
+    #[test]
+    fn add_tag_only_counts_the_new_tags() {
+        let mut connection = new_database();
+        let hashes = vec!["AB".to_string(), "CD".to_string()];
+
+        assert_eq!(1, add_tag(&mut connection, "beach", &hashes[..1]).unwrap());
+        assert_eq!(1, add_tag(&mut connection, "beach", &hashes).unwrap());
+        assert_eq!(2, remove_tag(&mut connection, "beach", &hashes).unwrap());
+        assert_eq!(0, remove_tag(&mut connection, "beach", &hashes).unwrap());
+    }
+}
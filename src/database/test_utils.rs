@@ -38,23 +38,21 @@ pub fn new_database_containing_catalog_and_library_entries(
 }
 
 pub fn catalog_contains(connection: &mut Connection, entry: &CatalogEntry) -> bool {
-    match connection.query_row(
-        "SELECT true FROM catalog WHERE hash = ?1 AND path = ?2",
-        params!(entry.sha256, entry.path),
-        |row| row.get::<_, bool>(0),
-    ) {
-        Ok(b) => b,
-        Err(_) => false,
-    }
+    connection
+        .query_row(
+            "SELECT true FROM catalog WHERE hash = ?1 AND path = ?2",
+            params!(entry.sha256, entry.path),
+            |row| row.get::<_, bool>(0),
+        )
+        .unwrap_or_default()
 }
 
 pub fn library_contains(connection: &mut Connection, entry: &LibraryEntry) -> bool {
-    match connection.query_row(
-        "SELECT true FROM library WHERE hash = ?1 AND path = ?2",
-        params!(entry.sha256(), entry.path().to_string_lossy().to_string()),
-        |row| row.get::<_, bool>(0),
-    ) {
-        Ok(b) => b,
-        Err(_) => false,
-    }
+    connection
+        .query_row(
+            "SELECT true FROM library WHERE hash = ?1 AND path = ?2",
+            params!(entry.sha256(), entry.path().to_string_lossy().to_string()),
+            |row| row.get::<_, bool>(0),
+        )
+        .unwrap_or_default()
 }
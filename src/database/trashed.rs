@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use eyre::Result;
+use rusqlite::{params, Connection, Row};
+
+/// A file moved to the trash by prune or inbox reject.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Trashed {
+    /// The path of the file in the trash.
+    pub(crate) path: PathBuf,
+    /// The path the file was moved from.
+    pub(crate) original_path: PathBuf,
+    pub(crate) trashed_at: NaiveDateTime,
+}
+
+/// Records the file of the content moved to the trash, `redundant` when another
+/// copy of the content was kept.
+pub(crate) fn record_trashed(
+    connection: &Connection,
+    hash: &str,
+    path: &str,
+    original_path: &str,
+    redundant: bool,
+) -> Result<usize> {
+    Ok(connection.execute(
+        "INSERT OR REPLACE INTO trashed (path, hash, original_path, trashed_at, redundant) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![path, hash, original_path, Local::now().naive_local(), redundant],
+    )?)
+}
+
+/// Forgets the file that left the trash, deleted or restored.
+pub(crate) fn forget_trashed(connection: &Connection, path: &str) -> Result<usize> {
+    Ok(connection.execute("DELETE FROM trashed WHERE path = ?1", [path])?)
+}
+
+/// The trashed files keyed by hash, the latest trashed one of each content.
+pub(crate) fn select_trashed(connection: &Connection) -> Result<HashMap<String, Trashed>> {
+    let mut statement = connection
+        .prepare("SELECT hash, path, original_path, trashed_at FROM trashed ORDER BY trashed_at")?;
+    let trashed = statement
+        .query_map([], trashed_row)?
+        .collect::<Result<HashMap<String, Trashed>, rusqlite::Error>>()?;
+    Ok(trashed)
+}
+
+/// The trashed files keyed by hash of the contents discarded, leaving out the
+/// redundant copies trashed while another copy was kept.
+pub(crate) fn select_discarded(connection: &Connection) -> Result<HashMap<String, Trashed>> {
+    let mut statement = connection.prepare(
+        "SELECT hash, path, original_path, trashed_at FROM trashed WHERE NOT redundant ORDER BY trashed_at",
+    )?;
+    let trashed = statement
+        .query_map([], trashed_row)?
+        .collect::<Result<HashMap<String, Trashed>, rusqlite::Error>>()?;
+    Ok(trashed)
+}
+
+/// The paths of the files moved to the trash before the date.
+pub(crate) fn select_trashed_before(
+    connection: &Connection,
+    date: NaiveDate,
+) -> Result<HashSet<PathBuf>> {
+    let mut statement = connection.prepare("SELECT path FROM trashed WHERE trashed_at < ?1")?;
+    let paths = statement
+        .query_map([date], |row| row.get::<_, String>(0).map(PathBuf::from))?
+        .collect::<Result<HashSet<PathBuf>, rusqlite::Error>>()?;
+    Ok(paths)
+}
+
+/// The `(hash, file)` of the trashed files of the content, or moved from or to the
+/// path, the latest trashed first.
+pub(crate) fn select_trashed_files(
+    connection: &Connection,
+    hash: &str,
+    path: &str,
+) -> Result<Vec<(String, Trashed)>> {
+    let mut statement = connection.prepare(
+        "SELECT hash, path, original_path, trashed_at FROM trashed WHERE hash = ?1 OR path = ?2 OR original_path = ?2 ORDER BY trashed_at DESC",
+    )?;
+    let trashed = statement
+        .query_map([hash, path], trashed_row)?
+        .collect::<Result<Vec<(String, Trashed)>, rusqlite::Error>>()?;
+    Ok(trashed)
+}
+
+fn trashed_row(row: &Row) -> Result<(String, Trashed), rusqlite::Error> {
+    Ok((
+        row.get(0)?,
+        Trashed {
+            path: PathBuf::from(row.get::<_, String>(1)?),
+            original_path: PathBuf::from(row.get::<_, String>(2)?),
+            trashed_at: row.get(3)?,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use chrono::NaiveDate;
+
+    use crate::database::test_utils::new_database;
+
+    use super::{
+        forget_trashed, record_trashed, select_discarded, select_trashed, select_trashed_before,
+        select_trashed_files,
+    };
+
+    #[test]
+    fn select_trashed_keeps_the_files_still_in_the_trash() {
+        let connection = new_database();
+        record_trashed(&connection, "H1", ".trash/card/a.jpg", "/card/a.jpg", false).unwrap();
+        record_trashed(&connection, "H2", ".trash/card/b.jpg", "/card/b.jpg", false).unwrap();
+        record_trashed(
+            &connection,
+            "H3",
+            ".trash/card/b.jpg",
+            "/other/card/b.jpg",
+            false,
+        )
+        .unwrap();
+        forget_trashed(&connection, ".trash/card/a.jpg").unwrap();
+
+        let trashed = select_trashed(&connection).unwrap();
+
+        assert_eq!(
+            vec!["H3"],
+            trashed.keys().map(String::as_str).collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            PathBuf::from("/other/card/b.jpg"),
+            trashed["H3"].original_path
+        );
+    }
+
+    #[test]
+    fn select_trashed_files_matches_the_hash_or_either_path() {
+        let connection = new_database();
+        record_trashed(&connection, "H1", "/trash/card/a.jpg", "/card/a.jpg", false).unwrap();
+        record_trashed(
+            &connection,
+            "H1",
+            "/trash/other/a.jpg",
+            "/other/a.jpg",
+            false,
+        )
+        .unwrap();
+        record_trashed(&connection, "H2", "/trash/card/b.jpg", "/card/b.jpg", false).unwrap();
+
+        let paths = |hash, path| {
+            select_trashed_files(&connection, hash, path)
+                .unwrap()
+                .into_iter()
+                .map(|(_, t)| t.path)
+                .collect::<Vec<PathBuf>>()
+        };
+
+        assert_eq!(2, paths("H1", "").len());
+        assert_eq!(
+            vec![PathBuf::from("/trash/card/b.jpg")],
+            paths("", "/card/b.jpg")
+        );
+        assert_eq!(
+            vec![PathBuf::from("/trash/card/b.jpg")],
+            paths("", "/trash/card/b.jpg")
+        );
+    }
+
+    #[test]
+    fn select_discarded_leaves_out_the_redundant_copies() {
+        let connection = new_database();
+        record_trashed(&connection, "H1", ".trash/card/a.jpg", "/card/a.jpg", false).unwrap();
+        record_trashed(&connection, "H2", ".trash/card/b.jpg", "/card/b.jpg", true).unwrap();
+
+        assert_eq!(
+            vec!["H1"],
+            select_discarded(&connection)
+                .unwrap()
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(2, select_trashed(&connection).unwrap().len());
+    }
+
+    #[test]
+    fn select_trashed_before_compares_the_trashing_time() {
+        let connection = new_database();
+        record_trashed(&connection, "H1", ".trash/card/a.jpg", "/card/a.jpg", false).unwrap();
+        record_trashed(&connection, "H2", ".trash/card/b.jpg", "/card/b.jpg", false).unwrap();
+        connection
+            .execute(
+                "UPDATE trashed SET trashed_at = '2024-04-30T23:59:59' WHERE hash = 'H1'",
+                [],
+            )
+            .unwrap();
+        let today = chrono::Local::now().date_naive();
+
+        assert_eq!(
+            HashSet::from([PathBuf::from(".trash/card/a.jpg")]),
+            select_trashed_before(&connection, today).unwrap()
+        );
+        assert!(
+            select_trashed_before(&connection, NaiveDate::from_ymd_opt(2024, 4, 30).unwrap())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
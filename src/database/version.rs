@@ -0,0 +1,78 @@
+use chrono::Local;
+use eyre::Result;
+use rusqlite::{params, Connection};
+
+/// A library file derived from another one, e.g. a TIFF exported from a RAW.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Version {
+    pub(crate) master: String,
+    pub(crate) derived: String,
+    /// The editing tool, when known.
+    pub(crate) tool: Option<String>,
+}
+
+/// Links the derived contents to their masters, all or none of them. Returns the
+/// number of links that did not exist already.
+pub(crate) fn link_versions(connection: &mut Connection, versions: &[Version]) -> Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut count = 0;
+    {
+        let mut statement = transaction.prepare(
+            "INSERT OR IGNORE INTO version (master, derived, tool, created_at) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let now = Local::now().naive_local();
+        for Version {
+            master,
+            derived,
+            tool,
+        } in versions
+        {
+            count += statement.execute(params![master, derived, tool, now])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// All the links between masters and derived contents.
+pub(crate) fn select_versions(connection: &Connection) -> Result<Vec<Version>> {
+    let mut statement = connection
+        .prepare("SELECT master, derived, tool FROM version ORDER BY created_at, derived")?;
+    let versions = statement
+        .query_map([], |row| {
+            Ok(Version {
+                master: row.get(0)?,
+                derived: row.get(1)?,
+                tool: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<Version>, rusqlite::Error>>()?;
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::test_utils::new_database;
+
+    use super::{link_versions, select_versions, Version};
+
+    #[test]
+    fn link_versions_records_each_link_once() {
+        let mut connection = new_database();
+        let version = Version {
+            master: "AB".to_string(),
+            derived: "CD".to_string(),
+            tool: Some("darktable".to_string()),
+        };
+
+        assert_eq!(
+            1,
+            link_versions(&mut connection, std::slice::from_ref(&version)).unwrap()
+        );
+        assert_eq!(
+            0,
+            link_versions(&mut connection, std::slice::from_ref(&version)).unwrap()
+        );
+        assert_eq!(vec![version], select_versions(&connection).unwrap());
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+
+use crate::database::common::sha256_digest_reader;
+
+/// The external tool encrypting exported files, configured in the `[encryption]`
+/// section of the repository configuration.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "tool", rename_all = "lowercase")]
+pub(crate) enum Encryption {
+    /// Encrypts for a key of the GnuPG keyring, decrypting with the gpg agent.
+    Gpg { recipient: String },
+    /// Encrypts for an age recipient, decrypting with the identity file.
+    Age {
+        recipient: String,
+        identity: Option<PathBuf>,
+    },
+}
+
+impl Encryption {
+    /// The command line tool doing the encryption.
+    pub(crate) fn tool(&self) -> &'static str {
+        match self {
+            Self::Gpg { .. } => "gpg",
+            Self::Age { .. } => "age",
+        }
+    }
+
+    /// The extension appended to the encrypted files.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Self::Gpg { .. } => "gpg",
+            Self::Age { .. } => "age",
+        }
+    }
+
+    /// The path of the encrypted version of a file.
+    pub(crate) fn encrypted_path(&self, path: &Path) -> PathBuf {
+        let mut encrypted = path.as_os_str().to_owned();
+        encrypted.push(".");
+        encrypted.push(self.extension());
+        PathBuf::from(encrypted)
+    }
+
+    pub(crate) fn encrypt(&self, from: &Path, to: &Path) -> Result<()> {
+        let status = self
+            .encrypt_command(from, to)
+            .status()
+            .wrap_err("Failed to run the encryption tool")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(eyre!("Failed to encrypt {}: {}", from.display(), status))
+        }
+    }
+
+    pub(crate) fn decrypt(&self, from: &Path, to: &Path) -> Result<()> {
+        let status = self
+            .decrypt_command(from)
+            .stdout(File::create(to)?)
+            .status()
+            .wrap_err("Failed to run the encryption tool")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(eyre!("Failed to decrypt {}: {}", from.display(), status))
+        }
+    }
+
+    /// Decrypts a file and returns the sha256 digest of its plain content.
+    pub(crate) fn decrypted_sha256(&self, path: &Path) -> Result<String> {
+        let mut child = self
+            .decrypt_command(path)
+            .stdout(Stdio::piped())
+            .spawn()
+            .wrap_err("Failed to run the encryption tool")?;
+        let digest = sha256_digest_reader(child.stdout.take().expect("piped"))?;
+        let status = child.wait()?;
+        if status.success() {
+            Ok(digest)
+        } else {
+            Err(eyre!("Failed to decrypt {}: {}", path.display(), status))
+        }
+    }
+
+    fn encrypt_command(&self, from: &Path, to: &Path) -> Command {
+        let mut command;
+        match self {
+            Self::Gpg { recipient } => {
+                command = Command::new(self.tool());
+                command.args(["--batch", "--yes", "--encrypt", "--recipient", recipient]);
+            }
+            Self::Age { recipient, .. } => {
+                command = Command::new(self.tool());
+                command.args(["--encrypt", "--recipient", recipient]);
+            }
+        }
+        command.arg("--output").arg(to).arg(from);
+        command
+    }
+
+    fn decrypt_command(&self, path: &Path) -> Command {
+        let mut command;
+        match self {
+            Self::Gpg { .. } => {
+                command = Command::new(self.tool());
+                command.args(["--batch", "--quiet", "--decrypt"]);
+            }
+            Self::Age { identity, .. } => {
+                command = Command::new(self.tool());
+                command.arg("--decrypt");
+                if let Some(identity) = identity {
+                    command.arg("--identity").arg(identity);
+                }
+            }
+        }
+        command.arg(path);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, path::Path};
+
+    use super::Encryption;
+
+    fn args(command: &std::process::Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn encrypt_command_uses_the_gpg_recipient() {
+        let encryption = Encryption::Gpg {
+            recipient: "me@example.com".to_string(),
+        };
+        let command = encryption.encrypt_command(Path::new("a.jpg"), Path::new("a.jpg.gpg"));
+
+        assert_eq!("gpg", command.get_program());
+        assert_eq!(
+            vec![
+                "--batch",
+                "--yes",
+                "--encrypt",
+                "--recipient",
+                "me@example.com",
+                "--output",
+                "a.jpg.gpg",
+                "a.jpg"
+            ],
+            args(&command)
+        );
+    }
+
+    #[test]
+    fn decrypt_command_uses_the_age_identity() {
+        let encryption = Encryption::Age {
+            recipient: "age1xyz".to_string(),
+            identity: Some("key.txt".into()),
+        };
+        let command = encryption.decrypt_command(Path::new("a.jpg.age"));
+
+        assert_eq!("age", command.get_program());
+        assert_eq!(
+            vec!["--decrypt", "--identity", "key.txt", "a.jpg.age"],
+            args(&command)
+        );
+    }
+
+    #[test]
+    fn encrypted_path_appends_the_extension() {
+        let encryption = Encryption::Gpg {
+            recipient: "me".to_string(),
+        };
+        assert_eq!(
+            Path::new("2023/5/a.jpg.gpg"),
+            encryption.encrypted_path(Path::new("2023/5/a.jpg"))
+        );
+    }
+}
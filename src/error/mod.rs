@@ -0,0 +1,187 @@
+use std::{
+    fmt::{Display, Formatter},
+    io,
+};
+
+use clap::{builder::PossibleValuesParser, Arg};
+use eyre::Report;
+use serde::Serialize;
+
+pub(crate) const ERROR_FORMAT: &str = "error-format";
+
+/// The global argument selecting how a failure of the command is printed.
+pub(crate) fn error_format_arg() -> Arg {
+    Arg::new(ERROR_FORMAT)
+        .long(ERROR_FORMAT)
+        .value_name("FORMAT")
+        .global(true)
+        .value_parser(PossibleValuesParser::new(["text", "json"]))
+        .default_value("text")
+        .help("Prints failures as text, or as a JSON object with a stable error code")
+}
+
+/// The stable codes of the failures, for wrappers to branch on.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ErrorCode {
+    Io,
+    HashMismatch,
+    /// Files of the same digest with different bytes.
+    ContentMismatch,
+    MissingExifDate,
+    DbConstraint,
+    Database,
+    RepositoryLocked,
+    Other,
+}
+
+/// A failure of a known kind, carried through eyre.
+#[derive(Debug)]
+pub(crate) struct Error {
+    code: ErrorCode,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// A database failure, telling constraint violations apart.
+    pub(crate) fn database(error: rusqlite::Error, context: impl Display) -> Self {
+        let code = match error.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::ConstraintViolation) => ErrorCode::DbConstraint,
+            _ => ErrorCode::Database,
+        };
+        Self::new(code, format!("{}: {}", context, error))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The failures of the entries of a batch, printed one per line.
+#[derive(Debug)]
+pub(crate) struct Failures(pub(crate) Vec<Report>);
+
+impl Display for Failures {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            &self
+                .0
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+}
+
+impl std::error::Error for Failures {}
+
+/// The code of the first known failure of the chain. A batch has the code its
+/// failures share.
+pub(crate) fn code(report: &Report) -> ErrorCode {
+    report
+        .chain()
+        .find_map(|cause| {
+            if let Some(error) = cause.downcast_ref::<Error>() {
+                Some(error.code)
+            } else if let Some(Failures(failures)) = cause.downcast_ref::<Failures>() {
+                let mut codes = failures.iter().map(code);
+                let first = codes.next().unwrap_or(ErrorCode::Other);
+                Some(if codes.all(|c| c == first) {
+                    first
+                } else {
+                    ErrorCode::Other
+                })
+            } else if let Some(error) = cause.downcast_ref::<rusqlite::Error>() {
+                Some(match error.sqlite_error_code() {
+                    Some(rusqlite::ErrorCode::ConstraintViolation) => ErrorCode::DbConstraint,
+                    _ => ErrorCode::Database,
+                })
+            } else {
+                cause.downcast_ref::<io::Error>().map(|_| ErrorCode::Io)
+            }
+        })
+        .unwrap_or(ErrorCode::Other)
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    code: ErrorCode,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<JsonError>,
+}
+
+impl From<&Report> for JsonError {
+    fn from(report: &Report) -> Self {
+        let errors = report
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<Failures>())
+            .map(|Failures(failures)| failures.iter().map(JsonError::from).collect())
+            .unwrap_or_default();
+        Self {
+            code: code(report),
+            message: format!("{:#}", report),
+            errors,
+        }
+    }
+}
+
+/// The failure as a JSON object with its code, message and, for a batch, the
+/// failures of its entries.
+pub(crate) fn to_json(report: &Report) -> String {
+    serde_json::to_string(&JsonError::from(report)).expect("errors serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use eyre::{eyre, Report, WrapErr};
+    use rusqlite::Connection;
+
+    use super::{code, to_json, Error, ErrorCode, Failures};
+
+    #[test]
+    fn code_finds_the_known_failures_in_the_chain() {
+        let io: Result<(), io::Error> = Err(io::Error::other("disk"));
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute("CREATE TABLE t (a TEXT PRIMARY KEY)", [])
+            .unwrap();
+        let insert = || connection.execute("INSERT INTO t VALUES ('a')", []);
+        insert().unwrap();
+
+        assert_eq!(
+            ErrorCode::Io,
+            code(&io.wrap_err("copy failed").unwrap_err())
+        );
+        assert_eq!(ErrorCode::DbConstraint, code(&insert().unwrap_err().into()));
+        assert_eq!(ErrorCode::Other, code(&eyre!("unknown")));
+    }
+
+    #[test]
+    fn to_json_reports_the_failures_of_a_batch() {
+        let failures: Report = Failures(vec![
+            Error::new(ErrorCode::HashMismatch, "a.jpeg").into(),
+            Error::new(ErrorCode::HashMismatch, "b.jpeg").into(),
+        ])
+        .into();
+
+        assert_eq!(
+            r#"{"code":"hash-mismatch","message":"a.jpeg\nb.jpeg","errors":[{"code":"hash-mismatch","message":"a.jpeg"},{"code":"hash-mismatch","message":"b.jpeg"}]}"#,
+            to_json(&failures)
+        );
+    }
+}
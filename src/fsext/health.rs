@@ -0,0 +1,124 @@
+use std::{
+    fs::{metadata, remove_file, File},
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result};
+
+/// The state of a destination directory when a long running copy started, used
+/// to detect that the drive holding it was unmounted midway. Once a USB drive
+/// drops, its mount point is an empty directory of the local disk: writes still
+/// succeed but land on the wrong filesystem.
+pub(crate) struct DestinationHealth {
+    root: PathBuf,
+    marker: PathBuf,
+    device: Option<u64>,
+}
+
+impl DestinationHealth {
+    /// Records the state of `root`, whose `marker` entry must exist as long as the
+    /// destination stays mounted.
+    pub(crate) fn new(root: &Path, marker: &Path) -> Result<Self> {
+        let health = Self {
+            root: root.to_path_buf(),
+            marker: root.join(marker),
+            device: device(root)?,
+        };
+        health.check()?;
+        Ok(health)
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Fails when the destination is no longer the recorded filesystem or can't be
+    /// written to.
+    pub(crate) fn check(&self) -> Result<()> {
+        if !self.marker.exists() {
+            return Err(eyre!(
+                "{} disappeared, is the destination still mounted?",
+                self.marker.display()
+            ));
+        }
+        if device(&self.root)? != self.device {
+            return Err(eyre!(
+                "{} moved to another filesystem, is the destination still mounted?",
+                self.root.display()
+            ));
+        }
+        let probe = self.root.join(".photo_works_probe");
+        File::create(&probe)
+            .and_then(|_| remove_file(&probe))
+            .map_err(|e| eyre!("{} is not writable: {}", self.root.display(), e))
+    }
+
+    /// Fails when the destination doesn't have room for `size` more bytes.
+    pub(crate) fn check_space(&self, size: u64) -> Result<()> {
+        let available = fs2::available_space(&self.root)?;
+        if available < size {
+            Err(eyre!(
+                "{} has {} bytes available, {} needed",
+                self.root.display(),
+                available,
+                size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Result<Option<u64>> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(Some(metadata(path)?.dev()))
+}
+
+#[cfg(not(unix))]
+fn device(path: &Path) -> Result<Option<u64>> {
+    metadata(path)?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir},
+        path::Path,
+    };
+
+    use tempfile::TempDir;
+
+    use super::DestinationHealth;
+
+    #[test]
+    fn check_passes_while_the_marker_exists() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+
+        let health = DestinationHealth::new(directory.path(), Path::new(".photo_works")).unwrap();
+        assert!(health.check().is_ok());
+    }
+
+    #[test]
+    fn check_fails_once_the_marker_disappears() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+        let health = DestinationHealth::new(directory.path(), Path::new(".photo_works")).unwrap();
+
+        remove_dir(directory.path().join(".photo_works")).unwrap();
+        assert!(health.check().is_err());
+    }
+
+    #[test]
+    fn check_space_fails_beyond_the_available_space() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+        let health = DestinationHealth::new(directory.path(), Path::new(".photo_works")).unwrap();
+
+        assert!(health.check_space(1).is_ok());
+        assert!(health.check_space(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+/// The suffixes of the files SQLite writes next to a database.
+const DATABASE_SUFFIXES: [&str; 4] = ["", "-wal", "-shm", "-journal"];
+
+/// The paths photo_works writes for itself, which are never pictures: the
+/// `.photo_works` directory with its thumbnails and snapshots, the trash, and the
+/// database with its journal files wherever `--db` puts it. Catalog and check
+/// skip them even when their names are not hidden.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ManagedPaths {
+    directories: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl ManagedPaths {
+    pub(crate) fn with_directory(mut self, directory: PathBuf) -> Self {
+        self.directories.push(directory);
+        self
+    }
+
+    pub(crate) fn with_database(mut self, database: &Path) -> Self {
+        self.files.extend(DATABASE_SUFFIXES.iter().map(|suffix| {
+            let mut file = database.as_os_str().to_owned();
+            file.push(suffix);
+            PathBuf::from(file)
+        }));
+        self
+    }
+
+    /// True for the managed files and the paths under the managed directories.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.files.iter().any(|f| f == path) || self.directories.iter().any(|d| path.starts_with(d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::ManagedPaths;
+
+    #[test]
+    fn contains_the_database_files_and_the_directory_trees() {
+        let managed = ManagedPaths::default()
+            .with_directory("/photos/trash".into())
+            .with_database(Path::new("/photos/photos.db3"));
+
+        assert!(managed.contains(Path::new("/photos/trash")));
+        assert!(managed.contains(Path::new("/photos/trash/2024/a.jpg")));
+        assert!(managed.contains(Path::new("/photos/photos.db3")));
+        assert!(managed.contains(Path::new("/photos/photos.db3-wal")));
+        assert!(!managed.contains(Path::new("/photos/trash.jpg")));
+        assert!(!managed.contains(Path::new("/photos/photos.db3.jpg")));
+        assert!(!managed.contains(Path::new("/photos/2024/a.jpg")));
+    }
+}
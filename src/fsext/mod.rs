@@ -0,0 +1,90 @@
+use std::{
+    fs::{read_dir, remove_dir},
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+
+pub(crate) mod health;
+pub(crate) mod managed;
+pub(crate) mod source;
+
+/// Removes the directories emptied by the removal of `file`, walking up its
+/// ancestors until a non empty directory or the closest of `roots` is reached.
+/// Nothing is removed when `file` is not under one of the `roots`.
+pub(crate) fn remove_empty_ancestors(file: &Path, roots: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut removed = vec![];
+    let root = match closest_root(file, roots) {
+        Some(root) => root,
+        None => return Ok(removed),
+    };
+    let mut directory = file.parent();
+    while let Some(current) = directory {
+        if current == root || !current.starts_with(root) || !is_empty_dir(current)? {
+            break;
+        }
+        remove_dir(current)?;
+        removed.push(current.to_path_buf());
+        directory = current.parent();
+    }
+    Ok(removed)
+}
+
+fn closest_root<'a>(file: &Path, roots: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    roots
+        .iter()
+        .filter(|root| file.starts_with(root) && file != root.as_path())
+        .max_by_key(|root| root.components().count())
+}
+
+fn is_empty_dir(path: &Path) -> Result<bool> {
+    Ok(path.is_dir() && read_dir(path)?.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, File};
+
+    use tempfile::TempDir;
+
+    use super::remove_empty_ancestors;
+
+    #[test]
+    fn remove_empty_ancestors_removes_empty_directories_up_to_the_root() {
+        let root = TempDir::new().unwrap();
+        let file = root.path().join("a").join("b").join("c.png");
+        create_dir_all(file.parent().unwrap()).unwrap();
+
+        let removed = remove_empty_ancestors(&file, &[root.path().to_path_buf()]).unwrap();
+
+        assert_eq!(2, removed.len());
+        assert!(!root.path().join("a").exists());
+        assert!(root.path().exists());
+    }
+
+    #[test]
+    fn remove_empty_ancestors_stops_at_non_empty_directory() {
+        let root = TempDir::new().unwrap();
+        let file = root.path().join("a").join("b").join("c.png");
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(root.path().join("a").join("d.png")).unwrap();
+
+        let removed = remove_empty_ancestors(&file, &[root.path().to_path_buf()]).unwrap();
+
+        assert_eq!(vec![root.path().join("a").join("b")], removed);
+        assert!(root.path().join("a").exists());
+    }
+
+    #[test]
+    fn remove_empty_ancestors_ignores_files_outside_of_the_roots() {
+        let root = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        let file = other.path().join("a").join("c.png");
+        create_dir_all(file.parent().unwrap()).unwrap();
+
+        let removed = remove_empty_ancestors(&file, &[root.path().to_path_buf()]).unwrap();
+
+        assert!(removed.is_empty());
+        assert!(other.path().join("a").exists());
+    }
+}
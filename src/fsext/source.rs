@@ -0,0 +1,157 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use eyre::{eyre, Result};
+use walkdir::WalkDir;
+
+/// Opens a source file for reading only, so that photo_works never holds a writable
+/// handle on originals.
+pub(crate) fn open_read_only(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).open(path)
+}
+
+/// Fails when `destination` is under one of the source `roots`: originals are never
+/// created or modified by catalog and import.
+pub(crate) fn ensure_outside_sources(destination: &Path, roots: &[PathBuf]) -> Result<()> {
+    match roots.iter().find(|root| destination.starts_with(root)) {
+        Some(root) => Err(eyre!(
+            "Refusing to write {} under the source {}",
+            destination.display(),
+            root.display()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The size and modification time of every file under source roots, taken before
+/// a command to verify afterwards that it left the sources untouched.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SourceSnapshot {
+    files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+    /// The directories under the roots left out, which the command may write to.
+    excluded: Vec<PathBuf>,
+}
+
+impl SourceSnapshot {
+    pub(crate) fn take(roots: &[PathBuf]) -> Result<Self> {
+        Self::take_excluding(roots, &[])
+    }
+
+    pub(crate) fn take_excluding(roots: &[PathBuf], excluded: &[PathBuf]) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for root in roots {
+            for entry in WalkDir::new(root)
+                .into_iter()
+                .filter_entry(|e| !excluded.iter().any(|d| e.path() == d))
+                .filter_map(|e| e.ok())
+            {
+                if entry.file_type().is_file() {
+                    let metadata = entry.metadata()?;
+                    files.insert(
+                        entry.into_path(),
+                        (metadata.len(), metadata.modified().ok()),
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            files,
+            excluded: excluded.to_vec(),
+        })
+    }
+
+    /// The files added, removed or modified since the snapshot.
+    pub(crate) fn changes(&self, roots: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let current = Self::take_excluding(roots, &self.excluded)?;
+        let mut changes = self
+            .files
+            .iter()
+            .filter(|(path, state)| current.files.get(*path) != Some(state))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+        changes.extend(
+            current
+                .files
+                .keys()
+                .filter(|path| !self.files.contains_key(*path))
+                .cloned(),
+        );
+        Ok(changes)
+    }
+
+    /// Fails with the list of changed files when the sources were touched.
+    pub(crate) fn verify_untouched(&self, roots: &[PathBuf]) -> Result<()> {
+        let changes = self.changes(roots)?;
+        if changes.is_empty() {
+            println!("Verified {} source files are untouched", self.files.len());
+            Ok(())
+        } else {
+            Err(eyre!(
+                "{} source files were touched:\n{}",
+                changes.len(),
+                changes
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{remove_file, write},
+        path::PathBuf,
+    };
+
+    use tempfile::TempDir;
+
+    use super::{ensure_outside_sources, SourceSnapshot};
+
+    #[test]
+    fn ensure_outside_sources_refuses_destinations_under_a_root() {
+        let roots = vec![PathBuf::from("/photos")];
+
+        assert!(ensure_outside_sources(&PathBuf::from("/photos/library/a.jpeg"), &roots).is_err());
+        assert!(ensure_outside_sources(&PathBuf::from("/library/a.jpeg"), &roots).is_ok());
+    }
+
+    #[test]
+    fn changes_are_empty_for_untouched_sources() {
+        let directory = TempDir::new().unwrap();
+        write(directory.path().join("a.jpeg"), "a").unwrap();
+        let roots = vec![directory.path().to_path_buf()];
+
+        let snapshot = SourceSnapshot::take(&roots).unwrap();
+        assert!(snapshot.changes(&roots).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changes_report_modified_added_and_removed_files() {
+        let directory = TempDir::new().unwrap();
+        write(directory.path().join("a.jpeg"), "a").unwrap();
+        write(directory.path().join("b.jpeg"), "b").unwrap();
+        let roots = vec![directory.path().to_path_buf()];
+        let snapshot = SourceSnapshot::take(&roots).unwrap();
+
+        write(directory.path().join("a.jpeg"), "modified").unwrap();
+        remove_file(directory.path().join("b.jpeg")).unwrap();
+        write(directory.path().join("c.jpeg"), "c").unwrap();
+
+        assert_eq!(
+            vec![
+                directory.path().join("a.jpeg"),
+                directory.path().join("b.jpeg"),
+                directory.path().join("c.jpeg")
+            ],
+            snapshot.changes(&roots).unwrap()
+        );
+    }
+}
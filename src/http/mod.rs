@@ -0,0 +1,296 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use eyre::{eyre, Context, Result};
+use indicatif::HumanDuration;
+use serde_json::Value;
+
+use crate::config::HttpConfig;
+
+/// The curl exit codes of the failures worth a retry: the host not resolved, the
+/// connection refused, a timeout, a TLS handshake failure, an empty reply or a
+/// broken transfer.
+const TRANSIENT_EXIT_CODES: [i32; 8] = [5, 6, 7, 28, 35, 52, 55, 56];
+
+/// The HTTP statuses worth a retry.
+const TRANSIENT_STATUSES: [u16; 6] = [408, 429, 500, 502, 503, 504];
+
+/// The curl exit code of an HTTP status of 400 or more, with `--fail`.
+const HTTP_ERROR_EXIT_CODE: i32 = 22;
+
+/// The URL schemes whose uploads curl resumes where the server file ends.
+const RESUMABLE_SCHEMES: [&str; 3] = ["ftp://", "ftps://", "sftp://"];
+
+/// The URL schemes the requests are sent to.
+const SCHEMES: [&str; 5] = ["http://", "https://", "ftp://", "ftps://", "sftp://"];
+
+/// The arguments of a `curl` run, and the configuration holding the secret, passed
+/// on its standard input rather than on the command line.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Request {
+    pub(crate) arguments: Vec<String>,
+    pub(crate) config: String,
+}
+
+impl Request {
+    pub(crate) fn new(arguments: &[&str], config: String) -> Self {
+        Self {
+            arguments: arguments.iter().map(|a| a.to_string()).collect(),
+            config,
+        }
+    }
+
+    pub(crate) fn arg(mut self, argument: impl Into<String>) -> Self {
+        self.arguments.push(argument.into());
+        self
+    }
+
+    /// A text field of a multipart form, whose value curl doesn't interpret.
+    pub(crate) fn field(self, name: &str, value: &str) -> Self {
+        self.arg("--form-string").arg(format!("{}={}", name, value))
+    }
+
+    /// A file field of a multipart form.
+    pub(crate) fn file(self, name: &str, path: &Path) -> Self {
+        let path = path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        self.arg("--form").arg(format!("{}=@\"{}\"", name, path))
+    }
+
+    pub(crate) fn json(self, body: &Value) -> Self {
+        self.arg("--header")
+            .arg("Content-Type: application/json")
+            .arg("--data")
+            .arg(body.to_string())
+    }
+
+    fn url(&self) -> &str {
+        self.arguments
+            .iter()
+            .find(|a| SCHEMES.iter().any(|s| a.starts_with(s)))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Whether the request uploads a file to a server that can resume it.
+    fn is_resumable(&self) -> bool {
+        self.arguments.iter().any(|a| a == "--upload-file")
+            && RESUMABLE_SCHEMES.iter().any(|s| self.url().starts_with(s))
+    }
+}
+
+/// Sends the requests with `curl`, capped to the bandwidth and through the proxy
+/// of the `[http]` configuration. The requests failing on a transient error are
+/// sent again after an exponential backoff, the uploads to FTP or SFTP servers
+/// resuming the partial file.
+pub(crate) struct Client {
+    config: HttpConfig,
+}
+
+/// The outcome of a curl run.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Success(String),
+    Failure { transient: bool, message: String },
+}
+
+impl Client {
+    pub(crate) fn new(config: HttpConfig) -> Self {
+        Self { config }
+    }
+
+    /// Sends the request and returns the body of the response.
+    pub(crate) fn send(&self, request: &Request) -> Result<String> {
+        let mut retry = 0;
+        loop {
+            match self.run(request, retry > 0)? {
+                Outcome::Success(body) => return Ok(body),
+                Outcome::Failure {
+                    transient: true,
+                    message,
+                } if retry < self.config.retries() => {
+                    let delay = self.config.backoff(retry);
+                    eprintln!(
+                        "Failed to send {}: {}, retrying in {}",
+                        request.url(),
+                        message,
+                        HumanDuration(delay)
+                    );
+                    thread::sleep(delay);
+                    retry += 1;
+                }
+                Outcome::Failure { message, .. } => {
+                    return Err(eyre!("Failed to send {}: {}", request.url(), message))
+                }
+            }
+        }
+    }
+
+    /// Sends the request and parses the JSON response.
+    pub(crate) fn send_json(&self, request: &Request) -> Result<Value> {
+        let body = self.send(request)?;
+        serde_json::from_str(&body).wrap_err_with(|| format!("Unexpected response `{}`", body))
+    }
+
+    fn run(&self, request: &Request, retrying: bool) -> Result<Outcome> {
+        let mut child = Command::new("curl")
+            .args(self.arguments(request, retrying))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .wrap_err("Failed to run curl")?;
+        child
+            .stdin
+            .take()
+            .expect("piped")
+            .write_all(self.config(request).as_bytes())?;
+        let output = child.wait_with_output()?;
+        Ok(outcome(
+            output.status.code(),
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+
+    /// The arguments of the request, writing the HTTP status on a last line.
+    fn arguments(&self, request: &Request, retrying: bool) -> Vec<String> {
+        let mut arguments = ["--silent", "--show-error", "--fail", "--config", "-"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<String>>();
+        arguments.extend(["--write-out".to_string(), "\\n%{http_code}".to_string()]);
+        if let Some(rate) = self.config.max_rate() {
+            arguments.extend(["--limit-rate".to_string(), rate.to_string()]);
+        }
+        if retrying && request.is_resumable() {
+            arguments.extend(["--continue-at".to_string(), "-".to_string()]);
+        }
+        arguments.extend(request.arguments.iter().cloned());
+        arguments
+    }
+
+    /// The configuration of the request along with the proxy, which may hold
+    /// credentials.
+    fn config(&self, request: &Request) -> String {
+        match self.config.proxy() {
+            Some(proxy) => format!("{}{}", request.config, config_option("proxy", proxy)),
+            None => request.config.clone(),
+        }
+    }
+}
+
+/// The outcome of a curl run from its exit code and outputs, the HTTP status on
+/// the last line of the standard output.
+fn outcome(exit_code: Option<i32>, stdout: &str, stderr: &str) -> Outcome {
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", stdout));
+    if exit_code == Some(0) {
+        return Outcome::Success(body.to_owned());
+    }
+    let status = status.trim().parse::<u16>().unwrap_or_default();
+    Outcome::Failure {
+        transient: match exit_code {
+            Some(HTTP_ERROR_EXIT_CODE) => TRANSIENT_STATUSES.contains(&status),
+            Some(code) => TRANSIENT_EXIT_CODES.contains(&code),
+            None => false,
+        },
+        message: stderr.trim().to_owned(),
+    }
+}
+
+/// An option of a curl configuration, with its value quoted.
+pub(crate) fn config_option(name: &str, value: &str) -> String {
+    format!(
+        "{} = \"{}\"\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// The path with the characters other than the unreserved ones and `/`
+/// percent-encoded, for the URLs.
+pub(crate) fn url_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::config::HttpConfig;
+
+    use super::{config_option, outcome, url_path, Client, Outcome, Request};
+
+    fn client(toml: &str) -> Client {
+        Client::new(toml::from_str::<HttpConfig>(toml).unwrap())
+    }
+
+    #[test]
+    fn outcome_retries_the_rate_limited_and_server_errors() {
+        assert_eq!(
+            Outcome::Success("{\"id\":1}".to_string()),
+            outcome(Some(0), "{\"id\":1}\n201", "")
+        );
+        let transient = |exit_code, stdout| match outcome(exit_code, stdout, "error") {
+            Outcome::Failure { transient, .. } => transient,
+            Outcome::Success(_) => panic!("Expected a failure"),
+        };
+        assert!(transient(Some(22), "\n429"));
+        assert!(transient(Some(22), "\n503"));
+        assert!(!transient(Some(22), "\n404"));
+        assert!(transient(Some(7), "\n000"));
+        assert!(!transient(Some(26), "\n000"));
+        assert!(!transient(None, ""));
+    }
+
+    #[test]
+    fn arguments_cap_the_rate_and_resume_the_sftp_uploads() {
+        let client = client("max_rate = \"1MiB\"\nproxy = \"http://proxy:3128\"");
+        let request = Request::new(&["--upload-file", "/repo/a.jpg"], String::new())
+            .arg("sftp://nas/photos/a.jpg");
+
+        let arguments = client.arguments(&request, true);
+
+        assert_eq!(
+            vec![
+                "--limit-rate",
+                "1048576",
+                "--continue-at",
+                "-",
+                "--upload-file",
+                "/repo/a.jpg",
+                "sftp://nas/photos/a.jpg"
+            ],
+            arguments[7..]
+        );
+        assert!(!client
+            .arguments(&request, false)
+            .contains(&"--continue-at".to_string()));
+        assert_eq!(
+            config_option("proxy", "http://proxy:3128"),
+            client.config(&request)
+        );
+    }
+
+    #[test]
+    fn url_path_keeps_the_separators() {
+        assert_eq!(
+            "2024/%C3%A9t%C3%A9/a%2Bb.jpg",
+            url_path(Path::new("2024/été/a+b.jpg"))
+        );
+    }
+}
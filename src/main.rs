@@ -1,13 +1,47 @@
-use std::ffi::OsString;
+use std::{ffi::OsString, process::exit};
 
-use clap::Command;
-use clapext::{SubApplication, SubCommandHolder};
-use command::{catalog, check, import, init, prune};
-use eyre::Result;
+use clap::{arg, Command};
+use clapext::{flag_arguments, subcommand_position, SubApplication, SubCommandHolder};
+use command::{
+    adopt, agent, auth, catalog, check, db, doctor, events, export, history, import, inbox, init,
+    protect, prune, refresh_metadata, relayout, restore, review, search, stats, tag, thumbnails,
+    verify_export, version,
+};
+use database::invocation::{record_invocation, select_invocation};
+use eyre::{eyre, Result};
+use report::SessionReport;
+use repository::Repository;
 
+mod bundle;
 mod clapext;
 mod command;
+mod config;
 mod database;
+mod encryption;
+mod error;
+mod fsext;
+mod http;
+mod media;
+mod metadata;
+mod naming;
+mod privacy;
+mod progress;
+mod publish;
+mod remote;
+mod report;
+mod repository;
+mod rules;
+mod secrets;
+mod style;
+mod thumbnail;
+mod trash;
+
+/// The flag repeating the remembered flags of the previous run of the command.
+const AGAIN: &str = "--again";
+
+/// The flags of the dry runs, which are not remembered for `--again` since they
+/// write nothing to the repository.
+const DRY_RUNS: [&str; 2] = ["--dry-run", "--preview"];
 
 struct PhotoWorks {
     sub_commands: SubCommandHolder,
@@ -24,7 +58,13 @@ impl PhotoWorks {
         let command = Command::new("photo_works")
             .about("A photo management CLI")
             .subcommand_required(true)
-            .arg_required_else_help(true);
+            .arg_required_else_help(true)
+            .allow_external_subcommands(true)
+            .arg(repository::profile_arg())
+            .arg(repository::repo_arg())
+            .arg(repository::db_arg())
+            .arg(error::error_format_arg())
+            .arg(arg!(--again "Repeats the command with the flags of its previous run in the repository, such as --jobs and the filters"));
         self.sub_commands.enrich_command(command)
     }
 
@@ -33,16 +73,116 @@ impl PhotoWorks {
         self
     }
 
+    /// Runs the command line, expanding the aliases and default arguments of the
+    /// repository configuration before dispatching to the subcommands. The stages of
+    /// an alias are summarized in a session report.
     fn run<I, T>(self, itr: I) -> Result<()>
     where
         I: IntoIterator<Item = T>,
         T: Into<OsString> + Clone,
     {
-        self.sub_commands
-            .handle(&self.command().get_matches_from(itr))
+        let args = itr.into_iter().map(Into::into).collect::<Vec<OsString>>();
+        let matches = self.command().get_matches_from(&args);
+        let repository = Repository::locate(&matches)?;
+        let args = if matches.get_flag("again") {
+            self.again(&repository, args)?
+        } else {
+            args
+        };
+        match matches.subcommand() {
+            Some((name, _)) if !self.sub_commands.contains(name) => {
+                let config = repository.config()?;
+                let stages = config
+                    .alias(name)
+                    .ok_or(eyre!("Unknown command or alias `{}`", name))?;
+                let position = subcommand_position(&self.command(), &args)
+                    .ok_or(eyre!("No subcommand in the arguments"))?;
+                let mut report = SessionReport::start(name, &repository)?;
+                let mut result = Ok(());
+                for stage in stages {
+                    let mut stage_args = args[..position].to_vec();
+                    stage_args.extend(stage.iter().map(OsString::from));
+                    result = self.dispatch(&repository, stage_args);
+                    report.record(stage, &result);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                println!("Session summary in {}", report.save(&repository)?.display());
+                result
+            }
+            _ => self.dispatch(&repository, args),
+        }
+    }
+
+    /// Dispatches to the subcommand, recording its remembered flags for `--again`
+    /// once they are parsed, whether it succeeds or not. The commands diagnosing the
+    /// repository run as given, without its configuration nor its database.
+    fn dispatch(&self, repository: &Repository, args: Vec<OsString>) -> Result<()> {
+        // Clap shows the help, or the usage errors, of the arguments without a
+        // subcommand.
+        let parsed = self.command().get_matches_from(&args);
+        let name = parsed
+            .subcommand_name()
+            .ok_or(eyre!("No subcommand in the arguments"))?
+            .to_owned();
+        if !self.sub_commands.contains(&name) {
+            return Err(eyre!("Unknown command `{}`", name));
+        }
+        if self.sub_commands.diagnoses(&name) {
+            return self.sub_commands.handle(&parsed, repository);
+        }
+        let position = subcommand_position(&self.command(), &args)
+            .ok_or(eyre!("No subcommand in the arguments"))?;
+        let remembered = self.sub_commands.remembered(&name);
+        let given = &args[position + 1..];
+        let dry_run = given.iter().any(|a| DRY_RUNS.iter().any(|d| a == d));
+        let arguments = self
+            .command()
+            .find_subcommand(&name)
+            .map(|command| flag_arguments(command, given, remembered))
+            .unwrap_or_default();
+        let args = with_defaults(args, position, repository.config()?.defaults(&name));
+        let matches = self.command().get_matches_from(args);
+        if !remembered.is_empty() && !dry_run && repository.db_path().exists() {
+            record_invocation(&*repository.open_database()?, &name, &arguments)?;
+        }
+        self.sub_commands.handle(&matches, repository)
+    }
+
+    /// Replaces `--again` with the remembered flags of the previous run of the
+    /// command, inserted after its name so that the arguments given override them.
+    /// The arguments without a subcommand are left to clap, which shows the help.
+    fn again(&self, repository: &Repository, mut args: Vec<OsString>) -> Result<Vec<OsString>> {
+        let Some(position) = subcommand_position(&self.command(), &args) else {
+            return Ok(args);
+        };
+        let Some(again) = args[..position].iter().position(|a| a == AGAIN) else {
+            return Ok(args);
+        };
+        args.remove(again);
+        let position = position - 1;
+        let name = args[position].to_string_lossy().to_string();
+        let previous = if repository.db_path().exists() {
+            select_invocation(&*repository.open_database()?, &name)?
+        } else {
+            None
+        }
+        .ok_or(eyre!("No previous run of {} to repeat", name))?;
+        println!("Repeating {} {}", name, previous.join(" "));
+        Ok(with_defaults(args, position, &previous))
     }
 }
 
+/// Inserts the default arguments right after the subcommand name.
+fn with_defaults(mut args: Vec<OsString>, position: usize, defaults: &[String]) -> Vec<OsString> {
+    args.splice(
+        position + 1..position + 1,
+        defaults.iter().map(OsString::from),
+    );
+    args
+}
+
 fn app() -> PhotoWorks {
     PhotoWorks::new()
         .register(init::Init)
@@ -50,12 +190,56 @@ fn app() -> PhotoWorks {
         .register(import::Import)
         .register(check::Check)
         .register(prune::Prune)
+        .register(export::Export)
+        .register(verify_export::VerifyExport)
+        .register(auth::Auth)
+        .register(doctor::Doctor)
+        .register(db::Db)
+        .register(agent::Agent)
+        .register(tag::Tag)
+        .register(stats::Stats)
+        .register(relayout::Relayout)
+        .register(command::metadata::Metadata)
+        .register(command::rules::Rules)
+        .register(review::Review)
+        .register(search::Search)
+        .register(command::trash::Trash)
+        .register(version::Versions)
+        .register(protect::Protect)
+        .register(refresh_metadata::RefreshMetadata)
+        .register(thumbnails::Thumbnails)
+        .register(adopt::Adopt)
+        .register(events::Events)
+        .register(inbox::Inbox)
+        .register(command::publish::Publish)
+        .register(command::sync::Sync)
+        .register(restore::Restore)
+        .register(history::History)
+        .register(command::query::Query)
+        .register(command::stack::Stacks)
+        .register(command::library::Library)
 }
 
 fn main() -> Result<()> {
     env_logger::init();
 
-    app().run(std::env::args_os())
+    let app = app();
+    let args = std::env::args_os().collect::<Vec<OsString>>();
+    // Leniently, for the arguments completed by run, e.g. by --again.
+    let json = app
+        .command()
+        .ignore_errors(true)
+        .get_matches_from(&args)
+        .get_one::<String>(error::ERROR_FORMAT)
+        .is_some_and(|f| f == "json");
+    app.run(args).or_else(|e| {
+        if json {
+            eprintln!("{}", error::to_json(&e));
+            exit(1)
+        } else {
+            Err(e)
+        }
+    })
 }
 
 #[cfg(test)]
@@ -68,7 +252,14 @@ mod tests {
     use clap::{ArgMatches, Command};
     use eyre::Result;
 
-    use crate::{app, clapext::SubApplication, PhotoWorks};
+    use std::{ffi::OsString, path::PathBuf};
+
+    use tempfile::TempDir;
+
+    use crate::{
+        app, clapext::SubApplication, database::invocation::record_invocation,
+        repository::Repository, with_defaults, PhotoWorks,
+    };
 
     #[test]
     fn register_add_a_sub_application_command() {
@@ -90,6 +281,111 @@ mod tests {
         assert!(invoked.load(Ordering::Relaxed))
     }
 
+    #[test]
+    fn run_fails_for_an_unknown_alias() {
+        let (invoked, sub_app) = given_a_sub_app();
+        let app = PhotoWorks::new().register(sub_app);
+
+        assert_eq!(
+            "Unknown command or alias `unknown`",
+            app.run(vec!["photo_works", "unknown"])
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        assert!(!invoked.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn with_defaults_inserts_the_defaults_after_the_subcommand() {
+        let args = ["photo_works", "--profile", "a", "import", "/card"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+
+        assert_eq!(
+            [
+                "photo_works",
+                "--profile",
+                "a",
+                "import",
+                "--priority",
+                "ext:cr2",
+                "/card"
+            ]
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<OsString>>(),
+            with_defaults(args, 3, &["--priority".to_string(), "ext:cr2".to_string()])
+        );
+    }
+
+    #[test]
+    fn again_inserts_the_flags_of_the_previous_run_before_the_given_ones() {
+        let directory = TempDir::new().unwrap();
+        std::fs::create_dir(directory.path().join(".photo_works")).unwrap();
+        let repository = Repository::new(directory.path().to_owned());
+        record_invocation(
+            &repository.open_database().unwrap(),
+            "test",
+            &["--year".to_string(), "2024".to_string()],
+        )
+        .unwrap();
+        let (_, sub_app) = given_a_sub_app();
+        let app = PhotoWorks::new().register(sub_app);
+        let repo = directory.path().to_string_lossy().to_string();
+        let args = |values: &[&str]| values.iter().map(OsString::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            args(&["photo_works", "--repo", &repo, "test", "--year", "2024"]),
+            app.again(
+                &repository,
+                args(&["photo_works", "--again", "--repo", &repo, "test"])
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            args(&[
+                "photo_works",
+                "--repo",
+                &repo,
+                "test",
+                "--year",
+                "2024",
+                "--year",
+                "2025"
+            ]),
+            app.again(
+                &repository,
+                args(&[
+                    "photo_works",
+                    "--again",
+                    "--repo",
+                    &repo,
+                    "test",
+                    "--year",
+                    "2025"
+                ]),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn again_leaves_the_arguments_without_subcommand_to_clap() {
+        let app = app();
+        let repository = Repository::new(PathBuf::from("."));
+        let args = |values: &[&str]| values.iter().map(OsString::from).collect::<Vec<_>>();
+
+        for values in [
+            &["photo_works"][..],
+            &["photo_works", "--repo", "x"],
+            &["photo_works", "--again"],
+        ] {
+            assert_eq!(args(values), app.again(&repository, args(values)).unwrap());
+        }
+    }
+
     #[test]
     fn command_is_consistent() {
         let app = app();
@@ -120,7 +416,7 @@ mod tests {
             Command::new("test")
         }
 
-        fn handle(&self, _: &ArgMatches) -> Result<()> {
+        fn handle(&self, _: &ArgMatches, _: &Repository) -> Result<()> {
             self.invoked
                 .store(true, std::sync::atomic::Ordering::Relaxed);
             Ok(())
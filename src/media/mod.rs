@@ -0,0 +1,459 @@
+use std::{
+    ffi::OsStr,
+    fmt::{Display, Formatter},
+    fs::read,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    str::FromStr,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use eyre::Result;
+use serde::Deserialize;
+
+use crate::fsext::source::open_read_only;
+
+pub(crate) mod pair;
+pub(crate) mod sidecar;
+pub(crate) mod stack;
+pub(crate) mod validation;
+
+/// A file format identified from the content of a file.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) struct MediaType {
+    mime: &'static str,
+    extensions: &'static [&'static str],
+}
+
+pub(crate) const JPEG: MediaType = MediaType::new("image/jpeg", &["jpg", "jpeg", "jpe"]);
+pub(crate) const PNG: MediaType = MediaType::new("image/png", &["png"]);
+pub(crate) const GIF: MediaType = MediaType::new("image/gif", &["gif"]);
+pub(crate) const WEBP: MediaType = MediaType::new("image/webp", &["webp"]);
+pub(crate) const BMP: MediaType = MediaType::new("image/bmp", &["bmp"]);
+pub(crate) const TIFF: MediaType = MediaType::new(
+    "image/tiff",
+    &[
+        "tif", "tiff", "dng", "nef", "nrw", "arw", "srw", "pef", "erf",
+    ],
+);
+pub(crate) const CR2: MediaType = MediaType::new("image/x-canon-cr2", &["cr2"]);
+pub(crate) const CR3: MediaType = MediaType::new("image/x-canon-cr3", &["cr3"]);
+pub(crate) const RAF: MediaType = MediaType::new("image/x-fuji-raf", &["raf"]);
+pub(crate) const ORF: MediaType = MediaType::new("image/x-olympus-orf", &["orf"]);
+pub(crate) const RW2: MediaType = MediaType::new("image/x-panasonic-rw2", &["rw2"]);
+pub(crate) const HEIC: MediaType = MediaType::new("image/heic", &["heic", "heif", "hif"]);
+pub(crate) const AVIF: MediaType = MediaType::new("image/avif", &["avif"]);
+pub(crate) const MP4: MediaType = MediaType::new("video/mp4", &["mp4", "m4v"]);
+pub(crate) const QUICKTIME: MediaType = MediaType::new("video/quicktime", &["mov", "qt"]);
+pub(crate) const AVI: MediaType = MediaType::new("video/x-msvideo", &["avi"]);
+
+/// Extensions of the camera raw formats, which share their TIFF or ISO media
+/// structure with other formats and are told apart by their extension.
+const RAW_EXTENSIONS: &[&str] = &[
+    "dng", "nef", "nrw", "arw", "srw", "pef", "erf", "cr2", "cr3", "raf", "orf", "rw2",
+];
+
+const HEADER_LENGTH: usize = 16;
+
+/// Videos up to this many seconds are motion photos or short clips, classified as
+/// animations.
+const ANIMATION_SECONDS: f64 = 4.0;
+
+/// What a library file is, beyond its format.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MediaKind {
+    Photo,
+    Video,
+    /// Animated GIF or WebP, motion photo or short clip.
+    Animation,
+    Screenshot,
+}
+
+impl MediaKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Photo => "photo",
+            Self::Video => "video",
+            Self::Animation => "animation",
+            Self::Screenshot => "screenshot",
+        }
+    }
+}
+
+impl Display for MediaKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MediaKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> std::result::Result<Self, Self::Err> {
+        match kind {
+            "photo" => Ok(Self::Photo),
+            "video" => Ok(Self::Video),
+            "animation" => Ok(Self::Animation),
+            "screenshot" => Ok(Self::Screenshot),
+            _ => Err(format!(
+                "Invalid kind `{}`, expected photo, video, animation or screenshot",
+                kind
+            )),
+        }
+    }
+}
+
+impl MediaType {
+    const fn new(mime: &'static str, extensions: &'static [&'static str]) -> Self {
+        Self { mime, extensions }
+    }
+
+    pub(crate) fn mime(&self) -> &'static str {
+        self.mime
+    }
+
+    /// The canonical extension for the format.
+    pub(crate) fn extension(&self) -> &'static str {
+        self.extensions[0]
+    }
+
+    /// Returns true when the extension is one used for the format, ignoring case.
+    pub(crate) fn accepts_extension(&self, extension: &OsStr) -> bool {
+        let extension = extension.to_string_lossy().to_lowercase();
+        self.extensions.iter().any(|e| *e == extension)
+    }
+}
+
+/// Returns true when the file has the extension of a camera raw format.
+pub(crate) fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| RAW_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Determines the media type of a file from its leading magic bytes.
+pub(crate) fn detect(path: &Path) -> Result<Option<MediaType>> {
+    let mut reader = BufReader::new(open_read_only(path)?);
+    let mut header = Vec::with_capacity(HEADER_LENGTH);
+    reader
+        .by_ref()
+        .take(HEADER_LENGTH as u64)
+        .read_to_end(&mut header)?;
+    Ok(detect_header(&header))
+}
+
+fn detect_header(header: &[u8]) -> Option<MediaType> {
+    match header {
+        [0xFF, 0xD8, 0xFF, ..] => Some(JPEG),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(PNG),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(GIF),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(WEBP),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'A', b'V', b'I', b' ', ..] => Some(AVI),
+        [b'F', b'U', b'J', b'I', b'F', b'I', b'L', b'M', ..] => Some(RAF),
+        [b'I', b'I', b'*', 0, _, _, _, _, b'C', b'R', ..] => Some(CR2),
+        [b'I', b'I', b'*', 0, ..] | [b'M', b'M', 0, b'*', ..] => Some(TIFF),
+        [b'I', b'I', b'R', b'O' | b'S', ..] => Some(ORF),
+        [b'I', b'I', b'U', 0, ..] => Some(RW2),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] if brand.len() >= 4 => {
+            detect_brand(&brand[..4])
+        }
+        [b'B', b'M', ..] => Some(BMP),
+        _ => None,
+    }
+}
+
+/// Classifies a file of the media type. Screenshots are recognized by their name,
+/// animations by their content and videos by their duration.
+pub(crate) fn classify(path: &Path, mime: Option<&str>) -> Result<Option<MediaKind>> {
+    let Some(mime) = mime else {
+        return Ok(None);
+    };
+    let name = path
+        .file_name()
+        .map(|n| {
+            n.to_string_lossy()
+                .to_lowercase()
+                .replace([' ', '_', '-'], "")
+        })
+        .unwrap_or_default();
+    Ok(Some(if mime.starts_with("video/") {
+        match video_seconds(path)? {
+            Some(seconds) if seconds <= ANIMATION_SECONDS => MediaKind::Animation,
+            _ => MediaKind::Video,
+        }
+    } else if name.contains("screenshot") {
+        MediaKind::Screenshot
+    } else if (mime == GIF.mime || mime == WEBP.mime) && is_animated(&read(path)?) {
+        MediaKind::Animation
+    } else {
+        MediaKind::Photo
+    }))
+}
+
+/// Returns true for a GIF with several frames or a WebP with the animation flag.
+fn is_animated(content: &[u8]) -> bool {
+    match content {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', b'V', b'P', b'8', b'X', _, _, _, _, flags, ..] => {
+            flags & 0x02 != 0
+        }
+        [b'G', b'I', b'F', ..] => {
+            content
+                .windows(3)
+                .filter(|w| *w == [0x21, 0xF9, 0x04])
+                .count()
+                > 1
+        }
+        _ => false,
+    }
+}
+
+/// The seconds between the epoch of the ISO base media files, 1904-01-01, and the
+/// Unix epoch.
+const MOVIE_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// The fields of the `mvhd` box of an ISO base media file.
+#[derive(Debug, PartialEq)]
+struct MovieHeader {
+    /// The seconds since 1904-01-01 UTC, 0 when unknown.
+    creation_time: u64,
+    timescale: u64,
+    duration: u64,
+}
+
+/// The duration of an ISO base media file, from the `mvhd` box of its `moov` box.
+fn video_seconds(path: &Path) -> Result<Option<f64>> {
+    Ok(movie_header(path)?
+        .filter(|h| h.timescale > 0)
+        .map(|h| h.duration as f64 / h.timescale as f64))
+}
+
+/// The date and time an MP4 or QuickTime video was recorded, from the `mvhd` box
+/// of its `moov` box, in local time. The containers record it in UTC.
+pub(crate) fn video_creation_datetime(path: &Path) -> Result<Option<NaiveDateTime>> {
+    Ok(movie_header(path)?
+        .and_then(|h| creation_datetime(h.creation_time))
+        .map(|utc| utc.with_timezone(&Local).naive_local()))
+}
+
+/// The UTC time of a creation time of the containers, none when unknown.
+fn creation_datetime(creation_time: u64) -> Option<DateTime<Utc>> {
+    if creation_time == 0 {
+        return None;
+    }
+    let seconds = i64::try_from(creation_time).ok()? - MOVIE_EPOCH_OFFSET;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+/// Reads the `mvhd` box of the `moov` box.
+fn movie_header(path: &Path) -> Result<Option<MovieHeader>> {
+    let mut reader = BufReader::new(open_read_only(path)?);
+    let mut end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    while let Some((kind, start, size)) = next_box(&mut reader, end)? {
+        match &kind {
+            b"moov" => end = start + size,
+            b"mvhd" => {
+                let mut header = Vec::with_capacity(32);
+                reader.by_ref().take(32).read_to_end(&mut header)?;
+                return Ok(match (header.first(), header.len()) {
+                    (Some(0), 20..) => Some(MovieHeader {
+                        creation_time: u32::from_be_bytes(header[4..8].try_into()?) as u64,
+                        timescale: u32::from_be_bytes(header[12..16].try_into()?) as u64,
+                        duration: u32::from_be_bytes(header[16..20].try_into()?) as u64,
+                    }),
+                    (Some(1), 32..) => Some(MovieHeader {
+                        creation_time: u64::from_be_bytes(header[4..12].try_into()?),
+                        timescale: u32::from_be_bytes(header[20..24].try_into()?) as u64,
+                        duration: u64::from_be_bytes(header[24..32].try_into()?),
+                    }),
+                    _ => None,
+                });
+            }
+            _ => {
+                reader.seek(SeekFrom::Start(start + size))?;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the header of the next box before the end, returning its type, the
+/// position of its content and the size of its content.
+fn next_box(reader: &mut (impl Read + Seek), end: u64) -> Result<Option<([u8; 4], u64, u64)>> {
+    let position = reader.stream_position()?;
+    if position + 8 > end {
+        return Ok(None);
+    }
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    let kind: [u8; 4] = header[4..].try_into()?;
+    let (header_size, size) = match u32::from_be_bytes(header[..4].try_into()?) {
+        0 => (8, end - position),
+        1 => {
+            let mut large = [0; 8];
+            reader.read_exact(&mut large)?;
+            (16, u64::from_be_bytes(large))
+        }
+        size => (8, size as u64),
+    };
+    if size < header_size || position + size > end {
+        return Ok(None);
+    }
+    Ok(Some((kind, position + header_size, size - header_size)))
+}
+
+/// Identifies ISO base media files (HEIF, MP4, QuickTime) from their major brand.
+fn detect_brand(brand: &[u8]) -> Option<MediaType> {
+    match brand {
+        b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1" => Some(HEIC),
+        b"avif" | b"avis" => Some(AVIF),
+        b"crx " => Some(CR3),
+        b"qt  " => Some(QUICKTIME),
+        b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1" | b"M4V "
+        | b"M4A " | b"dash" | b"3gp4" | b"3gp5" => Some(MP4),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, path::PathBuf};
+
+    use std::fs::write;
+
+    use tempfile::TempDir;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{
+        classify, creation_datetime, detect, detect_header, is_animated, is_raw, movie_header,
+        MediaKind, MovieHeader, CR2, HEIC, JPEG, PNG, QUICKTIME, TIFF,
+    };
+
+    #[test]
+    fn movie_header_reads_the_creation_time() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("clip.mov");
+        let mut content = b"\x00\x00\x00\x14ftypqt  \x00\x00\x00\x00qt  ".to_vec();
+        content.extend(b"\x00\x00\x00\x30moov\x00\x00\x00\x28mvhd\x01\x00\x00\x00");
+        content.extend(3_800_000_000u64.to_be_bytes());
+        content.extend(3_800_000_000u64.to_be_bytes());
+        content.extend(600u32.to_be_bytes());
+        content.extend(6000u64.to_be_bytes());
+        write(&path, content).unwrap();
+
+        assert_eq!(
+            Some(MovieHeader {
+                creation_time: 3_800_000_000,
+                timescale: 600,
+                duration: 6000
+            }),
+            movie_header(&path).unwrap()
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 5, 31, 11, 33, 20).unwrap()),
+            creation_datetime(3_800_000_000)
+        );
+        assert_eq!(None, creation_datetime(0));
+    }
+
+    #[test]
+    fn classify_recognizes_screenshots_and_short_videos() {
+        let directory = TempDir::new().unwrap();
+        let video = |name: &str, seconds: u32| {
+            let path = directory.path().join(name);
+            let mut content = b"\x00\x00\x00\x10ftypisom\x00\x00\x00\x00".to_vec();
+            content.extend(b"\x00\x00\x00\x1cmoov\x00\x00\x00\x14mvhd\x00\x00\x00\x00");
+            content.extend([0; 8]);
+            content.extend(1000u32.to_be_bytes());
+            content.extend((seconds * 1000).to_be_bytes());
+            write(&path, content).unwrap();
+            classify(&path, Some("video/mp4")).unwrap()
+        };
+        let jpeg: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+
+        assert_eq!(Some(MediaKind::Animation), video("motion.mp4", 2));
+        assert_eq!(Some(MediaKind::Video), video("clip.mp4", 30));
+        assert_eq!(
+            Some(MediaKind::Screenshot),
+            classify(&PathBuf::from("Screen Shot 2024.png"), Some("image/png")).unwrap()
+        );
+        assert_eq!(
+            Some(MediaKind::Photo),
+            classify(&jpeg, Some("image/jpeg")).unwrap()
+        );
+        assert_eq!(None, classify(&jpeg, None).unwrap());
+    }
+
+    #[test]
+    fn is_animated_counts_the_gif_frames() {
+        let frame = [0x21, 0xF9, 0x04, 0, 0, 0, 0, 0];
+        let single = [&b"GIF89a"[..], &frame].concat();
+        let animated = [&b"GIF89a"[..], &frame, &frame].concat();
+
+        assert!(!is_animated(&single));
+        assert!(is_animated(&animated));
+    }
+
+    #[test]
+    fn detect_recognizes_a_jpeg_file() {
+        let path: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        assert_eq!(Some(JPEG), detect(&path).unwrap());
+    }
+
+    #[test]
+    fn detect_returns_none_for_unknown_content() {
+        assert_eq!(None, detect(&PathBuf::from("Cargo.toml")).unwrap());
+    }
+
+    #[test]
+    fn detect_header_recognizes_a_png() {
+        assert_eq!(
+            Some(PNG),
+            detect_header(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0])
+        );
+    }
+
+    #[test]
+    fn detect_header_distinguishes_cr2_from_tiff() {
+        assert_eq!(
+            Some(CR2),
+            detect_header(b"II*\x00\x10\x00\x00\x00CR\x02\x00")
+        );
+        assert_eq!(
+            Some(TIFF),
+            detect_header(b"II*\x00\x08\x00\x00\x00\x00\x00")
+        );
+    }
+
+    #[test]
+    fn detect_header_uses_the_iso_media_brand() {
+        assert_eq!(
+            Some(HEIC),
+            detect_header(b"\x00\x00\x00\x18ftypheic\x00\x00")
+        );
+        assert_eq!(
+            Some(QUICKTIME),
+            detect_header(b"\x00\x00\x00\x14ftypqt  \x00\x00")
+        );
+    }
+
+    #[test]
+    fn detect_header_returns_none_for_a_truncated_header() {
+        assert_eq!(None, detect_header(&[0xFF, 0xD8]));
+    }
+
+    #[test]
+    fn is_raw_recognizes_the_raw_extensions() {
+        assert!(is_raw(&PathBuf::from("IMG_0001.CR2")));
+        assert!(is_raw(&PathBuf::from("DSC_0001.nef")));
+        assert!(!is_raw(&PathBuf::from("IMG_0001.JPG")));
+        assert!(!is_raw(&PathBuf::from("scan.tif")));
+    }
+
+    #[test]
+    fn accepts_extension_ignores_case() {
+        assert!(JPEG.accepts_extension(OsStr::new("JPG")));
+        assert!(!JPEG.accepts_extension(OsStr::new("png")));
+    }
+}
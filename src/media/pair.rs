@@ -0,0 +1,57 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::is_raw;
+
+/// The shot of a picture: its path without extension, ignoring case, which a RAW
+/// picture shares with the JPEG the camera writes along, e.g. `IMG_0001.CR2` and
+/// `IMG_0001.JPG`.
+pub(crate) fn shot(path: &Path) -> PathBuf {
+    let mut shot = path.to_path_buf();
+    shot.set_extension("");
+    PathBuf::from(shot.to_string_lossy().to_lowercase())
+}
+
+/// The `(raw, companion)` indexes of the paths of a same shot, a RAW picture and
+/// one of the other pictures. RAW pictures are never companions.
+pub(crate) fn find_pairs(paths: &[PathBuf]) -> Vec<(usize, usize)> {
+    let mut shots: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (index, path) in paths.iter().enumerate() {
+        shots.entry(shot(path)).or_default().push(index);
+    }
+    let mut pairs = shots
+        .into_values()
+        .flat_map(|shot| {
+            let (raws, companions): (Vec<usize>, Vec<usize>) =
+                shot.into_iter().partition(|i| is_raw(&paths[*i]));
+            raws.into_iter()
+                .flat_map(move |raw| companions.clone().into_iter().map(move |c| (raw, c)))
+        })
+        .collect::<Vec<(usize, usize)>>();
+    pairs.sort();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::find_pairs;
+
+    #[test]
+    fn find_pairs_matches_the_raw_with_the_pictures_of_its_shot() {
+        let paths = [
+            "/card/IMG_0001.JPG",
+            "/card/img_0001.cr2",
+            "/card/IMG_0002.JPG",
+            "/other/IMG_0001.JPG",
+            "/card/IMG_0003.NEF",
+            "/card/IMG_0003.HEIC",
+        ]
+        .map(PathBuf::from);
+
+        assert_eq!(vec![(1, 0), (4, 5)], find_pairs(&paths));
+    }
+}
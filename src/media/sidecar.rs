@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use super::is_raw;
+
+/// The extensions of the sidecars, in the case they are looked for.
+const SIDECAR_EXTENSIONS: [&str; 2] = ["xmp", "XMP"];
+
+/// The XMP sidecar holding the edits of a RAW picture, next to it. It is named
+/// after the whole name of the picture, e.g. `IMG_0001.CR2.xmp` as darktable
+/// writes it, or after its stem, `IMG_0001.xmp` as Lightroom does.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Sidecar {
+    path: PathBuf,
+    extension: &'static str,
+    keeps_extension: bool,
+}
+
+impl Sidecar {
+    /// The sidecar of the picture, none when it is not a RAW or has none.
+    pub(crate) fn find(picture: &Path) -> Option<Self> {
+        if !is_raw(picture) {
+            return None;
+        }
+        [true, false].into_iter().find_map(|keeps_extension| {
+            SIDECAR_EXTENSIONS.into_iter().find_map(|extension| {
+                let path = sidecar_path(picture, extension, keeps_extension);
+                path.is_file().then_some(Self {
+                    path,
+                    extension,
+                    keeps_extension,
+                })
+            })
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the sidecar of the picture copied or moved to `picture`, named
+    /// the same way.
+    pub(crate) fn path_for(&self, picture: &Path) -> PathBuf {
+        sidecar_path(picture, self.extension, self.keeps_extension)
+    }
+}
+
+fn sidecar_path(picture: &Path, extension: &str, keeps_extension: bool) -> PathBuf {
+    let mut name = if keeps_extension {
+        picture.file_name()
+    } else {
+        picture.file_stem()
+    }
+    .unwrap_or_default()
+    .to_owned();
+    name.push(".");
+    name.push(extension);
+    picture.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::write, path::Path};
+
+    use tempfile::TempDir;
+
+    use super::Sidecar;
+
+    #[test]
+    fn find_prefers_the_sidecar_named_after_the_whole_name() {
+        let directory = TempDir::new().unwrap();
+        let picture = directory.path().join("IMG_0001.CR2");
+        write(&picture, "raw").unwrap();
+        assert_eq!(None, Sidecar::find(&picture));
+
+        write(directory.path().join("IMG_0001.XMP"), "lightroom").unwrap();
+        let sidecar = Sidecar::find(&picture).unwrap();
+        assert_eq!(directory.path().join("IMG_0001.XMP"), sidecar.path());
+        assert_eq!(
+            Path::new("2024/05/18/IMG_0001_1.XMP"),
+            sidecar.path_for(Path::new("2024/05/18/IMG_0001_1.CR2"))
+        );
+
+        write(directory.path().join("IMG_0001.CR2.xmp"), "darktable").unwrap();
+        let sidecar = Sidecar::find(&picture).unwrap();
+        assert_eq!(directory.path().join("IMG_0001.CR2.xmp"), sidecar.path());
+        assert_eq!(
+            Path::new("2024/05/18/IMG_0001_1.CR2.xmp"),
+            sidecar.path_for(Path::new("2024/05/18/IMG_0001_1.CR2"))
+        );
+    }
+
+    #[test]
+    fn find_ignores_the_pictures_other_than_raw() {
+        let directory = TempDir::new().unwrap();
+        let picture = directory.path().join("IMG_0001.jpg");
+        write(&picture, "jpeg").unwrap();
+        write(directory.path().join("IMG_0001.xmp"), "xmp").unwrap();
+
+        assert_eq!(None, Sidecar::find(&picture));
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use chrono::NaiveDateTime;
+
+/// The most seconds between two frames of a stack.
+const MAX_GAP_SECONDS: i64 = 3;
+/// The fewest frames of a stack.
+const MIN_FRAMES: usize = 3;
+/// The fewest degrees the camera turns between two frames of a panorama.
+const MIN_YAW_STEP: f64 = 5.0;
+
+/// The pictures of a same shot taken in a burst, kept as one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum StackKind {
+    /// The exposures of an HDR bracket, each with its exposure compensation.
+    Bracket,
+    /// The frames of a panorama sweep, each facing further in the same direction.
+    Panorama,
+}
+
+impl Display for StackKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bracket => "bracket",
+            Self::Panorama => "panorama",
+        })
+    }
+}
+
+impl FromStr for StackKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "bracket" => Ok(Self::Bracket),
+            "panorama" => Ok(Self::Panorama),
+            _ => Err(format!("Invalid stack kind `{}`", kind)),
+        }
+    }
+}
+
+/// The metadata of a picture telling the frames of a stack.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Frame {
+    pub(crate) datetime: NaiveDateTime,
+    pub(crate) camera: Option<String>,
+    /// The exposure compensation in EV.
+    pub(crate) exposure_bias: Option<f64>,
+    /// The degrees from the north the camera faced.
+    pub(crate) direction: Option<f64>,
+}
+
+/// The indexes of the frames of each stack, in shooting order: at least 3
+/// pictures of a same camera taken a few seconds apart, either with different
+/// exposure compensations or turning the same way between each.
+pub(crate) fn find_stacks(frames: &[Frame]) -> Vec<(StackKind, Vec<usize>)> {
+    let mut order = (0..frames.len()).collect::<Vec<usize>>();
+    order.sort_by(|a, b| {
+        (&frames[*a].camera, frames[*a].datetime).cmp(&(&frames[*b].camera, frames[*b].datetime))
+    });
+    let mut bursts: Vec<Vec<usize>> = vec![];
+    for index in order {
+        match bursts.last_mut() {
+            Some(burst) if follows(&frames[*burst.last().expect("not empty")], &frames[index]) => {
+                burst.push(index)
+            }
+            _ => bursts.push(vec![index]),
+        }
+    }
+    bursts
+        .into_iter()
+        .filter(|burst| burst.len() >= MIN_FRAMES)
+        .filter_map(|burst| {
+            let frames = burst.iter().map(|i| &frames[*i]).collect::<Vec<&Frame>>();
+            if is_bracket(&frames) {
+                Some((StackKind::Bracket, burst))
+            } else if is_panorama(&frames) {
+                Some((StackKind::Panorama, burst))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// True when the next frame was taken by the same camera shortly after.
+fn follows(previous: &Frame, next: &Frame) -> bool {
+    previous.camera == next.camera
+        && (next.datetime - previous.datetime).num_seconds() <= MAX_GAP_SECONDS
+}
+
+/// True when the frames have as many distinct exposure compensations as a bracket
+/// of the fewest frames.
+fn is_bracket(frames: &[&Frame]) -> bool {
+    let Some(mut biases) = frames
+        .iter()
+        .map(|f| f.exposure_bias.map(|b| (b * 10.0).round() as i64))
+        .collect::<Option<Vec<i64>>>()
+    else {
+        return false;
+    };
+    biases.sort();
+    biases.dedup();
+    biases.len() >= MIN_FRAMES
+}
+
+/// True when the camera turns the same way, by a few degrees at least, between
+/// each frame.
+fn is_panorama(frames: &[&Frame]) -> bool {
+    let Some(directions) = frames
+        .iter()
+        .map(|f| f.direction)
+        .collect::<Option<Vec<f64>>>()
+    else {
+        return false;
+    };
+    // The turns between -180 and 180 degrees, across the north.
+    let turns = directions
+        .windows(2)
+        .map(|w| (w[1] - w[0] + 540.0).rem_euclid(360.0) - 180.0)
+        .collect::<Vec<f64>>();
+    turns.iter().all(|t| *t >= MIN_YAW_STEP) || turns.iter().all(|t| *t <= -MIN_YAW_STEP)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{find_stacks, Frame, StackKind};
+
+    fn at(second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 7, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, second)
+            .unwrap()
+    }
+
+    fn frame(second: u32, exposure_bias: Option<f64>, direction: Option<f64>) -> Frame {
+        Frame {
+            datetime: at(second),
+            camera: Some("X-T5".to_string()),
+            exposure_bias,
+            direction,
+        }
+    }
+
+    #[test]
+    fn find_stacks_groups_the_brackets() {
+        let frames = [
+            frame(2, Some(0.0), None),
+            frame(0, Some(-2.0), None),
+            frame(1, Some(2.0), None),
+            frame(30, Some(0.0), None),
+            frame(31, Some(0.0), None),
+            frame(32, Some(0.0), None),
+        ];
+
+        assert_eq!(
+            vec![(StackKind::Bracket, vec![1, 2, 0])],
+            find_stacks(&frames)
+        );
+    }
+
+    #[test]
+    fn find_stacks_groups_the_panorama_sweeps_across_the_north() {
+        let frames = [
+            frame(0, Some(0.0), Some(340.0)),
+            frame(2, Some(0.0), Some(10.0)),
+            frame(4, Some(0.0), Some(40.0)),
+            frame(20, Some(0.0), Some(10.0)),
+            frame(21, Some(0.0), Some(40.0)),
+            frame(22, Some(0.0), Some(20.0)),
+        ];
+
+        assert_eq!(
+            vec![(StackKind::Panorama, vec![0, 1, 2])],
+            find_stacks(&frames)
+        );
+    }
+
+    #[test]
+    fn find_stacks_keeps_the_cameras_apart() {
+        let mut frames = vec![
+            frame(0, Some(-1.0), None),
+            frame(1, Some(0.0), None),
+            frame(1, Some(1.0), None),
+        ];
+        frames[2].camera = Some("Pixel 8".to_string());
+
+        assert!(find_stacks(&frames).is_empty());
+    }
+}
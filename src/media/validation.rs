@@ -0,0 +1,150 @@
+use std::{fs::read, path::Path};
+
+use eyre::Result;
+
+use super::{MediaType, JPEG, PNG};
+
+/// Scans the structure of an image and returns a description of the first
+/// defect found. Formats without a structural check are considered valid.
+pub(crate) fn validate(path: &Path, media_type: MediaType) -> Result<Option<String>> {
+    if media_type == JPEG {
+        Ok(validate_jpeg(&read(path)?).err())
+    } else if media_type == PNG {
+        Ok(validate_png(&read(path)?).err())
+    } else {
+        Ok(None)
+    }
+}
+
+fn validate_jpeg(bytes: &[u8]) -> Result<(), String> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err("Missing JPEG start of image marker".to_string());
+    }
+    let mut position = 2;
+    loop {
+        if position + 1 >= bytes.len() {
+            return Err("Missing JPEG end of image marker".to_string());
+        }
+        if bytes[position] != 0xFF {
+            return Err(format!("Invalid JPEG marker at offset {}", position));
+        }
+        let marker = bytes[position + 1];
+        match marker {
+            0xFF => position += 1,
+            0xD9 => return Ok(()),
+            0x01 | 0xD0..=0xD7 => position += 2,
+            _ => {
+                position = skip_segment(bytes, position)?;
+                if marker == 0xDA {
+                    position = skip_entropy_coded_data(bytes, position);
+                }
+            }
+        }
+    }
+}
+
+fn skip_segment(bytes: &[u8], position: usize) -> Result<usize, String> {
+    if position + 4 > bytes.len() {
+        return Err(format!("Truncated JPEG segment at offset {}", position));
+    }
+    let length = u16::from_be_bytes([bytes[position + 2], bytes[position + 3]]) as usize;
+    let end = position + 2 + length;
+    if length < 2 || end > bytes.len() {
+        Err(format!("Truncated JPEG segment at offset {}", position))
+    } else {
+        Ok(end)
+    }
+}
+
+/// Skips the scan data up to the next marker, ignoring stuffed bytes and restart markers.
+fn skip_entropy_coded_data(bytes: &[u8], mut position: usize) -> usize {
+    while position + 1 < bytes.len() {
+        if bytes[position] == 0xFF && !matches!(bytes[position + 1], 0x00 | 0xD0..=0xD7) {
+            return position;
+        }
+        position += 1;
+    }
+    bytes.len()
+}
+
+fn validate_png(bytes: &[u8]) -> Result<(), String> {
+    let mut position = 8;
+    while position + 8 <= bytes.len() {
+        let length = u32::from_be_bytes([
+            bytes[position],
+            bytes[position + 1],
+            bytes[position + 2],
+            bytes[position + 3],
+        ]) as usize;
+        let chunk_type = &bytes[position + 4..position + 8];
+        position += 12 + length;
+        if position > bytes.len() {
+            return Err(format!(
+                "Truncated PNG {} chunk",
+                String::from_utf8_lossy(chunk_type)
+            ));
+        }
+        if chunk_type == b"IEND" {
+            return Ok(());
+        }
+    }
+    Err("Missing PNG IEND chunk".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read, path::PathBuf};
+
+    use super::{validate, validate_jpeg, validate_png};
+    use crate::media::JPEG;
+
+    fn given_a_jpeg() -> Vec<u8> {
+        read(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_accepts_a_valid_jpeg() {
+        let path: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        assert_eq!(None, validate(&path, JPEG).unwrap());
+    }
+
+    #[test]
+    fn validate_jpeg_rejects_a_truncated_jpeg() {
+        let bytes = given_a_jpeg();
+        assert!(validate_jpeg(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn validate_jpeg_rejects_a_jpeg_without_start_marker() {
+        let bytes = given_a_jpeg();
+        assert_eq!(
+            Err("Missing JPEG start of image marker".to_string()),
+            validate_jpeg(&bytes[2..])
+        );
+    }
+
+    #[test]
+    fn validate_png_accepts_an_empty_iend_terminated_png() {
+        let mut bytes = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(b"IEND");
+        bytes.extend_from_slice(&[0xAE, 0x42, 0x60, 0x82]);
+        assert_eq!(Ok(()), validate_png(&bytes));
+    }
+
+    #[test]
+    fn validate_png_rejects_a_truncated_chunk() {
+        let mut bytes = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        bytes.extend_from_slice(&[0, 0, 0, 13]);
+        bytes.extend_from_slice(b"IHDR");
+        assert_eq!(
+            Err("Truncated PNG IHDR chunk".to_string()),
+            validate_png(&bytes)
+        );
+    }
+}
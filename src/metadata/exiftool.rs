@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use chrono::NaiveDateTime;
+use eyre::{eyre, Context, Result};
+
+use crate::error::{Error, ErrorCode};
+
+use super::{kilometers_per_hour, MetadataBackend, Telemetry, VideoContainer};
+
+const EXIFTOOL: &str = "exiftool";
+
+/// The `exiftool` command, which reads and writes most formats, RAW and video
+/// included.
+pub(crate) struct ExifTool;
+
+impl ExifTool {
+    /// True when exiftool is installed.
+    pub(crate) fn available() -> bool {
+        Command::new(EXIFTOOL)
+            .arg("-ver")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// The value of a tag, printed without its name.
+    fn tag(&self, path: &Path, tag: &str) -> Result<Option<String>> {
+        let output = Command::new(EXIFTOOL)
+            .args(["-s3", "-d", "%Y-%m-%d %H:%M:%S", &format!("-{}", tag)])
+            .arg(path)
+            .output()
+            .wrap_err("Failed to run exiftool")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "exiftool failed for {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(parse_value(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// The values of the tags found in the file, by tag name, the numbers printed
+    /// as such rather than converted for display.
+    fn values(&self, path: &Path, tags: &[&str]) -> Result<HashMap<String, String>> {
+        let output = Command::new(EXIFTOOL)
+            .args(["-s", "-n"])
+            .args(tags.iter().map(|t| format!("-{}", t)))
+            .arg(path)
+            .output()
+            .wrap_err("Failed to run exiftool")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "exiftool failed for {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(parse_values(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+impl MetadataBackend for ExifTool {
+    fn name(&self) -> &'static str {
+        EXIFTOOL
+    }
+
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime> {
+        let Some(value) = self.tag(path, "DateTimeOriginal")? else {
+            if VideoContainer::accepts(path) {
+                return VideoContainer.original_datetime(path);
+            }
+            return Err(
+                Error::new(ErrorCode::MissingExifDate, "DateTimeOriginal tag not found").into(),
+            );
+        };
+        NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S")
+            .wrap_err("Failed to parse DateTimeOriginal")
+    }
+
+    fn camera_model(&self, path: &Path) -> Option<String> {
+        self.tag(path, "Model").ok().flatten()
+    }
+
+    fn telemetry(&self, path: &Path) -> Telemetry {
+        let Ok(values) = self.values(
+            path,
+            &[
+                "GPSAltitude",
+                "GPSAltitudeRef",
+                "GPSImgDirection",
+                "GPSSpeed",
+                "GPSSpeedRef",
+            ],
+        ) else {
+            return Telemetry::default();
+        };
+        let number = |tag| values.get(tag)?.parse::<f64>().ok();
+        let below_sea_level = number("GPSAltitudeRef") == Some(1.0);
+        Telemetry {
+            altitude: number("GPSAltitude").map(|a| if below_sea_level { -a.abs() } else { a }),
+            direction: number("GPSImgDirection"),
+            speed: number("GPSSpeed")
+                .map(|s| kilometers_per_hour(s, values.get("GPSSpeedRef").map(String::as_str))),
+        }
+    }
+
+    fn exposure_bias(&self, path: &Path) -> Option<f64> {
+        self.values(path, &["ExposureCompensation"])
+            .ok()?
+            .get("ExposureCompensation")?
+            .parse()
+            .ok()
+    }
+
+    fn write_original_date(&self, path: &Path, date: NaiveDateTime) -> Result<()> {
+        let status = Command::new(EXIFTOOL)
+            .arg("-overwrite_original")
+            .arg(format!(
+                "-DateTimeOriginal={}",
+                date.format("%Y:%m:%d %H:%M:%S")
+            ))
+            .arg(path)
+            .stdout(Stdio::null())
+            .status()
+            .wrap_err("Failed to run exiftool")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(eyre!("exiftool failed to write {}", path.display()))
+        }
+    }
+}
+
+/// The first line of the output, none when exiftool printed nothing.
+fn parse_value(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+}
+
+/// The values of the `Tag : value` lines of the output, by tag name.
+fn parse_values(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(tag, value)| (tag.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_value, parse_values};
+
+    #[test]
+    fn parse_value_reads_the_first_line() {
+        assert_eq!(
+            Some("2023-05-18 10:00:00".to_string()),
+            parse_value("2023-05-18 10:00:00\n")
+        );
+        assert_eq!(None, parse_value(""));
+    }
+
+    #[test]
+    fn parse_values_reads_the_tags_by_name() {
+        let values = parse_values(
+            "GPSAltitude                     : 120.5\nGPSSpeedRef                     : K\n",
+        );
+
+        assert_eq!(2, values.len());
+        assert_eq!(Some("120.5"), values.get("GPSAltitude").map(String::as_str));
+        assert_eq!(Some("K"), values.get("GPSSpeedRef").map(String::as_str));
+    }
+}
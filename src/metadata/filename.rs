@@ -0,0 +1,140 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+/// The date and time written in the file name by the phones and screenshot tools,
+/// e.g. `IMG_20230518_143012.jpg`, `PXL_20230518_143012345.jpg` or
+/// `Screenshot 2023-05-18 at 14.30.12.png`, midnight when the name has no time.
+pub(crate) fn filename_datetime(name: &str) -> Option<NaiveDateTime> {
+    let chars = name.chars().collect::<Vec<char>>();
+    (0..chars.len())
+        .filter(|&i| chars[i].is_ascii_digit() && (i == 0 || !chars[i - 1].is_ascii_digit()))
+        .find_map(|i| compact(&chars[i..]).or_else(|| separated(&chars[i..])))
+}
+
+/// A `20230518` date, then an optional `_143012` time.
+fn compact(chars: &[char]) -> Option<NaiveDateTime> {
+    if chars.get(8).is_some_and(char::is_ascii_digit) {
+        return None;
+    }
+    let date = date(
+        number(chars, 0, 4)?,
+        number(chars, 4, 2)?,
+        number(chars, 6, 2)?,
+    )?;
+    let time = match chars.get(8) {
+        Some('_' | '-' | 'T' | ' ') => chars.get(9..).and_then(time),
+        _ => None,
+    };
+    Some(date.and_time(time.unwrap_or_default()))
+}
+
+/// A `2023-05-18` date, then an optional `_14-30-12` or ` at 14.30.12` time.
+fn separated(chars: &[char]) -> Option<NaiveDateTime> {
+    if chars.get(4) != Some(&'-') || chars.get(7) != Some(&'-') {
+        return None;
+    }
+    let date = date(
+        number(chars, 0, 4)?,
+        number(chars, 5, 2)?,
+        number(chars, 8, 2)?,
+    )?;
+    if chars.get(10).is_some_and(char::is_ascii_digit) {
+        return None;
+    }
+    let rest = chars.get(10..).unwrap_or_default();
+    let rest = match rest {
+        [' ', 'a', 't', ' ', rest @ ..] | ['_' | '-' | 'T' | ' ', rest @ ..] => rest,
+        _ => &[],
+    };
+    Some(date.and_time(time(rest).unwrap_or_default()))
+}
+
+/// A `143012` time, possibly followed by milliseconds, or a `14.30.12` time
+/// separated by dots, dashes or colons.
+fn time(chars: &[char]) -> Option<NaiveTime> {
+    let separated = matches!(chars.get(2), Some('.' | '-' | ':')) && chars.get(5) == chars.get(2);
+    let (hour, minute, second) = if separated {
+        (
+            number(chars, 0, 2)?,
+            number(chars, 3, 2)?,
+            number(chars, 6, 2)?,
+        )
+    } else {
+        (
+            number(chars, 0, 2)?,
+            number(chars, 2, 2)?,
+            number(chars, 4, 2)?,
+        )
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// A date of a plausible year.
+fn date(year: u32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1900..2100)
+        .contains(&year)
+        .then(|| NaiveDate::from_ymd_opt(year as i32, month, day))
+        .flatten()
+}
+
+/// The number written with the count of digits at the start.
+fn number(chars: &[char], start: usize, count: usize) -> Option<u32> {
+    let digits = chars.get(start..start + count)?;
+    digits
+        .iter()
+        .all(char::is_ascii_digit)
+        .then(|| digits.iter().collect::<String>().parse().ok())
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::filename_datetime;
+
+    fn datetime(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+    ) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn filename_datetime_reads_the_names_of_the_phones_and_screenshots() {
+        assert_eq!(
+            Some(datetime(2023, 5, 18, 14, 30, 12)),
+            filename_datetime("IMG_20230518_143012.jpg")
+        );
+        assert_eq!(
+            Some(datetime(2023, 5, 18, 14, 30, 12)),
+            filename_datetime("PXL_20230518_143012345.jpg")
+        );
+        assert_eq!(
+            Some(datetime(2023, 5, 18, 14, 30, 12)),
+            filename_datetime("Screenshot 2023-05-18 at 14.30.12.png")
+        );
+        assert_eq!(
+            Some(datetime(2023, 5, 18, 0, 0, 0)),
+            filename_datetime("Screenshot_2023-05-18.png")
+        );
+        assert_eq!(
+            Some(datetime(2023, 5, 18, 0, 0, 0)),
+            filename_datetime("VID-20230518-WA0001.mp4")
+        );
+    }
+
+    #[test]
+    fn filename_datetime_ignores_the_other_numbers() {
+        assert_eq!(None, filename_datetime("IMG_0001.JPG"));
+        assert_eq!(None, filename_datetime("DSC_123456789.JPG"));
+        assert_eq!(None, filename_datetime("scan_20231399.jpg"));
+        assert_eq!(None, filename_datetime("hawaii.jpg"));
+    }
+}
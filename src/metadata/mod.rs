@@ -0,0 +1,596 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::metadata,
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, ErrorCode},
+    fsext::source::open_read_only,
+    media::{self, MP4, QUICKTIME},
+};
+
+use self::{exiftool::ExifTool, filename::filename_datetime};
+
+pub(crate) mod exiftool;
+pub(crate) mod filename;
+
+/// Reads, and possibly writes, the metadata embedded in the pictures.
+pub(crate) trait MetadataBackend {
+    fn name(&self) -> &'static str;
+
+    /// The date and time the picture was taken, in the local time of the camera.
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime>;
+
+    /// The date the picture was taken.
+    fn original_date(&self, path: &Path) -> Result<NaiveDate> {
+        Ok(self.original_datetime(path)?.date())
+    }
+
+    /// The date and time the picture was taken, along with where it was read.
+    fn dated(&self, path: &Path) -> Result<(NaiveDateTime, DateSource)> {
+        Ok((self.original_datetime(path)?, DateSource::Metadata))
+    }
+
+    fn camera_model(&self, path: &Path) -> Option<String>;
+
+    /// The altitude, direction and speed the GPS recorded, nothing for the formats
+    /// the backend doesn't read.
+    fn telemetry(&self, _path: &Path) -> Telemetry {
+        Telemetry::default()
+    }
+
+    /// The exposure compensation in EV, which varies along a bracket.
+    fn exposure_bias(&self, _path: &Path) -> Option<f64> {
+        None
+    }
+
+    /// Records the date the picture was taken in the file.
+    fn write_original_date(&self, path: &Path, _date: NaiveDateTime) -> Result<()> {
+        Err(eyre!(
+            "{} can't write the metadata of {}",
+            self.name(),
+            path.display()
+        ))
+    }
+}
+
+/// The GPS readings beyond the position, which the drones and the action cameras
+/// record.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Telemetry {
+    /// The meters above the sea level, negative below.
+    pub(crate) altitude: Option<f64>,
+    /// The degrees from the north the camera faced, from 0 to 360.
+    pub(crate) direction: Option<f64>,
+    /// The speed of the camera in km/h.
+    pub(crate) speed: Option<f64>,
+}
+
+impl Telemetry {
+    /// The readings, each taken from the other telemetry when missing here.
+    pub(crate) fn or(self, other: Telemetry) -> Telemetry {
+        Telemetry {
+            altitude: self.altitude.or(other.altitude),
+            direction: self.direction.or(other.direction),
+            speed: self.speed.or(other.speed),
+        }
+    }
+}
+
+/// The speed in km/h of a speed in the unit of the GPSSpeedRef tag: `K` for km/h,
+/// `M` for mph and `N` for knots.
+fn kilometers_per_hour(speed: f64, unit: Option<&str>) -> f64 {
+    match unit {
+        Some("M") => speed * 1.609_344,
+        Some("N") => speed * 1.852,
+        _ => speed,
+    }
+}
+
+/// The `[metadata]` table of the repository configuration.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct MetadataConfig {
+    #[serde(default)]
+    backend: Backend,
+    /// The sources of the date tried in order for the pictures whose metadata
+    /// has none, e.g. `["filename", "mtime"]`.
+    #[serde(default)]
+    date_fallbacks: Vec<DateSource>,
+}
+
+/// The reader of the picture metadata.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Backend {
+    /// The built-in EXIF reader, falling back to exiftool when it is installed.
+    #[default]
+    Auto,
+    /// The built-in EXIF reader only, with the creation date of the MP4 and
+    /// QuickTime videos.
+    Exif,
+    /// exiftool only.
+    ExifTool,
+}
+
+/// Where the date of a picture was read.
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DateSource {
+    /// The EXIF, or the container of the videos.
+    Metadata,
+    /// The date written in the file name, e.g. `IMG_20230518_143012.jpg`.
+    FileName,
+    /// The last modification time of the file.
+    MTime,
+}
+
+impl DateSource {
+    /// The date and time of the file from this source, for the fallbacks.
+    fn read(&self, backend: &dyn MetadataBackend, path: &Path) -> Option<NaiveDateTime> {
+        match self {
+            Self::Metadata => backend.original_datetime(path).ok(),
+            Self::FileName => filename_datetime(&path.file_name()?.to_string_lossy()),
+            Self::MTime => metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(|t| DateTime::<Local>::from(t).naive_local()),
+        }
+    }
+}
+
+impl Display for DateSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Metadata => "metadata",
+            Self::FileName => "filename",
+            Self::MTime => "mtime",
+        })
+    }
+}
+
+impl MetadataConfig {
+    pub(crate) fn kind(&self) -> Backend {
+        self.backend
+    }
+
+    pub(crate) fn date_fallbacks(&self) -> &[DateSource] {
+        &self.date_fallbacks
+    }
+
+    pub(crate) fn backend(&self) -> Box<dyn MetadataBackend> {
+        let backend: Box<dyn MetadataBackend> = match self.backend {
+            Backend::Auto => Box::new(Auto {
+                exiftool: ExifTool::available().then_some(ExifTool),
+            }),
+            Backend::Exif => Box::new(Exif),
+            Backend::ExifTool => Box::new(ExifTool),
+        };
+        if self.date_fallbacks.is_empty() {
+            backend
+        } else {
+            Box::new(WithFallbacks {
+                backend,
+                fallbacks: self.date_fallbacks.clone(),
+            })
+        }
+    }
+}
+
+/// The EXIF reader of the kamadak-exif crate, limited to the formats it knows.
+pub(crate) struct Exif;
+
+impl Exif {
+    /// The JPEG thumbnail the camera embedded in the EXIF metadata, if any.
+    pub(crate) fn thumbnail(&self, path: &Path) -> Option<Vec<u8>> {
+        let exif = read_exif(path).ok()?;
+        let field = |tag| {
+            exif.get_field(tag, exif::In::THUMBNAIL)?
+                .value
+                .get_uint(0)
+                .map(|v| v as usize)
+        };
+        let offset = field(exif::Tag::JPEGInterchangeFormat)?;
+        let length = field(exif::Tag::JPEGInterchangeFormatLength)?;
+        exif.buf().get(offset..offset + length).map(<[u8]>::to_vec)
+    }
+}
+
+impl MetadataBackend for Exif {
+    fn name(&self) -> &'static str {
+        "exif"
+    }
+
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime> {
+        if VideoContainer::accepts(path) {
+            return VideoContainer.original_datetime(path);
+        }
+        original_datetime(&read_exif(path)?)
+    }
+
+    fn camera_model(&self, path: &Path) -> Option<String> {
+        let exif = read_exif(path).ok()?;
+        match &exif.get_field(exif::Tag::Model, exif::In::PRIMARY)?.value {
+            exif::Value::Ascii(values) => values
+                .first()
+                .map(|v| String::from_utf8_lossy(v).trim().to_owned()),
+            _ => None,
+        }
+    }
+
+    fn telemetry(&self, path: &Path) -> Telemetry {
+        let Ok(exif) = read_exif(path) else {
+            return Telemetry::default();
+        };
+        let field = |tag| exif.get_field(tag, exif::In::PRIMARY).map(|f| &f.value);
+        let rational = |tag| match field(tag)? {
+            exif::Value::Rational(values) => values
+                .first()
+                .map(exif::Rational::to_f64)
+                .filter(|v| v.is_finite()),
+            _ => None,
+        };
+        let below_sea_level =
+            field(exif::Tag::GPSAltitudeRef).and_then(|v| v.get_uint(0)) == Some(1);
+        let speed_unit = match field(exif::Tag::GPSSpeedRef) {
+            Some(exif::Value::Ascii(values)) => values
+                .first()
+                .map(|v| String::from_utf8_lossy(v).trim().to_owned()),
+            _ => None,
+        };
+        Telemetry {
+            altitude: rational(exif::Tag::GPSAltitude)
+                .map(|a| if below_sea_level { -a } else { a }),
+            direction: rational(exif::Tag::GPSImgDirection),
+            speed: rational(exif::Tag::GPSSpeed)
+                .map(|s| kilometers_per_hour(s, speed_unit.as_deref())),
+        }
+    }
+
+    fn exposure_bias(&self, path: &Path) -> Option<f64> {
+        let exif = read_exif(path).ok()?;
+        match &exif
+            .get_field(exif::Tag::ExposureBiasValue, exif::In::PRIMARY)?
+            .value
+        {
+            exif::Value::SRational(values) => values
+                .first()
+                .map(exif::SRational::to_f64)
+                .filter(|v| v.is_finite()),
+            _ => None,
+        }
+    }
+}
+
+/// The creation date the MP4 and QuickTime containers record in their `mvhd`
+/// box, the videos having no EXIF.
+pub(crate) struct VideoContainer;
+
+impl VideoContainer {
+    /// True for the MP4 and QuickTime files.
+    pub(crate) fn accepts(path: &Path) -> bool {
+        matches!(media::detect(path), Ok(Some(t)) if t == MP4 || t == QUICKTIME)
+    }
+}
+
+impl MetadataBackend for VideoContainer {
+    fn name(&self) -> &'static str {
+        "video"
+    }
+
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime> {
+        media::video_creation_datetime(path)?
+            .ok_or(Error::new(ErrorCode::MissingExifDate, "mvhd creation time not found").into())
+    }
+
+    fn camera_model(&self, _path: &Path) -> Option<String> {
+        None
+    }
+}
+
+/// The EXIF reader first, then exiftool for the formats the reader doesn't know
+/// and for the writes.
+struct Auto {
+    exiftool: Option<ExifTool>,
+}
+
+impl MetadataBackend for Auto {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime> {
+        Exif.original_datetime(path)
+            .or_else(|e| match &self.exiftool {
+                Some(exiftool) => exiftool.original_datetime(path),
+                None => Err(e),
+            })
+    }
+
+    fn camera_model(&self, path: &Path) -> Option<String> {
+        Exif.camera_model(path)
+            .or_else(|| self.exiftool.as_ref()?.camera_model(path))
+    }
+
+    fn telemetry(&self, path: &Path) -> Telemetry {
+        let telemetry = Exif.telemetry(path);
+        match &self.exiftool {
+            Some(exiftool) if telemetry == Telemetry::default() => exiftool.telemetry(path),
+            _ => telemetry,
+        }
+    }
+
+    fn exposure_bias(&self, path: &Path) -> Option<f64> {
+        Exif.exposure_bias(path)
+            .or_else(|| self.exiftool.as_ref()?.exposure_bias(path))
+    }
+
+    fn write_original_date(&self, path: &Path, date: NaiveDateTime) -> Result<()> {
+        match &self.exiftool {
+            Some(exiftool) => exiftool.write_original_date(path, date),
+            None => Err(eyre!("Writing metadata requires exiftool")),
+        }
+    }
+}
+
+/// The backend, then the date fallbacks of the configuration for the pictures
+/// whose metadata has no date.
+struct WithFallbacks {
+    backend: Box<dyn MetadataBackend>,
+    fallbacks: Vec<DateSource>,
+}
+
+impl MetadataBackend for WithFallbacks {
+    fn name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    fn original_datetime(&self, path: &Path) -> Result<NaiveDateTime> {
+        self.dated(path).map(|(datetime, _)| datetime)
+    }
+
+    fn dated(&self, path: &Path) -> Result<(NaiveDateTime, DateSource)> {
+        self.backend.dated(path).or_else(|e| {
+            self.fallbacks
+                .iter()
+                .find_map(|f| f.read(self.backend.as_ref(), path).map(|d| (d, *f)))
+                .ok_or(e)
+        })
+    }
+
+    fn camera_model(&self, path: &Path) -> Option<String> {
+        self.backend.camera_model(path)
+    }
+
+    fn telemetry(&self, path: &Path) -> Telemetry {
+        self.backend.telemetry(path)
+    }
+
+    fn exposure_bias(&self, path: &Path) -> Option<f64> {
+        self.backend.exposure_bias(path)
+    }
+
+    fn write_original_date(&self, path: &Path, date: NaiveDateTime) -> Result<()> {
+        self.backend.write_original_date(path, date)
+    }
+}
+
+fn original_datetime(exif: &exif::Exif) -> Result<NaiveDateTime> {
+    if let Some(datetime_field) = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY) {
+        NaiveDateTime::parse_from_str(
+            &datetime_field
+                .value
+                .display_as(exif::Tag::DateTimeOriginal)
+                .to_string(),
+            "%Y-%m-%d %H:%M:%S",
+        )
+        .wrap_err("Failed to parse DateTimmeOriginal")
+    } else {
+        Err(Error::new(ErrorCode::MissingExifDate, "DateTimeOriginal tag not found").into())
+    }
+}
+
+/// The latitude and longitude of the EXIF GPS tags, in decimal degrees, negative
+/// to the south and the west.
+pub(crate) fn gps_coordinates(path: &Path) -> Option<(f64, f64)> {
+    let exif = read_exif(path).ok()?;
+    let degrees = |tag, reference, negative: &[u8]| {
+        let exif::Value::Rational(values) = &exif.get_field(tag, exif::In::PRIMARY)?.value else {
+            return None;
+        };
+        let [d, m, s] = values.get(..3)? else {
+            return None;
+        };
+        let value = d.to_f64() + m.to_f64() / 60.0 + s.to_f64() / 3600.0;
+        let negated = exif
+            .get_field(reference, exif::In::PRIMARY)
+            .is_some_and(|f| match &f.value {
+                exif::Value::Ascii(refs) => refs.first().is_some_and(|r| r.as_slice() == negative),
+                _ => false,
+            });
+        Some(if negated { -value } else { value })
+    };
+    Some((
+        degrees(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, b"S")?,
+        degrees(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, b"W")?,
+    ))
+}
+
+fn read_exif(path: &Path) -> Result<exif::Exif> {
+    let file = open_read_only(path)?;
+    let mut bufreader = std::io::BufReader::new(&file);
+    let exifreader = exif::Reader::new();
+    exifreader
+        .read_from_container(&mut bufreader)
+        .wrap_err(eyre!("Failed to read exif for {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{copy, write},
+        path::PathBuf,
+    };
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use tempfile::TempDir;
+
+    use super::{
+        original_datetime, read_exif, DateSource, Exif, MetadataBackend, MetadataConfig, Telemetry,
+        WithFallbacks,
+    };
+
+    #[test]
+    fn camera_model_is_none_for_non_exif_file() {
+        assert_eq!(None, Exif.camera_model(&PathBuf::from("Cargo.toml")));
+    }
+
+    #[test]
+    fn read_exif_returns_an_error() {
+        assert!(read_exif(&PathBuf::from("Cargo.toml")).is_err());
+    }
+
+    #[test]
+    fn original_datetime_returns_the_original_naive_date_from_exif() {
+        let path: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+        let exif = read_exif(&path).unwrap();
+
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2023, 5, 18).unwrap(),
+            original_datetime(&exif).unwrap().date()
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2023, 5, 18).unwrap(),
+            Exif.original_date(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn original_datetime_returns_error() {
+        let path: PathBuf = ["resources", "test", "no_original_date.jpeg"]
+            .iter()
+            .collect();
+
+        let exif = read_exif(&path).unwrap();
+        assert!(original_datetime(&exif).is_err());
+    }
+
+    #[test]
+    fn original_date_of_a_video_comes_from_its_container() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("clip.mp4");
+        let mut content = b"\x00\x00\x00\x10ftypisom\x00\x00\x00\x00".to_vec();
+        content.extend(b"\x00\x00\x00\x24moov\x00\x00\x00\x1cmvhd\x00\x00\x00\x00");
+        // 2024-05-18 12:00:00 UTC, in seconds since 1904.
+        content.extend(3_798_878_400u32.to_be_bytes());
+        content.extend([0; 4]);
+        content.extend(1000u32.to_be_bytes());
+        content.extend(30_000u32.to_be_bytes());
+        write(&path, content).unwrap();
+
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 5, 18).unwrap(),
+            Exif.original_date(&path).unwrap()
+        );
+        write(&path, b"\x00\x00\x00\x10ftypisom\x00\x00\x00\x00").unwrap();
+        assert!(Exif.original_date(&path).is_err());
+    }
+
+    #[test]
+    fn telemetry_reads_the_gps_altitude_direction_and_speed() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("drone.jpg");
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        // The IFD0, pointing to the GPS IFD at 26, whose rationals follow at 92.
+        tiff.extend(1u16.to_le_bytes());
+        tiff.extend([0x25, 0x88, 4, 0, 1, 0, 0, 0]);
+        tiff.extend(26u32.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        let entries: [(u16, u16, [u8; 4]); 5] = [
+            (0x05, 1, [1, 0, 0, 0]),
+            (0x06, 5, 92u32.to_le_bytes()),
+            (0x0C, 2, *b"N\0\0\0"),
+            (0x0D, 5, 100u32.to_le_bytes()),
+            (0x11, 5, 108u32.to_le_bytes()),
+        ];
+        tiff.extend((entries.len() as u16).to_le_bytes());
+        for (tag, kind, value) in entries {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(kind.to_le_bytes());
+            tiff.extend(if kind == 2 { 2u32 } else { 1u32 }.to_le_bytes());
+            tiff.extend(value);
+        }
+        tiff.extend(0u32.to_le_bytes());
+        for (numerator, denominator) in [(12u32, 1u32), (10, 1), (2715, 10)] {
+            tiff.extend(numerator.to_le_bytes());
+            tiff.extend(denominator.to_le_bytes());
+        }
+        let mut content = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        content.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        content.extend(b"Exif\0\0");
+        content.extend(tiff);
+        content.extend([0xFF, 0xD9]);
+        write(&path, content).unwrap();
+
+        let telemetry = Exif.telemetry(&path);
+
+        assert_eq!(Some(-12.0), telemetry.altitude);
+        assert_eq!(Some(271.5), telemetry.direction);
+        assert!((telemetry.speed.unwrap() - 18.52).abs() < 1e-9);
+        assert_eq!(
+            Telemetry::default(),
+            Exif.telemetry(&PathBuf::from("resources/test/kami_neko.jpeg"))
+        );
+    }
+
+    #[test]
+    fn dated_tries_the_fallbacks_in_order() {
+        let directory = TempDir::new().unwrap();
+        let named = directory.path().join("IMG_20230518_143012.jpeg");
+        let plain = directory.path().join("plain.jpeg");
+        for path in [&named, &plain] {
+            copy("resources/test/no_original_date.jpeg", path).unwrap();
+        }
+        let backend = WithFallbacks {
+            backend: Box::new(Exif),
+            fallbacks: vec![DateSource::FileName, DateSource::MTime],
+        };
+
+        assert_eq!(
+            (
+                NaiveDate::from_ymd_opt(2023, 5, 18)
+                    .unwrap()
+                    .and_hms_opt(14, 30, 12)
+                    .unwrap(),
+                DateSource::FileName
+            ),
+            backend.dated(&named).unwrap()
+        );
+        assert_eq!(DateSource::MTime, backend.dated(&plain).unwrap().1);
+        assert_eq!(
+            DateSource::Metadata,
+            backend
+                .dated(&PathBuf::from("resources/test/kami_neko.jpeg"))
+                .unwrap()
+                .1
+        );
+        assert!(MetadataConfig::default().backend().dated(&named).is_err());
+    }
+
+    #[test]
+    fn thumbnail_is_none_for_non_exif_file() {
+        assert_eq!(None, Exif.thumbnail(&PathBuf::from("Cargo.toml")));
+    }
+
+    #[test]
+    fn write_original_date_is_not_supported_by_the_exif_reader() {
+        assert!(Exif
+            .write_original_date(&PathBuf::from("a.jpeg"), NaiveDateTime::MIN)
+            .is_err());
+    }
+}
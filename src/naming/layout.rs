@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+
+use super::DateLayout;
+
+/// The directory layout of a hand-organized library picture.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) enum Layout {
+    /// One of the date layouts that relayout produces.
+    Date(DateLayout),
+    /// `2023/Holidays in Rome`, a year directory then an event name.
+    Event,
+}
+
+/// The layouts in the order they win ties, e.g. a tree of `2023/11/12` only
+/// is padded.
+const LAYOUTS: [Layout; 4] = [
+    Layout::Date(DateLayout::Padded),
+    Layout::Date(DateLayout::MonthNames),
+    Layout::Date(DateLayout::Unpadded),
+    Layout::Event,
+];
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Date(layout) => layout.fmt(f),
+            Self::Event => f.write_str("event"),
+        }
+    }
+}
+
+/// The layouts the directory of the path is compatible with, e.g. both padded
+/// and unpadded for `2023/11/12/a.jpg`. None for the pictures outside a year
+/// directory.
+pub(crate) fn matching_layouts(path: &Path) -> Vec<Layout> {
+    let directories = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    let mut layouts = match directories.as_slice() {
+        [.., year, month, day] if is_year(year) => date_layouts(month, day),
+        _ => vec![],
+    };
+    if let [.., year, name] = directories.as_slice() {
+        if layouts.is_empty()
+            && is_year(year)
+            && !name.chars().all(|c| c.is_ascii_digit())
+            && month_name_directory(name).is_none()
+        {
+            layouts.push(Layout::Event);
+        }
+    }
+    layouts
+}
+
+/// The padded directory of an unpadded date directory, e.g. `drone/2023/05/08`
+/// for `drone/2023/5/8`. None for the directories already padded and the other
+/// directories.
+pub(crate) fn padded_directory(directory: &Path) -> Option<PathBuf> {
+    let names = directory
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    let [parents @ .., year, month, day] = names.as_slice() else {
+        return None;
+    };
+    if !is_year(year) || date_layouts(month, day) != [Layout::Date(DateLayout::Unpadded)] {
+        return None;
+    }
+    let month = format!("{:02}", number(month, 12)?);
+    let day = format!("{:02}", number(day, 31)?);
+    Some(
+        parents
+            .iter()
+            .chain([year, &month, &day])
+            .collect::<PathBuf>(),
+    )
+}
+
+fn is_year(name: &str) -> bool {
+    name.len() == 4 && name.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The value of a one or two digits directory name within the range.
+fn number(name: &str, max: u32) -> Option<u32> {
+    (!name.is_empty() && name.len() <= 2 && name.chars().all(|c| c.is_ascii_digit()))
+        .then(|| name.parse().ok())
+        .flatten()
+        .filter(|n| (1..=max).contains(n))
+}
+
+/// The month of a `05-May` directory, with its English name.
+fn month_name_directory(name: &str) -> Option<u32> {
+    let (digits, month_name) = name.split_once('-')?;
+    let month = number(digits, 12).filter(|_| digits.len() == 2)?;
+    let expected = NaiveDate::from_ymd_opt(2000, month, 1)?
+        .format("%B")
+        .to_string();
+    (month_name == expected).then_some(month)
+}
+
+fn date_layouts(month: &str, day: &str) -> Vec<Layout> {
+    if number(day, 31).is_none() {
+        return vec![];
+    }
+    if month_name_directory(month).is_some() {
+        return if day.len() == 2 {
+            vec![Layout::Date(DateLayout::MonthNames)]
+        } else {
+            vec![]
+        };
+    }
+    if number(month, 12).is_none() {
+        return vec![];
+    }
+    let mut layouts = vec![];
+    if month.len() == 2 && day.len() == 2 {
+        layouts.push(Layout::Date(DateLayout::Padded));
+    }
+    if !month.starts_with('0') && !day.starts_with('0') {
+        layouts.push(Layout::Date(DateLayout::Unpadded));
+    }
+    layouts
+}
+
+/// The layouts of a sample of the library paths.
+#[derive(Debug, PartialEq)]
+pub(crate) struct LayoutDetection {
+    counts: HashMap<Layout, usize>,
+    sampled: usize,
+}
+
+impl LayoutDetection {
+    /// Classifies up to `size` paths, evenly spread over the sorted paths so that
+    /// every part of the tree is represented.
+    pub(crate) fn sample(paths: &[PathBuf], size: usize) -> Self {
+        let step = paths.len().div_ceil(size.max(1)).max(1);
+        let mut counts = HashMap::new();
+        let mut sampled = 0;
+        for path in paths.iter().step_by(step) {
+            for layout in matching_layouts(path) {
+                *counts.entry(layout).or_insert(0) += 1;
+            }
+            sampled += 1;
+        }
+        Self { counts, sampled }
+    }
+
+    pub(crate) fn sampled(&self) -> usize {
+        self.sampled
+    }
+
+    /// The number of sampled paths compatible with each layout, in tie order.
+    pub(crate) fn counts(&self) -> Vec<(Layout, usize)> {
+        LAYOUTS
+            .iter()
+            .filter_map(|layout| self.counts.get(layout).map(|count| (*layout, *count)))
+            .collect()
+    }
+
+    /// The layout most of the sampled paths are compatible with.
+    pub(crate) fn dominant(&self) -> Option<Layout> {
+        self.counts()
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(layout, _)| layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::naming::DateLayout;
+
+    use super::{matching_layouts, padded_directory, Layout, LayoutDetection};
+
+    #[test]
+    fn matching_layouts_classifies_the_directories() {
+        assert_eq!(
+            vec![Layout::Date(DateLayout::Unpadded)],
+            matching_layouts(Path::new("2023/5/8/a.jpg"))
+        );
+        assert_eq!(
+            vec![Layout::Date(DateLayout::Padded)],
+            matching_layouts(Path::new("drone/2023/05/08/a.mp4"))
+        );
+        assert_eq!(
+            vec![
+                Layout::Date(DateLayout::Padded),
+                Layout::Date(DateLayout::Unpadded)
+            ],
+            matching_layouts(Path::new("2023/11/12/a.jpg"))
+        );
+        assert_eq!(
+            vec![Layout::Date(DateLayout::MonthNames)],
+            matching_layouts(Path::new("2023/05-May/08/a.jpg"))
+        );
+        assert_eq!(
+            vec![Layout::Event],
+            matching_layouts(Path::new("2023/Holidays in Rome/a.jpg"))
+        );
+        assert!(matching_layouts(Path::new("2023/05-May/a.jpg")).is_empty());
+        assert!(matching_layouts(Path::new("2023/13/1/a.jpg")).is_empty());
+        assert!(matching_layouts(Path::new("misc/a.jpg")).is_empty());
+        assert!(matching_layouts(Path::new("a.jpg")).is_empty());
+    }
+
+    #[test]
+    fn padded_directory_pads_the_unpadded_dates_only() {
+        assert_eq!(
+            Some(PathBuf::from("drone/2023/05/08")),
+            padded_directory(Path::new("drone/2023/5/8"))
+        );
+        assert_eq!(
+            Some(PathBuf::from("2023/11/02")),
+            padded_directory(Path::new("2023/11/2"))
+        );
+        assert_eq!(None, padded_directory(Path::new("2023/11/12")));
+        assert_eq!(None, padded_directory(Path::new("2023/05/08")));
+        assert_eq!(None, padded_directory(Path::new("2023/Rome/8")));
+        assert_eq!(None, padded_directory(Path::new("5/8")));
+    }
+
+    #[test]
+    fn dominant_is_the_layout_of_most_sampled_paths() {
+        let paths = [
+            "2023/11/12/a.jpg",
+            "2023/05/08/b.jpg",
+            "2023/5/8/c.jpg",
+            "2023/12/1/d.jpg",
+            "2023/Rome/e.jpg",
+        ]
+        .map(PathBuf::from);
+        let detection = LayoutDetection::sample(&paths, 100);
+
+        assert_eq!(5, detection.sampled());
+        assert_eq!(
+            vec![
+                (Layout::Date(DateLayout::Padded), 2),
+                (Layout::Date(DateLayout::Unpadded), 3),
+                (Layout::Event, 1)
+            ],
+            detection.counts()
+        );
+        assert_eq!(
+            Some(Layout::Date(DateLayout::Unpadded)),
+            detection.dominant()
+        );
+    }
+
+    #[test]
+    fn dominant_prefers_padded_when_tied() {
+        let detection = LayoutDetection::sample(&[PathBuf::from("2023/11/12/a.jpg")], 100);
+        assert_eq!(Some(Layout::Date(DateLayout::Padded)), detection.dominant());
+        assert_eq!(None, LayoutDetection::sample(&[], 100).dominant());
+    }
+
+    #[test]
+    fn sample_spreads_over_the_paths() {
+        let paths = (0..10)
+            .map(|i| PathBuf::from(format!("2023/5/{}/a.jpg", i + 1)))
+            .collect::<Vec<_>>();
+        assert_eq!(4, LayoutDetection::sample(&paths, 4).sampled());
+    }
+}
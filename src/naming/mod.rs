@@ -0,0 +1,540 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Datelike, Local, NaiveDate};
+use eyre::{eyre, Result};
+use serde::Deserialize;
+
+pub(crate) mod layout;
+pub(crate) mod template;
+
+use self::template::{short_hash, FileNameTemplate, PathTemplate};
+
+/// The `[library]` table of the repository configuration, restricting the file
+/// names of the library to what its sync targets accept, e.g. 255 bytes names
+/// and no `<>:"/\|?*` on exFAT or SMB shares.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub(crate) struct LibraryNaming {
+    /// The longest path relative to the repository root, in bytes.
+    max_path_length: Option<usize>,
+    /// The longest file name, extension included, in bytes.
+    max_file_name_length: Option<usize>,
+    /// Characters replaced with `_` in the file names, along with the control
+    /// characters.
+    illegal_characters: Option<String>,
+    #[serde(default)]
+    non_ascii: NonAscii,
+    #[serde(default)]
+    collision_suffix: CollisionSuffix,
+    #[serde(default)]
+    date_layout: DateLayout,
+    /// The template of the date directories, e.g. `{year}/{year}-{month:02}`,
+    /// replacing the date layout.
+    path_template: Option<PathTemplate>,
+    /// The template of the file stems, e.g. `{stem}_{shorthash}`, whose
+    /// `{shorthash}` makes the names unique without collision suffixes.
+    file_name_template: Option<FileNameTemplate>,
+}
+
+/// The directories of the pictures taken on a date.
+#[derive(Deserialize, Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum DateLayout {
+    /// `2023/5/8`, which `library repath` pads.
+    Unpadded,
+    /// `2023/05/08`, which sorts in file browsers.
+    #[default]
+    Padded,
+    /// `2023/05-May/08`, with the English month names whatever the locale.
+    MonthNames,
+}
+
+/// The suffix telling apart the files whose names collide in the library.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum CollisionSuffix {
+    /// `_1`, `_2`...
+    #[default]
+    Counter,
+    /// The first 8 digits of the content digest, e.g. `_3fa2b1c0`.
+    ShortHash,
+    /// The import time, e.g. `_20240701T101500`.
+    Timestamp,
+}
+
+/// How the non ASCII characters of the file names, e.g. emoji or CJK, are written.
+/// The original name is recorded in the library either way.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NonAscii {
+    #[default]
+    Keep,
+    /// Replaced with their closest ASCII spelling, e.g. `日本` becomes `Ri Ben`.
+    Transliterate,
+    /// Replaced with the hexadecimal of their UTF-8 bytes.
+    Hex,
+}
+
+impl Display for DateLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unpadded => "unpadded",
+            Self::Padded => "padded",
+            Self::MonthNames => "month-names",
+        })
+    }
+}
+
+impl FromStr for DateLayout {
+    type Err = String;
+
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        match layout {
+            "unpadded" => Ok(Self::Unpadded),
+            "padded" => Ok(Self::Padded),
+            "month-names" => Ok(Self::MonthNames),
+            _ => Err(format!(
+                "Invalid date layout `{}`, expected unpadded, padded or month-names",
+                layout
+            )),
+        }
+    }
+}
+
+impl DateLayout {
+    fn directory(&self, date: NaiveDate) -> PathBuf {
+        let (month, day) = match self {
+            Self::Unpadded => (date.month().to_string(), date.day().to_string()),
+            Self::Padded => (date.format("%m").to_string(), date.format("%d").to_string()),
+            Self::MonthNames => (
+                date.format("%m-%B").to_string(),
+                date.format("%d").to_string(),
+            ),
+        };
+        [date.year().to_string(), month, day].iter().collect()
+    }
+}
+
+impl LibraryNaming {
+    /// The naming laying out the date directories with the layout, without a path
+    /// template.
+    pub(crate) fn with_date_layout(mut self, date_layout: DateLayout) -> Self {
+        self.date_layout = date_layout;
+        self.path_template = None;
+        self
+    }
+
+    pub(crate) fn with_path_template(mut self, path_template: PathTemplate) -> Self {
+        self.path_template = Some(path_template);
+        self
+    }
+
+    /// The directory of the pictures taken on the date, relative to the repository
+    /// root.
+    pub(crate) fn directory(&self, date: NaiveDate) -> PathBuf {
+        match &self.path_template {
+            Some(template) => template.directory(date),
+            None => self.date_layout.directory(date),
+        }
+    }
+
+    /// The configured date layout, none when a path template replaces it.
+    pub(crate) fn date_layout(&self) -> Option<DateLayout> {
+        self.path_template.is_none().then_some(self.date_layout)
+    }
+
+    /// The directory holding the date directory of the library path, under the
+    /// configured directories or any of the layouts, e.g. `drone` for
+    /// `drone/2023/5/8/a.mp4`. Empty when the path is not in a date directory.
+    pub(crate) fn sub_root(&self, path: &Path, date: NaiveDate) -> PathBuf {
+        self.date_directory_root(path, date).unwrap_or_default()
+    }
+
+    /// The directory holding the date directory of the library path, as
+    /// [`Self::sub_root`], none when the path is not in a date directory.
+    pub(crate) fn date_directory_root(&self, path: &Path, date: NaiveDate) -> Option<PathBuf> {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        std::iter::once(self.directory(date))
+            .chain(
+                [
+                    DateLayout::Unpadded,
+                    DateLayout::Padded,
+                    DateLayout::MonthNames,
+                ]
+                .iter()
+                .map(|layout| layout.directory(date)),
+            )
+            .find(|directory| parent.ends_with(directory))
+            .map(|directory| {
+                let depth = parent.components().count() - directory.components().count();
+                parent.components().take(depth).collect()
+            })
+    }
+
+    /// The stem of the library file of the content, from the file name template
+    /// when one is configured.
+    pub(crate) fn stem(&self, stem: &OsStr, sha256: &str) -> OsString {
+        match &self.file_name_template {
+            Some(template) => template.stem(&stem.to_string_lossy(), sha256).into(),
+            None => stem.to_owned(),
+        }
+    }
+
+    /// The suffixes to try in turn for a file name: none, then the one of the
+    /// configured scheme, then counters should the scheme collide too. Only none
+    /// when the file name template holds the short hash of the content.
+    pub(crate) fn suffixes(&self, sha256: &str) -> impl Iterator<Item = String> {
+        let probes = match &self.file_name_template {
+            Some(template) if template.is_collision_proof() => 1,
+            _ => usize::MAX,
+        };
+        let scheme = match self.collision_suffix {
+            CollisionSuffix::Counter => None,
+            CollisionSuffix::ShortHash => Some(format!("_{}", short_hash(sha256))),
+            CollisionSuffix::Timestamp => {
+                Some(format!("_{}", Local::now().format("%Y%m%dT%H%M%S")))
+            }
+        };
+        let prefix = scheme.clone().unwrap_or_default();
+        std::iter::once(String::new())
+            .chain(scheme)
+            .chain((1..).map(move |i| format!("{}_{}", prefix, i)))
+            .take(probes)
+    }
+
+    fn is_unrestricted(&self) -> bool {
+        self.max_path_length.is_none()
+            && self.max_file_name_length.is_none()
+            && self.illegal_characters.is_none()
+            && self.non_ascii == NonAscii::Keep
+    }
+
+    /// The name of a file of the directory made of the stem, the suffix telling
+    /// it apart from the other files and the extension. The non ASCII and illegal
+    /// characters of the stem are replaced and the stem is truncated to fit the
+    /// length limits.
+    pub(crate) fn file_name(
+        &self,
+        directory: &Path,
+        stem: &OsStr,
+        suffix: &str,
+        extension: &OsStr,
+    ) -> Result<OsString> {
+        let mut name = OsString::new();
+        if self.is_unrestricted() {
+            name.push(stem);
+            name.push(suffix);
+            name.push(".");
+            name.push(extension);
+            return Ok(name);
+        }
+        let stem =
+            self.replace_illegal_characters(&self.replace_non_ascii(&stem.to_string_lossy()));
+        let extension = self.replace_illegal_characters(&extension.to_string_lossy());
+        let fixed_length = suffix.len() + 1 + extension.len();
+        let directory_length = directory.as_os_str().len() + 1;
+        let available = [
+            self.max_file_name_length
+                .map(|max| max.checked_sub(fixed_length)),
+            self.max_path_length
+                .map(|max| max.checked_sub(directory_length + fixed_length)),
+        ]
+        .into_iter()
+        .flatten()
+        .try_fold(usize::MAX, |available, limit| {
+            limit.map(|l| available.min(l))
+        })
+        .filter(|available| *available > 0)
+        .ok_or(eyre!(
+            "No room for a file name in {} within the length limits",
+            directory.display()
+        ))?;
+        name.push(truncate(&stem, available));
+        name.push(suffix);
+        name.push(".");
+        name.push(extension);
+        Ok(name)
+    }
+
+    fn replace_non_ascii(&self, name: &str) -> String {
+        match self.non_ascii {
+            NonAscii::Keep => name.to_owned(),
+            NonAscii::Transliterate => deunicode::deunicode(name),
+            NonAscii::Hex => name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii() {
+                        c.to_string()
+                    } else {
+                        c.to_string()
+                            .bytes()
+                            .map(|b| format!("{:02x}", b))
+                            .collect()
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn replace_illegal_characters(&self, name: &str) -> String {
+        let illegal = self.illegal_characters.as_deref().unwrap_or_default();
+        name.chars()
+            .map(|c| {
+                if c.is_control() || c == '/' || illegal.contains(c) {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+}
+
+/// The longest prefix of the name within `length` bytes, cut on a character
+/// boundary.
+fn truncate(name: &str, length: usize) -> &str {
+    let mut end = name.len().min(length);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        path::{Path, PathBuf},
+    };
+
+    use chrono::NaiveDate;
+
+    use super::{truncate, CollisionSuffix, DateLayout, LibraryNaming, NonAscii};
+
+    #[test]
+    fn directory_uses_slash_separator() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 2).unwrap();
+        assert_eq!(
+            [2023.to_string(), 12.to_string(), "02".to_string()]
+                .iter()
+                .collect::<PathBuf>(),
+            LibraryNaming::default().directory(date)
+        );
+    }
+
+    #[test]
+    fn directory_pads_the_month_and_day() {
+        let date = NaiveDate::from_ymd_opt(2023, 5, 8).unwrap();
+        let naming = |date_layout| LibraryNaming {
+            date_layout,
+            ..Default::default()
+        };
+        assert_eq!(
+            PathBuf::from("2023/05/08"),
+            naming(DateLayout::Padded).directory(date)
+        );
+        assert_eq!(
+            PathBuf::from("2023/05-May/08"),
+            naming(DateLayout::MonthNames).directory(date)
+        );
+    }
+
+    #[test]
+    fn directory_follows_the_path_template_over_the_date_layout() {
+        let naming: LibraryNaming =
+            toml::from_str("date_layout = \"month-names\"\npath_template = \"{year}/{month:02}\"")
+                .unwrap();
+
+        assert_eq!(
+            PathBuf::from("2023/05"),
+            naming.directory(NaiveDate::from_ymd_opt(2023, 5, 8).unwrap())
+        );
+        assert_eq!(None, naming.date_layout());
+        assert!(toml::from_str::<LibraryNaming>("path_template = \"{month}\"").is_err());
+    }
+
+    #[test]
+    fn sub_root_is_the_directory_above_the_date_directory() {
+        let date = NaiveDate::from_ymd_opt(2023, 5, 8).unwrap();
+        assert_eq!(
+            PathBuf::from("drone"),
+            LibraryNaming::default().sub_root(Path::new("drone/2023/05/08/a.mp4"), date)
+        );
+        assert_eq!(
+            PathBuf::new(),
+            LibraryNaming::default().sub_root(Path::new("2023/5/8/a.jpg"), date)
+        );
+        assert_eq!(
+            PathBuf::new(),
+            LibraryNaming::default().sub_root(Path::new("misc/a.jpg"), date)
+        );
+        let naming: LibraryNaming =
+            toml::from_str("path_template = \"{year}/{year}-{month:02}\"").unwrap();
+        assert_eq!(
+            PathBuf::from("drone"),
+            naming.sub_root(Path::new("drone/2023/2023-05/a.mp4"), date)
+        );
+    }
+
+    #[test]
+    fn file_name_is_unchanged_without_restrictions() {
+        assert_eq!(
+            OsString::from("a:b_1.jpg"),
+            LibraryNaming::default()
+                .file_name(
+                    Path::new("2024"),
+                    OsStr::new("a:b"),
+                    "_1",
+                    OsStr::new("jpg")
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_replaces_the_illegal_characters() {
+        let naming = LibraryNaming {
+            illegal_characters: Some(":?".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            OsString::from("a_b_.jpg"),
+            naming
+                .file_name(Path::new("2024"), OsStr::new("a:b?"), "", OsStr::new("jpg"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_transliterates_the_non_ascii_characters() {
+        let naming = LibraryNaming {
+            non_ascii: NonAscii::Transliterate,
+            ..Default::default()
+        };
+        assert_eq!(
+            OsString::from("cafe.jpg"),
+            naming
+                .file_name(Path::new("2024"), OsStr::new("café"), "", OsStr::new("jpg"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_hex_encodes_the_non_ascii_characters() {
+        let naming = LibraryNaming {
+            non_ascii: NonAscii::Hex,
+            ..Default::default()
+        };
+        assert_eq!(
+            OsString::from("cafc3a9_1.jpg"),
+            naming
+                .file_name(
+                    Path::new("2024"),
+                    OsStr::new("café"),
+                    "_1",
+                    OsStr::new("jpg")
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_truncates_the_stem_keeping_suffix_and_extension() {
+        let naming = LibraryNaming {
+            max_file_name_length: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            OsString::from("abcd_1.jpg"),
+            naming
+                .file_name(
+                    Path::new("2024"),
+                    OsStr::new("abcdefgh"),
+                    "_1",
+                    OsStr::new("jpg")
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_fits_the_path_length() {
+        let naming = LibraryNaming {
+            max_path_length: Some(16),
+            ..Default::default()
+        };
+        assert_eq!(
+            OsString::from("abcde.jpg"),
+            naming
+                .file_name(
+                    Path::new("2024/5"),
+                    OsStr::new("abcdefgh"),
+                    "",
+                    OsStr::new("jpg")
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn file_name_fails_without_room_for_the_stem() {
+        let naming = LibraryNaming {
+            max_file_name_length: Some(6),
+            ..Default::default()
+        };
+        assert!(naming
+            .file_name(
+                Path::new("2024"),
+                OsStr::new("abc"),
+                "_1",
+                OsStr::new("jpg")
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn suffixes_start_with_the_plain_name_then_the_scheme() {
+        let naming = LibraryNaming {
+            collision_suffix: CollisionSuffix::ShortHash,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["", "_3fa2b1c0", "_3fa2b1c0_1"],
+            naming
+                .suffixes("3FA2B1C0D4")
+                .take(3)
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(
+            vec!["", "_1", "_2"],
+            LibraryNaming::default()
+                .suffixes("3FA2B1C0D4")
+                .take(3)
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn short_hash_template_names_the_files_without_suffixes() {
+        let naming: LibraryNaming =
+            toml::from_str("file_name_template = \"{stem}_{shorthash}\"").unwrap();
+
+        assert_eq!(
+            OsString::from("IMG_0001_3fa2b1c0"),
+            naming.stem(OsStr::new("IMG_0001"), "3FA2B1C0D4")
+        );
+        assert_eq!(
+            vec![""],
+            naming.suffixes("3FA2B1C0D4").collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn truncate_cuts_on_a_character_boundary() {
+        assert_eq!("é", truncate("éé", 3));
+    }
+}
@@ -0,0 +1,285 @@
+use std::{path::PathBuf, str::FromStr};
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+/// A template of the directories of the pictures taken on a date, such as
+/// `{year}/{month:02}/{day:02}` or `{year}/{year}-{month:02}`, in `/` separated
+/// directories.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(try_from = "String")]
+pub(crate) struct PathTemplate(Vec<Part<DateField>>);
+
+/// A template of the file stems of the library, such as `{stem}_{shorthash}`,
+/// whose `{shorthash}` tells the files apart without suffixes.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(try_from = "String")]
+pub(crate) struct FileNameTemplate(Vec<Part<NameField>>);
+
+#[derive(Debug, PartialEq, Clone)]
+enum Part<F> {
+    Text(String),
+    Field(F),
+}
+
+/// The placeholders of a kind of template.
+trait Field: Sized {
+    /// The placeholders accepted, for the error messages.
+    const PLACEHOLDERS: &'static str;
+
+    fn parse(placeholder: &str) -> Option<Self>;
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum DateField {
+    Year,
+    /// The month number, zero padded to 2 digits or not.
+    Month(bool),
+    /// The day of the month, zero padded to 2 digits or not.
+    Day(bool),
+    /// The English month name, whatever the locale.
+    MonthName,
+}
+
+impl Field for DateField {
+    const PLACEHOLDERS: &'static str =
+        "{year}, {month}, {month:02}, {day}, {day:02} or {month_name}";
+
+    fn parse(placeholder: &str) -> Option<Self> {
+        match placeholder {
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month(false)),
+            "month:02" => Some(Self::Month(true)),
+            "day" => Some(Self::Day(false)),
+            "day:02" => Some(Self::Day(true)),
+            "month_name" => Some(Self::MonthName),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum NameField {
+    /// The stem of the original file name.
+    Stem,
+    /// The first 8 hexadecimal digits of the content digest, in lowercase.
+    ShortHash,
+}
+
+impl Field for NameField {
+    const PLACEHOLDERS: &'static str = "{stem} or {shorthash}";
+
+    fn parse(placeholder: &str) -> Option<Self> {
+        match placeholder {
+            "stem" => Some(Self::Stem),
+            "shorthash" => Some(Self::ShortHash),
+            _ => None,
+        }
+    }
+}
+
+impl PathTemplate {
+    /// The directory of the date, relative to the library root.
+    pub(crate) fn directory(&self, date: NaiveDate) -> PathBuf {
+        let directory = self
+            .0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(DateField::Year) => date.year().to_string(),
+                Part::Field(DateField::Month(false)) => date.month().to_string(),
+                Part::Field(DateField::Month(true)) => format!("{:02}", date.month()),
+                Part::Field(DateField::Day(false)) => date.day().to_string(),
+                Part::Field(DateField::Day(true)) => format!("{:02}", date.day()),
+                Part::Field(DateField::MonthName) => date.format("%B").to_string(),
+            })
+            .collect::<String>();
+        directory.split('/').collect()
+    }
+}
+
+impl FileNameTemplate {
+    /// The stem of the library file of the content named `stem`.
+    pub(crate) fn stem(&self, stem: &str, sha256: &str) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(NameField::Stem) => stem.to_owned(),
+                Part::Field(NameField::ShortHash) => short_hash(sha256),
+            })
+            .collect()
+    }
+
+    /// True when the stems hold the digest of the content.
+    pub(crate) fn is_collision_proof(&self) -> bool {
+        self.0.contains(&Part::Field(NameField::ShortHash))
+    }
+}
+
+/// The first 8 hexadecimal digits of the digest, in lowercase.
+pub(crate) fn short_hash(sha256: &str) -> String {
+    sha256.chars().take(8).collect::<String>().to_lowercase()
+}
+
+/// The text and placeholders of the template, in order, the errors naming the kind
+/// of template.
+fn parse<F: Field>(kind: &str, template: &str) -> Result<Vec<Part<F>>, String> {
+    let invalid = |reason: String| format!("Invalid {} `{}`: {}", kind, template, reason);
+    let mut pieces = template.split('{');
+    let mut parts = vec![];
+    let mut text = pieces.next().unwrap_or_default();
+    for piece in pieces {
+        if text.contains('}') {
+            return Err(invalid("unopened }".to_string()));
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text.to_owned()));
+        }
+        let (placeholder, rest) = piece
+            .split_once('}')
+            .ok_or_else(|| invalid("unclosed {".to_string()))?;
+        parts.push(Part::Field(F::parse(placeholder).ok_or_else(|| {
+            invalid(format!(
+                "unknown {{{}}}, expected {}",
+                placeholder,
+                F::PLACEHOLDERS
+            ))
+        })?));
+        text = rest;
+    }
+    if text.contains('}') {
+        return Err(invalid("unopened }".to_string()));
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text.to_owned()));
+    }
+    Ok(parts)
+}
+
+impl FromStr for PathTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid path template `{}`: {}", template, reason);
+        if template
+            .split('/')
+            .any(|d| d.is_empty() || d == "." || d == "..")
+        {
+            return Err(invalid(
+                "it must be relative, without empty, . or .. directories",
+            ));
+        }
+        let parts = parse("path template", template)?;
+        if !parts.contains(&Part::Field(DateField::Year)) {
+            return Err(invalid("it has no {year}"));
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl TryFrom<String> for PathTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
+
+impl FromStr for FileNameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| format!("Invalid file name template `{}`: {}", template, reason);
+        if template.contains(['/', '.']) {
+            return Err(invalid("it names the stem, without / or ."));
+        }
+        let parts = parse("file name template", template)?;
+        if !parts.iter().any(|p| matches!(p, Part::Field(_))) {
+            return Err(invalid("it has no {stem} nor {shorthash}"));
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl TryFrom<String> for FileNameTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use super::{FileNameTemplate, PathTemplate};
+
+    #[test]
+    fn directory_fills_the_placeholders() {
+        let date = NaiveDate::from_ymd_opt(2023, 5, 8).unwrap();
+        let directory = |template: &str| template.parse::<PathTemplate>().unwrap().directory(date);
+
+        assert_eq!(
+            PathBuf::from("2023/05/08"),
+            directory("{year}/{month:02}/{day:02}")
+        );
+        assert_eq!(
+            PathBuf::from("2023/2023-05"),
+            directory("{year}/{year}-{month:02}")
+        );
+        assert_eq!(
+            PathBuf::from("photos/2023/5-May"),
+            directory("photos/{year}/{month}-{month_name}")
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_the_invalid_templates() {
+        for template in [
+            "{month}/{day}",
+            "{year}/{week}",
+            "{year}/{month",
+            "{year}/month}",
+            "/{year}",
+            "{year}//{month}",
+            "../{year}",
+        ] {
+            assert!(
+                template.parse::<PathTemplate>().is_err(),
+                "{} is accepted",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn stem_fills_the_stem_and_short_hash() {
+        let template = "{stem}_{shorthash}".parse::<FileNameTemplate>().unwrap();
+
+        assert_eq!(
+            "IMG_0001_3fa2b1c0",
+            template.stem("IMG_0001", "3FA2B1C0D4E5")
+        );
+        assert!(template.is_collision_proof());
+        assert!(!"x{stem}"
+            .parse::<FileNameTemplate>()
+            .unwrap()
+            .is_collision_proof());
+    }
+
+    #[test]
+    fn file_name_template_rejects_the_invalid_templates() {
+        for template in ["photo", "{stem}.jpg", "{stem}/{shorthash}", "{year}"] {
+            assert!(
+                template.parse::<FileNameTemplate>().is_err(),
+                "{} is accepted",
+                template
+            );
+        }
+    }
+}
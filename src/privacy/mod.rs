@@ -0,0 +1,363 @@
+use std::{
+    fs::{read, write},
+    path::Path,
+};
+
+use eyre::Result;
+use serde::Deserialize;
+
+use crate::metadata::gps_coordinates;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+/// The mean radius of the Earth, in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// The blurred coordinates are rounded to a tenth of degree, about 11 km.
+const BLUR_STEPS_PER_DEGREE: f64 = 10.0;
+
+/// The tag of the IFD0 entry pointing to the GPS IFD.
+const GPS_IFD_POINTER: u16 = 0x8825;
+
+/// The GPS tags a blurred picture keeps: the version, the latitude and longitude
+/// and their references.
+const BLUR_KEPT_TAGS: [u16; 5] = [0, 1, 2, 3, 4];
+
+/// The GPS tags of the latitude and the longitude.
+const COORDINATE_TAGS: [u16; 2] = [2, 4];
+
+/// The TIFF type of the unsigned rationals.
+const RATIONAL: u16 = 5;
+
+/// The `[privacy]` table of the repository configuration: the zones whose
+/// coordinates the exported and published copies hide, while the library files
+/// keep them.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct PrivacyConfig {
+    #[serde(default)]
+    action: PrivacyAction,
+    #[serde(default)]
+    zones: Vec<PrivacyZone>,
+}
+
+/// What the copies of the pictures taken inside a zone keep of their coordinates.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PrivacyAction {
+    /// Nothing, the GPS tags are removed.
+    #[default]
+    Strip,
+    /// The latitude and longitude rounded to a tenth of degree, about 11 km, the
+    /// other GPS tags being removed.
+    Blur,
+}
+
+/// A circle around a private place, e.g. home.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct PrivacyZone {
+    pub(crate) name: String,
+    latitude: f64,
+    longitude: f64,
+    /// The radius in meters.
+    radius: f64,
+}
+
+/// What the privacy zones did to the copy of a picture.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Redaction<'a> {
+    /// The picture was not taken inside a zone.
+    Unchanged,
+    /// The coordinates of the copy were stripped or blurred.
+    Redacted(&'a str),
+    /// The picture was taken inside the zone, and its format can't be rewritten.
+    Unsupported(&'a str),
+}
+
+impl PrivacyZone {
+    fn contains(&self, coordinates: (f64, f64)) -> bool {
+        distance((self.latitude, self.longitude), coordinates) <= self.radius
+    }
+}
+
+impl PrivacyConfig {
+    /// The zone the picture was taken in, according to its GPS tags.
+    pub(crate) fn zone_of(&self, path: &Path) -> Option<&PrivacyZone> {
+        if self.zones.is_empty() {
+            return None;
+        }
+        let coordinates = gps_coordinates(path)?;
+        self.zones.iter().find(|zone| zone.contains(coordinates))
+    }
+
+    /// Strips or blurs the coordinates of the copy of a picture taken inside a
+    /// zone. Only the JPEG files are rewritten.
+    pub(crate) fn redact(&self, copy: &Path) -> Result<Redaction<'_>> {
+        let Some(zone) = self.zone_of(copy) else {
+            return Ok(Redaction::Unchanged);
+        };
+        let mut content = read(copy)?;
+        match rewrite_gps(&mut content, self.action) {
+            Some(()) => {
+                write(copy, content)?;
+                Ok(Redaction::Redacted(&zone.name))
+            }
+            None => Ok(Redaction::Unsupported(&zone.name)),
+        }
+    }
+}
+
+/// The great-circle distance in meters between two coordinates in degrees.
+fn distance((latitude1, longitude1): (f64, f64), (latitude2, longitude2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (latitude1.to_radians(), latitude2.to_radians());
+    let half_chord = ((phi2 - phi1) / 2.0).sin().powi(2)
+        + phi1.cos() * phi2.cos() * ((longitude2 - longitude1).to_radians() / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * half_chord.sqrt().asin()
+}
+
+/// Rewrites the GPS IFD of the EXIF of a JPEG file in place, keeping the tags
+/// of the action and zeroing the values of the others. None when the content is
+/// not a JPEG with GPS tags.
+fn rewrite_gps(content: &mut [u8], action: PrivacyAction) -> Option<()> {
+    let start = exif_start(content)?;
+    let mut tiff = Tiff::new(&mut content[start..])?;
+    let gps = tiff.gps_ifd()?;
+    let count = tiff.u16(gps)? as usize;
+    let mut kept = vec![];
+    for index in 0..count {
+        let entry = gps + 2 + 12 * index;
+        let tag = tiff.u16(entry)?;
+        let kind = tiff.u16(entry + 2)?;
+        let size = type_size(kind)? * tiff.u32(entry + 4)? as usize;
+        let value = if size <= 4 {
+            entry + 8
+        } else {
+            tiff.u32(entry + 8)? as usize
+        };
+        if action == PrivacyAction::Blur && BLUR_KEPT_TAGS.contains(&tag) {
+            if COORDINATE_TAGS.contains(&tag) {
+                if kind != RATIONAL || size != 24 {
+                    return None;
+                }
+                tiff.blur_degrees(value)?;
+            }
+            kept.push(tiff.data.get(entry..entry + 12)?.to_vec());
+        } else if size > 4 {
+            tiff.data.get_mut(value..value + size)?.fill(0);
+        }
+    }
+    tiff.data.get_mut(gps + 2..gps + 2 + 12 * count)?.fill(0);
+    for (index, entry) in kept.iter().enumerate() {
+        let at = gps + 2 + 12 * index;
+        tiff.data[at..at + 12].copy_from_slice(entry);
+    }
+    tiff.set_u16(gps, kept.len() as u16)
+}
+
+/// The start of the TIFF header of the EXIF segment of a JPEG file.
+fn exif_start(content: &[u8]) -> Option<usize> {
+    if !content.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut position = 2;
+    while let Some(&[0xFF, marker, high, low]) = content.get(position..position + 4) {
+        // The image data starts at the start of scan, after the metadata.
+        if marker == 0xDA {
+            return None;
+        }
+        if marker == 0xE1 && content.get(position + 4..position + 10) == Some(b"Exif\0\0") {
+            return Some(position + 10);
+        }
+        position += 2 + u16::from_be_bytes([high, low]) as usize;
+    }
+    None
+}
+
+/// The size in bytes of a value of the TIFF type.
+fn type_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// The TIFF structure of an EXIF segment, offsets being relative to its header.
+struct Tiff<'a> {
+    data: &'a mut [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a mut [u8]) -> Option<Self> {
+        let big_endian = match data.get(..2)? {
+            b"MM" => true,
+            b"II" => false,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    /// The offset of the GPS IFD, from the pointer of the IFD0.
+    fn gps_ifd(&self) -> Option<usize> {
+        let ifd0 = self.u32(4)? as usize;
+        (0..self.u16(ifd0)? as usize)
+            .map(|index| ifd0 + 2 + 12 * index)
+            .find(|&entry| self.u16(entry) == Some(GPS_IFD_POINTER))
+            .and_then(|entry| self.u32(entry + 8))
+            .map(|offset| offset as usize)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn set_u16(&mut self, at: usize, value: u16) -> Option<()> {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data.get_mut(at..at + 2)?.copy_from_slice(&bytes);
+        Some(())
+    }
+
+    fn set_u32(&mut self, at: usize, value: u32) -> Option<()> {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data.get_mut(at..at + 4)?.copy_from_slice(&bytes);
+        Some(())
+    }
+
+    /// Rounds the degrees, minutes and seconds rationals at the offset.
+    fn blur_degrees(&mut self, at: usize) -> Option<()> {
+        let mut degrees = 0.0;
+        for (index, unit) in [1.0, 60.0, 3600.0].iter().enumerate() {
+            let numerator = self.u32(at + 8 * index)? as f64;
+            let denominator = self.u32(at + 8 * index + 4)? as f64;
+            if denominator > 0.0 {
+                degrees += numerator / denominator / unit;
+            }
+        }
+        let steps = (degrees * BLUR_STEPS_PER_DEGREE).round() as u32;
+        for (index, (numerator, denominator)) in
+            [(steps, BLUR_STEPS_PER_DEGREE as u32), (0, 1), (0, 1)]
+                .into_iter()
+                .enumerate()
+        {
+            self.set_u32(at + 8 * index, numerator)?;
+            self.set_u32(at + 8 * index + 4, denominator)?;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read, write};
+
+    use tempfile::TempDir;
+
+    use crate::metadata::gps_coordinates;
+
+    use super::{
+        distance,
+        test_utils::{
+            given_a_jpeg_taken_at_the_eiffel_tower, given_a_tiff_taken_at_the_eiffel_tower,
+        },
+        PrivacyConfig, Redaction,
+    };
+
+    fn config(action: &str) -> PrivacyConfig {
+        toml::from_str(&format!(
+            "action = \"{}\"\n[[zones]]\nname = \"home\"\nlatitude = 48.8584\nlongitude = 2.2945\nradius = 500",
+            action
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn distance_is_the_great_circle_distance() {
+        let paris_to_london = distance((48.8566, 2.3522), (51.5074, -0.1278));
+        assert!((paris_to_london - 343_500.0).abs() < 1_000.0);
+    }
+
+    #[test]
+    fn redact_strips_the_coordinates_taken_in_a_zone() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("a.jpg");
+        write(&path, given_a_jpeg_taken_at_the_eiffel_tower()).unwrap();
+        let config = config("strip");
+        let coordinates = gps_coordinates(&path).unwrap();
+        assert!((coordinates.0 - 48.8583).abs() < 0.0001);
+
+        assert_eq!(Redaction::Redacted("home"), config.redact(&path).unwrap());
+
+        assert_eq!(None, gps_coordinates(&path));
+        assert!(!read(&path)
+            .unwrap()
+            .windows(8)
+            .any(|w| w == [48, 0, 0, 0, 1, 0, 0, 0]));
+        assert_eq!(Redaction::Unchanged, config.redact(&path).unwrap());
+    }
+
+    #[test]
+    fn redact_blurs_the_coordinates_taken_in_a_zone() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("a.jpg");
+        write(&path, given_a_jpeg_taken_at_the_eiffel_tower()).unwrap();
+
+        assert_eq!(
+            Redaction::Redacted("home"),
+            config("blur").redact(&path).unwrap()
+        );
+
+        assert_eq!(Some((48.9, 2.3)), gps_coordinates(&path));
+    }
+
+    #[test]
+    fn redact_tells_the_formats_it_cannot_rewrite() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("a.tif");
+        write(&path, given_a_tiff_taken_at_the_eiffel_tower()).unwrap();
+
+        assert_eq!(
+            Redaction::Unsupported("home"),
+            config("strip").redact(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn redact_leaves_the_pictures_taken_elsewhere() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("a.jpg");
+        let content = given_a_jpeg_taken_at_the_eiffel_tower();
+        write(&path, &content).unwrap();
+        let config: PrivacyConfig = toml::from_str(
+            "[[zones]]\nname = \"home\"\nlatitude = 45.76\nlongitude = 4.83\nradius = 1000",
+        )
+        .unwrap();
+
+        assert_eq!(Redaction::Unchanged, config.redact(&path).unwrap());
+        assert_eq!(content, read(&path).unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+/// A TIFF whose EXIF places it at 48°51'30"N 2°17'40"E, 35 m high.
+pub(crate) fn given_a_tiff_taken_at_the_eiffel_tower() -> Vec<u8> {
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    // The IFD0, pointing to the GPS IFD at 26.
+    tiff.extend(1u16.to_le_bytes());
+    tiff.extend([0x25, 0x88, 4, 0]);
+    tiff.extend(1u32.to_le_bytes());
+    tiff.extend(26u32.to_le_bytes());
+    tiff.extend(0u32.to_le_bytes());
+    // The GPS IFD, its rationals following at 104.
+    let entries: [(u16, u16, u32, [u8; 4]); 6] = [
+        (0, 1, 4, [2, 3, 0, 0]),
+        (1, 2, 2, *b"N\0\0\0"),
+        (2, 5, 3, 104u32.to_le_bytes()),
+        (3, 2, 2, *b"E\0\0\0"),
+        (4, 5, 3, 128u32.to_le_bytes()),
+        (6, 5, 1, 152u32.to_le_bytes()),
+    ];
+    tiff.extend((entries.len() as u16).to_le_bytes());
+    for (tag, kind, count, value) in entries {
+        tiff.extend(tag.to_le_bytes());
+        tiff.extend(kind.to_le_bytes());
+        tiff.extend(count.to_le_bytes());
+        tiff.extend(value);
+    }
+    tiff.extend(0u32.to_le_bytes());
+    for (numerator, denominator) in [(48, 1), (51, 1), (30, 1), (2, 1), (17, 1), (40, 1), (35, 1)] {
+        tiff.extend((numerator as u32).to_le_bytes());
+        tiff.extend((denominator as u32).to_le_bytes());
+    }
+    tiff
+}
+
+/// A JPEG holding the EXIF of [`given_a_tiff_taken_at_the_eiffel_tower`].
+pub(crate) fn given_a_jpeg_taken_at_the_eiffel_tower() -> Vec<u8> {
+    let tiff = given_a_tiff_taken_at_the_eiffel_tower();
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+    jpeg.extend(b"Exif\0\0");
+    jpeg.extend(tiff);
+    jpeg.extend([0xFF, 0xD9]);
+    jpeg
+}
@@ -0,0 +1,169 @@
+use std::{
+    fmt::Display,
+    io::{stdout, IsTerminal},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// The interval between the progress lines logged when the standard output is
+/// not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The bar of the running operation, which the lines printed meanwhile go above.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// The progress of a long-running operation over files, drawn as a bar with the
+/// bytes processed, throughput and ETA when the standard output is a terminal,
+/// and logged as a line every ten seconds otherwise.
+pub(crate) struct Progress {
+    label: String,
+    bar: Option<ProgressBar>,
+    total_files: u64,
+    total_bytes: u64,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+struct State {
+    files: u64,
+    bytes: u64,
+    logged_at: Instant,
+}
+
+impl Progress {
+    /// Starts reporting the progress over the files totaling the bytes.
+    pub(crate) fn start(label: impl Into<String>, total_files: u64, total_bytes: u64) -> Self {
+        let label = label.into();
+        let bar = stdout().is_terminal().then(|| {
+            let bar = ProgressBar::with_draw_target(
+                Some(total_bytes),
+                ProgressDrawTarget::stdout(),
+            )
+            .with_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta} {msg}",
+                )
+                .expect("valid template")
+                .progress_chars("=> "),
+            )
+            .with_prefix(label.clone());
+            bar.set_message(format!("0/{} files", total_files));
+            bar
+        });
+        *ACTIVE.lock().expect("progress lock") = bar.clone();
+        let start = Instant::now();
+        Self {
+            label,
+            bar,
+            total_files,
+            total_bytes,
+            start,
+            state: Mutex::new(State {
+                files: 0,
+                bytes: 0,
+                logged_at: start,
+            }),
+        }
+    }
+
+    /// Counts a file of the bytes as processed.
+    pub(crate) fn inc(&self, bytes: u64) {
+        let mut state = self.state.lock().expect("progress lock");
+        state.files += 1;
+        state.bytes += bytes;
+        match &self.bar {
+            Some(bar) => {
+                bar.set_message(format!("{}/{} files", state.files, self.total_files));
+                bar.inc(bytes);
+            }
+            None if state.logged_at.elapsed() >= LOG_INTERVAL => {
+                state.logged_at = Instant::now();
+                println!(
+                    "{}",
+                    log_line(
+                        &self.label,
+                        (state.files, self.total_files),
+                        (state.bytes, self.total_bytes),
+                        self.start.elapsed()
+                    )
+                );
+            }
+            None => {}
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        *ACTIVE.lock().expect("progress lock") = None;
+    }
+}
+
+/// Prints the line, above the progress bar of the running operation if any.
+pub(crate) fn print_line(line: impl Display) {
+    match ACTIVE.lock().expect("progress lock").as_ref() {
+        Some(bar) => bar.println(line.to_string()),
+        None => println!("{}", line),
+    }
+}
+
+/// The size of the file, 0 when it can't be read.
+pub(crate) fn file_size(path: &Path) -> u64 {
+    path.metadata().map(|m| m.len()).unwrap_or_default()
+}
+
+/// A progress line such as `Checking: 120/4800 files, 1.17 GiB/37.25 GiB,
+/// 35.10 MiB/s, ETA 17 minutes`.
+fn log_line(
+    label: &str,
+    (files, total_files): (u64, u64),
+    (bytes, total_bytes): (u64, u64),
+    elapsed: Duration,
+) -> String {
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    let eta = if bytes > 0 {
+        HumanDuration(Duration::from_secs_f64(
+            seconds * total_bytes.saturating_sub(bytes) as f64 / bytes as f64,
+        ))
+        .to_string()
+    } else {
+        "unknown".to_string()
+    };
+    format!(
+        "{}: {}/{} files, {}/{}, {}/s, ETA {}",
+        label,
+        files,
+        total_files,
+        HumanBytes(bytes),
+        HumanBytes(total_bytes),
+        HumanBytes((bytes as f64 / seconds) as u64),
+        eta
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::log_line;
+
+    #[test]
+    fn log_line_estimates_the_time_left_from_the_throughput() {
+        assert_eq!(
+            "Checking: 10/40 files, 100.00 MiB/400.00 MiB, 10.00 MiB/s, ETA 30 seconds",
+            log_line(
+                "Checking",
+                (10, 40),
+                (100 << 20, 400 << 20),
+                Duration::from_secs(10)
+            )
+        );
+        assert!(log_line("Checking", (0, 40), (0, 400), Duration::ZERO).ends_with("ETA unknown"));
+    }
+}
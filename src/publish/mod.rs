@@ -0,0 +1,536 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    env,
+    fs::remove_file,
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::http::{config_option, url_path, Client, Request};
+
+/// The folder of the PhotoPrism originals the pictures are uploaded to.
+const PHOTOPRISM_FOLDER: &str = "photo_works";
+
+/// A self-hosted gallery server, configured in a `[publish.<NAME>]` table of the
+/// repository configuration. The `publish.<NAME>` secret holds its API key or
+/// password. The requests are sent with the [Client].
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "server", rename_all = "lowercase")]
+pub(crate) enum Publisher {
+    /// Uploads with the Immich API, authenticated by an API key.
+    Immich { url: String },
+    /// Uploads with `pwg.images.addSimple`, in the album when given.
+    Piwigo {
+        url: String,
+        username: String,
+        album: Option<u32>,
+    },
+    /// Uploads over WebDAV to the originals folder PhotoPrism indexes. The
+    /// captions and tags are not sent.
+    PhotoPrism { url: String, username: String },
+}
+
+/// What a gallery shows of a picture besides its file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct PublishedMetadata {
+    pub(crate) title: String,
+    pub(crate) caption: Option<String>,
+    pub(crate) tags: Vec<String>,
+}
+
+/// A library picture to publish.
+pub(crate) struct Publication<'a> {
+    pub(crate) sha256: &'a str,
+    /// The path of the file relative to the repository root.
+    pub(crate) path: &'a Path,
+    pub(crate) original_date: Option<NaiveDate>,
+    pub(crate) metadata: &'a PublishedMetadata,
+}
+
+impl Publisher {
+    /// Opens a session on the server, logging in when it needs it.
+    pub(crate) fn connect(&self, secret: String, client: Client) -> Result<Session<'_>> {
+        let session = Session {
+            publisher: self,
+            client,
+            secret,
+            cookies: match self {
+                Self::Piwigo { .. } => Some(
+                    env::temp_dir().join(format!("photo_works-piwigo-{}.cookies", process::id())),
+                ),
+                _ => None,
+            },
+            folders: RefCell::new(HashSet::new()),
+        };
+        if let Self::Piwigo { .. } = self {
+            let response = session.client.send_json(&session.piwigo_login())?;
+            piwigo_result(&response)?;
+        }
+        Ok(session)
+    }
+}
+
+/// A session on a gallery server.
+pub(crate) struct Session<'a> {
+    publisher: &'a Publisher,
+    client: Client,
+    secret: String,
+    /// The cookie jar of the Piwigo session.
+    cookies: Option<PathBuf>,
+    /// The WebDAV folders created during the session.
+    folders: RefCell<HashSet<String>>,
+}
+
+impl Session<'_> {
+    /// Uploads the picture with its metadata, reading it from the repository
+    /// root. Returns the id of the picture on the server.
+    pub(crate) fn upload(&self, root: &Path, publication: &Publication) -> Result<String> {
+        match self.publisher {
+            Publisher::Immich { .. } => {
+                let response = self
+                    .client
+                    .send_json(&self.immich_upload(&root.join(publication.path), publication)?)?;
+                let id = response["id"]
+                    .as_str()
+                    .ok_or(eyre!("Unexpected Immich response {}", response))?
+                    .to_owned();
+                self.update(root, &id, publication)?;
+                Ok(id)
+            }
+            Publisher::Piwigo { .. } => {
+                let response = self.client.send_json(&self.piwigo_add(
+                    &root.join(publication.path),
+                    publication,
+                    None,
+                ))?;
+                Ok(piwigo_result(&response)?["image_id"].to_string())
+            }
+            Publisher::PhotoPrism { url, .. } => {
+                let mut folder = PathBuf::from(PHOTOPRISM_FOLDER);
+                self.photoprism_folder(url, &folder)?;
+                for component in publication.path.parent().into_iter().flatten() {
+                    folder.push(component);
+                    self.photoprism_folder(url, &folder)?;
+                }
+                let id = Path::new(PHOTOPRISM_FOLDER).join(publication.path);
+                self.client
+                    .send(&self.photoprism_put(&root.join(publication.path), &id))?;
+                Ok(id.to_string_lossy().to_string())
+            }
+        }
+    }
+
+    /// Sends the metadata of a picture uploaded before.
+    pub(crate) fn update(
+        &self,
+        root: &Path,
+        remote_id: &str,
+        publication: &Publication,
+    ) -> Result<()> {
+        match self.publisher {
+            Publisher::Immich { .. } => {
+                self.client
+                    .send(&self.immich_describe(remote_id, publication.metadata))?;
+                if !publication.metadata.tags.is_empty() {
+                    let tags = self
+                        .client
+                        .send_json(&self.immich_upsert_tags(&publication.metadata.tags))?;
+                    let ids = tags
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|t| t["id"].as_str())
+                        .collect::<Vec<&str>>();
+                    self.client.send(&self.immich_tag(remote_id, &ids))?;
+                }
+                Ok(())
+            }
+            // Piwigo only sets tags by name when adding the picture, sent again
+            // under its id.
+            Publisher::Piwigo { .. } => {
+                let response = self.client.send_json(&self.piwigo_add(
+                    &root.join(publication.path),
+                    publication,
+                    Some(remote_id),
+                ))?;
+                piwigo_result(&response).map(|_| ())
+            }
+            Publisher::PhotoPrism { .. } => Ok(()),
+        }
+    }
+
+    /// Downloads the original file of a picture uploaded before.
+    pub(crate) fn download(&self, remote_id: &str, to: &Path) -> Result<()> {
+        let request = self
+            .download_request(remote_id)?
+            .arg("--output")
+            .arg(to.to_string_lossy());
+        self.client.send(&request).map(|_| ())
+    }
+
+    fn download_request(&self, remote_id: &str) -> Result<Request> {
+        match self.publisher {
+            Publisher::Immich { .. } => {
+                Ok(self.immich_request("GET", &format!("/api/assets/{}/original", remote_id)))
+            }
+            Publisher::Piwigo { .. } => {
+                let cookies = self.cookies.as_ref().expect("piwigo");
+                let response = self.client.send_json(
+                    &Request::new(&["--cookie", &cookies.to_string_lossy()], String::new())
+                        .field("method", "pwg.images.getInfo")
+                        .field("image_id", remote_id)
+                        .arg(self.piwigo_url()),
+                )?;
+                let url = piwigo_result(&response)?["element_url"]
+                    .as_str()
+                    .ok_or(eyre!("Unexpected Piwigo response {}", response))?;
+                Ok(Request::new(&["--cookie", &cookies.to_string_lossy()], String::new()).arg(url))
+            }
+            Publisher::PhotoPrism { url, .. } => {
+                Ok(Request::new(&[], self.photoprism_user()).arg(format!(
+                    "{}/originals/{}",
+                    url.trim_end_matches('/'),
+                    url_path(Path::new(remote_id))
+                )))
+            }
+        }
+    }
+
+    fn immich_upload(&self, path: &Path, publication: &Publication) -> Result<Request> {
+        let created = match publication.original_date {
+            Some(date) => format!("{}T00:00:00.000Z", date.format("%Y-%m-%d")),
+            None => DateTime::<Utc>::from(path.metadata()?.modified()?).to_rfc3339(),
+        };
+        Ok(self
+            .immich_request("POST", "/api/assets")
+            .file("assetData", path)
+            .field("deviceAssetId", publication.sha256)
+            .field("deviceId", "photo_works")
+            .field("fileCreatedAt", &created)
+            .field("fileModifiedAt", &created))
+    }
+
+    fn immich_describe(&self, remote_id: &str, metadata: &PublishedMetadata) -> Request {
+        self.immich_request("PUT", &format!("/api/assets/{}", remote_id))
+            .json(&json!({ "description": metadata.caption.as_deref().unwrap_or_default() }))
+    }
+
+    fn immich_upsert_tags(&self, tags: &[String]) -> Request {
+        self.immich_request("PUT", "/api/tags")
+            .json(&json!({ "tags": tags }))
+    }
+
+    fn immich_tag(&self, remote_id: &str, tag_ids: &[&str]) -> Request {
+        self.immich_request("PUT", "/api/tags/assets")
+            .json(&json!({ "tagIds": tag_ids, "assetIds": [remote_id] }))
+    }
+
+    fn immich_request(&self, method: &str, endpoint: &str) -> Request {
+        let Publisher::Immich { url } = self.publisher else {
+            unreachable!("Not an Immich server")
+        };
+        Request::new(
+            &["--request", method],
+            config_option("header", &format!("x-api-key: {}", self.secret)),
+        )
+        .arg(format!("{}{}", url.trim_end_matches('/'), endpoint))
+    }
+
+    fn piwigo_login(&self) -> Request {
+        let Publisher::Piwigo { username, .. } = self.publisher else {
+            unreachable!("Not a Piwigo server")
+        };
+        let cookies = self.cookies.as_ref().expect("piwigo");
+        Request::new(
+            &["--cookie-jar", &cookies.to_string_lossy()],
+            config_option("form-string", &format!("password={}", self.secret)),
+        )
+        .field("method", "pwg.session.login")
+        .field("username", username)
+        .arg(self.piwigo_url())
+    }
+
+    fn piwigo_add(
+        &self,
+        path: &Path,
+        publication: &Publication,
+        remote_id: Option<&str>,
+    ) -> Request {
+        let Publisher::Piwigo { album, .. } = self.publisher else {
+            unreachable!("Not a Piwigo server")
+        };
+        let cookies = self.cookies.as_ref().expect("piwigo");
+        let metadata = publication.metadata;
+        let mut request = Request::new(&["--cookie", &cookies.to_string_lossy()], String::new())
+            .field("method", "pwg.images.addSimple")
+            .file("image", path)
+            .field("name", &metadata.title)
+            .field("comment", metadata.caption.as_deref().unwrap_or_default())
+            .field("tags", &metadata.tags.join(","));
+        if let Some(album) = album {
+            request = request.field("category", &album.to_string());
+        }
+        if let Some(remote_id) = remote_id {
+            request = request.field("image_id", remote_id);
+        }
+        request.arg(self.piwigo_url())
+    }
+
+    fn piwigo_url(&self) -> String {
+        let Publisher::Piwigo { url, .. } = self.publisher else {
+            unreachable!("Not a Piwigo server")
+        };
+        format!("{}/ws.php?format=json", url.trim_end_matches('/'))
+    }
+
+    /// Creates the WebDAV folder once per session, ignoring the folders that
+    /// exist already.
+    fn photoprism_folder(&self, url: &str, folder: &Path) -> Result<()> {
+        let folder = format!("{}/", url_path(folder));
+        if self.folders.borrow_mut().insert(folder.clone()) {
+            let request = Request::new(
+                &["--request", "MKCOL", "--output", "/dev/null"],
+                self.photoprism_user(),
+            )
+            .arg(format!(
+                "{}/originals/{}",
+                url.trim_end_matches('/'),
+                folder
+            ));
+            // An existing folder fails with 405 Method Not Allowed.
+            let _ = self.client.send(&request);
+        }
+        Ok(())
+    }
+
+    fn photoprism_put(&self, path: &Path, remote_id: &Path) -> Request {
+        let Publisher::PhotoPrism { url, .. } = self.publisher else {
+            unreachable!("Not a PhotoPrism server")
+        };
+        Request::new(&["--upload-file"], self.photoprism_user())
+            .arg(path.to_string_lossy())
+            .arg(format!(
+                "{}/originals/{}",
+                url.trim_end_matches('/'),
+                url_path(remote_id)
+            ))
+    }
+
+    fn photoprism_user(&self) -> String {
+        let Publisher::PhotoPrism { username, .. } = self.publisher else {
+            unreachable!("Not a PhotoPrism server")
+        };
+        config_option("user", &format!("{}:{}", username, self.secret))
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        if let Some(cookies) = &self.cookies {
+            let _ = remove_file(cookies);
+        }
+    }
+}
+
+/// The result of a Piwigo response, failing with its message when its status is
+/// not ok.
+fn piwigo_result(response: &Value) -> Result<&Value> {
+    match response["stat"].as_str() {
+        Some("ok") => Ok(&response["result"]),
+        _ => Err(eyre!(
+            "Piwigo failed: {}",
+            response["message"]
+                .as_str()
+                .unwrap_or("unexpected response")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashSet, path::Path};
+
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use crate::{
+        config::HttpConfig,
+        http::{config_option, Client, Request},
+    };
+
+    use super::{piwigo_result, Publication, PublishedMetadata, Publisher, Session};
+
+    fn session(publisher: &Publisher) -> Session<'_> {
+        Session {
+            publisher,
+            client: Client::new(HttpConfig::default()),
+            secret: "s3cr\"t".to_string(),
+            cookies: Some("/tmp/cookies".into()),
+            folders: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn metadata() -> PublishedMetadata {
+        PublishedMetadata {
+            title: "a.jpg".to_string(),
+            caption: Some("Lake trip".to_string()),
+            tags: vec!["beach".to_string(), "family".to_string()],
+        }
+    }
+
+    #[test]
+    fn immich_upload_passes_the_api_key_in_the_config() {
+        let publisher = Publisher::Immich {
+            url: "https://photos.example.com/".to_string(),
+        };
+        let metadata = metadata();
+        let publication = Publication {
+            sha256: "AB",
+            path: Path::new("2024/7/12/a.jpg"),
+            original_date: NaiveDate::from_ymd_opt(2024, 7, 12),
+            metadata: &metadata,
+        };
+
+        let request = session(&publisher)
+            .immich_upload(Path::new("/repo/2024/7/12/a.jpg"), &publication)
+            .unwrap();
+
+        assert_eq!(
+            Request::new(
+                &[
+                    "--request",
+                    "POST",
+                    "https://photos.example.com/api/assets",
+                    "--form",
+                    "assetData=@\"/repo/2024/7/12/a.jpg\"",
+                    "--form-string",
+                    "deviceAssetId=AB",
+                    "--form-string",
+                    "deviceId=photo_works",
+                    "--form-string",
+                    "fileCreatedAt=2024-07-12T00:00:00.000Z",
+                    "--form-string",
+                    "fileModifiedAt=2024-07-12T00:00:00.000Z",
+                ],
+                "header = \"x-api-key: s3cr\\\"t\"\n".to_string()
+            ),
+            request
+        );
+    }
+
+    #[test]
+    fn piwigo_add_sends_the_tags_and_album() {
+        let publisher = Publisher::Piwigo {
+            url: "https://gallery.example.com".to_string(),
+            username: "me".to_string(),
+            album: Some(3),
+        };
+        let metadata = metadata();
+        let publication = Publication {
+            sha256: "AB",
+            path: Path::new("a.jpg"),
+            original_date: None,
+            metadata: &metadata,
+        };
+
+        let request =
+            session(&publisher).piwigo_add(Path::new("/repo/a.jpg"), &publication, Some("12"));
+
+        assert_eq!(
+            vec![
+                "--cookie",
+                "/tmp/cookies",
+                "--form-string",
+                "method=pwg.images.addSimple",
+                "--form",
+                "image=@\"/repo/a.jpg\"",
+                "--form-string",
+                "name=a.jpg",
+                "--form-string",
+                "comment=Lake trip",
+                "--form-string",
+                "tags=beach,family",
+                "--form-string",
+                "category=3",
+                "--form-string",
+                "image_id=12",
+                "https://gallery.example.com/ws.php?format=json",
+            ],
+            request.arguments
+        );
+        assert!(request.config.is_empty());
+    }
+
+    #[test]
+    fn photoprism_put_encodes_the_path() {
+        let publisher = Publisher::PhotoPrism {
+            url: "http://nas:2342".to_string(),
+            username: "admin".to_string(),
+        };
+
+        let request = session(&publisher).photoprism_put(
+            Path::new("/repo/2024/7/Lake trip.jpg"),
+            Path::new("photo_works/2024/7/Lake trip.jpg"),
+        );
+
+        assert_eq!(
+            "http://nas:2342/originals/photo_works/2024/7/Lake%20trip.jpg",
+            request.arguments.last().unwrap()
+        );
+        assert_eq!(config_option("user", "admin:s3cr\"t"), request.config);
+    }
+
+    #[test]
+    fn piwigo_result_fails_with_the_message() {
+        assert_eq!(
+            &json!(1),
+            piwigo_result(&json!({"stat": "ok", "result": 1})).unwrap()
+        );
+        assert_eq!(
+            "Piwigo failed: Access denied",
+            piwigo_result(&json!({"stat": "fail", "message": "Access denied"}))
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn publisher_reads_the_server_kind() {
+        let publisher: Publisher =
+            toml::from_str("server = \"photoprism\"\nurl = \"http://nas\"\nusername = \"me\"")
+                .unwrap();
+        assert_eq!(
+            Publisher::PhotoPrism {
+                url: "http://nas".to_string(),
+                username: "me".to_string()
+            },
+            publisher
+        );
+    }
+
+    #[test]
+    fn download_request_reads_the_photoprism_original() {
+        let publisher = Publisher::PhotoPrism {
+            url: "http://nas/".to_string(),
+            username: "me".to_string(),
+        };
+
+        let request = session(&publisher)
+            .download_request("photo_works/2024/été.jpg")
+            .unwrap();
+
+        assert_eq!(
+            Request::new(
+                &["http://nas/originals/photo_works/2024/%C3%A9t%C3%A9.jpg"],
+                config_option("user", "me:s3cr\"t")
+            ),
+            request
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::process::Command;
+
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::database::catalog_entry::CatalogEntry;
+
+/// The prefix marking catalog paths that live on a remote volume.
+pub(crate) const SSH_SCHEME: &str = "ssh://";
+
+/// A directory of a remote host reachable with the `ssh` command, written
+/// `ssh://[user@]host[:port]/path`. The files are hashed on the host so that only
+/// their digests cross the network.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RemoteSource {
+    host: String,
+    port: Option<u16>,
+    path: String,
+    agent: bool,
+}
+
+/// A file hashed by `photo_works agent`, streamed as one JSON line per file.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct AgentRecord {
+    pub(crate) path: String,
+    pub(crate) sha256: String,
+    pub(crate) size: u64,
+    /// Modification time in seconds since the Unix epoch.
+    pub(crate) modified: Option<u64>,
+}
+
+impl RemoteSource {
+    pub(crate) fn parse(url: &str) -> Result<Option<Self>> {
+        let rest = match url
+            .strip_prefix(SSH_SCHEME)
+            .or_else(|| url.strip_prefix("sftp://"))
+        {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        let (authority, path) = rest
+            .find('/')
+            .map(|index| rest.split_at(index))
+            .ok_or(eyre!("Missing path in {}", url))?;
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(
+                    port.parse()
+                        .wrap_err_with(|| format!("Invalid port in {}", url))?,
+                ),
+            ),
+            None => (authority, None),
+        };
+        if host.is_empty() {
+            return Err(eyre!("Missing host in {}", url));
+        }
+        Ok(Some(Self {
+            host: host.to_owned(),
+            port,
+            path: path.trim_end_matches('/').to_owned(),
+            agent: false,
+        }))
+    }
+
+    /// Hashes with `photo_works agent` on the host rather than with `sha256sum`.
+    pub(crate) fn with_agent(self, agent: bool) -> Self {
+        Self { agent, ..self }
+    }
+
+    /// The catalog path of a file of the host.
+    pub(crate) fn url(&self, path: &str) -> String {
+        match self.port {
+            Some(port) => format!("{}{}:{}{}", SSH_SCHEME, self.host, port, path),
+            None => format!("{}{}{}", SSH_SCHEME, self.host, path),
+        }
+    }
+
+    pub(crate) fn root_url(&self) -> String {
+        self.url(&self.path)
+    }
+
+    /// Hashes the non hidden files under the remote directory with `sha256sum`, or
+    /// with the agent.
+    pub(crate) fn catalog_entries(&self) -> Result<Vec<CatalogEntry>> {
+        let output = self.hash_command().output().wrap_err("Failed to run ssh")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "Failed to hash {}: {}",
+                self.root_url(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| self.parse_line(line))
+            .collect()
+    }
+
+    fn parse_line(&self, line: &str) -> Result<CatalogEntry> {
+        if self.agent {
+            let record: AgentRecord = serde_json::from_str(line)
+                .wrap_err_with(|| format!("Unexpected agent output `{}`", line))?;
+            Ok(CatalogEntry::new(record.sha256, self.url(&record.path)))
+        } else {
+            let (hash, path) = line
+                .split_once("  ")
+                .ok_or(eyre!("Unexpected sha256sum output `{}`", line))?;
+            Ok(CatalogEntry::new(hash.to_uppercase(), self.url(path)))
+        }
+    }
+
+    fn hash_command(&self) -> Command {
+        let mut command = Command::new("ssh");
+        if let Some(port) = self.port {
+            command.args(["-p", &port.to_string()]);
+        }
+        command.arg(&self.host);
+        if self.agent {
+            command.arg(format!("photo_works agent {}", shell_quote(&self.path)));
+        } else {
+            command.arg(format!(
+                "find {} -type f ! -path '*/.*' -exec sha256sum {{}} +",
+                shell_quote(&self.path)
+            ));
+        }
+        command
+    }
+}
+
+/// True when the catalog path is on a remote volume.
+pub(crate) fn is_remote(path: &str) -> bool {
+    path.starts_with(SSH_SCHEME)
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_remote, RemoteSource};
+
+    #[test]
+    fn parse_line_reads_agent_records() {
+        let remote = RemoteSource::parse("ssh://server/photos")
+            .unwrap()
+            .unwrap()
+            .with_agent(true);
+
+        let entry = remote
+            .parse_line(r#"{"path":"/photos/a.jpeg","sha256":"AB","size":2,"modified":null}"#)
+            .unwrap();
+
+        assert_eq!("AB", entry.sha256());
+        assert_eq!("ssh://server/photos/a.jpeg", entry.path().to_string_lossy());
+    }
+
+    #[test]
+    fn parse_ignores_local_paths() {
+        assert_eq!(None, RemoteSource::parse("/photos").unwrap());
+    }
+
+    #[test]
+    fn parse_reads_host_port_and_path() {
+        let remote = RemoteSource::parse("ssh://me@server:2222/photos/")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!("ssh://me@server:2222/photos", remote.root_url());
+        assert!(is_remote(&remote.url("/photos/a.jpeg")));
+    }
+
+    #[test]
+    fn parse_rejects_a_missing_path() {
+        assert!(RemoteSource::parse("ssh://server").is_err());
+    }
+
+    #[test]
+    fn hash_command_quotes_the_path() {
+        let remote = RemoteSource::parse("ssh://server/my photos")
+            .unwrap()
+            .unwrap();
+        let command = remote.hash_command();
+
+        assert_eq!(
+            vec![
+                "server",
+                "find '/my photos' -type f ! -path '*/.*' -exec sha256sum {} +"
+            ],
+            command
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+}
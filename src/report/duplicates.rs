@@ -0,0 +1,146 @@
+use std::{fs::metadata, io::Write};
+
+use eyre::Result;
+
+use crate::{
+    database::catalog_entry::CatalogEntry,
+    metadata::{Exif, MetadataBackend},
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const STYLE: &str = "body{font-family:sans-serif}section{border-top:1px solid #ccc}\
+.group{display:flex;flex-wrap:wrap;gap:1em}figure{margin:0;width:220px}\
+img{max-width:200px;max-height:200px}figcaption{font-size:small;word-break:break-all}";
+
+/// A standalone HTML page showing the copies of each duplicate side by side, with
+/// the thumbnail embedded in their EXIF metadata or a link to the local file. The
+/// duplicates are written as they are added, the count last.
+pub(crate) struct DuplicatesHtml<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> DuplicatesHtml<W> {
+    pub(crate) fn start(mut writer: W) -> Result<Self> {
+        writeln!(
+            writer,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Duplicates</title><style>{}</style></head><body>\n<h1>Duplicates</h1>",
+            STYLE
+        )?;
+        Ok(Self { writer, count: 0 })
+    }
+
+    pub(crate) fn add(&mut self, hash: &str, copies: &[CatalogEntry]) -> Result<()> {
+        write!(
+            self.writer,
+            "<section><h2>{} ({} copies)</h2><div class=\"group\">",
+            escape(hash),
+            copies.len()
+        )?;
+        for copy in copies {
+            self.writer.write_all(figure(copy).as_bytes())?;
+        }
+        writeln!(self.writer, "</div></section>")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W> {
+        writeln!(
+            self.writer,
+            "<p>{} duplicates</p>\n</body></html>",
+            self.count
+        )?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn figure(entry: &CatalogEntry) -> String {
+    let path = entry.path();
+    let image = if entry.is_remote() {
+        String::new()
+    } else {
+        let src = match Exif.thumbnail(&path) {
+            Some(thumbnail) => format!("data:image/jpeg;base64,{}", base64(&thumbnail)),
+            None => format!("file://{}", path.display()),
+        };
+        format!("<img loading=\"lazy\" src=\"{}\">", escape(&src))
+    };
+    let size = metadata(&path)
+        .map(|m| format!("{} bytes", m.len()))
+        .unwrap_or_default();
+    let date = Exif
+        .original_date(&path)
+        .map(|d| d.to_string())
+        .unwrap_or_default();
+    format!(
+        "<figure>{}<figcaption>{}<br>{} {}</figcaption></figure>",
+        image,
+        escape(&path.to_string_lossy()),
+        size,
+        date
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::catalog_entry::CatalogEntry;
+
+    use super::{base64, DuplicatesHtml};
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!("TWFu", base64(b"Man"));
+        assert_eq!("TWE=", base64(b"Ma"));
+        assert_eq!("TQ==", base64(b"M"));
+    }
+
+    #[test]
+    fn duplicates_html_shows_each_copy_of_a_group() {
+        let mut report = DuplicatesHtml::start(vec![]).unwrap();
+        report
+            .add(
+                "AB",
+                &[
+                    CatalogEntry::new("AB".to_string(), "/card/<a>.jpg".to_string()),
+                    CatalogEntry::new("AB".to_string(), "ssh://nas/b.jpg".to_string()),
+                ],
+            )
+            .unwrap();
+
+        let html = String::from_utf8(report.finish().unwrap()).unwrap();
+
+        assert!(html.contains("<h2>AB (2 copies)</h2>"));
+        assert!(html.contains("/card/&lt;a&gt;.jpg"));
+        assert!(html.contains("ssh://nas/b.jpg"));
+        assert_eq!(1, html.matches("<img").count());
+        assert!(html.contains("<p>1 duplicates</p>"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    fmt::Write,
+    fs::{create_dir_all, write},
+    path::PathBuf,
+};
+
+use chrono::{Local, NaiveDateTime};
+use eyre::Result;
+
+use crate::{
+    database::stats::{counts, Counts},
+    repository::Repository,
+};
+
+pub(crate) mod duplicates;
+
+const SUMMARY_FILE: &str = "summary.md";
+
+/// The summary of the stages of an alias pipeline, saved as markdown under
+/// `.photo_works/reports/<timestamp>` once the pipeline ends.
+pub(crate) struct SessionReport {
+    alias: String,
+    started_at: NaiveDateTime,
+    before: Option<Counts>,
+    stages: Vec<(String, Option<String>)>,
+}
+
+impl SessionReport {
+    /// Records the state of the repository before the first stage.
+    pub(crate) fn start(alias: &str, repository: &Repository) -> Result<Self> {
+        Ok(Self {
+            alias: alias.to_owned(),
+            started_at: Local::now().naive_local(),
+            before: repository_counts(repository)?,
+            stages: vec![],
+        })
+    }
+
+    /// Records the outcome of a stage, given as its arguments.
+    pub(crate) fn record<T>(&mut self, stage: &[String], result: &Result<T>) {
+        self.stages.push((
+            stage.join(" "),
+            result.as_ref().err().map(|e| e.to_string()),
+        ));
+    }
+
+    /// Writes the summary with the state of the repository after the last stage
+    /// and returns its path.
+    pub(crate) fn save(&self, repository: &Repository) -> Result<PathBuf> {
+        let after = repository_counts(repository)?;
+        let directory = repository
+            .root()
+            .join(".photo_works")
+            .join("reports")
+            .join(self.started_at.format("%Y%m%dT%H%M%S").to_string());
+        create_dir_all(&directory)?;
+        let path = directory.join(SUMMARY_FILE);
+        write(
+            &path,
+            self.to_markdown(Local::now().naive_local(), after.as_ref())?,
+        )?;
+        Ok(path)
+    }
+
+    fn to_markdown(&self, finished_at: NaiveDateTime, after: Option<&Counts>) -> Result<String> {
+        let mut markdown = String::new();
+        writeln!(markdown, "# Session `{}`\n", self.alias)?;
+        writeln!(
+            markdown,
+            "Started {}, finished {} ({} seconds).\n",
+            self.started_at.format("%Y-%m-%d %H:%M:%S"),
+            finished_at.format("%Y-%m-%d %H:%M:%S"),
+            (finished_at - self.started_at).num_seconds()
+        )?;
+        writeln!(markdown, "## Stages\n")?;
+        writeln!(markdown, "| Stage | Result |\n| --- | --- |")?;
+        for (stage, error) in &self.stages {
+            let result = match error {
+                Some(error) => format!("failed: {}", error.replace('\n', " ")),
+                None => "ok".to_string(),
+            };
+            writeln!(markdown, "| `{}` | {} |", stage, result)?;
+        }
+        let before = self.before.unwrap_or_default();
+        let after = after.copied().unwrap_or_default();
+        writeln!(markdown, "\n## Files\n")?;
+        writeln!(
+            markdown,
+            "| | Before | After | Change |\n| --- | ---: | ---: | ---: |"
+        )?;
+        for (label, before, after) in [
+            ("Found (cataloged)", before.cataloged, after.cataloged),
+            ("Imported", before.imported, after.imported),
+            (
+                "Skipped (pending import)",
+                before.pending_import,
+                after.pending_import,
+            ),
+            (
+                "Duplicate copies",
+                before.duplicate_copies,
+                after.duplicate_copies,
+            ),
+            ("Failures (corrupt)", before.problems, after.problems),
+        ] {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {:+} |",
+                label,
+                before,
+                after,
+                after - before
+            )?;
+        }
+        Ok(markdown)
+    }
+}
+
+/// The counts of an existing repository database, without creating one.
+fn repository_counts(repository: &Repository) -> Result<Option<Counts>> {
+    if repository.db_path().exists() {
+        Ok(Some(counts(&*repository.open_database()?)?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use eyre::eyre;
+
+    use crate::database::stats::Counts;
+
+    use super::SessionReport;
+
+    #[test]
+    fn to_markdown_reports_the_stages_and_the_changes() {
+        let started_at = NaiveDate::from_ymd_opt(2024, 7, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let mut report = SessionReport {
+            alias: "weekly".to_string(),
+            started_at,
+            before: Some(Counts {
+                cataloged: 10,
+                ..Default::default()
+            }),
+            stages: vec![],
+        };
+        report.record(&["catalog".to_string(), "/card".to_string()], &Ok(()));
+        report.record::<()>(&["import".to_string()], &Err(eyre!("Disk full")));
+
+        let markdown = report
+            .to_markdown(
+                started_at + chrono::Duration::seconds(42),
+                Some(&Counts {
+                    cataloged: 25,
+                    imported: 12,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+
+        assert!(markdown.contains("(42 seconds)"));
+        assert!(markdown.contains("| `catalog /card` | ok |"));
+        assert!(markdown.contains("| `import` | failed: Disk full |"));
+        assert!(markdown.contains("| Found (cataloged) | 10 | 25 | +15 |"));
+        assert!(markdown.contains("| Imported | 0 | 12 | +12 |"));
+    }
+}
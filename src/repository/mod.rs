@@ -0,0 +1,330 @@
+use std::{
+    cell::{RefCell, RefMut},
+    env::{current_dir, set_current_dir, var_os},
+    fs::{canonicalize, File, TryLockError},
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches};
+use eyre::{eyre, Context, Result};
+use rusqlite::Connection;
+
+use crate::{
+    config::{RepositoryConfig, UserConfig},
+    database::{self, snapshot::snapshot},
+    error::{Error, ErrorCode},
+    fsext::managed::ManagedPaths,
+    trash::TrashBin,
+};
+
+pub(crate) const PROFILE: &str = "profile";
+pub(crate) const REPO: &str = "repo";
+pub(crate) const DB: &str = "db";
+
+/// The environment variable selecting the database when `--db` is not given.
+const DB_VARIABLE: &str = "PHOTO_WORKS_DB";
+
+/// The directory holding a `.photo_works` database and the library.
+pub(crate) struct Repository {
+    root: PathBuf,
+    /// The database replacing the one of the repository, absolute.
+    db: Option<PathBuf>,
+    /// The connection to the database, opened on first use and then shared by
+    /// the commands run against the repository.
+    connection: RefCell<Option<Connection>>,
+}
+
+/// The global argument selecting the repository of a profile.
+pub(crate) fn profile_arg() -> Arg {
+    Arg::new(PROFILE)
+        .long(PROFILE)
+        .value_name("NAME")
+        .global(true)
+        .help("Runs against the repository of a profile of the user configuration")
+}
+
+/// The global argument selecting the repository by its root directory.
+pub(crate) fn repo_arg() -> Arg {
+    Arg::new(REPO)
+        .long(REPO)
+        .value_name("PATH")
+        .global(true)
+        .conflicts_with(PROFILE)
+        .help("Runs against the repository at the path instead of the one holding the current directory")
+}
+
+/// The global argument selecting the database file.
+pub(crate) fn db_arg() -> Arg {
+    Arg::new(DB)
+        .long(DB)
+        .value_name("PATH")
+        .global(true)
+        .help("Uses the database file instead of the one of the repository, also read from PHOTO_WORKS_DB")
+}
+
+/// The database of `--db` or `PHOTO_WORKS_DB`, made absolute from the current
+/// directory, if any.
+fn db_override(matches: &ArgMatches) -> Result<Option<PathBuf>> {
+    Ok(matches
+        .get_one::<String>(DB)
+        .map(PathBuf::from)
+        .or_else(|| var_os(DB_VARIABLE).map(PathBuf::from))
+        .map(|db| current_dir().map(|current| current.join(db)))
+        .transpose()?)
+}
+
+/// The closest directory holding a `.photo_works` directory, the start directory
+/// or one of its ancestors, as git finds its repository.
+pub(crate) fn discover(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|directory| directory.join(".photo_works").is_dir())
+        .map(Path::to_path_buf)
+}
+
+impl Repository {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            db: None,
+            connection: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn with_db(mut self, db: Option<PathBuf>) -> Self {
+        self.db = db;
+        self
+    }
+
+    /// Makes the repository the current directory so that library paths resolve
+    /// from its root. Relative path arguments must be resolved before entering.
+    /// Fails outside a repository rather than creating an empty database.
+    pub(crate) fn enter(&self) -> Result<()> {
+        if !self.root.join(".photo_works").is_dir() {
+            return Err(eyre!(
+                "{} is not in a photo_works repository, run photo_works init or pass --repo",
+                self.root.display()
+            ));
+        }
+        set_current_dir(&self.root)?;
+        Ok(())
+    }
+
+    /// Selects the repository of the `--repo` or `--profile` argument, or the one
+    /// holding the current directory. Falls back to the current directory outside a
+    /// repository. The database is the one of `--db` or `PHOTO_WORKS_DB` when given,
+    /// relative to the current directory.
+    pub(crate) fn locate(matches: &ArgMatches) -> Result<Self> {
+        let db = db_override(matches)?;
+        let root = match (
+            matches.get_one::<String>(REPO),
+            matches.get_one::<String>(PROFILE),
+        ) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(profile)) => UserConfig::load()?.profile(profile)?.path().to_owned(),
+            (None, None) => {
+                let current = current_dir()?;
+                discover(&current).unwrap_or(current)
+            }
+        };
+        let root = canonicalize(&root)
+            .wrap_err_with(|| format!("Can't find repository {}", root.display()))?;
+        Ok(Self::new(root).with_db(db))
+    }
+
+    /// The database of `--db` or `PHOTO_WORKS_DB`, if any.
+    pub(crate) fn db(&self) -> Option<&Path> {
+        self.db.as_deref()
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn config(&self) -> Result<RepositoryConfig> {
+        RepositoryConfig::load(&self.root.join(".photo_works").join("config.toml"))
+    }
+
+    pub(crate) fn db_path(&self) -> PathBuf {
+        self.db
+            .clone()
+            .unwrap_or_else(|| self.root.join(".photo_works").join("db.db3"))
+    }
+
+    pub(crate) fn snapshots_path(&self) -> PathBuf {
+        self.root.join(".photo_works").join("snapshots")
+    }
+
+    pub(crate) fn thumbnails_path(&self) -> PathBuf {
+        self.root.join(".photo_works").join("thumbnails")
+    }
+
+    /// The folder of the copies redacted by the privacy zones while they are
+    /// published.
+    pub(crate) fn redacted_path(&self) -> PathBuf {
+        self.root.join(".photo_works").join("redacted")
+    }
+
+    /// The trash of the repository, at the `[trash]` path resolved against the root.
+    pub(crate) fn trash(&self) -> Result<TrashBin> {
+        Ok(TrashBin::new(self.root.join(self.config()?.trash().path())))
+    }
+
+    /// The directories photo_works writes to, which are never sources: the library
+    /// at the root, holding `.photo_works`, and the trash when it is elsewhere.
+    pub(crate) fn managed_directories(&self) -> Result<Vec<PathBuf>> {
+        let trash = self.trash()?.directory().to_owned();
+        let trash = canonicalize(&trash).unwrap_or(trash);
+        let mut directories = vec![self.root.clone()];
+        if !trash.starts_with(&self.root) {
+            directories.push(trash);
+        }
+        Ok(directories)
+    }
+
+    /// The working files of the repository, which are never cataloged.
+    pub(crate) fn managed_paths(&self) -> Result<ManagedPaths> {
+        let trash = self.trash()?.directory().to_owned();
+        Ok(ManagedPaths::default()
+            .with_directory(self.root.join(".photo_works"))
+            .with_directory(canonicalize(&trash).unwrap_or(trash))
+            .with_database(&self.db_path()))
+    }
+
+    /// The connection to the database, opened and migrated by the first call. A
+    /// command borrows it for as long as it uses it, once at a time.
+    pub(crate) fn open_database(&self) -> Result<RefMut<'_, Connection>> {
+        let mut connection = self
+            .connection
+            .try_borrow_mut()
+            .map_err(|_| eyre!("The database connection is already in use"))?;
+        if connection.is_none() {
+            *connection = Some(database::open(&self.db_path())?);
+        }
+        Ok(RefMut::map(connection, |c| c.as_mut().expect("opened")))
+    }
+
+    /// Closes the shared connection, if opened, before the database file is
+    /// replaced. The next use opens the new file.
+    pub(crate) fn close_database(&self) -> Result<()> {
+        self.connection
+            .try_borrow_mut()
+            .map_err(|_| eyre!("The database connection is still in use"))?
+            .take();
+        Ok(())
+    }
+
+    /// Takes the exclusive lock of the repository for a mutating command. A second
+    /// mutating process fails immediately instead of interleaving its changes to the
+    /// library, while read-only commands rely on the database busy timeout to wait
+    /// for the pending writes. The lock is released when dropped. The database is
+    /// snapshotted once locked, as configured by `[snapshots]`.
+    pub(crate) fn lock(&self) -> Result<File> {
+        let file = File::create(self.root.join(".photo_works").join("lock"))?;
+        match file.try_lock() {
+            Ok(()) => {
+                let keep = self.config()?.snapshots().keep();
+                if keep > 0 && self.db_path().exists() {
+                    snapshot(&self.db_path(), &self.snapshots_path(), keep)?;
+                }
+                Ok(file)
+            }
+            Err(TryLockError::WouldBlock) => Err(Error::new(
+                ErrorCode::RepositoryLocked,
+                format!(
+                    "Repository {} is locked by another photo_works process",
+                    self.root.display()
+                ),
+            )
+            .into()),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::create_dir_all};
+
+    use clap::Command;
+
+    use tempfile::TempDir;
+
+    use super::{db_arg, discover, profile_arg, repo_arg, Repository};
+
+    #[test]
+    fn locate_uses_the_database_of_the_db_argument() {
+        let directory = TempDir::new().unwrap();
+        let matches = Command::new("photo_works")
+            .arg(profile_arg())
+            .arg(repo_arg())
+            .arg(db_arg())
+            .get_matches_from([
+                "photo_works",
+                "--repo",
+                &directory.path().to_string_lossy(),
+                "--db",
+                "other.db3",
+            ]);
+        let repository = Repository::locate(&matches).unwrap();
+
+        assert_eq!(
+            current_dir().unwrap().join("other.db3"),
+            repository.db_path()
+        );
+        assert_eq!(
+            directory
+                .path()
+                .canonicalize()
+                .unwrap()
+                .join(".photo_works"),
+            repository.snapshots_path().parent().unwrap()
+        );
+    }
+
+    #[test]
+    fn discover_finds_the_closest_repository_above() {
+        let directory = TempDir::new().unwrap();
+        let outer = directory.path().join("outer");
+        let inner = outer.join("2023/05/inner");
+        create_dir_all(outer.join(".photo_works")).unwrap();
+        create_dir_all(inner.join(".photo_works")).unwrap();
+        create_dir_all(inner.join("08")).unwrap();
+
+        assert_eq!(Some(outer.clone()), discover(&outer.join("2023/05")));
+        assert_eq!(Some(inner.clone()), discover(&inner.join("08")));
+        assert_eq!(None, discover(directory.path()));
+    }
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+        let repository = Repository::new(directory.path().to_path_buf());
+
+        let lock = repository.lock().unwrap();
+        assert!(repository.lock().is_err());
+        drop(lock);
+        assert!(repository.lock().is_ok());
+    }
+
+    #[test]
+    fn open_database_shares_one_connection() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join(".photo_works")).unwrap();
+        let repository = Repository::new(directory.path().to_path_buf());
+
+        let connection = repository.open_database().unwrap();
+        connection
+            .execute("CREATE TEMPORARY TABLE shared (id INTEGER)", [])
+            .unwrap();
+        assert!(repository.open_database().is_err());
+        drop(connection);
+
+        assert!(repository
+            .open_database()
+            .unwrap()
+            .execute("INSERT INTO shared VALUES (1)", [])
+            .is_ok());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result};
+use glob::Pattern;
+use serde::Deserialize;
+
+use crate::{
+    media::{self, MediaKind},
+    metadata::MetadataBackend,
+};
+
+/// A `[[rules]]` entry of the repository configuration, evaluated for each file at
+/// import. The first rule whose conditions all hold applies.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct ImportRule {
+    name: String,
+    /// Matches the camera models containing it, ignoring case.
+    camera: Option<String>,
+    /// Matches the absolute paths of the cataloged files.
+    path: Option<Glob>,
+    /// Matches the media types starting with it, e.g. `video/` or `image/jpeg`.
+    media_type: Option<String>,
+    /// Matches the extension, ignoring case.
+    extension: Option<String>,
+    /// Matches the photos, videos, animations or screenshots.
+    kind: Option<MediaKind>,
+    #[serde(default)]
+    action: RuleAction,
+    /// Tags given to the imported files.
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Name of the `[roots]` entry holding the imported files.
+    root: Option<String>,
+    /// Directory of the library, relative to the root, holding the date directories
+    /// of the imported files.
+    sub_root: Option<PathBuf>,
+}
+
+/// What happens to the files matching a rule.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RuleAction {
+    #[default]
+    Import,
+    /// Leaves the file pending in the catalog.
+    Skip,
+    /// Records the file as a problem, which excludes it from later imports too.
+    Quarantine,
+}
+
+/// A glob pattern validated when the configuration is read.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub(crate) struct Glob(Pattern);
+
+impl TryFrom<String> for Glob {
+    type Error = glob::PatternError;
+
+    fn try_from(pattern: String) -> std::result::Result<Self, Self::Error> {
+        Pattern::new(&pattern).map(Self)
+    }
+}
+
+impl ImportRule {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn action(&self) -> RuleAction {
+        self.action
+    }
+
+    pub(crate) fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// The directory holding the date directories of the imported files, none for
+    /// the repository root.
+    pub(crate) fn destination(&self, roots: &BTreeMap<String, PathBuf>) -> Result<Option<PathBuf>> {
+        let root = match &self.root {
+            Some(name) => Some(roots.get(name).ok_or(eyre!(
+                "Rule {} imports into the unknown root {}, add it to [roots]",
+                self.name,
+                name
+            ))?),
+            None => None,
+        };
+        Ok(match (root, &self.sub_root) {
+            (Some(root), Some(sub_root)) => Some(root.join(sub_root)),
+            (Some(root), None) => Some(root.to_owned()),
+            (None, sub_root) => sub_root.to_owned(),
+        })
+    }
+
+    /// Returns true when all the conditions hold for the file. The camera model is
+    /// only read when the rule has a camera condition.
+    fn matches(&self, path: &Path, mime: Option<&str>, metadata: &dyn MetadataBackend) -> bool {
+        self.path
+            .as_ref()
+            .is_none_or(|Glob(p)| p.matches_path(path))
+            && self.extension.as_ref().is_none_or(|extension| {
+                path.extension()
+                    .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(extension))
+            })
+            && self
+                .media_type
+                .as_ref()
+                .is_none_or(|t| mime.is_some_and(|m| m.starts_with(t.as_str())))
+            && self
+                .kind
+                .is_none_or(|kind| media::classify(path, mime).is_ok_and(|k| k == Some(kind)))
+            && self.camera.as_ref().is_none_or(|camera| {
+                metadata
+                    .camera_model(path)
+                    .is_some_and(|m| m.to_lowercase().contains(&camera.to_lowercase()))
+            })
+    }
+}
+
+/// The first rule matching the file of the media type.
+pub(crate) fn evaluate<'a>(
+    rules: &'a [ImportRule],
+    path: &Path,
+    mime: Option<&str>,
+    metadata: &dyn MetadataBackend,
+) -> Option<&'a ImportRule> {
+    rules.iter().find(|r| r.matches(path, mime, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    };
+
+    use serde::Deserialize;
+
+    use crate::metadata::Exif;
+
+    use super::{evaluate, ImportRule, RuleAction};
+
+    #[derive(Deserialize)]
+    struct Rules {
+        rules: Vec<ImportRule>,
+    }
+
+    fn given_rules(toml: &str) -> Vec<ImportRule> {
+        toml::from_str::<Rules>(toml).unwrap().rules
+    }
+
+    #[test]
+    fn evaluate_returns_the_first_rule_matching_all_its_conditions() {
+        let rules = given_rules(
+            r#"
+            [[rules]]
+            name = "drone"
+            path = "*/DJI/*"
+            media_type = "video/"
+            sub_root = "drone"
+
+            [[rules]]
+            name = "screenshots"
+            extension = "png"
+            action = "skip"
+
+            [[rules]]
+            name = "screen captures"
+            kind = "screenshot"
+            action = "quarantine"
+            "#,
+        );
+        let rule = |path: &str, mime| evaluate(&rules, Path::new(path), mime, &Exif);
+
+        assert_eq!(
+            Some("drone"),
+            rule("/card/DJI/a.mp4", Some("video/mp4")).map(|r| r.name())
+        );
+        assert_eq!(None, rule("/card/DJI/a.jpg", Some("image/jpeg")));
+        assert_eq!(
+            Some(RuleAction::Skip),
+            rule("/card/a.PNG", Some("image/png")).map(|r| r.action())
+        );
+        assert_eq!(
+            Some("screen captures"),
+            rule("/card/Screenshot 2024.jpg", Some("image/jpeg")).map(|r| r.name())
+        );
+    }
+
+    #[test]
+    fn camera_condition_fails_without_a_camera_model() {
+        let rules = given_rules(
+            r#"
+            [[rules]]
+            name = "phone"
+            camera = "pixel"
+            tags = ["phone"]
+            "#,
+        );
+
+        assert_eq!(None, evaluate(&rules, Path::new("Cargo.toml"), None, &Exif));
+    }
+
+    #[test]
+    fn destination_is_the_sub_root_of_the_named_root() {
+        let rules = given_rules(
+            r#"
+            [[rules]]
+            name = "drone"
+            root = "drone"
+            sub_root = "footage"
+
+            [[rules]]
+            name = "scans"
+            root = "scans"
+            "#,
+        );
+        let roots = BTreeMap::from([("drone".to_string(), PathBuf::from("/mnt/drone"))]);
+
+        assert_eq!(
+            Some(PathBuf::from("/mnt/drone/footage")),
+            rules[0].destination(&roots).unwrap()
+        );
+        assert!(rules[1].destination(&roots).is_err());
+    }
+
+    #[test]
+    fn invalid_path_patterns_are_rejected() {
+        assert!(toml::from_str::<Rules>("[[rules]]\nname = \"a\"\npath = \"[\"").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::env;
+
+use eyre::Result;
+use keyring::Entry;
+
+/// Keyring service under which the secrets are stored.
+const KEYRING_SERVICE: &str = "photo_works";
+
+/// The environment variable overriding the keyring for a secret,
+/// e.g. `PHOTO_WORKS_SECRET_S3` for `s3`.
+fn variable(name: &str) -> String {
+    format!(
+        "PHOTO_WORKS_SECRET_{}",
+        name.to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
+/// The secret stored under the name, from the environment first then from the
+/// keyring. A missing keyring is treated as an empty one.
+pub(crate) fn secret(name: &str) -> Result<Option<String>> {
+    if let Ok(value) = env::var(variable(name)) {
+        return Ok(Some(value));
+    }
+    match Entry::new(KEYRING_SERVICE, name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) | Err(keyring::Error::PlatformFailure(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn store(name: &str, value: &str) -> Result<()> {
+    Ok(Entry::new(KEYRING_SERVICE, name)?.set_password(value)?)
+}
+
+pub(crate) fn remove(name: &str) -> Result<()> {
+    match Entry::new(KEYRING_SERVICE, name)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serial_test::serial;
+
+    use super::{secret, variable};
+
+    #[test]
+    fn variable_is_derived_from_the_name() {
+        assert_eq!("PHOTO_WORKS_SECRET_S3", variable("s3"));
+        assert_eq!(
+            "PHOTO_WORKS_SECRET_SMTP_PASSWORD",
+            variable("smtp-password")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn secret_reads_the_environment_first() {
+        env::set_var("PHOTO_WORKS_SECRET_TEST", "value");
+        let result = secret("test");
+        env::remove_var("PHOTO_WORKS_SECRET_TEST");
+
+        assert_eq!(Some("value".to_string()), result.unwrap());
+    }
+}
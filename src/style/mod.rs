@@ -0,0 +1,117 @@
+use std::{
+    env,
+    fmt::{self, Display},
+    io::{stdout, IsTerminal},
+    sync::OnceLock,
+};
+
+/// The status of a file reported by check, import or prune, shown in color when
+/// the standard output is a terminal.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) enum Status {
+    Ok,
+    Missing,
+    Corrupt,
+    Skipped,
+    Quarantined,
+    Failed,
+    Trashed,
+    Restored,
+    Protected,
+    Redacted,
+}
+
+impl Status {
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Missing => "MISSING",
+            Status::Corrupt => "CORRUPT",
+            Status::Skipped => "SKIPPED",
+            Status::Quarantined => "QUARANTINED",
+            Status::Failed => "FAILED",
+            Status::Trashed => "TRASHED",
+            Status::Restored => "RESTORED",
+            Status::Protected => "PROTECTED",
+            Status::Redacted => "REDACTED",
+        }
+    }
+
+    /// The ANSI color code of the status.
+    fn color(&self) -> &'static str {
+        match self {
+            Status::Ok | Status::Restored | Status::Protected | Status::Redacted => GREEN,
+            Status::Missing | Status::Skipped | Status::Trashed => YELLOW,
+            Status::Corrupt | Status::Quarantined | Status::Failed => RED,
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&paint(
+            self.label(),
+            &[BOLD, self.color()],
+            colors_enabled(),
+        ))
+    }
+}
+
+const BOLD: &str = "1";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// The text in bold when colors are enabled.
+pub(crate) fn bold(text: &str) -> String {
+    paint(text, &[BOLD], colors_enabled())
+}
+
+/// True when the standard output is a terminal and `NO_COLOR` is not set.
+pub(crate) fn colors_enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| {
+        use_colors(
+            stdout().is_terminal(),
+            env::var_os("NO_COLOR").map(|v| v.to_string_lossy().to_string()),
+        )
+    })
+}
+
+/// `NO_COLOR` disables the colors when set to a non empty value, see no-color.org.
+fn use_colors(terminal: bool, no_color: Option<String>) -> bool {
+    terminal && no_color.is_none_or(|v| v.is_empty())
+}
+
+fn paint(text: &str, codes: &[&str], enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{paint, use_colors, Status, BOLD, RED};
+
+    #[test]
+    fn use_colors_requires_a_terminal_and_no_no_color() {
+        assert!(use_colors(true, None));
+        assert!(use_colors(true, Some(String::new())));
+        assert!(!use_colors(true, Some("1".to_string())));
+        assert!(!use_colors(false, None));
+    }
+
+    #[test]
+    fn paint_wraps_the_text_in_escape_codes_when_enabled() {
+        assert_eq!(
+            "\x1b[1;31mCORRUPT\x1b[0m",
+            paint(Status::Corrupt.label(), &[BOLD, RED], true)
+        );
+        assert_eq!(
+            "CORRUPT",
+            paint(Status::Corrupt.label(), &[BOLD, RED], false)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    cell::Cell,
+    fs::{create_dir_all, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::mpsc::{sync_channel, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+
+use clap::{arg, builder::PossibleValuesParser, Arg};
+use eyre::{eyre, Result};
+
+use crate::metadata::Exif;
+
+/// The tool resizing the pictures without an embedded thumbnail.
+const CONVERT: &str = "convert";
+/// The size of the longest side of the generated thumbnails.
+const THUMBNAIL_SIZE: &str = "320x320";
+/// The number of pictures waiting for the inline worker before the next ones are
+/// left for `photo_works thumbnails`.
+const QUEUE_CAPACITY: usize = 64;
+
+/// When the thumbnails of the imported pictures are generated.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum ThumbnailMode {
+    /// By a background worker while the import goes on.
+    Inline,
+    /// Later, by `photo_works thumbnails`.
+    Defer,
+    Off,
+}
+
+impl FromStr for ThumbnailMode {
+    type Err = eyre::Report;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "inline" => Ok(Self::Inline),
+            "defer" => Ok(Self::Defer),
+            "off" => Ok(Self::Off),
+            _ => Err(eyre!("Unknown thumbnail mode {}", mode)),
+        }
+    }
+}
+
+/// The `--thumbs` argument of the import.
+pub(crate) fn thumbs_arg() -> Arg {
+    arg!(--thumbs <MODE> "Generates the thumbnails of the imported pictures inline, in a background worker, defers them to photo_works thumbnails, or skips them")
+        .value_parser(PossibleValuesParser::new(["inline", "defer", "off"]))
+        .default_value("off")
+}
+
+/// The thumbnail of a content in the thumbnails directory.
+pub(crate) fn thumbnail_path(directory: &Path, sha256: &str) -> PathBuf {
+    directory.join(format!("{}.jpg", sha256))
+}
+
+/// Writes the thumbnail of the picture, from its embedded EXIF thumbnail or
+/// resized by ImageMagick. Returns false when neither is available.
+pub(crate) fn generate(picture: &Path, thumbnail: &Path) -> Result<bool> {
+    if thumbnail.exists() {
+        return Ok(true);
+    }
+    if let Some(parent) = thumbnail.parent() {
+        create_dir_all(parent)?;
+    }
+    if let Some(bytes) = Exif.thumbnail(picture) {
+        write(thumbnail, bytes)?;
+        return Ok(true);
+    }
+    let status = Command::new(CONVERT)
+        .arg(format!("{}[0]", picture.display()))
+        .args(["-auto-orient", "-thumbnail", THUMBNAIL_SIZE])
+        .arg(thumbnail)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) => Ok(status.success()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A single background thread generating thumbnails while the import copies the
+/// next pictures. Its queue is bounded so that it never slows the import down:
+/// the pictures submitted while it is full are left for `photo_works thumbnails`.
+pub(crate) struct ThumbnailWorker {
+    sender: SyncSender<(PathBuf, String)>,
+    handle: JoinHandle<usize>,
+    deferred: Cell<usize>,
+}
+
+impl ThumbnailWorker {
+    pub(crate) fn start(directory: PathBuf) -> Self {
+        let (sender, receiver) = sync_channel::<(PathBuf, String)>(QUEUE_CAPACITY);
+        let handle = thread::spawn(move || {
+            let mut generated = 0;
+            for (picture, sha256) in receiver {
+                let thumbnail = thumbnail_path(&directory, &sha256);
+                match generate(&picture, &thumbnail) {
+                    Ok(true) => generated += 1,
+                    Ok(false) => {}
+                    Err(e) => println!("No thumbnail for {}: {}", picture.display(), e),
+                }
+                thread::yield_now();
+            }
+            generated
+        });
+        Self {
+            sender,
+            handle,
+            deferred: Cell::new(0),
+        }
+    }
+
+    /// Queues the picture, unless the worker is behind.
+    pub(crate) fn submit(&self, picture: &Path, sha256: &str) {
+        match self
+            .sender
+            .try_send((picture.to_path_buf(), sha256.to_owned()))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.deferred.set(self.deferred.get() + 1)
+            }
+        }
+    }
+
+    /// Waits for the queued thumbnails, returning the number generated and the
+    /// number of pictures left for later.
+    pub(crate) fn finish(self) -> (usize, usize) {
+        drop(self.sender);
+        let generated = self.handle.join().unwrap_or_default();
+        (generated, self.deferred.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::{generate, thumbnail_path, ThumbnailMode, ThumbnailWorker};
+
+    #[test]
+    fn thumbnail_mode_parses_the_argument_values() {
+        assert_eq!(ThumbnailMode::Inline, "inline".parse().unwrap());
+        assert_eq!(ThumbnailMode::Off, "off".parse().unwrap());
+        assert!("later".parse::<ThumbnailMode>().is_err());
+    }
+
+    #[test]
+    fn worker_generates_the_thumbnails_of_the_submitted_pictures() {
+        let directory = TempDir::new().unwrap();
+        let picture: PathBuf = ["resources", "test", "kami_neko.jpeg"].iter().collect();
+
+        let worker = ThumbnailWorker::start(directory.path().to_path_buf());
+        worker.submit(&picture, "1");
+        worker.submit(Path::new("Cargo.toml"), "2");
+        let (generated, deferred) = worker.finish();
+
+        assert_eq!(0, deferred);
+        assert_eq!(
+            generate(&picture, &thumbnail_path(directory.path(), "1")).unwrap(),
+            generated == 1
+        );
+    }
+}
@@ -0,0 +1,400 @@
+mod system;
+
+use std::{
+    fs::{copy, create_dir_all, remove_file},
+    path::{Component, Path, PathBuf},
+};
+
+use eyre::{eyre, Result};
+use rusqlite::Connection;
+use walkdir::WalkDir;
+
+use crate::{
+    database::{
+        catalog::persist_catalog_entries,
+        catalog_entry::CatalogEntry,
+        trashed::{forget_trashed, record_trashed, Trashed},
+    },
+    fsext::remove_empty_ancestors,
+    media::sidecar::Sidecar,
+    style::Status,
+};
+
+use self::system::remove_trash_info;
+pub(crate) use self::system::SystemTrash;
+
+/// The trash directory of the earlier versions, under the repository root.
+pub(crate) const LEGACY_TRASH: &str = ".trash";
+
+/// The trash directory of the repository, configured by `[trash]`, where prune
+/// and inbox reject move the files until trash empty deletes them. With a system
+/// trash, the files are moved to the trash of the desktop instead.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TrashBin {
+    directory: PathBuf,
+    system: Option<SystemTrash>,
+}
+
+impl TrashBin {
+    pub(crate) fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            system: None,
+        }
+    }
+
+    pub(crate) fn with_system_trash(mut self, system: SystemTrash) -> Self {
+        self.system = Some(system);
+        self
+    }
+
+    pub(crate) fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Moves the cataloged file to the trash, along with its XMP sidecar, recording
+    /// it so that import recognizes the content.
+    pub(crate) fn move_in(&self, connection: &Connection, entry: &CatalogEntry) -> Result<()> {
+        self.move_in_recording(connection, entry, false)
+    }
+
+    /// Moves the redundant copy of a content kept elsewhere to the trash, recording
+    /// it so that import does not take the content for discarded.
+    pub(crate) fn move_in_redundant(
+        &self,
+        connection: &Connection,
+        entry: &CatalogEntry,
+    ) -> Result<()> {
+        self.move_in_recording(connection, entry, true)
+    }
+
+    fn move_in_recording(
+        &self,
+        connection: &Connection,
+        entry: &CatalogEntry,
+        redundant: bool,
+    ) -> Result<()> {
+        let original_path = entry.path();
+        let sidecar = Sidecar::find(&original_path);
+        let trash_path = match &self.system {
+            Some(system) => {
+                if let Some(sidecar) = &sidecar {
+                    system.move_in(sidecar.path())?;
+                }
+                system.move_in(&original_path)?
+            }
+            None => {
+                let trash_path = self.free_path_for(entry)?;
+                let trash_dir = trash_path.parent().ok_or(eyre!("Invalid Directory"))?;
+                create_dir_all(trash_dir)?;
+                copy(&original_path, &trash_path)?;
+                remove_file(&original_path)?;
+                if let Some(sidecar) = &sidecar {
+                    copy(sidecar.path(), sidecar.path_for(&trash_path))?;
+                    remove_file(sidecar.path())?;
+                }
+                trash_path
+            }
+        };
+        record_trashed(
+            connection,
+            entry.sha256(),
+            &trash_path.to_string_lossy(),
+            &original_path.to_string_lossy(),
+            redundant,
+        )?;
+        println!("{} {}", Status::Trashed, original_path.display());
+        Ok(())
+    }
+
+    /// Moves the trashed file of the content back to where it was trashed from,
+    /// along with its sidecar, and into the catalog. An existing file is never
+    /// overwritten.
+    pub(crate) fn restore(
+        &self,
+        connection: &mut Connection,
+        hash: &str,
+        trashed: &Trashed,
+    ) -> Result<()> {
+        if trashed.original_path.exists() {
+            return Err(eyre!("{} exists already", trashed.original_path.display()));
+        }
+        if let Some(parent) = trashed.original_path.parent() {
+            create_dir_all(parent)?;
+        }
+        copy(&trashed.path, &trashed.original_path)?;
+        if let Some(sidecar) = Sidecar::find(&trashed.path) {
+            let original = sidecar.path_for(&trashed.original_path);
+            if !original.exists() {
+                copy(sidecar.path(), original)?;
+            }
+        }
+        self.delete(connection, &trashed.path)?;
+        persist_catalog_entries(
+            connection,
+            &vec![CatalogEntry::new(
+                hash.to_owned(),
+                trashed.original_path.to_string_lossy().to_string(),
+            )],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the trashed file and its sidecar, along with the directories they
+    /// leave empty in the trash, and forgets it.
+    pub(crate) fn delete(&self, connection: &Connection, path: &Path) -> Result<()> {
+        if let Some(sidecar) = Sidecar::find(path) {
+            remove_file(sidecar.path())?;
+            remove_trash_info(sidecar.path())?;
+        }
+        remove_file(path)?;
+        remove_empty_ancestors(path, std::slice::from_ref(&self.directory))?;
+        remove_trash_info(path)?;
+        forget_trashed(connection, &path.to_string_lossy())?;
+        Ok(())
+    }
+
+    /// The path of the entry in the trash, under its whole original path so that
+    /// the files of the same name in different directories don't collide.
+    fn path_for(&self, entry: &CatalogEntry) -> Result<PathBuf> {
+        let original_path = entry.path();
+        original_path.file_name().ok_or(eyre!("Invalid File"))?;
+        let mut path = self.directory.clone();
+        for component in original_path.components() {
+            match component {
+                Component::Normal(name) => path.push(name),
+                Component::ParentDir => return Err(eyre!("Invalid File")),
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            }
+        }
+        Ok(path)
+    }
+
+    /// The path of the entry in the trash, suffixed when a file trashed before from
+    /// the same path is still there.
+    fn free_path_for(&self, entry: &CatalogEntry) -> Result<PathBuf> {
+        let path = self.path_for(entry)?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let mut candidate = path.clone();
+        let mut index = 0;
+        while candidate.exists() {
+            index += 1;
+            candidate = path.with_file_name(format!("{}_{}{}", stem, index, extension));
+        }
+        Ok(candidate)
+    }
+
+    /// The files in the trash, with their size.
+    pub(crate) fn files(&self) -> Vec<(PathBuf, u64)> {
+        WalkDir::new(&self.directory)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| {
+                let size = e.metadata().map(|m| m.len()).unwrap_or_default();
+                (e.into_path(), size)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, read_to_string, write},
+        path::PathBuf,
+    };
+
+    use tempfile::{NamedTempFile, TempDir};
+
+    use crate::database::{
+        catalog_entry::CatalogEntry,
+        test_utils::{catalog_contains, new_database},
+        trashed::select_trashed,
+    };
+
+    use super::TrashBin;
+
+    #[test]
+    fn path_for_keeps_the_original_path() {
+        let bin = TrashBin::new("/repo/.photo_works/trash".into());
+        let entry = CatalogEntry::new("1234".to_string(), "/card/a/b/c.png".to_string());
+        assert_eq!(
+            "/repo/.photo_works/trash/card/a/b/c.png",
+            bin.path_for(&entry).unwrap().to_string_lossy()
+        );
+        let entry = CatalogEntry::new("1234".to_string(), "c.png".to_string());
+        assert_eq!(
+            "/repo/.photo_works/trash/c.png",
+            bin.path_for(&entry).unwrap().to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn path_for_fails_when_no_file_name() {
+        let bin = TrashBin::new("trash".into());
+        let entry = CatalogEntry::new("1234".to_string(), "..".to_string());
+        let error = bin.path_for(&entry).err().unwrap().to_string();
+        assert_eq!("Invalid File", error);
+    }
+
+    #[test]
+    fn path_for_fails_when_leaving_the_trash() {
+        let bin = TrashBin::new("trash".into());
+        let entry = CatalogEntry::new("1234".to_string(), "a/../../c.png".to_string());
+        let error = bin.path_for(&entry).err().unwrap().to_string();
+        assert_eq!("Invalid File", error);
+    }
+
+    #[test]
+    fn move_in_keeps_the_files_of_the_same_name() {
+        let directory = TempDir::new().unwrap();
+        let bin = TrashBin::new(directory.path().join("trash"));
+        let connection = new_database();
+        let mut trashed = vec![];
+        for (hash, path, content) in [
+            ("1", "2022/07/img.jpg", "a"),
+            ("2", "2023/07/img.jpg", "b"),
+            ("3", "2023/07/img.jpg", "c"),
+        ] {
+            let path = directory.path().join("card").join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(&path, content).unwrap();
+            bin.move_in(
+                &connection,
+                &CatalogEntry::new(hash.to_string(), path.to_string_lossy().to_string()),
+            )
+            .unwrap();
+            trashed.push(
+                select_trashed(&connection)
+                    .unwrap()
+                    .remove(hash)
+                    .unwrap()
+                    .path,
+            );
+        }
+
+        let card = directory
+            .path()
+            .join("trash")
+            .join(directory.path().join("card").strip_prefix("/").unwrap());
+        assert_eq!(
+            vec![
+                card.join("2022/07/img.jpg"),
+                card.join("2023/07/img.jpg"),
+                card.join("2023/07/img_1.jpg")
+            ],
+            trashed
+        );
+        assert_eq!(
+            vec!["a", "b", "c"],
+            trashed
+                .iter()
+                .map(|p| read_to_string(p).unwrap())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn move_in_moves_the_file() {
+        let directory = TempDir::new().unwrap();
+        let bin = TrashBin::new(directory.path().join("trash"));
+        let file = NamedTempFile::new().unwrap();
+        let entry = CatalogEntry::new(
+            "1234".to_string(),
+            file.path().to_string_lossy().to_string(),
+        );
+        let trash_path = bin.path_for(&entry).unwrap();
+        let connection = new_database();
+        bin.move_in(&connection, &entry).unwrap();
+        assert!(trash_path.exists());
+        assert!(!file.path().exists());
+        assert_eq!(
+            trash_path,
+            select_trashed(&connection).unwrap()["1234"].path
+        );
+    }
+
+    #[test]
+    fn restore_moves_the_file_back_into_the_catalog() {
+        let directory = TempDir::new().unwrap();
+        let bin = TrashBin::new(directory.path().join("trash"));
+        let card = directory.path().join("card");
+        create_dir_all(&card).unwrap();
+        write(card.join("a.jpeg"), "a").unwrap();
+        let entry = CatalogEntry::new(
+            "1234".to_string(),
+            card.join("a.jpeg").to_string_lossy().to_string(),
+        );
+        let mut connection = new_database();
+        bin.move_in(&connection, &entry).unwrap();
+        let trashed = select_trashed(&connection).unwrap().remove("1234").unwrap();
+
+        bin.restore(&mut connection, "1234", &trashed).unwrap();
+
+        assert!(card.join("a.jpeg").exists());
+        assert!(!directory.path().join("trash/card").exists());
+        assert!(select_trashed(&connection).unwrap().is_empty());
+        assert!(catalog_contains(&mut connection, &entry));
+        bin.move_in(&connection, &entry).unwrap();
+        write(card.join("a.jpeg"), "b").unwrap();
+        assert!(bin.restore(&mut connection, "1234", &trashed).is_err());
+    }
+
+    #[test]
+    fn move_in_and_restore_carry_the_sidecar() {
+        let directory = TempDir::new().unwrap();
+        let bin = TrashBin::new(directory.path().join("trash"));
+        let card = directory.path().join("card");
+        create_dir_all(&card).unwrap();
+        write(card.join("IMG_0001.CR2"), "raw").unwrap();
+        write(card.join("IMG_0001.CR2.xmp"), "edits").unwrap();
+        let entry = CatalogEntry::new(
+            "1234".to_string(),
+            card.join("IMG_0001.CR2").to_string_lossy().to_string(),
+        );
+        let mut connection = new_database();
+
+        bin.move_in(&connection, &entry).unwrap();
+
+        assert!(!card.join("IMG_0001.CR2.xmp").exists());
+        let trashed = select_trashed(&connection).unwrap().remove("1234").unwrap();
+        let mut sidecar = trashed.path.clone().into_os_string();
+        sidecar.push(".xmp");
+        assert_eq!("edits", read_to_string(&sidecar).unwrap());
+
+        bin.restore(&mut connection, "1234", &trashed).unwrap();
+
+        assert_eq!(
+            "edits",
+            read_to_string(card.join("IMG_0001.CR2.xmp")).unwrap()
+        );
+        assert!(!PathBuf::from(sidecar).exists());
+    }
+
+    #[test]
+    fn files_lists_the_nested_files() {
+        let directory = TempDir::new().unwrap();
+        create_dir_all(directory.path().join("a/b")).unwrap();
+        write(directory.path().join("a/b/c.jpeg"), "abc").unwrap();
+        write(directory.path().join("d.jpeg"), "d").unwrap();
+
+        let mut files = TrashBin::new(directory.path().to_owned()).files();
+        files.sort();
+
+        assert_eq!(
+            vec![
+                (directory.path().join("a/b/c.jpeg"), 3),
+                (directory.path().join("d.jpeg"), 1)
+            ],
+            files
+        );
+        assert!(TrashBin::new(directory.path().join("missing"))
+            .files()
+            .is_empty());
+    }
+}
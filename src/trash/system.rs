@@ -0,0 +1,193 @@
+use std::{
+    env,
+    fs::{copy, create_dir_all, remove_file, rename, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use eyre::{eyre, Result};
+
+use crate::http::url_path;
+
+/// The trash of the desktop: the home trash of the freedesktop.org specification
+/// on Linux, `~/.Trash` on macOS. The files moved there are recovered with the
+/// desktop tools.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SystemTrash {
+    files: PathBuf,
+    /// The directory of the `.trashinfo` files, which macOS doesn't have.
+    info: Option<PathBuf>,
+}
+
+impl SystemTrash {
+    /// The trash of the user running the command.
+    pub(crate) fn locate() -> Result<Self> {
+        if cfg!(target_os = "macos") {
+            let home = home::home_dir().ok_or(eyre!("No home directory for the system trash"))?;
+            return Ok(Self {
+                files: home.join(".Trash"),
+                info: None,
+            });
+        }
+        if cfg!(unix) {
+            let data = env::var_os("XDG_DATA_HOME")
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| home::home_dir().map(|h| h.join(".local").join("share")))
+                .ok_or(eyre!("No home directory for the system trash"))?;
+            return Ok(Self::freedesktop(data.join("Trash")));
+        }
+        Err(eyre!("The system trash is not supported on this platform"))
+    }
+
+    fn freedesktop(directory: PathBuf) -> Self {
+        Self {
+            files: directory.join("files"),
+            info: Some(directory.join("info")),
+        }
+    }
+
+    /// Moves the file to the trash, along with the `.trashinfo` file the desktop
+    /// restores it with, and returns its path in the trash.
+    pub(crate) fn move_in(&self, file: &Path) -> Result<PathBuf> {
+        let name = file
+            .file_name()
+            .ok_or(eyre!("Invalid File"))?
+            .to_string_lossy()
+            .to_string();
+        create_dir_all(&self.files)?;
+        let path = match &self.info {
+            Some(info) => {
+                create_dir_all(info)?;
+                self.reserve(info, &name, file)?
+            }
+            None => self.free_path(&name),
+        };
+        if rename(file, &path).is_err() {
+            copy(file, &path)?;
+            remove_file(file)?;
+        }
+        Ok(path)
+    }
+
+    /// Creates the `.trashinfo` file of the first free name, as the specification
+    /// requires before the file is moved, and returns the path of the file.
+    fn reserve(&self, info: &Path, name: &str, original: &Path) -> Result<PathBuf> {
+        for candidate in candidates(name) {
+            let path = self.files.join(&candidate);
+            if path.exists() {
+                continue;
+            }
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(info.join(format!("{}.trashinfo", candidate)))
+            {
+                Ok(mut info_file) => {
+                    info_file.write_all(trash_info(original, Local::now()).as_bytes())?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("the candidates are endless")
+    }
+
+    fn free_path(&self, name: &str) -> PathBuf {
+        candidates(name)
+            .map(|candidate| self.files.join(candidate))
+            .find(|path| !path.exists())
+            .expect("the candidates are endless")
+    }
+}
+
+/// Removes the `.trashinfo` file of a file taken out of a freedesktop.org trash,
+/// found next to its `files` directory. Other files have none.
+pub(crate) fn remove_trash_info(path: &Path) -> Result<()> {
+    let (Some(files), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+    if files.file_name().is_some_and(|n| n == "files") {
+        let info = files
+            .with_file_name("info")
+            .join(format!("{}.trashinfo", name.to_string_lossy()));
+        if info.is_file() {
+            remove_file(info)?;
+        }
+    }
+    Ok(())
+}
+
+/// The name, then the name suffixed with `_1`, `_2`… before its extension.
+fn candidates(name: &str) -> impl Iterator<Item = String> + '_ {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (0..).map(move |index| match index {
+        0 => name.to_owned(),
+        _ => format!("{}_{}{}", stem, index, extension),
+    })
+}
+
+/// The content of the `.trashinfo` file of a file trashed at the time.
+fn trash_info(original: &Path, trashed_at: chrono::DateTime<Local>) -> String {
+    format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        url_path(original),
+        trashed_at.format("%Y-%m-%dT%H:%M:%S")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read_to_string, write},
+        path::Path,
+    };
+
+    use chrono::{Local, TimeZone};
+    use tempfile::TempDir;
+
+    use super::{remove_trash_info, trash_info, SystemTrash};
+
+    #[test]
+    fn trash_info_encodes_the_original_path() {
+        let trashed_at = Local.with_ymd_and_hms(2024, 5, 1, 10, 30, 0).unwrap();
+        assert_eq!(
+            "[Trash Info]\nPath=/card/Rome%20trip/a.jpg\nDeletionDate=2024-05-01T10:30:00\n",
+            trash_info(Path::new("/card/Rome trip/a.jpg"), trashed_at)
+        );
+    }
+
+    #[test]
+    fn move_in_keeps_the_files_of_the_same_name() {
+        let directory = TempDir::new().unwrap();
+        let trash = SystemTrash::freedesktop(directory.path().join("Trash"));
+        let mut trashed = vec![];
+        for folder in ["a", "b"] {
+            let file = directory.path().join(folder).join("img.jpg");
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            write(&file, folder).unwrap();
+            trashed.push(trash.move_in(&file).unwrap());
+            assert!(!file.exists());
+        }
+
+        let files = directory.path().join("Trash/files");
+        assert_eq!(
+            vec![files.join("img.jpg"), files.join("img_1.jpg")],
+            trashed
+        );
+        assert_eq!("b", read_to_string(&trashed[1]).unwrap());
+        let info = directory.path().join("Trash/info/img_1.jpg.trashinfo");
+        assert!(read_to_string(&info)
+            .unwrap()
+            .contains(&format!("Path={}/b/img.jpg", directory.path().display())));
+
+        remove_trash_info(&trashed[1]).unwrap();
+
+        assert!(!info.exists());
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::BTreeSet,
+    fs::{copy, create_dir_all, read, write},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+fn photo_works(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_photo_works"))
+        .current_dir(directory)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn files(directory: &Path) -> BTreeSet<PathBuf> {
+    WalkDir::new(directory)
+        .into_iter()
+        .map(|e| e.unwrap().path().to_owned())
+        .collect()
+}
+
+#[test]
+fn bare_photo_works_prints_the_help() {
+    let directory = TempDir::new().unwrap();
+    for args in [&[][..], &["--repo", "repository"]] {
+        let output = photo_works(directory.path(), args);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(Some(2), output.status.code(), "{}", stderr);
+        assert!(stderr.contains("Usage: photo_works"), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn import_dry_runs_write_no_file_in_the_repository() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    let pictures = directory.path().join("pictures");
+    create_dir_all(&pictures).unwrap();
+    copy(
+        ["resources", "test", "kami_neko.jpeg"]
+            .iter()
+            .collect::<PathBuf>(),
+        pictures.join("kami_neko.jpeg"),
+    )
+    .unwrap();
+    assert!(photo_works(directory.path(), &["init", "repository"])
+        .status
+        .success());
+    let pictures = pictures.to_string_lossy().to_string();
+    assert!(photo_works(&repository, &["catalog", &pictures])
+        .status
+        .success());
+    let before = files(&repository);
+
+    for args in [
+        &["import", "--dry-run", &pictures][..],
+        &["import", "--preview", &pictures],
+    ] {
+        let output = photo_works(&repository, args);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Would import 1 pictures"));
+    }
+
+    assert_eq!(before, files(&repository));
+}
+
+#[test]
+fn import_takes_the_copy_kept_by_prune_duplicates() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    let pictures = directory.path().join("pictures");
+    for copy_directory in ["a", "b"] {
+        create_dir_all(pictures.join(copy_directory)).unwrap();
+        copy(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<PathBuf>(),
+            pictures.join(copy_directory).join("kami_neko.jpeg"),
+        )
+        .unwrap();
+    }
+    assert!(photo_works(directory.path(), &["init", "repository"])
+        .status
+        .success());
+    let pictures = pictures.to_string_lossy().to_string();
+    assert!(photo_works(&repository, &["catalog", &pictures])
+        .status
+        .success());
+    assert!(photo_works(&repository, &["prune", "duplicates", "--yes"])
+        .status
+        .success());
+
+    let output = photo_works(&repository, &["import", &pictures]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Imported 1 pictures"), "{}", stdout);
+}
+
+#[test]
+fn doctor_diagnoses_a_corrupt_configuration_and_database() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    assert!(photo_works(directory.path(), &["init", "repository"])
+        .status
+        .success());
+    let settings = repository.join(".photo_works");
+    write(settings.join("config.toml"), "[trash\n").unwrap();
+    write(settings.join("db.db3"), "not a database").unwrap();
+
+    let output = photo_works(&repository, &["doctor"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(Some(1), output.status.code(), "{}", stdout);
+    assert!(stdout.contains("[error] config"), "{}", stdout);
+    assert!(stdout.contains("[error] database"), "{}", stdout);
+    assert_eq!(
+        b"not a database".to_vec(),
+        read(settings.join("db.db3")).unwrap()
+    );
+}
+
+#[test]
+fn again_repeats_the_remembered_flags_of_the_last_real_run() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    let pictures = directory.path().join("pictures");
+    create_dir_all(&pictures).unwrap();
+    copy(
+        ["resources", "test", "kami_neko.jpeg"]
+            .iter()
+            .collect::<PathBuf>(),
+        pictures.join("kami_neko.jpeg"),
+    )
+    .unwrap();
+    assert!(photo_works(directory.path(), &["init", "repository"])
+        .status
+        .success());
+    let pictures = pictures.to_string_lossy().to_string();
+    assert!(
+        photo_works(&repository, &["catalog", "--jobs", "2", &pictures])
+            .status
+            .success()
+    );
+    assert!(photo_works(
+        &repository,
+        &["import", "--dry-run", "--exclude", "*.gif", &pictures]
+    )
+    .status
+    .success());
+
+    let catalog = photo_works(&repository, &["--again", "catalog", &pictures]);
+    let import = photo_works(&repository, &["--again", "import", "--dry-run", &pictures]);
+
+    let stdout = String::from_utf8_lossy(&catalog.stdout);
+    assert!(stdout.contains("Repeating catalog --jobs 2"), "{}", stdout);
+    let stderr = String::from_utf8_lossy(&import.stderr);
+    assert!(stderr.contains("No previous run of import"), "{}", stderr);
+}
+
+#[test]
+fn trash_list_writes_no_file_in_the_repository() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    assert!(photo_works(directory.path(), &["init", "repository"])
+        .status
+        .success());
+    let before = files(&repository);
+
+    let output = photo_works(&repository, &["trash", "list"]);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(before, files(&repository));
+}
@@ -0,0 +1,80 @@
+use std::{
+    fs::{copy, create_dir_all},
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+};
+
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+fn photo_works(repository: &Path, args: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_photo_works"))
+        .current_dir(repository)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+fn succeeded(output: &Output) -> bool {
+    output.status.success()
+}
+
+fn was_locked_out(output: &Output) -> bool {
+    String::from_utf8_lossy(&output.stderr).contains("locked by another photo_works process")
+}
+
+#[test]
+fn concurrent_catalog_and_check_keep_the_repository_consistent() {
+    let directory = TempDir::new().unwrap();
+    let repository = directory.path().join("repository");
+    let pictures = directory.path().join("pictures");
+    create_dir_all(&repository).unwrap();
+    create_dir_all(&pictures).unwrap();
+    for i in 0..50 {
+        copy(
+            ["resources", "test", "kami_neko.jpeg"]
+                .iter()
+                .collect::<std::path::PathBuf>(),
+            pictures.join(format!("kami_{}.jpeg", i)),
+        )
+        .unwrap();
+    }
+    let init = photo_works(directory.path(), &["init", "repository"])
+        .wait_with_output()
+        .unwrap();
+    assert!(succeeded(&init));
+
+    let pictures = pictures.to_string_lossy().to_string();
+    let catalogs = (0..2)
+        .map(|_| photo_works(&repository, &["catalog", &pictures]))
+        .collect::<Vec<Child>>();
+    let checks = (0..2)
+        .map(|_| photo_works(&repository, &["check", "catalog"]))
+        .collect::<Vec<Child>>();
+
+    let catalogs = catalogs
+        .into_iter()
+        .map(|c| c.wait_with_output().unwrap())
+        .collect::<Vec<Output>>();
+    for check in checks {
+        assert!(succeeded(&check.wait_with_output().unwrap()));
+    }
+    assert!(catalogs.iter().any(succeeded));
+    assert!(catalogs.iter().all(|c| succeeded(c) || was_locked_out(c)));
+
+    let connection = Connection::open(repository.join(".photo_works").join("db.db3")).unwrap();
+    assert_eq!(
+        "ok",
+        connection
+            .query_row("PRAGMA integrity_check", [], |r| r.get::<_, String>(0))
+            .unwrap()
+    );
+    assert_eq!(
+        50,
+        connection
+            .query_row("SELECT count(*) FROM catalog", [], |r| r.get::<_, i64>(0))
+            .unwrap()
+    );
+}